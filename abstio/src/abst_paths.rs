@@ -324,6 +324,14 @@ pub fn path_prebaked_results(name: &MapName, scenario_name: &str) -> String {
     ))
 }
 
+/// The costs found by running iterative traffic assignment on a scenario
+pub fn path_assignment_costs(name: &MapName, scenario_name: &str) -> String {
+    path(format!(
+        "system/{}/{}/assignment_costs/{}/{}.bin",
+        name.city.country, name.city.city, name.map, scenario_name
+    ))
+}
+
 pub fn path_scenario(name: &MapName, scenario_name: &str) -> String {
     // TODO Getting complicated. Sometimes we're trying to load, so we should look for .bin, then
     // .json. But when we're writing a custom scenario, we actually want to write a .bin.
@@ -401,6 +409,21 @@ pub fn path_all_saves(name: &MapName, edits_name: &str, run_name: &str) -> Strin
     ))
 }
 
+/// Snapshots of a simulation saved by the headless API. These're kept apart from the savestates,
+/// which are found by listing their directory.
+pub fn path_snapshot(name: &MapName, snapshot: &str) -> String {
+    path(format!(
+        "player/snapshots/{}/{}/{}/{}.bin",
+        name.city.country, name.city.city, name.map, snapshot
+    ))
+}
+pub fn path_all_snapshots(name: &MapName) -> String {
+    path(format!(
+        "player/snapshots/{}/{}/{}",
+        name.city.country, name.city.city, name.map
+    ))
+}
+
 // Input data (For developers to build maps, not needed at runtime)
 
 pub fn path_popdat() -> String {
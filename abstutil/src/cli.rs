@@ -31,7 +31,8 @@ impl CmdArgs {
         }
     }
 
-    fn from_args(raw: Vec<String>) -> CmdArgs {
+    /// Parse flags from somewhere besides the real command line.
+    pub fn from_args(raw: Vec<String>) -> CmdArgs {
         let mut args = CmdArgs {
             kv: HashMap::new(),
             bits: HashSet::new(),
@@ -81,10 +82,27 @@ impl CmdArgs {
         key: &str,
         parser: F,
     ) -> Option<T> {
-        let value = self.optional(key)?;
+        match self.try_optional_parse(key, parser) {
+            Ok(result) => result,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Like `optional_parse`, but returns an error instead of panicking when the value is bad.
+    pub fn try_optional_parse<T, E, F: Fn(&str) -> Result<T, E>>(
+        &mut self,
+        key: &str,
+        parser: F,
+    ) -> anyhow::Result<Option<T>> {
+        let value = match self.optional(key) {
+            Some(value) => value,
+            None => {
+                return Ok(None);
+            }
+        };
         match parser(&value) {
-            Ok(result) => Some(result),
-            Err(_) => panic!("Bad argument {}={}", key, value),
+            Ok(result) => Ok(Some(result)),
+            Err(_) => bail!("Bad argument {}={}", key, value),
         }
     }
 
@@ -124,15 +142,23 @@ impl CmdArgs {
 
     // TODO Drop?
     pub fn done(&mut self) {
+        if let Err(err) = self.try_done() {
+            panic!("{}", err);
+        }
+    }
+
+    /// Like `done`, but returns an error instead of panicking when some arguments are unused.
+    pub fn try_done(&mut self) -> anyhow::Result<()> {
         if !self.kv.is_empty() {
-            panic!("Unused arguments: {:?}", self.kv);
+            bail!("Unused arguments: {:?}", self.kv);
         }
         if !self.bits.is_empty() {
-            panic!("Unused arguments: {:?}", self.bits);
+            bail!("Unused arguments: {:?}", self.bits);
         }
         if !self.free.is_empty() {
-            panic!("Unused free arguments: {:?}", self.free);
+            bail!("Unused free arguments: {:?}", self.free);
         }
+        Ok(())
     }
 }
 
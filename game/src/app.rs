@@ -85,6 +85,30 @@ impl App {
                     "- passengers_alighting: {} bytes",
                     prettyprint_usize(serialized_size_bytes(&a.passengers_alighting))
                 );
+                println!(
+                    "- passengers_denied: {} bytes",
+                    prettyprint_usize(serialized_size_bytes(&a.passengers_denied))
+                );
+                println!(
+                    "- schedule_deviations: {} bytes",
+                    prettyprint_usize(serialized_size_bytes(&a.schedule_deviations))
+                );
+                println!(
+                    "- headways: {} bytes",
+                    prettyprint_usize(serialized_size_bytes(&a.headways))
+                );
+                println!(
+                    "- carpools: {} bytes",
+                    prettyprint_usize(serialized_size_bytes(&a.carpools))
+                );
+                println!(
+                    "- ride_hail_pickups: {} bytes",
+                    prettyprint_usize(serialized_size_bytes(&a.ride_hail_pickups))
+                );
+                println!(
+                    "- ride_hail_legs: {} bytes",
+                    prettyprint_usize(serialized_size_bytes(&a.ride_hail_legs))
+                );
                 println!(
                     "- started_trips: {} bytes",
                     prettyprint_usize(serialized_size_bytes(&a.started_trips))
@@ -109,6 +133,34 @@ impl App {
                     "- parking_lot_changes: {} bytes",
                     prettyprint_usize(serialized_size_bytes(&a.parking_lot_changes))
                 );
+                println!(
+                    "- parking_stays: {} bytes",
+                    prettyprint_usize(serialized_size_bytes(&a.parking_stays))
+                );
+                println!(
+                    "- cruising_for_parking: {} bytes",
+                    prettyprint_usize(serialized_size_bytes(&a.cruising_for_parking))
+                );
+                println!(
+                    "- trip_emissions: {} bytes",
+                    prettyprint_usize(serialized_size_bytes(&a.trip_emissions))
+                );
+                println!(
+                    "- road_emissions: {} bytes",
+                    prettyprint_usize(serialized_size_bytes(&a.road_emissions))
+                );
+                println!(
+                    "- sidewalk_density: {} bytes",
+                    prettyprint_usize(serialized_size_bytes(&a.sidewalk_density))
+                );
+                println!(
+                    "- signal_priority: {} bytes",
+                    prettyprint_usize(serialized_size_bytes(&a.signal_priority))
+                );
+                println!(
+                    "- reroutes: {} bytes",
+                    prettyprint_usize(serialized_size_bytes(&a.reroutes))
+                );
             }
         }
     }
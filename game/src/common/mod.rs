@@ -374,6 +374,7 @@ pub fn color_for_mode(app: &App, m: TripMode) -> Color {
         TripMode::Bike => app.cs.unzoomed_bike,
         TripMode::Transit => app.cs.unzoomed_bus,
         TripMode::Drive => app.cs.unzoomed_car,
+        TripMode::Carpool | TripMode::RideHail => app.cs.unzoomed_carpool,
     }
 }
 
@@ -384,6 +385,7 @@ pub fn color_for_agent_type(app: &App, a: AgentType) -> Color {
         AgentType::Bus | AgentType::Train => app.cs.unzoomed_bus,
         AgentType::TransitRider => app.cs.bus_trip,
         AgentType::Car => app.cs.unzoomed_car,
+        AgentType::CarPassenger => app.cs.unzoomed_carpool,
     }
 }
 
@@ -395,6 +397,10 @@ pub fn color_for_trip_phase(app: &App, tpt: TripPhaseType) -> Color {
         TripPhaseType::Parking => app.cs.parking_trip,
         TripPhaseType::WaitingForBus(_, _) => app.cs.bus_layer,
         TripPhaseType::RidingBus(_, _, _) => app.cs.bus_trip,
+        TripPhaseType::WaitingForCarpool | TripPhaseType::WaitingForRideHail => {
+            app.cs.unzoomed_carpool
+        }
+        TripPhaseType::RidingCar(_) => app.cs.unzoomed_carpool,
         TripPhaseType::Cancelled | TripPhaseType::Finished => unreachable!(),
         TripPhaseType::DelayedStart => Color::YELLOW,
     }
@@ -12,6 +12,7 @@ use widgetry::{
     Key, Line, Menu, Outcome, Panel, State, Text, TextBox, TextExt, VerticalAlignment, Widget,
 };
 
+pub use self::parking::ParkingRulesEditor;
 pub use self::roads::RoadEditor;
 pub use self::routes::RouteEditor;
 pub use self::stop_signs::StopSignEditor;
@@ -23,6 +24,7 @@ use crate::debug::DebugMode;
 use crate::sandbox::{GameplayMode, SandboxMode, TimeWarpScreen};
 
 mod multiple_roads;
+mod parking;
 mod roads;
 mod routes;
 mod stop_signs;
@@ -873,7 +875,8 @@ fn cmd_to_id(cmd: &EditCmd) -> Option<ID> {
     match cmd {
         EditCmd::ChangeRoad { r, .. } => Some(ID::Road(*r)),
         EditCmd::ChangeIntersection { i, .. } => Some(ID::Intersection(*i)),
-        EditCmd::ChangeRouteSchedule { .. } => None,
+        EditCmd::ChangeRouteSchedule { .. } | EditCmd::ChangeRouteCapacity { .. } => None,
+        EditCmd::ChangeParkingLot { pl, .. } => Some(ID::ParkingLot(*pl)),
     }
 }
 
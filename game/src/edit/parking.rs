@@ -0,0 +1,142 @@
+use geom::Duration;
+use map_model::{EditCmd, ParkingLotID, ParkingRules, RoadID};
+use widgetry::{
+    EventCtx, GfxCtx, HorizontalAlignment, Key, Line, Outcome, Panel, Spinner, State, TextBox,
+    TextExt, VerticalAlignment, Widget,
+};
+
+use crate::app::App;
+use crate::app::Transition;
+use crate::edit::apply_map_edits;
+
+/// Edit the price, time limit, and permit zone for parking along a road or in a lot.
+pub struct ParkingRulesEditor {
+    panel: Panel,
+    target: Target,
+}
+
+#[derive(Clone, Copy)]
+enum Target {
+    Road(RoadID),
+    Lot(ParkingLotID),
+}
+
+impl ParkingRulesEditor {
+    pub fn new_road(ctx: &mut EventCtx, app: &mut App, r: RoadID) -> Box<dyn State<App>> {
+        ParkingRulesEditor::new_state(ctx, app, Target::Road(r))
+    }
+
+    pub fn new_lot(ctx: &mut EventCtx, app: &mut App, pl: ParkingLotID) -> Box<dyn State<App>> {
+        ParkingRulesEditor::new_state(ctx, app, Target::Lot(pl))
+    }
+
+    fn new_state(ctx: &mut EventCtx, app: &mut App, target: Target) -> Box<dyn State<App>> {
+        app.primary.current_selection = None;
+
+        let map = &app.primary.map;
+        let (title, rules) = match target {
+            Target::Road(r) => (
+                map.get_r(r).get_name(app.opts.language.as_ref()),
+                map.get_r(r).parking_rules.clone(),
+            ),
+            Target::Lot(pl) => (pl.to_string(), map.get_pl(pl).parking_rules.clone()),
+        };
+        Box::new(ParkingRulesEditor {
+            panel: Panel::new_builder(Widget::col(vec![
+                Widget::row(vec![
+                    Line("Parking rules").small_heading().into_widget(ctx),
+                    ctx.style().btn_close_widget(ctx),
+                ]),
+                Line(title).into_widget(ctx),
+                Widget::row(vec![
+                    "Price per hour ($)".text_widget(ctx).centered_vert(),
+                    Spinner::widget(ctx, "price", (0.0, 20.0), rules.price_per_hour, 0.25),
+                ]),
+                Widget::row(vec![
+                    "Time limit (0 for none)".text_widget(ctx).centered_vert(),
+                    Spinner::widget(
+                        ctx,
+                        "max stay",
+                        (Duration::ZERO, Duration::hours(24)),
+                        rules.max_stay.unwrap_or(Duration::ZERO),
+                        Duration::minutes(15),
+                    ),
+                ]),
+                Widget::row(vec![
+                    "Permit zone (blank for none)"
+                        .text_widget(ctx)
+                        .centered_vert(),
+                    TextBox::widget(
+                        ctx,
+                        "permit zone",
+                        rules.permit_zone.unwrap_or_else(String::new),
+                        false,
+                        10,
+                    ),
+                ]),
+                ctx.style()
+                    .btn_solid_primary
+                    .text("Apply")
+                    .hotkey(Key::Enter)
+                    .build_def(ctx),
+            ]))
+            .aligned(HorizontalAlignment::Center, VerticalAlignment::Top)
+            .build(ctx),
+            target,
+        })
+    }
+}
+
+impl State<App> for ParkingRulesEditor {
+    fn event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Transition {
+        ctx.canvas_movement();
+
+        if let Outcome::Clicked(x) = self.panel.event(ctx) {
+            match x.as_ref() {
+                "close" => {
+                    return Transition::Pop;
+                }
+                "Apply" => {
+                    let max_stay = self.panel.spinner("max stay");
+                    let permit_zone = self.panel.text_box("permit zone").trim().to_string();
+                    let rules = ParkingRules {
+                        price_per_hour: self.panel.spinner("price"),
+                        max_stay: if max_stay == Duration::ZERO {
+                            None
+                        } else {
+                            Some(max_stay)
+                        },
+                        permit_zone: if permit_zone.is_empty() {
+                            None
+                        } else {
+                            Some(permit_zone)
+                        },
+                    };
+
+                    let map = &app.primary.map;
+                    let mut edits = map.get_edits().clone();
+                    edits.commands.push(match self.target {
+                        Target::Road(r) => map.edit_road_cmd(r, |new| {
+                            new.parking_rules = rules.clone();
+                        }),
+                        Target::Lot(pl) => EditCmd::ChangeParkingLot {
+                            pl,
+                            old: map.get_pl(pl).parking_rules.clone(),
+                            new: rules,
+                        },
+                    });
+                    apply_map_edits(ctx, app, edits);
+
+                    return Transition::Pop;
+                }
+                _ => unreachable!(),
+            }
+        }
+
+        Transition::Keep
+    }
+
+    fn draw(&self, g: &mut GfxCtx, _: &App) {
+        self.panel.draw(g);
+    }
+}
@@ -15,7 +15,7 @@ use widgetry::{
 use crate::app::{App, Transition};
 use crate::common::Warping;
 use crate::edit::zones::ZoneEditor;
-use crate::edit::{apply_map_edits, can_edit_lane, speed_limit_choices};
+use crate::edit::{apply_map_edits, can_edit_lane, speed_limit_choices, ParkingRulesEditor};
 
 pub struct RoadEditor {
     r: RoadID,
@@ -286,6 +286,12 @@ impl State<App> for RoadEditor {
                         apply_map_edits(ctx, app, edits);
                     }
                     return Transition::Replace(ZoneEditor::new_state(ctx, app, self.r));
+                } else if x == "Parking rules" {
+                    // Like access restrictions, save the edits for this road first.
+                    if let Some(edits) = self.compress_edits(app) {
+                        apply_map_edits(ctx, app, edits);
+                    }
+                    return Transition::Replace(ParkingRulesEditor::new_road(ctx, app, self.r));
                 } else {
                     unreachable!()
                 }
@@ -653,6 +659,15 @@ fn make_main_panel(
             .btn_outline
             .text("Access restrictions")
             .build_def(ctx),
+        if road
+            .lanes_ltr()
+            .iter()
+            .any(|(_, _, lt)| *lt == LaneType::Parking)
+        {
+            ctx.style().btn_outline.text("Parking rules").build_def(ctx)
+        } else {
+            Widget::nothing()
+        },
     ]);
 
     Panel::new_builder(Widget::col(vec![
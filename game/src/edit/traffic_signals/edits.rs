@@ -24,6 +24,12 @@ impl ChangeDuration {
         signal: &ControlTrafficSignal,
         idx: usize,
     ) -> Box<dyn State<App>> {
+        let timing = match signal.stages[idx].stage_type {
+            StageType::Fixed(_) => "fixed",
+            StageType::Variable(_, _, _) => "variable",
+            StageType::Actuated { .. } => "actuated",
+            StageType::Adaptive { .. } => "adaptive",
+        };
         let panel = Panel::new_builder(Widget::col(vec![
             Widget::row(vec![
                 Line("How long should this stage last?")
@@ -45,18 +51,23 @@ impl ChangeDuration {
                 .secondary()
                 .into_widget(ctx),
             Widget::col(vec![
-                Text::from_all(match signal.stages[idx].stage_type {
-                    StageType::Fixed(_) => vec![
-                        Line("Fixed timing").small_heading(),
-                        Line(" (Adjust both values below to enable variable timing)"),
-                    ],
-                    StageType::Variable(_, _, _) => vec![
-                        Line("Variable timing").small_heading(),
-                        Line(" (Set either values below to 0 to use fixed timing."),
-                    ],
-                })
-                .into_widget(ctx)
-                .named("timing type"),
+                Widget::row(vec![
+                    "Timing:".text_widget(ctx).centered_vert(),
+                    Widget::dropdown(
+                        ctx,
+                        "timing",
+                        timing,
+                        vec![
+                            Choice::new("fixed", "fixed"),
+                            Choice::new("variable", "variable"),
+                            Choice::new("actuated", "actuated"),
+                            Choice::new("adaptive", "adaptive"),
+                        ],
+                    ),
+                ]),
+                describe_timing(timing)
+                    .into_widget(ctx)
+                    .named("timing type"),
                 Widget::row(vec![
                     "How much additional time can this stage last?"
                         .text_widget(ctx)
@@ -68,6 +79,15 @@ impl ChangeDuration {
                         match signal.stages[idx].stage_type {
                             StageType::Fixed(_) => Duration::ZERO,
                             StageType::Variable(_, _, additional) => additional,
+                            StageType::Actuated {
+                                min_green,
+                                max_green,
+                                ..
+                            }
+                            | StageType::Adaptive {
+                                min_green,
+                                max_green,
+                            } => max_green - min_green,
                         },
                         Duration::seconds(1.0),
                     ),
@@ -81,8 +101,9 @@ impl ChangeDuration {
                         "delay",
                         (Duration::ZERO, Duration::seconds(300.0)),
                         match signal.stages[idx].stage_type {
-                            StageType::Fixed(_) => Duration::ZERO,
+                            StageType::Fixed(_) | StageType::Adaptive { .. } => Duration::ZERO,
                             StageType::Variable(_, delay, _) => delay,
+                            StageType::Actuated { passage_time, .. } => passage_time,
                         },
                         Duration::seconds(1.0),
                     ),
@@ -110,10 +131,21 @@ impl SimpleState<App> for ChangeDuration {
                 let dt = panel.spinner("duration");
                 let delay = panel.spinner("delay");
                 let additional = panel.spinner("additional");
-                let new_type = if delay == Duration::ZERO || additional == Duration::ZERO {
-                    StageType::Fixed(dt)
-                } else {
-                    StageType::Variable(dt, delay, additional)
+                let timing: &'static str = panel.dropdown_value("timing");
+                let new_type = match timing {
+                    "variable" if delay != Duration::ZERO && additional != Duration::ZERO => {
+                        StageType::Variable(dt, delay, additional)
+                    }
+                    "actuated" => StageType::Actuated {
+                        min_green: dt,
+                        passage_time: delay.max(Duration::seconds(1.0)),
+                        max_green: dt + additional,
+                    },
+                    "adaptive" => StageType::Adaptive {
+                        min_green: dt,
+                        max_green: dt + additional,
+                    },
+                    _ => StageType::Fixed(dt),
                 };
                 let idx = self.idx;
                 Transition::Multi(vec![
@@ -136,22 +168,8 @@ impl SimpleState<App> for ChangeDuration {
         _: &mut App,
         panel: &mut Panel,
     ) -> Option<Transition> {
-        let new_label = Text::from_all(
-            if panel.spinner::<Duration>("delay") == Duration::ZERO
-                || panel.spinner::<Duration>("additional") == Duration::ZERO
-            {
-                vec![
-                    Line("Fixed timing").small_heading(),
-                    Line(" (Adjust both values below to enable variable timing)"),
-                ]
-            } else {
-                vec![
-                    Line("Variable timing").small_heading(),
-                    Line(" (Set either values below to 0 to use fixed timing."),
-                ]
-            },
-        )
-        .into_widget(ctx);
+        let timing: &'static str = panel.dropdown_value("timing");
+        let new_label = describe_timing(timing).into_widget(ctx);
         panel.replace(ctx, "timing type", new_label);
         None
     }
@@ -168,6 +186,27 @@ impl SimpleState<App> for ChangeDuration {
     }
 }
 
+fn describe_timing(timing: &str) -> Text {
+    Text::from_all(match timing {
+        "variable" => vec![
+            Line("Variable timing").small_heading(),
+            Line(" (Set either value below to 0 to use fixed timing)"),
+        ],
+        "actuated" => vec![
+            Line("Actuated timing").small_heading(),
+            Line(" (Skipped with no demand. Vehicles arriving within the delay extend it)"),
+        ],
+        "adaptive" => vec![
+            Line("Adaptive timing").small_heading(),
+            Line(" (Additional time is re-split each cycle by queue length)"),
+        ],
+        _ => vec![
+            Line("Fixed timing").small_heading(),
+            Line(" (The values below are ignored)"),
+        ],
+    })
+}
+
 pub fn edit_entire_signal(
     ctx: &mut EventCtx,
     app: &App,
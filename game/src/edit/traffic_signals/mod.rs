@@ -678,6 +678,18 @@ fn make_side_panel(
                     "Stage duration: {}, {}, {} (variable)",
                     min, delay, additional
                 ),
+                StageType::Actuated {
+                    min_green,
+                    passage_time,
+                    max_green,
+                } => format!(
+                    "Stage duration: {} to {}, extended by {} (actuated)",
+                    min_green, max_green, passage_time
+                ),
+                StageType::Adaptive {
+                    min_green,
+                    max_green,
+                } => format!("Stage duration: {} to {} (adaptive)", min_green, max_green),
             }
             .text_widget(ctx)
             .centered_vert(),
@@ -719,6 +731,8 @@ fn make_side_panel(
                 match canonical_signal.stages[idx].stage_type {
                     StageType::Fixed(d) => format!("{}", d),
                     StageType::Variable(min, _, _) => format!("{} (v)", min),
+                    StageType::Actuated { min_green, .. } => format!("{} (act)", min_green),
+                    StageType::Adaptive { min_green, .. } => format!("{} (ad)", min_green),
                 },
             )
             .text_widget(ctx),
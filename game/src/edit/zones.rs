@@ -7,8 +7,8 @@ use map_gui::tools::ColorDiscrete;
 use map_model::{AccessRestrictions, PathConstraints, RoadID};
 use sim::TripMode;
 use widgetry::{
-    Color, Drawable, EventCtx, GfxCtx, HorizontalAlignment, Key, Line, Outcome, Panel, State, Text,
-    VerticalAlignment, Widget,
+    Color, Drawable, EventCtx, GfxCtx, HorizontalAlignment, Key, Line, Outcome, Panel, Spinner,
+    State, Text, VerticalAlignment, Widget,
 };
 
 use crate::app::{App, Transition};
@@ -41,6 +41,7 @@ impl ZoneEditor {
             .into_iter()
             .map(TripMode::from_constraints)
             .collect();
+        let cap_vehicles_per_hour = start.access_restrictions.cap_vehicles_per_hour.unwrap_or(0);
 
         let (unzoomed, zoomed, legend) = draw_zone(ctx, app, &members);
         let orig_members = members.clone();
@@ -55,6 +56,18 @@ impl ZoneEditor {
                 legend,
                 make_instructions(ctx, &allow_through_traffic).named("instructions"),
                 checkbox_per_mode(ctx, app, &allow_through_traffic),
+                Widget::row(vec![
+                    "Cap vehicles entering per hour (0 for no cap)"
+                        .text_widget(ctx)
+                        .centered_vert(),
+                    Spinner::widget(
+                        ctx,
+                        "cap_vehicles_per_hour",
+                        (0, 10_000),
+                        cap_vehicles_per_hour,
+                        10,
+                    ),
+                ]),
                 Widget::custom_row(vec![
                     ctx.style()
                         .btn_solid_primary
@@ -105,8 +118,10 @@ impl State<App> for ZoneEditor {
                     // The original allow_through_traffic always includes this, and there's no way
                     // to exclude it, so stay consistent.
                     allow_through_traffic.insert(PathConstraints::Train);
+                    let cap: usize = self.panel.spinner("cap_vehicles_per_hour");
                     let new_access_restrictions = AccessRestrictions {
                         allow_through_traffic,
+                        cap_vehicles_per_hour: if cap == 0 { None } else { Some(cap) },
                     };
                     for r in &self.selector.roads {
                         let old_access_restrictions =
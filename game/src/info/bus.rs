@@ -55,6 +55,7 @@ fn stop_body(ctx: &mut EventCtx, app: &App, details: &mut Details, id: BusStopID
 
     let mut boardings: Counter<BusRouteID> = Counter::new();
     let mut alightings: Counter<BusRouteID> = Counter::new();
+    let mut denied: Counter<BusRouteID> = Counter::new();
     if let Some(list) = app.primary.sim.get_analytics().passengers_boarding.get(&id) {
         for (_, r, _) in list {
             boardings.inc(*r);
@@ -71,13 +72,19 @@ fn stop_body(ctx: &mut EventCtx, app: &App, details: &mut Details, id: BusStopID
             alightings.inc(*r);
         }
     }
+    if let Some(list) = app.primary.sim.get_analytics().passengers_denied.get(&id) {
+        for (_, r) in list {
+            denied.inc(*r);
+        }
+    }
     let mut txt = Text::new();
     txt.add_line("Total");
     txt.append(
         Line(format!(
-            ": {} boardings, {} alightings",
+            ": {} boardings, {} alightings, {} denied boardings",
             prettyprint_usize(boardings.sum()),
-            prettyprint_usize(alightings.sum())
+            prettyprint_usize(alightings.sum()),
+            prettyprint_usize(denied.sum())
         ))
         .secondary(),
     );
@@ -85,9 +92,10 @@ fn stop_body(ctx: &mut EventCtx, app: &App, details: &mut Details, id: BusStopID
         txt.add_line(format!("Route {}", r.short_name));
         txt.append(
             Line(format!(
-                ": {} boardings, {} alightings",
+                ": {} boardings, {} alightings, {} denied boardings",
                 prettyprint_usize(boardings.get(r.id)),
-                prettyprint_usize(alightings.get(r.id))
+                prettyprint_usize(alightings.get(r.id)),
+                prettyprint_usize(denied.get(r.id))
             ))
             .secondary(),
         );
@@ -129,6 +137,7 @@ fn bus_status_body(ctx: &mut EventCtx, app: &App, details: &mut Details, id: Car
         Tab::BusRoute(route.id),
     );
 
+    let capacity = app.primary.sim.transit_capacity(id);
     rows.push(
         Line(format!(
             "Currently has {} passengers",
@@ -136,6 +145,14 @@ fn bus_status_body(ctx: &mut EventCtx, app: &App, details: &mut Details, id: Car
         ))
         .into_widget(ctx),
     );
+    rows.push(
+        Line(format!(
+            "Capacity: {} seated, {} standing",
+            capacity.seated, capacity.standing
+        ))
+        .secondary()
+        .into_widget(ctx),
+    );
 
     Widget::col(rows)
 }
@@ -265,6 +282,44 @@ fn route_body(ctx: &mut EventCtx, app: &App, details: &mut Details, id: BusRoute
         .into_widget(ctx),
     );
 
+    {
+        let analytics = app.primary.sim.get_analytics();
+        let now = app.primary.sim.time();
+        let mut txt = Text::new();
+        if let Some(pct) = analytics.on_time_performance(id, now) {
+            txt.add_line(Line(format!(
+                "{}% of arrivals on time",
+                (pct * 100.0).round()
+            )));
+        }
+        let (bunched, total) = analytics.bunched_arrivals(id, now);
+        if total > 0 {
+            txt.add_line(
+                Line(format!(
+                    "{} / {} arrivals bunched with the previous vehicle",
+                    prettyprint_usize(bunched),
+                    prettyprint_usize(total)
+                ))
+                .secondary(),
+            );
+        }
+        let (changes, saved, imposed) = analytics.signal_priority_summary(None, Some(id), now);
+        if changes > 0 {
+            txt.add_line(
+                Line(format!(
+                    "Signal priority saved {} over {} requests, delaying others by {}",
+                    saved,
+                    prettyprint_usize(changes),
+                    imposed
+                ))
+                .secondary(),
+            );
+        }
+        if !txt.is_empty() {
+            rows.push(txt.into_widget(ctx));
+        }
+    }
+
     rows.push(format!("{} stops", route.stops.len()).text_widget(ctx));
     {
         let i = map.get_i(map.get_l(route.start).src_i);
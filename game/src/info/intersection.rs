@@ -87,6 +87,23 @@ fn traffic_body(ctx: &mut EventCtx, app: &App, id: IntersectionID, opts: &DataOp
                 .total_for(id)
         )
     ));
+    let (changes, saved, imposed) = app.primary.sim.get_analytics().signal_priority_summary(
+        Some(id),
+        None,
+        app.primary.sim.time(),
+    );
+    if changes > 0 {
+        txt.add_line(
+            Line(format!(
+                "{} signal priority changes saved buses and emergency vehicles {}, delaying \
+                 others by {}",
+                prettyprint_usize(changes),
+                saved,
+                imposed
+            ))
+            .secondary(),
+        );
+    }
     rows.push(txt.into_widget(ctx));
 
     rows.push(opts.to_controls(ctx, app));
@@ -340,6 +357,26 @@ fn traffic_signal_body(ctx: &mut EventCtx, app: &App, id: IntersectionID) -> Wid
                     delay,
                     additional
                 )),
+                StageType::Actuated {
+                    min_green,
+                    passage_time,
+                    max_green,
+                } => Line(format!(
+                    "Stage {}: {} to {}, extended by {} (actuated)",
+                    idx + 1,
+                    min_green,
+                    max_green,
+                    passage_time
+                )),
+                StageType::Adaptive {
+                    min_green,
+                    max_green,
+                } => Line(format!(
+                    "Stage {}: {} to {} (adaptive)",
+                    idx + 1,
+                    min_green,
+                    max_green
+                )),
             }
             .into_widget(ctx),
         );
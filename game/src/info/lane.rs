@@ -2,6 +2,7 @@ use std::collections::HashSet;
 
 use abstutil::prettyprint_usize;
 use map_model::{LaneID, PathConstraints};
+use sim::ParkingSpot;
 use widgetry::{EventCtx, Line, LinePlot, PlotOptions, Series, Text, TextExt, Widget};
 
 use crate::app::App;
@@ -47,12 +48,43 @@ fn info_body(ctx: &EventCtx, app: &App, id: LaneID) -> Widget {
                 l.number_parking_spots(app.primary.map.get_config())
             ),
         ));
+        if !r.parking_rules.is_unrestricted() {
+            kv.push(("Parking rules", r.parking_rules.describe()));
+            let revenue: f64 = app
+                .primary
+                .sim
+                .get_analytics()
+                .parking_stays
+                .iter()
+                .filter(|(_, spot, _, _, _)| match spot {
+                    ParkingSpot::Onstreet(x, _) => *x == l.id,
+                    _ => false,
+                })
+                .map(|(_, _, _, price, _)| *price)
+                .sum();
+            kv.push(("Parking revenue so far", format!("${:.2}", revenue)));
+        }
     } else {
         kv.push(("Speed limit", r.speed_limit.to_string(&app.opts.units)));
     }
 
     kv.push(("Length", l.length().to_string(&app.opts.units)));
 
+    if l.is_walkable() {
+        let analytics = app.primary.sim.get_analytics();
+        if let Some(worst) = analytics.worst_pedestrian_los(l.id) {
+            let hour = app.primary.sim.time().get_hours();
+            let now = analytics
+                .pedestrian_los(l.id, hour)
+                .map(|los| format!("{:?}", los))
+                .unwrap_or_else(|| "A".to_string());
+            kv.push((
+                "Pedestrian level of service",
+                format!("{} this hour, {:?} at worst", now, worst),
+            ));
+        }
+    }
+
     rows.extend(make_table(ctx, kv));
 
     if l.is_parking() {
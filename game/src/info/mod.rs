@@ -18,7 +18,7 @@ use widgetry::{
 use crate::app::{App, Transition};
 use crate::common::{color_for_agent_type, Warping};
 use crate::debug::path_counter::PathCounter;
-use crate::edit::{EditMode, RouteEditor};
+use crate::edit::{EditMode, ParkingRulesEditor, RouteEditor};
 use crate::layer::PANEL_PLACEMENT;
 use crate::sandbox::{dashboards, GameplayMode, SandboxMode, TimeWarpScreen};
 
@@ -584,6 +584,22 @@ impl InfoPanel {
                             )),
                         ])),
                     )
+                } else if let Some(x) = action.strip_prefix("edit Parking lot #") {
+                    (
+                        false,
+                        Some(Transition::Multi(vec![
+                            Transition::Push(EditMode::new_state(
+                                ctx,
+                                app,
+                                ctx_actions.gameplay_mode(),
+                            )),
+                            Transition::Push(ParkingRulesEditor::new_lot(
+                                ctx,
+                                app,
+                                ParkingLotID(x.parse::<usize>().unwrap()),
+                            )),
+                        ])),
+                    )
                 } else if action == "Explore demand across all traffic signals" {
                     (
                         false,
@@ -2,7 +2,8 @@ use std::collections::HashSet;
 
 use abstutil::prettyprint_usize;
 use map_model::ParkingLotID;
-use widgetry::{EventCtx, Line, LinePlot, PlotOptions, Series, TextExt, Widget};
+use sim::ParkingSpot;
+use widgetry::{EventCtx, Key, Line, LinePlot, PlotOptions, Series, TextExt, Widget};
 
 use crate::app::App;
 use crate::info::{header_btns, make_tabs, Details, Tab};
@@ -27,6 +28,35 @@ fn info_body(ctx: &mut EventCtx, app: &App, id: ParkingLotID) -> Widget {
         )
         .text_widget(ctx),
     );
+    if !pl.parking_rules.is_unrestricted() {
+        let revenue: f64 = app
+            .primary
+            .sim
+            .get_analytics()
+            .parking_stays
+            .iter()
+            .filter(|(_, spot, _, _, _)| match spot {
+                ParkingSpot::Lot(x, _) => *x == pl.id,
+                _ => false,
+            })
+            .map(|(_, _, _, price, _)| *price)
+            .sum();
+        rows.push(
+            format!(
+                "Parking is {}. ${:.2} collected so far",
+                pl.parking_rules.describe(),
+                revenue
+            )
+            .text_widget(ctx),
+        );
+    }
+    rows.push(
+        ctx.style()
+            .btn_outline
+            .text("Edit parking rules")
+            .hotkey(Key::E)
+            .build_widget(ctx, format!("edit {}", pl.id)),
+    );
 
     let mut series = vec![Series {
         label: format!("After \"{}\"", app.primary.map.get_edits().edits_name),
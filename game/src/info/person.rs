@@ -149,6 +149,8 @@ fn trips_body(
                         TripMode::Bike => "system/assets/meters/bike.svg",
                         TripMode::Drive => "system/assets/meters/car.svg",
                         TripMode::Transit => "system/assets/meters/bus.svg",
+                        TripMode::Carpool => "system/assets/meters/car.svg",
+                        TripMode::RideHail => "system/assets/meters/car.svg",
                     },
                 )
                 // we want the icon to be about the same height as the text
@@ -553,17 +555,20 @@ fn parked_car_body(ctx: &mut EventCtx, app: &App, details: &mut Details, id: Car
     // TODO prev trips, next trips, etc
     let mut rows = vec![];
 
-    let p = app.primary.sim.get_owner_of_car(id).unwrap();
-    rows.push(
-        ctx.style()
-            .btn_outline
-            .text(format!("Owned by {}", p))
-            .build_def(ctx),
-    );
-    details.hyperlinks.insert(
-        format!("Owned by {}", p),
-        Tab::PersonTrips(p, BTreeMap::new()),
-    );
+    if let Some(p) = app.primary.sim.get_owner_of_car(id) {
+        rows.push(
+            ctx.style()
+                .btn_outline
+                .text(format!("Owned by {}", p))
+                .build_def(ctx),
+        );
+        details.hyperlinks.insert(
+            format!("Owned by {}", p),
+            Tab::PersonTrips(p, BTreeMap::new()),
+        );
+    } else {
+        rows.push("Part of the ride-hailing fleet".text_widget(ctx));
+    }
 
     if let Some(p) = app.primary.sim.lookup_parked_car(id) {
         match p.spot {
@@ -627,11 +632,19 @@ fn header(
                     }
                     AgentID::Car(c) => match c.vehicle_type {
                         VehicleType::Car => ("driving", Some("system/assets/meters/car.svg")),
+                        VehicleType::Emergency => (
+                            "responding to an emergency",
+                            Some("system/assets/meters/car.svg"),
+                        ),
                         VehicleType::Bike => ("biking", Some("system/assets/meters/bike.svg")),
                         VehicleType::Bus | VehicleType::Train => unreachable!(),
                     },
-                    AgentID::BusPassenger(_, _) => {
-                        ("riding a bus", Some("system/assets/meters/bus.svg"))
+                    AgentID::BusPassenger(_, c) => {
+                        if c.vehicle_type == VehicleType::Car {
+                            ("riding in a car", Some("system/assets/meters/car.svg"))
+                        } else {
+                            ("riding a bus", Some("system/assets/meters/bus.svg"))
+                        }
                     }
                 }
             } else {
@@ -2,7 +2,7 @@ use std::collections::{BTreeMap, HashSet};
 
 use maplit::btreemap;
 
-use geom::{Distance, Duration, Percent, Polygon, Pt2D};
+use geom::{Circle, Distance, Duration, Percent, Polygon, Pt2D};
 use map_gui::ID;
 use map_model::{Map, Path, PathStep, Traversable};
 use sim::{
@@ -329,6 +329,19 @@ pub fn finished(
             waiting.to_string(&app.opts.units).text_widget(ctx),
         ]));
 
+        let analytics = if open_trips[&id].show_after {
+            app.primary.sim.get_analytics()
+        } else {
+            app.prebaked()
+        };
+        if let Some(emissions) = analytics.trip_emissions.get(&id) {
+            col.push(Widget::custom_row(vec![
+                Widget::custom_row(vec![Line("Emissions").secondary().into_widget(ctx)])
+                    .force_width_window_pct(ctx, col_width),
+                emissions.describe().text_widget(ctx),
+            ]));
+        }
+
         col.push(Widget::custom_row(vec![
             Widget::custom_row(vec![Line("Purpose").secondary().into_widget(ctx)])
                 .force_width_window_pct(ctx, col_width),
@@ -442,6 +455,7 @@ fn describe_problems(
                     }
                     Problem::ArterialIntersectionCrossing(_) => {}
                     Problem::IntersectionDelay(_, _) => {}
+                    Problem::DeniedBoarding(_) => {}
                 }
             }
             let mut txt = Text::new();
@@ -587,6 +601,24 @@ fn draw_problems(
                     ]),
                 ));
             }
+            Problem::DeniedBoarding(bs) => {
+                let pt = map.get_bs(*bs).sidewalk_pos.pt(map);
+                details.unzoomed.append(
+                    GeomBatch::load_svg(ctx, "system/assets/tools/alert.svg")
+                        .centered_on(pt)
+                        .color(RewriteColor::ChangeAlpha(0.8)),
+                );
+                details.zoomed.append(
+                    GeomBatch::load_svg(ctx, "system/assets/tools/alert.svg")
+                        .scale(0.5)
+                        .color(RewriteColor::ChangeAlpha(0.5))
+                        .centered_on(pt),
+                );
+                details.tooltips.push((
+                    Circle::new(pt, Distance::meters(10.0)).to_polygon(),
+                    Text::from("The vehicle arriving here was full, so this rider had to wait."),
+                ));
+            }
         }
     }
 }
@@ -689,6 +721,10 @@ fn make_timeline(
                         "system/assets/timeline/waiting_for_bus.svg"
                     }
                     TripPhaseType::RidingBus(_, _, _) => "system/assets/timeline/riding_bus.svg",
+                    TripPhaseType::WaitingForCarpool | TripPhaseType::WaitingForRideHail => {
+                        "system/assets/timeline/delayed_start.svg"
+                    }
+                    TripPhaseType::RidingCar(_) => "system/assets/timeline/driving.svg",
                     TripPhaseType::Cancelled | TripPhaseType::Finished => unreachable!(),
                     TripPhaseType::DelayedStart => "system/assets/timeline/delayed_start.svg",
                 },
@@ -11,6 +11,7 @@ use crate::sandbox::dashboards;
 mod elevation;
 pub mod favorites;
 pub mod map;
+mod noise;
 mod pandemic;
 mod parking;
 mod population;
@@ -104,6 +105,7 @@ impl PickLayer {
                     btn("throughput", Key::T),
                     btn("traffic jams", Key::J),
                     btn("cycling activity", Key::B),
+                    btn("noise", Key::W),
                 ]),
                 Widget::col(vec![
                     "Map".text_widget(ctx),
@@ -179,6 +181,9 @@ impl State<App> for PickLayer {
                 "elevation" => {
                     app.primary.layer = Some(Box::new(elevation::ElevationContours::new(ctx, app)));
                 }
+                "noise" => {
+                    app.primary.layer = Some(Box::new(noise::Noise::new(ctx, app)));
+                }
                 "map edits" => {
                     app.primary.layer = Some(Box::new(map::Static::edits(ctx, app)));
                 }
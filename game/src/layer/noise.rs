@@ -0,0 +1,176 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Write;
+
+use anyhow::Result;
+
+use geom::Time;
+use map_gui::tools::{ColorLegend, ColorNetwork};
+use map_gui::ID;
+use map_model::RoadID;
+use widgetry::{Drawable, EventCtx, GfxCtx, Line, Outcome, Panel, Text, TextExt, Widget};
+
+use crate::app::App;
+use crate::layer::{header, Layer, LayerOutcome, PANEL_PLACEMENT};
+
+/// Anything quieter than this is shown as the lowest color
+const QUIET_DB: f64 = 45.0;
+/// And anything louder than this, the highest
+const LOUD_DB: f64 = 75.0;
+
+pub struct Noise {
+    time: Time,
+    roads: BTreeMap<RoadID, f64>,
+    tooltip: Option<Text>,
+    unzoomed: Drawable,
+    zoomed: Drawable,
+    panel: Panel,
+}
+
+impl Layer for Noise {
+    fn name(&self) -> Option<&'static str> {
+        Some("noise")
+    }
+    fn event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Option<LayerOutcome> {
+        let mut recalc_tooltip = false;
+        if app.primary.sim.time() != self.time {
+            *self = Noise::new(ctx, app);
+            recalc_tooltip = true;
+        }
+
+        if ctx.canvas.cam_zoom < app.opts.min_zoom_for_detail {
+            if ctx.redo_mouseover() || recalc_tooltip {
+                self.tooltip = None;
+                if let Some(ID::Road(r)) = app.mouseover_unzoomed_roads_and_intersections(ctx) {
+                    self.tooltip = Some(Text::from(format!("{:.1} dB", self.roads[&r])));
+                }
+            }
+        } else {
+            self.tooltip = None;
+        }
+
+        match self.panel.event(ctx) {
+            Outcome::Clicked(x) => match x.as_ref() {
+                "close" => {
+                    return Some(LayerOutcome::Close);
+                }
+                "Export to CSV" => {
+                    let msg = match export_noise(app) {
+                        Ok(paths) => format!("Data exported to {}", paths.join(" and ")),
+                        Err(err) => format!("Export failed: {}", err),
+                    };
+                    self.panel.replace(
+                        ctx,
+                        "export result",
+                        Text::from(Line(msg).secondary())
+                            .wrap_to_pct(ctx, 15)
+                            .into_widget(ctx)
+                            .named("export result"),
+                    );
+                }
+                _ => unreachable!(),
+            },
+            _ => {}
+        }
+        None
+    }
+    fn draw(&self, g: &mut GfxCtx, app: &App) {
+        self.panel.draw(g);
+        if g.canvas.cam_zoom < app.opts.min_zoom_for_detail {
+            g.redraw(&self.unzoomed);
+        } else {
+            g.redraw(&self.zoomed);
+        }
+        if let Some(ref txt) = self.tooltip {
+            g.draw_mouse_tooltip(txt.clone());
+        }
+    }
+    fn draw_minimap(&self, g: &mut GfxCtx) {
+        g.redraw(&self.unzoomed);
+    }
+}
+
+impl Noise {
+    pub fn new(ctx: &mut EventCtx, app: &App) -> Noise {
+        let map = &app.primary.map;
+        let analytics = app.primary.sim.get_analytics();
+        let roads = analytics.road_noise(map, app.primary.sim.time().get_hours());
+        let buildings = sim::building_noise(&roads, map);
+
+        let scale = &app.cs.good_to_bad_red;
+        let pct = |db: f64| ((db - QUIET_DB) / (LOUD_DB - QUIET_DB)).max(0.0).min(1.0);
+        let mut colorer = ColorNetwork::new(app);
+        for (r, db) in &roads {
+            colorer.add_r(*r, scale.eval(pct(*db)));
+        }
+        for (b, db) in buildings {
+            colorer.add_b(b, scale.eval(pct(db)));
+        }
+        let (unzoomed, zoomed) = colorer.build(ctx);
+
+        let panel = Panel::new_builder(Widget::col(vec![
+            header(ctx, "Noise"),
+            Text::from(
+                Line(
+                    "Estimated traffic noise during the current hour, along roads and at buildings",
+                )
+                .secondary(),
+            )
+            .wrap_to_pct(ctx, 15)
+            .into_widget(ctx),
+            ColorLegend::gradient(
+                ctx,
+                scale,
+                vec![format!("{} dB", QUIET_DB), format!("{} dB", LOUD_DB)],
+            ),
+            ctx.style().btn_outline.text("Export to CSV").build_def(ctx),
+            "".text_widget(ctx).named("export result"),
+        ]))
+        .aligned_pair(PANEL_PLACEMENT)
+        .build(ctx);
+
+        Noise {
+            time: app.primary.sim.time(),
+            roads,
+            tooltip: None,
+            unzoomed,
+            zoomed,
+            panel,
+        }
+    }
+}
+
+/// Writes the noise level of every road and building for each hour so far. Returns the paths.
+fn export_noise(app: &App) -> Result<Vec<String>> {
+    let map = &app.primary.map;
+    let analytics = app.primary.sim.get_analytics();
+    let suffix = format!(
+        "{}_{}.csv",
+        map.get_name().as_filename(),
+        app.primary.sim.time().as_filename()
+    );
+    let roads_path = format!("road_noise_{}", suffix);
+    let bldgs_path = format!("building_noise_{}", suffix);
+    let mut roads_file = File::create(&roads_path)?;
+    let mut bldgs_file = File::create(&bldgs_path)?;
+    writeln!(roads_file, "road,osm_way_id,hour,decibels")?;
+    writeln!(bldgs_file, "building,hour,decibels")?;
+
+    for hour in 0..=app.primary.sim.time().get_hours() {
+        let roads = analytics.road_noise(map, hour);
+        for (b, db) in sim::building_noise(&roads, map) {
+            writeln!(bldgs_file, "{},{},{:.1}", b.0, hour, db)?;
+        }
+        for (r, db) in roads {
+            writeln!(
+                roads_file,
+                "{},{},{},{:.1}",
+                r.0,
+                map.get_r(r).orig_id.osm_way_id.0,
+                hour,
+                db
+            )?;
+        }
+    }
+    Ok(vec![roads_path, bldgs_path])
+}
@@ -73,6 +73,12 @@ impl ProblemMap {
                         Problem::ArterialIntersectionCrossing(t) => {
                             app.primary.map.get_t(*t).geom.middle()
                         }
+                        Problem::DeniedBoarding(bs) => app
+                            .primary
+                            .map
+                            .get_bs(*bs)
+                            .sidewalk_pos
+                            .pt(&app.primary.map),
                     });
                 }
             }
@@ -133,6 +139,9 @@ impl ProblemMap {
             show_arterial_crossings: self
                 .panel
                 .is_checked("show where pedestrians cross arterial intersections"),
+            show_denied_boardings: self
+                .panel
+                .is_checked("show where transit riders couldn't board full vehicles"),
         }
     }
 }
@@ -148,6 +157,7 @@ pub struct Options {
     show_complex_crossings: bool,
     show_overtakes: bool,
     show_arterial_crossings: bool,
+    show_denied_boardings: bool,
     // TODO Time range
 }
 
@@ -162,6 +172,7 @@ impl Options {
             show_complex_crossings: true,
             show_overtakes: true,
             show_arterial_crossings: true,
+            show_denied_boardings: true,
         }
     }
 
@@ -174,6 +185,7 @@ impl Options {
             Problem::ComplexIntersectionCrossing(_) => self.show_complex_crossings,
             Problem::OvertakeDesired(_) => self.show_overtakes,
             Problem::ArterialIntersectionCrossing(_) => self.show_arterial_crossings,
+            Problem::DeniedBoarding(_) => self.show_denied_boardings,
         }
     }
 }
@@ -237,6 +249,12 @@ fn make_controls(
         None,
         opts.show_arterial_crossings,
     ));
+    col.push(Toggle::checkbox(
+        ctx,
+        "show where transit riders couldn't board full vehicles",
+        None,
+        opts.show_denied_boardings,
+    ));
 
     col.push(Toggle::choice(
         ctx,
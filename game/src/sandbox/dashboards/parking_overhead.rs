@@ -1,5 +1,6 @@
-use geom::Duration;
-use sim::{TripEndpoint, TripID, TripPhaseType};
+use abstutil::prettyprint_usize;
+use geom::{Distance, Duration};
+use sim::{ParkingArea, TripEndpoint, TripID, TripPhaseType};
 use widgetry::table::{Col, Filter, Table};
 use widgetry::{
     EventCtx, Filler, GeomBatch, GfxCtx, Line, Outcome, Panel, State, Text, Toggle, Widget,
@@ -20,36 +21,38 @@ pub struct ParkingOverhead {
 impl ParkingOverhead {
     pub fn new_state(ctx: &mut EventCtx, app: &App) -> Box<dyn State<App>> {
         let table = make_table(app);
+        let mut txt = Text::from_multiline(vec![
+            Line(
+                "Trips taken by car also include time to walk between the building \
+                 and parking spot, as well as the time to find parking.",
+            ),
+            Line("Overhead is 1 - driving time / total time"),
+            Line("Ideally, overhead is 0% -- the entire trip is just spent driving."),
+            Line(""),
+            Line("High overhead could mean:"),
+            Line(
+                "- the car burned more resources and caused more traffic looking for \
+                 parking",
+            ),
+            Line(
+                "- somebody with impaired movement had to walk far to reach their \
+                 vehicle",
+            ),
+            Line("- the person was inconvenienced"),
+            Line(""),
+            Line(
+                "Note: Trips beginning/ending outside the map have an artifically \
+                 high overhead,",
+            ),
+            Line("since the time spent driving off-map isn't shown here."),
+            Line(""),
+        ]);
+        txt.extend(cruising_summary(app));
         let col = Widget::col(vec![
             DashTab::ParkingOverhead.picker(ctx, app),
             Widget::col(vec![
                 Widget::row(vec![
-                    Text::from_multiline(vec![
-                        Line(
-                            "Trips taken by car also include time to walk between the building \
-                             and parking spot, as well as the time to find parking.",
-                        ),
-                        Line("Overhead is 1 - driving time / total time"),
-                        Line("Ideally, overhead is 0% -- the entire trip is just spent driving."),
-                        Line(""),
-                        Line("High overhead could mean:"),
-                        Line(
-                            "- the car burned more resources and caused more traffic looking for \
-                             parking",
-                        ),
-                        Line(
-                            "- somebody with impaired movement had to walk far to reach their \
-                             vehicle",
-                        ),
-                        Line("- the person was inconvenienced"),
-                        Line(""),
-                        Line(
-                            "Note: Trips beginning/ending outside the map have an artifically \
-                             high overhead,",
-                        ),
-                        Line("since the time spent driving off-map isn't shown here."),
-                    ])
-                    .into_widget(ctx),
+                    txt.into_widget(ctx),
                     Filler::square_width(ctx, 0.15).named("preview"),
                 ])
                 .evenly_spaced(),
@@ -110,6 +113,8 @@ struct Entry {
     driving_duration: Duration,
     parking_duration: Duration,
     walking_duration: Duration,
+    cruising_duration: Duration,
+    cruising_distance: Distance,
     percent_overhead: usize,
     starts_off_map: bool,
     ends_off_map: bool,
@@ -123,7 +128,8 @@ struct Filters {
 fn produce_raw_data(app: &App) -> Vec<Entry> {
     // Gather raw data
     let mut data = Vec::new();
-    for (id, phases) in app.primary.sim.get_analytics().get_all_trip_phases() {
+    let analytics = app.primary.sim.get_analytics();
+    for (id, phases) in analytics.get_all_trip_phases() {
         let trip = app.primary.sim.trip_info(id);
         let starts_off_map = matches!(trip.start, TripEndpoint::Border(_));
         let ends_off_map = matches!(trip.end, TripEndpoint::Border(_));
@@ -158,12 +164,19 @@ fn produce_raw_data(app: &App) -> Vec<Entry> {
             continue;
         }
 
+        let (cruising_duration, cruising_distance) = analytics
+            .cruising_for_parking
+            .get(&id)
+            .map(|(_, dt, dist, _)| (*dt, *dist))
+            .unwrap_or((Duration::ZERO, Distance::ZERO));
         data.push(Entry {
             trip: id,
             total_duration,
             driving_duration,
             parking_duration,
             walking_duration,
+            cruising_duration,
+            cruising_distance,
             percent_overhead: (100.0 * (1.0 - (driving_duration / total_duration))) as usize,
             starts_off_map,
             ends_off_map,
@@ -233,6 +246,20 @@ fn make_table(app: &App) -> Table<App, Entry, Filters> {
         }),
         Col::Sortable(Box::new(|rows| rows.sort_by_key(|x| x.walking_duration))),
     );
+    table.column(
+        "Time cruising",
+        Box::new(|ctx, app, x| {
+            Text::from(x.cruising_duration.to_string(&app.opts.units)).render(ctx)
+        }),
+        Col::Sortable(Box::new(|rows| rows.sort_by_key(|x| x.cruising_duration))),
+    );
+    table.column(
+        "Distance cruising",
+        Box::new(|ctx, app, x| {
+            Text::from(x.cruising_distance.to_string(&app.opts.units)).render(ctx)
+        }),
+        Col::Sortable(Box::new(|rows| rows.sort_by_key(|x| x.cruising_distance))),
+    );
     table.column(
         "Percent overhead",
         Box::new(|ctx, _, x| Text::from(format!("{}%", x.percent_overhead)).render(ctx)),
@@ -241,3 +268,61 @@ fn make_table(app: &App) -> Table<App, Entry, Filters> {
 
     table
 }
+
+/// Describe how much drivers cruised for parking overall, and where it was worst.
+fn cruising_summary(app: &App) -> Text {
+    let map = &app.primary.map;
+    let per_area = app.primary.sim.get_analytics().cruising_per_area(map);
+    let mut txt = Text::new();
+    if per_area.is_empty() {
+        txt.add_line(Line("Nobody has had to cruise for parking yet."));
+        return txt;
+    }
+
+    let mut trips = 0;
+    let mut total_time = Duration::ZERO;
+    let mut total_distance = Distance::ZERO;
+    let mut parked_illegally = 0;
+    let mut gave_up = 0;
+    for summary in &per_area {
+        trips += summary.trips;
+        total_time += summary.total_time;
+        total_distance += summary.total_distance;
+        parked_illegally += summary.parked_illegally;
+        gave_up += summary.gave_up;
+    }
+    txt.add_line(Line(format!(
+        "{} trips couldn't park near their destination and cruised for {} total, driving {}",
+        prettyprint_usize(trips),
+        total_time.to_string(&app.opts.units),
+        total_distance.to_string(&app.opts.units)
+    )));
+    if parked_illegally > 0 || gave_up > 0 {
+        txt.add_line(Line(format!(
+            "{} of them parked illegally, and {} gave up",
+            prettyprint_usize(parked_illegally),
+            prettyprint_usize(gave_up)
+        )));
+    }
+
+    let mut worst = per_area;
+    worst.sort_by_key(|summary| summary.total_time);
+    worst.reverse();
+    txt.add_line(Line("Most time spent cruising in:"));
+    for summary in worst.into_iter().take(3) {
+        let road = map
+            .get_r(summary.busiest_road)
+            .get_name(app.opts.language.as_ref());
+        let area = match summary.area {
+            ParkingArea::PermitZone(zone) => format!("permit zone {}, around {}", zone, road),
+            ParkingArea::Grid(_, _) => format!("the area around {}", road),
+        };
+        txt.add_line(Line(format!(
+            "- {}: {} trips, {} total",
+            area,
+            prettyprint_usize(summary.trips),
+            summary.total_time.to_string(&app.opts.units)
+        )));
+    }
+    txt
+}
@@ -17,6 +17,7 @@ pub enum ProblemType {
     ComplexIntersectionCrossing,
     OvertakeDesired,
     ArterialIntersectionCrossing,
+    DeniedBoarding,
 }
 
 impl From<&Problem> for ProblemType {
@@ -26,6 +27,7 @@ impl From<&Problem> for ProblemType {
             Problem::ComplexIntersectionCrossing(_) => Self::ComplexIntersectionCrossing,
             Problem::OvertakeDesired(_) => Self::OvertakeDesired,
             Problem::ArterialIntersectionCrossing(_) => Self::ArterialIntersectionCrossing,
+            Problem::DeniedBoarding(_) => Self::DeniedBoarding,
         }
     }
 }
@@ -47,6 +49,7 @@ impl ProblemType {
             ProblemType::ComplexIntersectionCrossing,
             ProblemType::OvertakeDesired,
             ProblemType::ArterialIntersectionCrossing,
+            ProblemType::DeniedBoarding,
         ]
     }
 }
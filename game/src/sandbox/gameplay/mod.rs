@@ -190,7 +190,9 @@ impl GameplayMode {
                     }
                     _ => {}
                 },
-                EditCmd::ChangeRouteSchedule { .. } => {}
+                EditCmd::ChangeRouteSchedule { .. }
+                | EditCmd::ChangeParkingLot { .. }
+                | EditCmd::ChangeRouteCapacity { .. } => {}
             }
         }
         true
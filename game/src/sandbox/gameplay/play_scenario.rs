@@ -275,6 +275,19 @@ impl EditScenarioModifiers {
                 .text("Repeat schedule multiple days")
                 .build_def(ctx),
         ]));
+        rows.push(Widget::row(vec![
+            Spinner::widget(ctx, "carpool_pct", (1, 100), 10_usize, 1),
+            ctx.style()
+                .btn_outline
+                .text("Form carpools from this percent of solo drivers")
+                .build_def(ctx),
+        ]));
+        rows.push(
+            ctx.style()
+                .btn_outline
+                .text("Choose modes and departure times")
+                .build_def(ctx),
+        );
         rows.push(Widget::horiz_separator(ctx, 1.0));
         rows.push(
             Widget::row(vec![
@@ -371,6 +384,24 @@ impl State<App> for EditScenarioModifiers {
                         self.modifiers.clone(),
                     ));
                 }
+                "Form carpools from this percent of solo drivers" => {
+                    self.modifiers.push(ScenarioModifier::FormCarpools {
+                        pct_ppl: self.panel.spinner("carpool_pct"),
+                    });
+                    return Transition::Replace(EditScenarioModifiers::new_state(
+                        ctx,
+                        self.scenario_name.clone(),
+                        self.modifiers.clone(),
+                    ));
+                }
+                "Choose modes and departure times" => {
+                    self.modifiers.push(ScenarioModifier::ChooseModes);
+                    return Transition::Replace(EditScenarioModifiers::new_state(
+                        ctx,
+                        self.scenario_name.clone(),
+                        self.modifiers.clone(),
+                    ));
+                }
                 x => {
                     if let Some(x) = x.strip_prefix("delete modifier ") {
                         self.modifiers.remove(x.parse::<usize>().unwrap() - 1);
@@ -438,7 +469,10 @@ impl ChangeMode {
                     Widget::dropdown(ctx, "to_mode", Some(TripMode::Bike), {
                         let mut choices = vec![Choice::new("cancel trip", None)];
                         for m in TripMode::all() {
-                            choices.push(Choice::new(m.ongoing_verb(), Some(m)));
+                            // Carpool passengers need a driver; use "Form carpools" instead
+                            if m != TripMode::Carpool {
+                                choices.push(Choice::new(m.ongoing_verb(), Some(m)));
+                            }
                         }
                         choices
                     }),
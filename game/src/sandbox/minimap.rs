@@ -253,6 +253,12 @@ fn make_agent_toggles(ctx: &mut EventCtx, app: &App, is_enabled: [bool; 4]) -> V
                 prettyprint_usize(counts.sov_drivers)
             ))
             .secondary(),
+            Line(format!(
+                "{} passengers in {} carpools",
+                prettyprint_usize(counts.car_passengers),
+                prettyprint_usize(counts.carpool_drivers)
+            ))
+            .secondary(),
         ]);
         colored_checkbox(
             ctx,
@@ -260,7 +266,7 @@ fn make_agent_toggles(ctx: &mut EventCtx, app: &App, is_enabled: [bool; 4]) -> V
             is_car_enabled,
             app.cs.unzoomed_car,
             "system/assets/meters/car.svg",
-            &prettyprint_usize(counts.sov_drivers),
+            &prettyprint_usize(counts.sov_drivers + counts.carpool_drivers),
             tooltip,
         )
     };
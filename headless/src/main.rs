@@ -8,15 +8,28 @@
 // it's now 01:01:00.0
 // > curl http://localhost:1234/data/get-road-thruput
 // ... huge JSON blob
+// > curl -N http://localhost:1234/stream/subscribe?kinds=trip_finished
+// ... one JSON line per event, as other requests advance the sim
+//
+// Every request applies to the "default" session, unless a `session` GET parameter says
+// otherwise. Each session has its own map, edits, scenario, and simulation:
+//
+// > curl -X POST http://localhost:1234/sessions/create?session=experiment -d '{"scenario": ...}'
+// > curl http://localhost:1234/sim/goto-time?session=experiment&t=01:00:00
+// > curl http://localhost:1234/sessions/destroy?session=experiment
+//
+// The default session can't be destroyed.
 
 #[macro_use]
 extern crate anyhow;
 #[macro_use]
 extern crate log;
 
+mod stream;
+
 use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::convert::TryFrom;
-use std::sync::RwLock;
+use std::sync::{Arc, Mutex, RwLock};
 
 use anyhow::Result;
 use hyper::{Body, Request, Response, Server, StatusCode};
@@ -28,48 +41,75 @@ use abstio::MapName;
 use abstutil::{serialize_btreemap, CmdArgs, Timer};
 use geom::{Distance, Duration, LonLat, Time};
 use map_model::{
-    CompressedMovementID, ControlTrafficSignal, EditCmd, EditIntersection, IntersectionID, Map,
-    MovementID, PermanentMapEdits, RoadID, TurnID,
+    BuildingID, CompressedMovementID, ControlTrafficSignal, EditCmd, EditEffects, EditIntersection,
+    IntersectionID, LaneID, Map, MapEdits, MovementID, ParkingLotID, PermanentEditCmd,
+    PermanentMapEdits, RoadID, TurnID,
 };
 use sim::{
-    AgentID, AgentType, DelayCause, ExternalPerson, PersonID, Scenario, ScenarioModifier, Sim,
-    SimFlags, SimOptions, TripID, TripMode, VehicleType,
+    AgentID, AgentType, AssignmentCosts, CruisingSummary, DelayCause, Emissions, ExternalPerson,
+    PersonID, Scenario, ScenarioModifier, SignalPriorityAction, Sim, SimFlags, SimOptions, TripID,
+    TripMode, VehicleType,
 };
 
+const DEFAULT_SESSION: &str = "default";
+
 lazy_static::lazy_static! {
-    static ref MAP: RwLock<Map> = RwLock::new(Map::blank());
-    static ref SIM: RwLock<Sim> = RwLock::new(Sim::new(&Map::blank(), SimOptions::new("tmp")));
-    static ref LOAD: RwLock<LoadSim> = RwLock::new({
-        LoadSim {
-            scenario: abstio::path_scenario(&MapName::seattle("montlake"), "weekday"),
-            modifiers: Vec::new(),
-            edits: None,
-            rng_seed: SimFlags::RNG_SEED,
-            opts: SimOptions::default(),
+    // Each session is locked separately, so they can run in parallel.
+    static ref SESSIONS: RwLock<BTreeMap<String, Arc<Mutex<Session>>>> =
+        RwLock::new(BTreeMap::new());
+}
+
+/// Everything needed to independently run one simulation
+struct Session {
+    map: Map,
+    sim: Sim,
+    load: LoadSim,
+    streams: Vec<stream::Subscriber>,
+    /// Saved in memory, by name
+    snapshots: BTreeMap<String, Snapshot>,
+}
+
+/// A copy of the simulation at some point, to restore later. The edits must match when restoring.
+#[derive(Clone, Serialize, Deserialize)]
+struct Snapshot {
+    sim: Sim,
+    edits: PermanentMapEdits,
+}
+
+impl Session {
+    fn new(load: LoadSim, timer: &mut Timer) -> Session {
+        let (map, sim) = load.setup(timer);
+        Session {
+            map,
+            sim,
+            load,
+            streams: Vec::new(),
+            snapshots: BTreeMap::new(),
         }
-    });
+    }
 }
 
 #[tokio::main]
 async fn main() {
     let mut args = CmdArgs::new();
     let mut timer = Timer::new("setup headless");
-    let rng_seed = args
-        .optional_parse("--rng_seed", |s| s.parse())
-        .unwrap_or(SimFlags::RNG_SEED);
-    let opts = SimOptions::from_args(&mut args, rng_seed);
+    let (rng_seed, opts) = parse_sim_flags(&mut args).unwrap();
     let port = args.required("--port").parse::<u16>().unwrap();
+    let assignment_costs = args.enabled("--assignment_costs");
     args.done();
 
-    {
-        let mut load = LOAD.write().unwrap();
-        load.rng_seed = rng_seed;
-        load.opts = opts;
-
-        let (map, sim) = load.setup(&mut timer);
-        *MAP.write().unwrap() = map;
-        *SIM.write().unwrap() = sim;
-    }
+    let load = LoadSim {
+        scenario: abstio::path_scenario(&MapName::seattle("montlake"), "weekday"),
+        modifiers: Vec::new(),
+        edits: None,
+        assignment_costs,
+        rng_seed,
+        opts,
+    };
+    SESSIONS.write().unwrap().insert(
+        DEFAULT_SESSION.to_string(),
+        Arc::new(Mutex::new(Session::new(load, &mut timer))),
+    );
 
     let addr = std::net::SocketAddr::from(([127, 0, 0, 1], port));
     info!("Listening on http://{}", addr);
@@ -92,35 +132,92 @@ async fn serve_req(req: Request<Body>) -> Result<Response<Body>, hyper::Error> {
             .collect();
     let body = hyper::body::to_bytes(req).await?.to_vec();
     info!("Handling {}", path);
-    Ok(
-        match handle_command(
-            &path,
-            &params,
-            &body,
-            &mut SIM.write().unwrap(),
-            &mut MAP.write().unwrap(),
-            &mut LOAD.write().unwrap(),
-        ) {
-            Ok(resp) => Response::new(Body::from(resp)),
-            Err(err) => {
-                error!("{}: {}", path, err);
-                Response::builder()
-                    .status(StatusCode::BAD_REQUEST)
-                    .body(Body::from(format!("Bad command {}: {}", path, err)))
-                    .unwrap()
+    Ok(match handle_request(&path, &params, &body) {
+        Ok(resp) => Response::new(resp),
+        Err(err) => {
+            error!("{}: {}", path, err);
+            Response::builder()
+                .status(StatusCode::BAD_REQUEST)
+                .body(Body::from(format!("Bad command {}: {}", path, err)))
+                .unwrap()
+        }
+    })
+}
+
+fn handle_request(path: &str, params: &HashMap<String, String>, body: &[u8]) -> Result<Body> {
+    let id = params
+        .get("session")
+        .map(|x| x.as_str())
+        .unwrap_or(DEFAULT_SESSION);
+
+    // Managing sessions
+    match path {
+        "/sessions/list" => {
+            let ids: Vec<String> = SESSIONS.read().unwrap().keys().cloned().collect();
+            return Ok(Body::from(abstutil::to_json(&ids)));
+        }
+        "/sessions/create" => {
+            if SESSIONS.read().unwrap().contains_key(id) {
+                bail!("session {} already exists", id);
+            }
+            let args: NewSession = abstutil::from_json(body)?;
+            let mut load = args.load;
+            let (rng_seed, opts) = try_parse_sim_flags(args.sim_flags)?;
+            load.rng_seed = rng_seed;
+            load.opts = opts;
+
+            // Don't block other sessions while loading
+            let session = Session::new(load, &mut Timer::new(format!("create session {}", id)));
+            let mut sessions = SESSIONS.write().unwrap();
+            if sessions.contains_key(id) {
+                bail!("session {} already exists", id);
+            }
+            sessions.insert(id.to_string(), Arc::new(Mutex::new(session)));
+            return Ok(Body::from(format!("session {} created", id)));
+        }
+        "/sessions/destroy" => {
+            if id == DEFAULT_SESSION {
+                bail!("the {} session can't be destroyed", DEFAULT_SESSION);
             }
-        },
-    )
+            // Any ongoing streams are closed when the session is dropped.
+            return if SESSIONS.write().unwrap().remove(id).is_some() {
+                Ok(Body::from(format!("session {} destroyed", id)))
+            } else {
+                Err(anyhow!("no session {}", id))
+            };
+        }
+        _ => {}
+    }
+
+    let session = SESSIONS
+        .read()
+        .unwrap()
+        .get(id)
+        .cloned()
+        .ok_or_else(|| anyhow!("no session {}", id))?;
+    let mut session = session.lock().unwrap();
+
+    // This one stays open, so handle it separately
+    if path == "/stream/subscribe" {
+        return stream::subscribe(params, &mut session.streams);
+    }
+
+    handle_command(path, params, body, &mut session).map(Body::from)
 }
 
 fn handle_command(
     path: &str,
     params: &HashMap<String, String>,
     body: &[u8],
-    sim: &mut Sim,
-    map: &mut Map,
-    load: &mut LoadSim,
+    session: &mut Session,
 ) -> Result<String> {
+    let Session {
+        map,
+        sim,
+        load,
+        streams,
+        snapshots,
+    } = session;
     let get = |key: &str| {
         params
             .get(key)
@@ -133,6 +230,7 @@ fn handle_command(
             let (new_map, new_sim) = load.setup(&mut Timer::new("reset sim"));
             *map = new_map;
             *sim = new_sim;
+            stream::reset(sim, streams);
             Ok("sim reloaded".to_string())
         }
         "/sim/load" => {
@@ -141,11 +239,13 @@ fn handle_command(
             load.scenario = args.scenario;
             load.modifiers = args.modifiers;
             load.edits = args.edits;
+            load.assignment_costs = args.assignment_costs;
 
             // Also reset
             let (new_map, new_sim) = load.setup(&mut Timer::new("reset sim"));
             *map = new_map;
             *sim = new_sim;
+            stream::reset(sim, streams);
 
             Ok("flags changed and sim reloaded".to_string())
         }
@@ -155,11 +255,59 @@ fn handle_command(
             if t <= sim.time() {
                 bail!("{} is in the past. call /sim/reset first?", t)
             } else {
-                let dt = t - sim.time();
-                sim.timed_step(map, dt, &mut None, &mut Timer::new("goto-time"));
+                stream::goto_time(sim, map, t, streams);
                 Ok(format!("it's now {}", t))
             }
         }
+        "/sim/save-snapshot" => {
+            let name = get("name")?;
+            if name.is_empty() || name.contains('/') || name.contains('.') {
+                bail!("snapshot name {} can't be empty or contain / or .", name);
+            }
+            let snapshot = Snapshot {
+                sim: sim.clone(),
+                edits: map.get_edits().to_permanent(map),
+            };
+            if params.get("to_disk").map(|x| x == "true").unwrap_or(false) {
+                let path = abstio::path_snapshot(map.get_name(), name);
+                abstio::write_binary(path.clone(), &snapshot);
+                Ok(format!("saved {} to {}", sim.time(), path))
+            } else {
+                snapshots.insert(name.to_string(), snapshot);
+                Ok(format!("saved {} in memory", sim.time()))
+            }
+        }
+        "/sim/list-snapshots" => Ok(abstutil::to_json(&SnapshotList {
+            in_memory: snapshots.keys().cloned().collect(),
+            on_disk: abstio::list_dir(abstio::path_all_snapshots(map.get_name()))
+                .into_iter()
+                .map(abstutil::basename)
+                .collect(),
+        })),
+        "/sim/restore-snapshot" => {
+            let name = get("name")?;
+            // Prefer the one in memory
+            let snapshot = match snapshots.get(name) {
+                Some(snapshot) => snapshot.clone(),
+                None => {
+                    let path = abstio::path_snapshot(map.get_name(), name);
+                    if !abstio::file_exists(&path) {
+                        bail!("no snapshot named {}", name);
+                    }
+                    abstio::maybe_read_binary::<Snapshot>(path, &mut Timer::throwaway())?
+                }
+            };
+            let edits = snapshot.edits.into_edits(map)?;
+            if edits.commands != map.get_edits().commands {
+                bail!(
+                    "snapshot {} was saved with different map edits; call /map/set-edits first",
+                    name
+                );
+            }
+            *sim = snapshot.sim;
+            stream::reset(sim, streams);
+            Ok(format!("restored {} from {}", name, sim.time()))
+        }
         "/sim/new-person" => {
             let input: ExternalPerson = abstutil::from_json(body)?;
             for trip in &input.trips {
@@ -288,36 +436,32 @@ fn handle_command(
         // Querying data
         "/data/get-finished-trips" => {
             let mut trips = Vec::new();
-            for (_, id, mode, maybe_duration) in &sim.get_analytics().finished_trips {
+            let analytics = sim.get_analytics();
+            for (_, id, mode, maybe_duration) in &analytics.finished_trips {
                 let distance_crossed = if maybe_duration.is_some() {
                     sim.finished_trip_details(*id).unwrap().2
                 } else {
                     Distance::ZERO
                 };
+                let (cruising_time, cruising_distance) = analytics
+                    .cruising_for_parking
+                    .get(id)
+                    .map(|(_, dt, dist, _)| (*dt, *dist))
+                    .unwrap_or((Duration::ZERO, Distance::ZERO));
                 trips.push(FinishedTrip {
                     id: *id,
                     person: sim.trip_to_person(*id).unwrap(),
                     duration: *maybe_duration,
                     distance_crossed,
                     mode: *mode,
+                    cruising_time,
+                    cruising_distance,
                 });
             }
             Ok(abstutil::to_json(&trips))
         }
         "/data/get-agent-positions" => Ok(abstutil::to_json(&AgentPositions {
-            agents: sim
-                .get_unzoomed_agents(map)
-                .into_iter()
-                .chain(sim.get_unzoomed_transit_riders(map))
-                .map(|a| AgentPosition {
-                    id: a.id,
-                    trip: sim.agent_to_trip(a.id),
-                    person: a.person,
-                    vehicle_type: a.id.to_vehicle_type(),
-                    pos: a.pos.to_gps(map.get_gps_bounds()),
-                    distance_crossed: sim.agent_properties(map, a.id).dist_crossed,
-                })
-                .collect(),
+            agents: agent_positions(sim, map),
         })),
         "/data/get-road-thruput" => Ok(abstutil::to_json(&RoadThroughput {
             counts: sim
@@ -340,6 +484,69 @@ fn handle_command(
                 })
                 .collect(),
         })),
+        "/data/get-emissions" => {
+            let analytics = sim.get_analytics();
+            Ok(abstutil::to_json(&EmissionsSummary {
+                total: analytics.total_emissions(),
+                hourly: analytics.hourly_emissions.clone(),
+                per_road: analytics
+                    .road_emissions
+                    .iter()
+                    .map(|(r, e)| (*r, *e))
+                    .collect(),
+                per_trip: analytics
+                    .trip_emissions
+                    .iter()
+                    .map(|(t, e)| (*t, *e))
+                    .collect(),
+            }))
+        }
+        "/data/get-noise" => {
+            let hour = get("hour")?.parse::<usize>()?;
+            let roads = sim.get_analytics().road_noise(map, hour);
+            Ok(abstutil::to_json(&NoiseLevels {
+                buildings: sim::building_noise(&roads, map).into_iter().collect(),
+                roads: roads.into_iter().collect(),
+            }))
+        }
+        "/data/get-signal-priority" => Ok(abstutil::to_json(&SignalPriorityLog {
+            records: sim
+                .get_analytics()
+                .signal_priority
+                .iter()
+                .map(
+                    |(time, intersection, route, action, time_saved, delay_imposed)| {
+                        SignalPriorityRecord {
+                            time: *time,
+                            intersection: *intersection,
+                            route: route.map(|r| map.get_br(r).short_name.clone()),
+                            action: *action,
+                            time_saved: *time_saved,
+                            delay_imposed: *delay_imposed,
+                        }
+                    },
+                )
+                .collect(),
+        })),
+        "/data/get-parking-summary" => {
+            let analytics = sim.get_analytics();
+            let (occupancy_per_lane, occupancy_per_lot) =
+                analytics.parking_occupancy_by_hour(sim.time());
+            Ok(abstutil::to_json(&ParkingSummary {
+                revenue: analytics.parking_revenue_by_hour(sim.time(), map),
+                occupancy_per_lane,
+                occupancy_per_lot,
+                violations: analytics
+                    .parking_stays
+                    .iter()
+                    .filter(|(_, _, _, _, overstayed)| *overstayed)
+                    .count(),
+            }))
+        }
+        "/data/get-cruising-summary" => Ok(abstutil::to_json(&CruisingPerArea {
+            per_area: sim.get_analytics().cruising_per_area(map),
+        })),
+        "/data/get-ride-hail-summary" => Ok(abstutil::to_json(&sim.ride_hail_summary())),
         "/data/trip-time-lower-bound" => {
             let id = TripID(get("id")?.parse::<usize>()?);
             let duration = sim.get_trip_time_lower_bound(map, id)?;
@@ -362,6 +569,26 @@ fn handle_command(
             Ok(abstutil::to_json(&results))
         }
         // Controlling the map
+        "/map/set-edits" => {
+            // Replaces all existing edits
+            let perma: PermanentMapEdits = abstutil::from_json(body)?;
+            let edits = perma.into_edits(map)?;
+            let result = apply_live_edits(map, sim, edits);
+            // So /sim/reset keeps the edits
+            load.edits = Some(map.get_edits().to_permanent(map));
+            Ok(abstutil::to_json(&result))
+        }
+        "/map/apply-edit-cmds" => {
+            // Adds to the existing edits
+            let cmds: Vec<PermanentEditCmd> = abstutil::from_json(body)?;
+            let mut edits = map.get_edits().clone();
+            for cmd in cmds {
+                edits.commands.push(cmd.into_cmd(map)?);
+            }
+            let result = apply_live_edits(map, sim, edits);
+            load.edits = Some(map.get_edits().to_permanent(map));
+            Ok(abstutil::to_json(&result))
+        }
         "/map/get-edits" => {
             let mut edits = map.get_edits().clone();
             edits.commands.clear();
@@ -392,6 +619,9 @@ struct FinishedTrip {
     duration: Option<Duration>,
     distance_crossed: Distance,
     mode: TripMode,
+    /// Time and distance spent looking for parking beyond the lane closest to the destination
+    cruising_time: Duration,
+    cruising_distance: Distance,
 }
 
 #[derive(Serialize)]
@@ -437,12 +667,83 @@ struct AgentPosition {
     distance_crossed: Distance,
 }
 
+fn agent_positions(sim: &Sim, map: &Map) -> Vec<AgentPosition> {
+    sim.get_unzoomed_agents(map)
+        .into_iter()
+        .chain(sim.get_unzoomed_transit_riders(map))
+        .map(|a| AgentPosition {
+            id: a.id,
+            trip: sim.agent_to_trip(a.id),
+            person: a.person,
+            vehicle_type: a.id.to_vehicle_type(),
+            pos: a.pos.to_gps(map.get_gps_bounds()),
+            distance_crossed: sim.agent_properties(map, a.id).dist_crossed,
+        })
+        .collect()
+}
+
 #[derive(Serialize)]
 struct RoadThroughput {
     // (road, agent type, hour since midnight, throughput for that one hour period)
     counts: Vec<(RoadID, AgentType, usize, usize)>,
 }
 
+#[derive(Serialize)]
+struct EmissionsSummary {
+    total: Emissions,
+    /// Indexed by hour since midnight
+    hourly: Vec<Emissions>,
+    per_road: Vec<(RoadID, Emissions)>,
+    per_trip: Vec<(TripID, Emissions)>,
+}
+
+/// In decibels
+#[derive(Serialize)]
+struct NoiseLevels {
+    roads: Vec<(RoadID, f64)>,
+    buildings: Vec<(BuildingID, f64)>,
+}
+
+/// Everything indexed by hour since midnight, up to the current time
+#[derive(Serialize)]
+struct ParkingSummary {
+    /// In dollars, including what cars still parked owe so far
+    revenue: Vec<f64>,
+    /// The average number of occupied spots along each parking lane
+    #[serde(serialize_with = "serialize_btreemap")]
+    occupancy_per_lane: BTreeMap<LaneID, Vec<f64>>,
+    /// The average number of occupied spots in each parking lot
+    #[serde(serialize_with = "serialize_btreemap")]
+    occupancy_per_lot: BTreeMap<ParkingLotID, Vec<f64>>,
+    /// How many cars that've left their spot stayed longer than the time limit
+    violations: usize,
+}
+
+#[derive(Serialize)]
+struct CruisingPerArea {
+    /// Grouped by the permit zone or grid square around each driver's destination
+    per_area: Vec<CruisingSummary>,
+}
+
+#[derive(Serialize)]
+struct SignalPriorityLog {
+    records: Vec<SignalPriorityRecord>,
+}
+
+/// A traffic signal changed for a bus or emergency vehicle
+#[derive(Serialize)]
+struct SignalPriorityRecord {
+    time: Time,
+    intersection: IntersectionID,
+    /// The short name of the bus route, or None for emergency vehicles
+    route: Option<String>,
+    action: SignalPriorityAction,
+    /// Roughly how much time the vehicle saved
+    time_saved: Duration,
+    /// Roughly how much delay everybody else waiting at the signal suffered, added together
+    delay_imposed: Duration,
+}
+
 #[derive(Serialize)]
 struct TrafficSignalState {
     current_stage_idx: usize,
@@ -461,23 +762,91 @@ struct BlockedByGraph {
     blocked_by: BTreeMap<AgentID, (Duration, DelayCause, Option<TripID>, Option<PersonID>)>,
 }
 
+#[derive(Serialize)]
+struct SnapshotList {
+    in_memory: Vec<String>,
+    on_disk: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct LiveEditResults {
+    effects: EditEffects,
+    /// Trips crossing something that changed are cancelled
+    trips_cancelled: usize,
+    parked_cars_displaced: usize,
+}
+
+/// Change the map without resetting the simulation.
+fn apply_live_edits(map: &mut Map, sim: &mut Sim, edits: MapEdits) -> LiveEditResults {
+    let mut timer = Timer::new("apply live edits");
+    let effects = map.must_apply_edits(edits);
+    map.recalculate_pathfinding_after_edits(&mut timer);
+    sim.handle_live_edited_traffic_signals(map);
+    let (trips_cancelled, parked_cars_displaced) = sim.handle_live_edits(map, &mut timer);
+    LiveEditResults {
+        effects,
+        trips_cancelled,
+        parked_cars_displaced,
+    }
+}
+
 #[derive(Deserialize)]
 struct LoadSim {
     scenario: String,
     modifiers: Vec<ScenarioModifier>,
     edits: Option<PermanentMapEdits>,
-    // These are fixed from the initial command line flags
+    /// Route cars using the costs saved by iterative traffic assignment for this scenario
+    #[serde(default)]
+    assignment_costs: bool,
+    // These are fixed when the session is created
     #[serde(skip_deserializing)]
     rng_seed: u64,
     #[serde(skip_deserializing)]
     opts: SimOptions,
 }
 
+#[derive(Deserialize)]
+struct NewSession {
+    #[serde(flatten)]
+    load: LoadSim,
+    /// Parsed like the command line, such as `["--rng_seed=42", "--infinite_parking"]`. If
+    /// missing, use the defaults.
+    #[serde(default)]
+    sim_flags: Vec<String>,
+}
+
+/// Returns the RNG seed and SimOptions
+fn parse_sim_flags(args: &mut CmdArgs) -> Result<(u64, SimOptions)> {
+    let rng_seed = args
+        .try_optional_parse("--rng_seed", |s| s.parse())?
+        .unwrap_or(SimFlags::RNG_SEED);
+    let opts = SimOptions::from_args(args, rng_seed)?;
+    Ok((rng_seed, opts))
+}
+
+/// Like `parse_sim_flags`, but for flags sent over the API. Unused flags are also errors, so typos
+/// don't silently fall back to the defaults.
+fn try_parse_sim_flags(flags: Vec<String>) -> Result<(u64, SimOptions)> {
+    let mut args = CmdArgs::from_args(flags);
+    let result = parse_sim_flags(&mut args).and_then(|result| {
+        args.try_done()?;
+        Ok(result)
+    });
+    result.map_err(|err| anyhow!("bad sim_flags: {}", err))
+}
+
 impl LoadSim {
     fn setup(&self, timer: &mut Timer) -> (Map, Sim) {
         let mut scenario: Scenario = abstio::must_read_object(self.scenario.clone(), timer);
 
         let mut map = Map::load_synchronously(scenario.map_name.path(), timer);
+        if self.assignment_costs {
+            let costs: AssignmentCosts = abstio::must_read_object(
+                abstio::path_assignment_costs(&scenario.map_name, &scenario.scenario_name),
+                timer,
+            );
+            costs.apply(&mut map, timer);
+        }
         if let Some(perma) = self.edits.clone() {
             let edits = perma.into_edits(&map).unwrap();
             map.must_apply_edits(edits);
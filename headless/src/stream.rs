@@ -0,0 +1,395 @@
+//! Instead of polling for huge JSON blobs, clients can subscribe to a stream of events and agent
+//! positions. As the simulation advances, every subscriber is sent the messages matching their
+//! filters, one JSON object per line.
+//!
+//! > curl -N 'http://localhost:1234/stream/subscribe?kinds=trip_finished,problem&positions_every=30'
+//!
+//! Supported GET parameters, all optional:
+//!
+//! - `kinds`: comma-separated event kinds to send. See `EVENT_KINDS`. Defaults to all of them.
+//! - `area`: `min_lon,min_lat,max_lon,max_lat`. Only send events and agents inside this area.
+//!   Events without a location are always sent.
+//! - `positions_every`: a duration like `30` or `1:00`. At this frequency of simulation time,
+//!   send the agents that moved or disappeared since the last update. If missing, don't send any
+//!   positions.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use anyhow::Result;
+use hyper::Body;
+use serde::Serialize;
+use tokio::sync::mpsc;
+
+use abstutil::Timer;
+use geom::{Duration, GPSBounds, LonLat, Pt2D, Time};
+use map_model::Map;
+use sim::{AgentID, Event, Problem, Sim};
+
+use crate::AgentPosition;
+
+/// Event kinds that clients can ask for
+const EVENT_KINDS: [&str; 13] = [
+    "trip_finished",
+    "trip_cancelled",
+    "intersection_delay",
+    "bus_arrival",
+    "bus_schedule",
+    "transit_passenger",
+    "problem",
+    "trip_capped",
+    "alert",
+    "overtake",
+    "signal_priority",
+    "reroute",
+    "cruising",
+];
+
+/// Even if nobody needs positions, stop this often to send events.
+const FLUSH_EVERY: Duration = Duration::const_seconds(60.0);
+
+pub struct Subscriber {
+    filter: Filter,
+    tx: mpsc::UnboundedSender<String>,
+    /// What was last sent, so only changes are sent next time
+    last_positions: BTreeMap<AgentID, LonLat>,
+    /// When positions should next be sent. None means immediately.
+    next_positions: Option<Time>,
+}
+
+struct Filter {
+    /// If None, send every kind
+    kinds: Option<BTreeSet<&'static str>>,
+    area: Option<GPSBounds>,
+    positions_every: Option<Duration>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type")]
+enum Message<'a> {
+    Event {
+        time: Time,
+        kind: &'static str,
+        event: &'a Event,
+    },
+    Positions {
+        time: Time,
+        /// Agents that appeared or moved
+        moved: Vec<&'a AgentPosition>,
+        /// Agents that vanished or left the area
+        removed: Vec<AgentID>,
+    },
+    /// The simulation was reset or reloaded. Any positions previously sent are stale.
+    Reset { time: Time },
+}
+
+/// Start a new stream. The response body stays open, receiving messages as the simulation
+/// advances, until the client disconnects.
+pub fn subscribe(
+    params: &HashMap<String, String>,
+    subscribers: &mut Vec<Subscriber>,
+) -> Result<Body> {
+    let filter = Filter::parse(params)?;
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+    let (mut sender, body) = Body::channel();
+    tokio::spawn(async move {
+        while let Some(msg) = rx.recv().await {
+            if sender.send_data(msg.into()).await.is_err() {
+                // The client disconnected. Dropping rx lets the next flush notice.
+                break;
+            }
+        }
+    });
+
+    subscribers.push(Subscriber {
+        filter,
+        tx,
+        last_positions: BTreeMap::new(),
+        next_positions: None,
+    });
+    info!("New stream subscriber, {} total", subscribers.len());
+    Ok(body)
+}
+
+/// Tell everybody the simulation has started over.
+pub fn reset(sim: &Sim, subscribers: &mut Vec<Subscriber>) {
+    let msg = to_line(&Message::Reset { time: sim.time() });
+    retain_subscribers(subscribers, |s| {
+        s.last_positions.clear();
+        s.next_positions = None;
+        s.tx.send(msg.clone()).is_ok()
+    });
+}
+
+/// Run the simulation until some time. If anybody's subscribed, stop along the way to send them
+/// events and positions.
+pub fn goto_time(sim: &mut Sim, map: &Map, end: Time, subscribers: &mut Vec<Subscriber>) {
+    if subscribers.is_empty() {
+        sim.disable_event_buffer();
+        sim.timed_step(
+            map,
+            end - sim.time(),
+            &mut None,
+            &mut Timer::new("goto-time"),
+        );
+        return;
+    }
+
+    sim.enable_event_buffer();
+    loop {
+        flush(sim, map, subscribers);
+        if subscribers.is_empty() || sim.time() >= end {
+            break;
+        }
+
+        let mut stop_at = end.min(sim.time() + FLUSH_EVERY);
+        for s in subscribers.iter() {
+            if let Some(t) = s.next_positions {
+                stop_at = stop_at.min(t);
+            }
+        }
+        sim.timed_step(
+            map,
+            stop_at - sim.time(),
+            &mut None,
+            &mut Timer::throwaway(),
+        );
+    }
+
+    // Everybody left partway through
+    if sim.time() < end {
+        sim.disable_event_buffer();
+        sim.timed_step(
+            map,
+            end - sim.time(),
+            &mut None,
+            &mut Timer::new("goto-time"),
+        );
+    }
+}
+
+/// Send buffered events and any positions that are due, and forget about subscribers who've
+/// disconnected.
+fn flush(sim: &mut Sim, map: &Map, subscribers: &mut Vec<Subscriber>) {
+    let now = sim.time();
+    let events: Vec<(Time, &'static str, Event, Option<LonLat>)> = sim
+        .drain_event_buffer()
+        .into_iter()
+        .filter_map(|(time, ev)| {
+            let kind = event_kind(&ev)?;
+            let pt = event_location(&ev, sim, map).map(|pt| pt.to_gps(map.get_gps_bounds()));
+            Some((time, kind, ev, pt))
+        })
+        .collect();
+
+    let positions = if subscribers.iter().any(|s| s.positions_due(now)) {
+        crate::agent_positions(sim, map)
+    } else {
+        Vec::new()
+    };
+
+    retain_subscribers(subscribers, |s| {
+        for (time, kind, event, pt) in &events {
+            if !s.filter.wants_kind(kind) || !pt.map(|pt| s.filter.in_area(pt)).unwrap_or(true) {
+                continue;
+            }
+            let msg = to_line(&Message::Event {
+                time: *time,
+                kind: *kind,
+                event,
+            });
+            if s.tx.send(msg).is_err() {
+                return false;
+            }
+        }
+
+        if s.positions_due(now) {
+            let mut moved = Vec::new();
+            let mut still_here = BTreeSet::new();
+            for a in &positions {
+                if !s.filter.in_area(a.pos) {
+                    continue;
+                }
+                still_here.insert(a.id);
+                if s.last_positions.get(&a.id) != Some(&a.pos) {
+                    moved.push(a);
+                }
+            }
+            let mut removed = Vec::new();
+            s.last_positions.retain(|id, _| {
+                if still_here.contains(id) {
+                    true
+                } else {
+                    removed.push(*id);
+                    false
+                }
+            });
+            for a in &moved {
+                s.last_positions.insert(a.id, a.pos);
+            }
+            s.next_positions = Some(now + s.filter.positions_every.unwrap());
+
+            let msg = to_line(&Message::Positions {
+                time: now,
+                moved,
+                removed,
+            });
+            if s.tx.send(msg).is_err() {
+                return false;
+            }
+        }
+
+        true
+    });
+}
+
+impl Subscriber {
+    fn positions_due(&self, now: Time) -> bool {
+        self.filter.positions_every.is_some()
+            && self.next_positions.map(|t| t <= now).unwrap_or(true)
+    }
+}
+
+impl Filter {
+    fn parse(params: &HashMap<String, String>) -> Result<Filter> {
+        let kinds = if let Some(list) = params.get("kinds") {
+            let mut kinds = BTreeSet::new();
+            for kind in list.split(',') {
+                match EVENT_KINDS.iter().find(|k| **k == kind) {
+                    Some(k) => {
+                        kinds.insert(*k);
+                    }
+                    None => bail!("unknown event kind {}; try {}", kind, EVENT_KINDS.join(",")),
+                }
+            }
+            Some(kinds)
+        } else {
+            None
+        };
+
+        let area = if let Some(raw) = params.get("area") {
+            let nums = raw
+                .split(',')
+                .map(|x| x.parse::<f64>())
+                .collect::<Result<Vec<_>, _>>()?;
+            if nums.len() != 4 {
+                bail!(
+                    "area should be min_lon,min_lat,max_lon,max_lat, not {}",
+                    raw
+                );
+            }
+            Some(GPSBounds::from(vec![
+                LonLat::new(nums[0], nums[1]),
+                LonLat::new(nums[2], nums[3]),
+            ]))
+        } else {
+            None
+        };
+
+        let positions_every = if let Some(raw) = params.get("positions_every") {
+            let dt = Duration::parse(raw)?;
+            if dt <= Duration::ZERO {
+                bail!("positions_every must be positive, not {}", raw);
+            }
+            Some(dt)
+        } else {
+            None
+        };
+
+        Ok(Filter {
+            kinds,
+            area,
+            positions_every,
+        })
+    }
+
+    fn wants_kind(&self, kind: &str) -> bool {
+        self.kinds
+            .as_ref()
+            .map(|kinds| kinds.contains(kind))
+            .unwrap_or(true)
+    }
+
+    fn in_area(&self, pt: LonLat) -> bool {
+        self.area
+            .as_ref()
+            .map(|area| area.contains(pt))
+            .unwrap_or(true)
+    }
+}
+
+/// Only some events are interesting outside of the simulation. The rest aren't streamed.
+fn event_kind(ev: &Event) -> Option<&'static str> {
+    match ev {
+        Event::TripFinished { .. } => Some("trip_finished"),
+        Event::TripCancelled(_, _) => Some("trip_cancelled"),
+        Event::IntersectionDelayMeasured(_, _, _, _) => Some("intersection_delay"),
+        Event::BusArrivedAtStop(_, _, _) => Some("bus_arrival"),
+        Event::BusScheduleAdherence { .. } => Some("bus_schedule"),
+        Event::PassengerBoardsTransit(_, _, _, _, _)
+        | Event::PassengerAlightsTransit(_, _, _, _)
+        | Event::PassengerDeniedBoarding(_, _, _, _, _) => Some("transit_passenger"),
+        Event::ProblemEncountered(_, _) => Some("problem"),
+        Event::TripCapped(_, _, _) => Some("trip_capped"),
+        Event::Alert(_, _) => Some("alert"),
+        Event::OncomingOvertake { .. } => Some("overtake"),
+        Event::SignalPriority { .. } => Some("signal_priority"),
+        Event::VehicleRerouted { .. } => Some("reroute"),
+        Event::CruisedForParking { .. } => Some("cruising"),
+        _ => None,
+    }
+}
+
+fn event_location(ev: &Event, sim: &Sim, map: &Map) -> Option<Pt2D> {
+    match ev {
+        Event::TripFinished { trip, .. } | Event::TripCancelled(trip, _) => {
+            Some(sim.trip_info(*trip).end.pt(map))
+        }
+        Event::IntersectionDelayMeasured(_, t, _, _) => Some(map.get_i(t.parent).polygon.center()),
+        Event::BusArrivedAtStop(_, _, stop)
+        | Event::BusScheduleAdherence { stop, .. }
+        | Event::PassengerBoardsTransit(_, _, _, stop, _)
+        | Event::PassengerAlightsTransit(_, _, _, stop)
+        | Event::PassengerDeniedBoarding(_, _, _, _, stop) => {
+            Some(map.get_bs(*stop).sidewalk_pos.pt(map))
+        }
+        Event::ProblemEncountered(_, problem) => Some(match problem {
+            Problem::IntersectionDelay(i, _) | Problem::ComplexIntersectionCrossing(i) => {
+                map.get_i(*i).polygon.center()
+            }
+            Problem::ArterialIntersectionCrossing(t) => map.get_i(t.parent).polygon.center(),
+            Problem::OvertakeDesired(on) => on.get_polyline(map).middle(),
+            Problem::DeniedBoarding(stop) => map.get_bs(*stop).sidewalk_pos.pt(map),
+        }),
+        Event::TripCapped(_, r, _) => Some(map.get_r(*r).center_pts.middle()),
+        Event::OncomingOvertake { lane, .. } | Event::VehicleRerouted { lane, .. } => {
+            Some(map.get_l(*lane).lane_center_pts.middle())
+        }
+        Event::SignalPriority { intersection, .. } => {
+            Some(map.get_i(*intersection).polygon.center())
+        }
+        Event::CruisedForParking { target, .. } => Some(map.get_b(*target).polygon.center()),
+        _ => None,
+    }
+}
+
+/// Keep only the subscribers for which the callback returns true.
+fn retain_subscribers<F: FnMut(&mut Subscriber) -> bool>(
+    subscribers: &mut Vec<Subscriber>,
+    mut f: F,
+) {
+    let mut idx = 0;
+    while idx < subscribers.len() {
+        if f(&mut subscribers[idx]) {
+            idx += 1;
+        } else {
+            subscribers.remove(idx);
+        }
+    }
+}
+
+fn to_line(msg: &Message) -> String {
+    let mut line = serde_json::to_string(msg).unwrap();
+    line.push('\n');
+    line
+}
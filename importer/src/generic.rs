@@ -1,9 +1,12 @@
 use serde::{Deserialize, Serialize};
 
 use abstio::MapName;
+use abstutil::Timer;
 use map_model::raw::RawMap;
+use map_model::Map;
 
 use crate::configuration::ImporterConfiguration;
+use crate::gtfs::GtfsOptions;
 use crate::utils::{download, osmconvert};
 
 /// Importing a new city can be done just by filling out this config file and specifying some
@@ -29,6 +32,9 @@ pub struct GenericCityImporter {
     pub include_railroads: bool,
     /// If provided, read polygons from this GeoJSON file and add them to the RawMap as buildings.
     pub extra_buildings: Option<String>,
+    /// If provided, import public transit routes and schedules from this GTFS feed.
+    #[serde(default)]
+    pub gtfs: Option<GtfsOptions>,
 }
 
 impl GenericCityImporter {
@@ -85,6 +91,28 @@ impl GenericCityImporter {
             timer,
         );
         map.save();
+
         map
     }
+
+    /// If a GTFS feed is configured, download it if needed, add its routes and schedules to the
+    /// map, then save it.
+    pub async fn add_gtfs(
+        &self,
+        map: &mut Map,
+        config: &ImporterConfiguration,
+        timer: &mut Timer<'_>,
+    ) {
+        if let Some(ref gtfs) = self.gtfs {
+            let dir = gtfs.feed_dir(map.get_city_name());
+            if gtfs.url.starts_with("http") {
+                download(config, dir.clone(), &gtfs.url).await;
+            }
+            if let Err(err) = crate::gtfs::import(map, &dir, gtfs.service_day, timer) {
+                error!("Couldn't import GTFS from {}: {}", dir, err);
+                return;
+            }
+            map.save();
+        }
+    }
 }
@@ -0,0 +1,525 @@
+//! Import public transit routes and schedules from any GTFS feed. Only the parts of
+//! <https://developers.google.com/transit/gtfs/reference> needed to figure out where and when
+//! vehicles run are used: stops.txt, routes.txt, trips.txt, stop_times.txt, and calendar.txt.
+//!
+//! Every distinct sequence of stops served by a route becomes one pattern. If the map already has
+//! a route from OSM that looks like the same pattern, its spawn times are replaced. Otherwise a new
+//! route is created, with stops snapped to the map.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use abstio::CityName;
+use abstutil::Timer;
+use geom::{Distance, Duration, GPSBounds, LonLat, Pt2D, Time};
+use map_model::{osm, BusRouteID, ExternalTransitRoute, Map, PathConstraints, ScheduledStop};
+
+#[derive(Serialize, Deserialize)]
+pub struct GtfsOptions {
+    /// The URL to a zipped GTFS feed. Like `osm_url`, this can instead be a path to a directory
+    /// containing an unzipped feed.
+    pub url: String,
+    /// Only trips running on this day of the week are imported.
+    pub service_day: ServiceDay,
+}
+
+impl GtfsOptions {
+    /// Where the unzipped feed lives
+    pub fn feed_dir(&self, city: &CityName) -> String {
+        if self.url.starts_with("http") {
+            city.input_path("gtfs/")
+        } else {
+            self.url.clone()
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ServiceDay {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+/// One sequence of stops served by some trips of a route
+#[derive(Debug)]
+struct Pattern {
+    short_name: String,
+    full_name: String,
+    is_bus: bool,
+    /// The shapes of all trips following this pattern, to match OSM routes tagged with one
+    shape_ids: BTreeSet<String>,
+    /// Only the stops inside the map boundary, in order
+    stops: Vec<(String, LonLat)>,
+    /// When each trip following this pattern reaches the first stop inside the map, in order
+    departures: Vec<Time>,
+    /// When trips should reach each stop, relative to the departure
+    schedule: Vec<ScheduledStop>,
+}
+
+/// Import routes and schedules from the feed into the map. The caller should save the map
+/// afterwards.
+pub fn import(map: &mut Map, dir: &str, service_day: ServiceDay, timer: &mut Timer) -> Result<()> {
+    timer.start("load GTFS");
+    let patterns = load_patterns(dir, service_day, map.get_gps_bounds())?;
+    timer.stop("load GTFS");
+
+    let mut matched: BTreeMap<BusRouteID, Vec<Time>> = BTreeMap::new();
+    let mut created = 0;
+    for (idx, pattern) in patterns.into_iter().enumerate() {
+        let pts: Vec<Pt2D> = pattern
+            .stops
+            .iter()
+            .map(|(_, pt)| pt.to_pt(map.get_gps_bounds()))
+            .collect();
+        if let Some(br) = match_existing_route(map, &pattern, &pts) {
+            matched
+                .entry(br)
+                .or_insert_with(Vec::new)
+                .extend(pattern.departures);
+            continue;
+        }
+
+        let full_name = pattern.full_name.clone();
+        let route = ExternalTransitRoute {
+            full_name: pattern.full_name,
+            short_name: pattern.short_name,
+            gtfs_trip_marker: pattern.shape_ids.into_iter().next(),
+            // Real OSM IDs are positive. The order of patterns only depends on the feed, so this
+            // is stable across imports.
+            osm_rel_id: osm::RelationID(-1 - idx as i64),
+            is_bus: pattern.is_bus,
+            stops: pattern
+                .stops
+                .into_iter()
+                .map(|(name, _)| name)
+                .zip(pts)
+                .collect(),
+            spawn_times: pattern.departures,
+            schedule: Some(pattern.schedule),
+        };
+        match map.hack_add_transit_route(route, timer) {
+            Ok(_) => {
+                created += 1;
+            }
+            Err(err) => {
+                warn!("Skipping GTFS route {}: {}", full_name, err);
+            }
+        }
+    }
+
+    if created > 0 {
+        map.hack_rebuild_pathfinding(timer);
+    }
+
+    info!(
+        "From GTFS, updated the schedule of {} routes and created {} new routes",
+        matched.len(),
+        created
+    );
+    for (br, mut times) in matched {
+        times.sort();
+        times.dedup();
+        map.hack_override_orig_spawn_times(br, times);
+    }
+    Ok(())
+}
+
+/// Find a route from OSM that probably describes the same pattern: the same type of vehicle, the
+/// same GTFS shape or short name, and first and last stops close to the pattern's.
+fn match_existing_route(map: &Map, pattern: &Pattern, pts: &[Pt2D]) -> Option<BusRouteID> {
+    let max_dist = Distance::meters(100.0);
+    map.all_bus_routes()
+        .iter()
+        .filter(|br| {
+            br.osm_rel_id.0 > 0
+                && (br.route_type == PathConstraints::Bus) == pattern.is_bus
+                && (br.short_name == pattern.short_name
+                    || br
+                        .gtfs_trip_marker
+                        .as_ref()
+                        // Seattle's markers have a weird suffix
+                        .map(|m| pattern.shape_ids.contains(m.split(':').next().unwrap()))
+                        .unwrap_or(false))
+        })
+        .filter_map(|br| {
+            let first = map.get_bs(br.stops[0]).driving_pos.pt(map).dist_to(pts[0]);
+            let last = map
+                .get_bs(*br.stops.last().unwrap())
+                .driving_pos
+                .pt(map)
+                .dist_to(*pts.last().unwrap());
+            if first <= max_dist && last <= max_dist {
+                Some((first + last, br.id))
+            } else {
+                None
+            }
+        })
+        .min_by_key(|(dist, _)| *dist)
+        .map(|(_, id)| id)
+}
+
+fn load_patterns(dir: &str, service_day: ServiceDay, bounds: &GPSBounds) -> Result<Vec<Pattern>> {
+    let services = active_services(dir, service_day)?;
+
+    let mut routes: BTreeMap<String, RouteRecord> = BTreeMap::new();
+    for rec in csv::Reader::from_path(format!("{}/routes.txt", dir))?.deserialize() {
+        let rec: RouteRecord = rec?;
+        if rec.is_bus().is_some() {
+            routes.insert(rec.route_id.clone(), rec);
+        }
+    }
+
+    let mut trips: BTreeMap<String, TripRecord> = BTreeMap::new();
+    for rec in csv::Reader::from_path(format!("{}/trips.txt", dir))?.deserialize() {
+        let rec: TripRecord = rec?;
+        if routes.contains_key(&rec.route_id)
+            && services
+                .as_ref()
+                .map(|s| s.contains(&rec.service_id))
+                .unwrap_or(true)
+        {
+            trips.insert(rec.trip_id.clone(), rec);
+        }
+    }
+
+    // Only keep stops inside the map
+    let mut stops: BTreeMap<String, (String, LonLat)> = BTreeMap::new();
+    for rec in csv::Reader::from_path(format!("{}/stops.txt", dir))?.deserialize() {
+        let rec: StopRecord = rec?;
+        if let (Some(lon), Some(lat)) = (rec.stop_lon, rec.stop_lat) {
+            let pt = LonLat::new(lon, lat);
+            if bounds.contains(pt) {
+                let name = rec.stop_name.unwrap_or_else(|| rec.stop_id.clone());
+                stops.insert(rec.stop_id, (name, pt));
+            }
+        }
+    }
+
+    // Per trip, (stop sequence, stop ID, departure time). Stops that aren't timepoints don't have
+    // a time.
+    let mut stop_times: BTreeMap<String, Vec<(usize, String, Option<Time>)>> = BTreeMap::new();
+    for rec in csv::Reader::from_path(format!("{}/stop_times.txt", dir))?.deserialize() {
+        let rec: StopTimeRecord = rec?;
+        if !trips.contains_key(&rec.trip_id) || !stops.contains_key(&rec.stop_id) {
+            continue;
+        }
+        let time = match rec
+            .departure_time
+            .or(rec.arrival_time)
+            .filter(|t| !t.is_empty())
+        {
+            Some(t) => Some(parse_time(&t)?),
+            None => None,
+        };
+        stop_times
+            .entry(rec.trip_id)
+            .or_insert_with(Vec::new)
+            .push((rec.stop_sequence, rec.stop_id, time));
+    }
+
+    let mut patterns: BTreeMap<(String, Vec<String>), Pattern> = BTreeMap::new();
+    for (trip_id, mut times) in stop_times {
+        if times.len() < 2 {
+            continue;
+        }
+        times.sort_by_key(|(seq, _, _)| *seq);
+        // If the first stop inside the map isn't a timepoint, the next known time is close enough.
+        let departure = match times.iter().find_map(|(_, _, time)| *time) {
+            Some(t) => t,
+            None => continue,
+        };
+        let trip = &trips[&trip_id];
+        let route = &routes[&trip.route_id];
+        let offsets: Vec<Option<Duration>> = times
+            .iter()
+            .map(|(_, _, time)| {
+                time.map(|t| {
+                    // The trip crossed midnight
+                    if t < departure {
+                        t + Duration::hours(24) - departure
+                    } else {
+                        t - departure
+                    }
+                })
+            })
+            .collect();
+        let stop_ids: Vec<String> = times.into_iter().map(|(_, id, _)| id).collect();
+
+        let pattern = patterns
+            .entry((trip.route_id.clone(), stop_ids.clone()))
+            .or_insert_with(|| {
+                let short_name = route.route_short_name.clone().unwrap_or_default();
+                let name = route
+                    .route_long_name
+                    .clone()
+                    .filter(|n| !n.is_empty())
+                    .unwrap_or_else(|| short_name.clone());
+                Pattern {
+                    full_name: format!("{} to {}", name, stops[stop_ids.last().unwrap()].0),
+                    short_name,
+                    is_bus: route.is_bus().unwrap(),
+                    shape_ids: BTreeSet::new(),
+                    stops: stop_ids.iter().map(|id| stops[id].clone()).collect(),
+                    departures: Vec::new(),
+                    // Trips following the same pattern usually have the same schedule relative
+                    // to their start, so just use the first.
+                    schedule: interpolate_schedule(&offsets),
+                }
+            });
+        pattern.departures.push(departure);
+        if let Some(ref shape) = trip.shape_id {
+            pattern.shape_ids.insert(shape.clone());
+        }
+    }
+
+    Ok(patterns
+        .into_iter()
+        .map(|(_, mut pattern)| {
+            pattern.departures.sort();
+            pattern.departures.dedup();
+            pattern
+        })
+        .collect())
+}
+
+/// Trips running past midnight may use times like 25:30:00, and some feeds go on for days. The
+/// simulation only covers one day, so wrap these around.
+fn parse_time(raw: &str) -> Result<Time> {
+    let time = Time::parse(raw)?;
+    let day = Duration::hours(24).inner_seconds();
+    Ok(Time::seconds_since_midnight(time.inner_seconds() % day))
+}
+
+/// Stops with a time in the feed are timepoints. Fill in the others by interpolating between the
+/// surrounding timepoints. There's at least one timepoint.
+fn interpolate_schedule(offsets: &[Option<Duration>]) -> Vec<ScheduledStop> {
+    let mut schedule = Vec::new();
+    for (idx, offset) in offsets.iter().enumerate() {
+        if let Some(offset) = offset {
+            schedule.push(ScheduledStop {
+                offset: *offset,
+                timepoint: true,
+            });
+            continue;
+        }
+        let prev = offsets[..idx]
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(i, x)| x.map(|x| (i, x)));
+        let next = offsets[idx + 1..]
+            .iter()
+            .enumerate()
+            .find_map(|(i, x)| x.map(|x| (idx + 1 + i, x)));
+        let offset = match (prev, next) {
+            (Some((i1, t1)), Some((i2, t2))) => {
+                t1 + (t2 - t1) * ((idx - i1) as f64 / (i2 - i1) as f64)
+            }
+            (Some((_, t)), None) => t,
+            // Before the first timepoint, which is where the trip departs
+            (None, _) => Duration::ZERO,
+        };
+        schedule.push(ScheduledStop {
+            offset,
+            timepoint: false,
+        });
+    }
+    schedule
+}
+
+/// Returns the services running on the given day, or None if the feed has no calendar and every
+/// service should be used.
+fn active_services(dir: &str, service_day: ServiceDay) -> Result<Option<BTreeSet<String>>> {
+    let path = format!("{}/calendar.txt", dir);
+    if !Path::new(&path).exists() {
+        warn!("{} doesn't exist, so using every service", path);
+        return Ok(None);
+    }
+    let mut services = BTreeSet::new();
+    for rec in csv::Reader::from_path(path)?.deserialize() {
+        let rec: CalendarRecord = rec?;
+        let runs = match service_day {
+            ServiceDay::Monday => rec.monday,
+            ServiceDay::Tuesday => rec.tuesday,
+            ServiceDay::Wednesday => rec.wednesday,
+            ServiceDay::Thursday => rec.thursday,
+            ServiceDay::Friday => rec.friday,
+            ServiceDay::Saturday => rec.saturday,
+            ServiceDay::Sunday => rec.sunday,
+        };
+        if runs == 1 {
+            services.insert(rec.service_id);
+        }
+    }
+    Ok(Some(services))
+}
+
+#[derive(Deserialize)]
+struct StopRecord {
+    stop_id: String,
+    stop_name: Option<String>,
+    stop_lat: Option<f64>,
+    stop_lon: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct RouteRecord {
+    route_id: String,
+    route_short_name: Option<String>,
+    route_long_name: Option<String>,
+    route_type: usize,
+}
+
+impl RouteRecord {
+    /// None if the route isn't a bus or some kind of rail, so can't be simulated
+    fn is_bus(&self) -> Option<bool> {
+        match self.route_type {
+            // Bus, trolleybus, and the extended bus types
+            3 | 11 | 700..=799 | 800 => Some(true),
+            // Tram, subway, rail, and the extended rail types
+            0 | 1 | 2 | 12 | 100..=199 | 400..=499 | 900..=999 => Some(false),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct TripRecord {
+    route_id: String,
+    service_id: String,
+    trip_id: String,
+    shape_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct StopTimeRecord {
+    trip_id: String,
+    arrival_time: Option<String>,
+    departure_time: Option<String>,
+    stop_id: String,
+    stop_sequence: usize,
+}
+
+#[derive(Deserialize)]
+struct CalendarRecord {
+    service_id: String,
+    monday: u8,
+    tuesday: u8,
+    wednesday: u8,
+    thursday: u8,
+    friday: u8,
+    saturday: u8,
+    sunday: u8,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixture_feed() {
+        let dir = "../tests/input/gtfs";
+        // Stop "far" is outside of this
+        let bounds = GPSBounds::from(vec![
+            LonLat::new(-122.31, 47.65),
+            LonLat::new(-122.30, 47.66),
+        ]);
+
+        let weekday = load_patterns(dir, ServiceDay::Monday, &bounds).unwrap();
+        let summary: Vec<(String, bool, Vec<String>, Vec<String>)> = weekday
+            .iter()
+            .map(|p| {
+                (
+                    p.full_name.clone(),
+                    p.is_bus,
+                    p.stops.iter().map(|(name, _)| name.clone()).collect(),
+                    p.departures.iter().map(|t| t.to_string()).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    "Campus Loop to Third Stop".to_string(),
+                    true,
+                    vec![
+                        "First Stop".to_string(),
+                        "Second Stop".to_string(),
+                        "Third Stop".to_string()
+                    ],
+                    // The 25:00 trip wraps around
+                    vec![
+                        "01:00:00.0".to_string(),
+                        "07:00:00.0".to_string(),
+                        "07:30:00.0".to_string()
+                    ],
+                ),
+                (
+                    "Campus Loop to First Stop".to_string(),
+                    true,
+                    vec![
+                        "Third Stop".to_string(),
+                        "Second Stop".to_string(),
+                        "First Stop".to_string()
+                    ],
+                    // This trip starts outside the map, so use the time at the first stop inside
+                    vec!["08:10:00.0".to_string()],
+                ),
+                (
+                    "Light Rail to Third Stop".to_string(),
+                    false,
+                    vec!["First Stop".to_string(), "Third Stop".to_string()],
+                    vec!["09:00:00.0".to_string()],
+                ),
+            ]
+        );
+        assert_eq!(
+            weekday[0].shape_ids,
+            vec!["loop_out".to_string()].into_iter().collect()
+        );
+        // The second stop isn't a timepoint
+        assert_eq!(
+            weekday[0].schedule,
+            vec![
+                ScheduledStop {
+                    offset: Duration::ZERO,
+                    timepoint: true
+                },
+                ScheduledStop {
+                    offset: Duration::minutes(5),
+                    timepoint: false
+                },
+                ScheduledStop {
+                    offset: Duration::minutes(10),
+                    timepoint: true
+                },
+            ]
+        );
+
+        // Only the weekend trip runs on Sunday. The ferry is never included.
+        let sunday = load_patterns(dir, ServiceDay::Sunday, &bounds).unwrap();
+        assert_eq!(sunday.len(), 1);
+        assert_eq!(
+            sunday[0].departures,
+            vec![Time::START_OF_DAY + Duration::hours(10)]
+        );
+    }
+
+    #[test]
+    fn test_parse_time() {
+        let parse = |raw| parse_time(raw).unwrap().to_string();
+        assert_eq!(parse("07:30:00"), "07:30:00.0");
+        assert_eq!(parse("25:30:00"), "01:30:00.0");
+        assert_eq!(parse("49:05:00"), "01:05:00.0");
+        assert!(parse_time("soon").is_err());
+    }
+}
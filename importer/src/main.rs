@@ -19,6 +19,7 @@ use configuration::{load_configuration, ImporterConfiguration};
 mod berlin;
 mod configuration;
 mod generic;
+mod gtfs;
 mod seattle;
 mod soundcast;
 mod uk;
@@ -268,6 +269,20 @@ impl Job {
                     }
                 }
 
+                if name.city != CityName::seattle() {
+                    if let Ok(city_cfg) = abstio::maybe_read_json::<generic::GenericCityImporter>(
+                        format!(
+                            "importer/config/{}/{}/cfg.json",
+                            self.city.country, self.city.city
+                        ),
+                        timer,
+                    ) {
+                        timer.start(format!("add GTFS schedules for {}", name.describe()));
+                        city_cfg.add_gtfs(&mut map, config, timer).await;
+                        timer.stop(format!("add GTFS schedules for {}", name.describe()));
+                    }
+                }
+
                 Some(map)
             } else if self.scenario {
                 Some(map_model::Map::load_synchronously(name.path(), timer))
@@ -189,7 +189,9 @@ fn clip_trips(map: &Map, popdat: &PopDat, huge_map: &Map, timer: &mut Timer) ->
                 borders.for_mode(orig.mode),
                 match orig.mode {
                     TripMode::Walk | TripMode::Transit => PathConstraints::Pedestrian,
-                    TripMode::Drive => PathConstraints::Car,
+                    TripMode::Drive | TripMode::Carpool | TripMode::RideHail => {
+                        PathConstraints::Car
+                    }
                     TripMode::Bike => PathConstraints::Bike,
                 },
                 maybe_huge_map.as_ref(),
@@ -275,6 +277,7 @@ pub fn make_weekday_scenario(
         scenario_name: "weekday".to_string(),
         map_name: map.get_name().clone(),
         people,
+        households: Vec::new(),
         only_seed_buses: None,
     }
     .remove_weird_schedules()
@@ -115,6 +115,7 @@ pub struct ColorScheme {
 
     // Unzoomed dynamic elements
     pub unzoomed_car: Color,
+    pub unzoomed_carpool: Color,
     pub unzoomed_bike: Color,
     pub unzoomed_bus: Color,
     pub unzoomed_pedestrian: Color,
@@ -127,6 +128,7 @@ pub struct ColorScheme {
     pub bus_body: Color,
     pub bus_label: Color,
     pub train_body: Color,
+    pub emergency_body: Color,
     pub ped_head: Color,
     pub ped_foot: Color,
     pub ped_preparing_bike_body: Color,
@@ -243,6 +245,7 @@ impl ColorScheme {
 
             // Unzoomed dynamic elements
             unzoomed_car: hex("#FE5f55"),
+            unzoomed_carpool: hex("#F4A261"),
             unzoomed_bike: hex("#90BE6D"),
             unzoomed_bus: hex("#FFD166"),
             unzoomed_pedestrian: hex("#457B9D"),
@@ -261,6 +264,7 @@ impl ColorScheme {
             bus_body: Color::rgb(50, 133, 117),
             bus_label: Color::rgb(249, 206, 24),
             train_body: hex("#42B6E9"),
+            emergency_body: hex("#EB3223"),
             ped_head: Color::rgb(139, 69, 19),
             ped_foot: Color::BLACK,
             ped_preparing_bike_body: Color::rgb(255, 0, 144),
@@ -245,6 +245,8 @@ fn zoomed_color_car(input: &DrawCarInput, sim: &Sim, cs: &ColorScheme) -> Color
         cs.bus_body
     } else if input.id.vehicle_type == VehicleType::Train {
         cs.train_body
+    } else if input.id.vehicle_type == VehicleType::Emergency {
+        cs.emergency_body
     } else {
         let color = match input.status {
             CarStatus::Moving => cs.rotating_color_agents(input.id.id),
@@ -9,7 +9,7 @@ use geom::Speed;
 
 use crate::raw::OriginalRoad;
 use crate::{
-    osm, AccessRestrictions, Direction, EditCmd, EditRoad, LaneSpec, LaneType, Map,
+    osm, AccessRestrictions, Direction, EditCmd, EditRoad, LaneSpec, LaneType, Map, ParkingRules,
     PermanentMapEdits, RoadID,
 };
 
@@ -111,6 +111,20 @@ pub fn upgrade(mut value: Value, map: &Map) -> Result<PermanentMapEdits> {
             .unwrap()
             .insert("version".to_string(), Value::Number(11.into()));
     }
+    if value["version"] == Value::Number(11.into()) {
+        add_vehicle_caps(&mut value);
+        value
+            .as_object_mut()
+            .unwrap()
+            .insert("version".to_string(), Value::Number(12.into()));
+    }
+    if value["version"] == Value::Number(12.into()) {
+        add_parking_rules(&mut value);
+        value
+            .as_object_mut()
+            .unwrap()
+            .insert("version".to_string(), Value::Number(13.into()));
+    }
 
     abstutil::from_json(&value.to_string().into_bytes())
 }
@@ -442,6 +456,29 @@ fn remove_vehicle_caps(value: &mut Value) {
     });
 }
 
+// Congestion capping came back, with an explicit cap_vehicles_per_hour in AccessRestrictions.
+fn add_vehicle_caps(value: &mut Value) {
+    walk(value, &|map| {
+        if map.contains_key("allow_through_traffic") && !map.contains_key("cap_vehicles_per_hour") {
+            map.insert("cap_vehicles_per_hour".to_string(), Value::Null);
+        }
+        false
+    });
+}
+
+// Roads gained ParkingRules. Before this, all parking was free.
+fn add_parking_rules(value: &mut Value) {
+    walk(value, &|map| {
+        if map.contains_key("lanes_ltr") && !map.contains_key("parking_rules") {
+            map.insert(
+                "parking_rules".to_string(),
+                serde_json::to_value(ParkingRules::new()).unwrap(),
+            );
+        }
+        false
+    });
+}
+
 // These're old structs used in fix_old_lane_cmds.
 #[derive(Debug, Deserialize)]
 struct OriginalLane {
@@ -10,13 +10,14 @@ use serde::{Deserialize, Serialize};
 use abstutil::Timer;
 use geom::{Distance, HashablePt2D, Line, Speed, Time};
 
-pub use self::perma::PermanentMapEdits;
+pub use self::perma::{PermanentEditCmd, PermanentMapEdits};
 use crate::make::initial::lane_specs::get_lane_specs_ltr;
 use crate::make::{match_points_to_lanes, snap_driveway, trim_path};
 use crate::{
     connectivity, AccessRestrictions, BuildingID, BusRouteID, ControlStopSign,
     ControlTrafficSignal, IntersectionID, IntersectionType, LaneID, LaneSpec, Map, MapConfig,
-    ParkingLotID, PathConstraints, Pathfinder, Road, RoadID, TurnID, Zone,
+    ParkingLotID, ParkingRules, PathConstraints, Pathfinder, Road, RoadID, TransitCapacity, TurnID,
+    Zone,
 };
 
 mod compat;
@@ -39,6 +40,7 @@ pub struct MapEdits {
     pub changed_roads: BTreeSet<RoadID>,
     pub original_intersections: BTreeMap<IntersectionID, EditIntersection>,
     pub changed_routes: BTreeSet<BusRouteID>,
+    pub original_parking_lots: BTreeMap<ParkingLotID, ParkingRules>,
 
     /// Some edits are included in the game by default, in data/system/proposals, as "community
     /// proposals." They require a description and may have a link to a write-up.
@@ -60,6 +62,7 @@ pub struct EditRoad {
     pub lanes_ltr: Vec<LaneSpec>,
     pub speed_limit: Speed,
     pub access_restrictions: AccessRestrictions,
+    pub parking_rules: ParkingRules,
 }
 
 impl EditRoad {
@@ -68,6 +71,7 @@ impl EditRoad {
             lanes_ltr: get_lane_specs_ltr(&r.osm_tags, cfg),
             speed_limit: r.speed_limit_from_osm(),
             access_restrictions: r.access_restrictions_from_osm(),
+            parking_rules: ParkingRules::new(),
         }
     }
 
@@ -110,6 +114,9 @@ impl EditRoad {
         if self.access_restrictions != other.access_restrictions {
             changes.push("access restrictions".to_string());
         }
+        if self.parking_rules != other.parking_rules {
+            changes.push("parking rules".to_string());
+        }
         changes
     }
 }
@@ -131,8 +138,19 @@ pub enum EditCmd {
         old: Vec<Time>,
         new: Vec<Time>,
     },
+    ChangeParkingLot {
+        pl: ParkingLotID,
+        old: ParkingRules,
+        new: ParkingRules,
+    },
+    ChangeRouteCapacity {
+        id: BusRouteID,
+        old: Option<TransitCapacity>,
+        new: Option<TransitCapacity>,
+    },
 }
 
+#[derive(Serialize)]
 pub struct EditEffects {
     pub changed_roads: BTreeSet<RoadID>,
     pub deleted_lanes: BTreeSet<LaneID>,
@@ -154,6 +172,7 @@ impl MapEdits {
             changed_roads: BTreeSet::new(),
             original_intersections: BTreeMap::new(),
             changed_routes: BTreeSet::new(),
+            original_parking_lots: BTreeMap::new(),
         }
     }
 
@@ -187,6 +206,7 @@ impl MapEdits {
         self.changed_roads.clear();
         self.original_intersections.clear();
         self.changed_routes.clear();
+        self.original_parking_lots.clear();
 
         for cmd in &self.commands {
             match cmd {
@@ -198,9 +218,15 @@ impl MapEdits {
                         self.original_intersections.insert(*i, old.clone());
                     }
                 }
-                EditCmd::ChangeRouteSchedule { id, .. } => {
+                EditCmd::ChangeRouteSchedule { id, .. }
+                | EditCmd::ChangeRouteCapacity { id, .. } => {
                     self.changed_routes.insert(*id);
                 }
+                EditCmd::ChangeParkingLot { pl, ref old, .. } => {
+                    if !self.original_parking_lots.contains_key(pl) {
+                        self.original_parking_lots.insert(*pl, old.clone());
+                    }
+                }
             }
         }
 
@@ -211,8 +237,10 @@ impl MapEdits {
             .retain(|i, orig| map.get_i_edit(*i) != orig.clone());
         self.changed_routes.retain(|br| {
             let r = map.get_br(*br);
-            r.spawn_times != r.orig_spawn_times
+            r.spawn_times != r.orig_spawn_times || r.capacity.is_some()
         });
+        self.original_parking_lots
+            .retain(|pl, orig| map.get_pl(*pl).parking_rules != *orig);
     }
 
     /// Assumes update_derived has been called.
@@ -233,10 +261,27 @@ impl MapEdits {
         }
         for r in &self.changed_routes {
             let r = map.get_br(*r);
-            self.commands.push(EditCmd::ChangeRouteSchedule {
-                id: r.id,
-                new: r.spawn_times.clone(),
-                old: r.orig_spawn_times.clone(),
+            if r.spawn_times != r.orig_spawn_times {
+                self.commands.push(EditCmd::ChangeRouteSchedule {
+                    id: r.id,
+                    new: r.spawn_times.clone(),
+                    old: r.orig_spawn_times.clone(),
+                });
+            }
+            // The importer never sets a capacity, so the original is always None
+            if r.capacity.is_some() {
+                self.commands.push(EditCmd::ChangeRouteCapacity {
+                    id: r.id,
+                    old: None,
+                    new: r.capacity,
+                });
+            }
+        }
+        for (pl, old) in &self.original_parking_lots {
+            self.commands.push(EditCmd::ChangeParkingLot {
+                pl: *pl,
+                old: old.clone(),
+                new: map.get_pl(*pl).parking_rules.clone(),
             });
         }
     }
@@ -252,6 +297,7 @@ impl MapEdits {
             // What exactly changed?
             if r.speed_limit != orig.speed_limit
                 || r.access_restrictions != orig.access_restrictions
+                || r.parking_rules != orig.parking_rules
             {
                 roads.insert(r.id);
             } else {
@@ -297,6 +343,18 @@ impl EditCmd {
             EditCmd::ChangeRouteSchedule { id, .. } => {
                 format!("reschedule route {}", map.get_br(*id).short_name)
             }
+            EditCmd::ChangeParkingLot { pl, new, .. } => {
+                details.push(new.describe());
+                format!("parking rules for {}", pl)
+            }
+            EditCmd::ChangeRouteCapacity { id, new, .. } => {
+                if let Some(cap) = new {
+                    details.push(format!("{} seated, {} standing", cap.seated, cap.standing));
+                } else {
+                    details.push("default capacity".to_string());
+                }
+                format!("capacity of route {}", map.get_br(*id).short_name)
+            }
         };
         (summary, details)
     }
@@ -313,6 +371,7 @@ impl EditCmd {
                 let road = &mut map.roads[r.0];
                 road.speed_limit = new.speed_limit;
                 road.access_restrictions = new.access_restrictions.clone();
+                road.parking_rules = new.parking_rules.clone();
 
                 effects.changed_roads.insert(road.id);
                 for i in [road.src_i, road.dst_i] {
@@ -373,6 +432,13 @@ impl EditCmd {
             EditCmd::ChangeRouteSchedule { id, new, .. } => {
                 map.bus_routes[id.0].spawn_times = new.clone();
             }
+            EditCmd::ChangeParkingLot { pl, new, .. } => {
+                map.parking_lots[pl.0].parking_rules = new.clone();
+                effects.changed_parking_lots.insert(*pl);
+            }
+            EditCmd::ChangeRouteCapacity { id, new, .. } => {
+                map.bus_routes[id.0].capacity = *new;
+            }
         }
     }
 
@@ -393,6 +459,16 @@ impl EditCmd {
                 old: new,
                 new: old,
             },
+            EditCmd::ChangeParkingLot { pl, old, new } => EditCmd::ChangeParkingLot {
+                pl,
+                old: new,
+                new: old,
+            },
+            EditCmd::ChangeRouteCapacity { id, old, new } => EditCmd::ChangeRouteCapacity {
+                id,
+                old: new,
+                new: old,
+            },
         }
     }
 }
@@ -731,6 +807,7 @@ impl Map {
             lanes_ltr: r.lane_specs(self),
             speed_limit: r.speed_limit,
             access_restrictions: r.access_restrictions.clone(),
+            parking_rules: r.parking_rules.clone(),
         }
     }
 
@@ -9,7 +9,7 @@ use geom::Time;
 
 use crate::edits::{EditCmd, EditIntersection, EditRoad, MapEdits};
 use crate::raw::OriginalRoad;
-use crate::{osm, ControlStopSign, IntersectionID, Map};
+use crate::{osm, ControlStopSign, IntersectionID, Map, ParkingRules, TransitCapacity};
 
 /// MapEdits are converted to this before serializing. Referencing things like LaneID in a Map won't
 /// work if the basemap is rebuilt from new OSM data, so instead we use stabler OSM IDs that're less
@@ -61,6 +61,16 @@ pub enum PermanentEditCmd {
         old: Vec<Time>,
         new: Vec<Time>,
     },
+    ChangeParkingLot {
+        osm_id: osm::OsmID,
+        old: ParkingRules,
+        new: ParkingRules,
+    },
+    ChangeRouteCapacity {
+        osm_rel_id: osm::RelationID,
+        old: Option<TransitCapacity>,
+        new: Option<TransitCapacity>,
+    },
 }
 
 impl EditCmd {
@@ -83,6 +93,18 @@ impl EditCmd {
                     new: new.clone(),
                 }
             }
+            EditCmd::ChangeParkingLot { pl, old, new } => PermanentEditCmd::ChangeParkingLot {
+                osm_id: map.get_pl(*pl).osm_id,
+                old: old.clone(),
+                new: new.clone(),
+            },
+            EditCmd::ChangeRouteCapacity { id, old, new } => {
+                PermanentEditCmd::ChangeRouteCapacity {
+                    osm_rel_id: map.get_br(*id).osm_rel_id,
+                    old: *old,
+                    new: *new,
+                }
+            }
         }
     }
 }
@@ -127,6 +149,28 @@ impl PermanentEditCmd {
                     .ok_or_else(|| anyhow!("can't find {}", osm_rel_id))?;
                 Ok(EditCmd::ChangeRouteSchedule { id, old, new })
             }
+            PermanentEditCmd::ChangeParkingLot { osm_id, old, new } => {
+                let pl = map
+                    .all_parking_lots()
+                    .iter()
+                    .find(|pl| pl.osm_id == osm_id)
+                    .ok_or_else(|| anyhow!("can't find parking lot {}", osm_id))?;
+                Ok(EditCmd::ChangeParkingLot {
+                    pl: pl.id,
+                    old,
+                    new,
+                })
+            }
+            PermanentEditCmd::ChangeRouteCapacity {
+                osm_rel_id,
+                old,
+                new,
+            } => {
+                let id = map
+                    .find_br(osm_rel_id)
+                    .ok_or_else(|| anyhow!("can't find {}", osm_rel_id))?;
+                Ok(EditCmd::ChangeRouteCapacity { id, old, new })
+            }
         }
     }
 }
@@ -138,7 +182,7 @@ impl MapEdits {
             map_name: map.get_name().clone(),
             edits_name: self.edits_name.clone(),
             // Increase this every time there's a schema change
-            version: 11,
+            version: 13,
             proposal_description: self.proposal_description.clone(),
             proposal_link: self.proposal_link.clone(),
             commands: self.commands.iter().map(|cmd| cmd.to_perma(map)).collect(),
@@ -165,6 +209,7 @@ impl PermanentMapEdits {
             changed_roads: BTreeSet::new(),
             original_intersections: BTreeMap::new(),
             changed_routes: BTreeSet::new(),
+            original_parking_lots: BTreeMap::new(),
         };
         edits.update_derived(map);
         Ok(edits)
@@ -187,6 +232,7 @@ impl PermanentMapEdits {
             changed_roads: BTreeSet::new(),
             original_intersections: BTreeMap::new(),
             changed_routes: BTreeSet::new(),
+            original_parking_lots: BTreeMap::new(),
         };
         edits.update_derived(map);
         edits
@@ -10,12 +10,14 @@ use geom::{
 };
 
 pub use self::parking_lots::snap_driveway;
+pub(crate) use self::transit::add_external_route;
+pub use self::transit::ExternalTransitRoute;
 use crate::pathfind::{CreateEngine, Pathfinder};
 use crate::raw::{OriginalRoad, RawMap};
 use crate::{
     connectivity, osm, AccessRestrictions, Area, AreaID, AreaType, ControlStopSign,
     ControlTrafficSignal, Intersection, IntersectionID, IntersectionType, Lane, LaneID, Map,
-    MapEdits, Movement, PathConstraints, Position, Road, RoadID, RoutingParams, Zone,
+    MapEdits, Movement, ParkingRules, PathConstraints, Position, Road, RoadID, RoutingParams, Zone,
 };
 
 mod bridges;
@@ -172,6 +174,7 @@ impl Map {
                     0
                 },
                 access_restrictions: AccessRestrictions::new(),
+                parking_rules: ParkingRules::new(),
                 percent_incline: raw_road.percent_incline,
             };
             road.speed_limit = road.speed_limit_from_osm();
@@ -3,12 +3,13 @@ use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use anyhow::Result;
 
 use abstutil::Timer;
-use geom::{Distance, Duration, FindClosest, HashablePt2D, Time};
+use geom::{Distance, Duration, FindClosest, HashablePt2D, Pt2D, Time};
 
 use crate::make::match_points_to_lanes;
 use crate::raw::{RawBusRoute, RawBusStop};
 use crate::{
-    BusRoute, BusRouteID, BusStop, BusStopID, LaneID, LaneType, Map, PathConstraints, Position,
+    osm, BusRoute, BusRouteID, BusStop, BusStopID, LaneID, LaneType, Map, PathConstraints,
+    Position, ScheduledStop,
 };
 
 /// Construct the final model of bus/train stops and routes. This is quite broken currently, so not
@@ -25,7 +26,13 @@ pub fn make_stops_and_routes(map: &mut Map, raw_routes: &[RawBusRoute], timer: &
         }
     }
 
-    // Remove orphaned bus stops. This messes up the BusStopID indexing.
+    remove_orphaned_stops(map);
+
+    timer.stop("make transit stops and routes");
+}
+
+/// Remove bus stops not served by any route. This messes up the BusStopID indexing.
+fn remove_orphaned_stops(map: &mut Map) {
     for id in map
         .bus_stops
         .keys()
@@ -40,8 +47,6 @@ pub fn make_stops_and_routes(map: &mut Map, raw_routes: &[RawBusRoute], timer: &
             .bus_stops
             .remove(&id);
     }
-
-    timer.stop("make transit stops and routes");
 }
 
 fn make_route(
@@ -64,26 +69,9 @@ fn make_route(
                 let stop_id = if let Some(id) = pt_to_stop.get(&(sidewalk_pos, driving_pos)) {
                     *id
                 } else {
-                    let id = BusStopID {
-                        sidewalk: sidewalk_pos.lane(),
-                        idx: map.get_l(sidewalk_pos.lane()).bus_stops.len(),
-                    };
+                    let id =
+                        create_stop(map, stop.name.clone(), sidewalk_pos, driving_pos, !r.is_bus);
                     pt_to_stop.insert((sidewalk_pos, driving_pos), id);
-                    map.lanes
-                        .get_mut(&sidewalk_pos.lane())
-                        .unwrap()
-                        .bus_stops
-                        .insert(id);
-                    map.bus_stops.insert(
-                        id,
-                        BusStop {
-                            id,
-                            name: stop.name.clone(),
-                            driving_pos,
-                            sidewalk_pos,
-                            is_train_stop: !r.is_bus,
-                        },
-                    );
                     id
                 };
                 stops.push(stop_id);
@@ -149,8 +137,52 @@ fn make_route(
         end_border,
         spawn_times: default_spawn_times(),
         orig_spawn_times: default_spawn_times(),
+        capacity: None,
+        schedule: None,
+    };
+
+    add_route(map, route)?;
+    Ok(())
+}
+
+fn create_stop(
+    map: &mut Map,
+    name: String,
+    sidewalk_pos: Position,
+    driving_pos: Position,
+    is_train_stop: bool,
+) -> BusStopID {
+    // remove_orphaned_stops may leave gaps in the indices, so don't just use the number of stops
+    let id = BusStopID {
+        sidewalk: sidewalk_pos.lane(),
+        idx: map
+            .get_l(sidewalk_pos.lane())
+            .bus_stops
+            .iter()
+            .map(|bs| bs.idx + 1)
+            .max()
+            .unwrap_or(0),
     };
+    map.lanes
+        .get_mut(&sidewalk_pos.lane())
+        .unwrap()
+        .bus_stops
+        .insert(id);
+    map.bus_stops.insert(
+        id,
+        BusStop {
+            id,
+            name,
+            driving_pos,
+            sidewalk_pos,
+            is_train_stop,
+        },
+    );
+    id
+}
 
+/// Make sure the route is connected, then add it to the map.
+fn add_route(map: &mut Map, route: BusRoute) -> Result<BusRouteID> {
     let mut debug_route = "All parts of the route:".to_string();
     debug_route = format!("{}\nStart at {}", debug_route, route.start);
     for (idx, bs) in route.stops.iter().enumerate() {
@@ -187,8 +219,161 @@ fn make_route(
         }
     }
 
+    let id = route.id;
     map.bus_routes.push(route);
-    Ok(())
+    Ok(id)
+}
+
+/// A transit route described by some source other than OSM, like GTFS.
+pub struct ExternalTransitRoute {
+    pub full_name: String,
+    pub short_name: String,
+    pub gtfs_trip_marker: Option<String>,
+    /// The route doesn't come from OSM, but edits refer to routes by this ID, so it must be unique
+    /// and stable across imports.
+    pub osm_rel_id: osm::RelationID,
+    /// If not, light rail
+    pub is_bus: bool,
+    /// The name and location of each stop, in order.
+    pub stops: Vec<(String, Pt2D)>,
+    /// Non-empty, times in order for one day when a vehicle should begin at start.
+    pub spawn_times: Vec<Time>,
+    /// If known, the schedule at each stop, in the same order as `stops`.
+    pub schedule: Option<Vec<ScheduledStop>>,
+}
+
+/// Snap the stops of an external route to the map and add the route. Stops are matched to the
+/// rightmost lane the vehicle can use and the closest sidewalk, reusing existing stops nearby.
+/// Stops that can't be snapped (usually because they're off the edge of the map) are skipped, as
+/// long as two remain. The route vanishes at its last stop, instead of leaving through a border.
+pub fn add_external_route(
+    map: &mut Map,
+    route: ExternalTransitRoute,
+    timer: &mut Timer,
+) -> Result<BusRouteID> {
+    let result = snap_external_route(map, route, timer);
+    if result.is_err() {
+        remove_orphaned_stops(map);
+    }
+    result
+}
+
+fn snap_external_route(
+    map: &mut Map,
+    route: ExternalTransitRoute,
+    timer: &mut Timer,
+) -> Result<BusRouteID> {
+    let route_type = if route.is_bus {
+        PathConstraints::Bus
+    } else {
+        PathConstraints::Train
+    };
+
+    let pts: HashSet<HashablePt2D> = route.stops.iter().map(|(_, pt)| pt.to_hashable()).collect();
+    let driving_pts = match_points_to_lanes(
+        map.get_bounds(),
+        pts.clone(),
+        map.all_lanes(),
+        |l| {
+            if route.is_bus {
+                route_type.can_use(l, map)
+            } else {
+                l.lane_type == LaneType::LightRail
+            }
+        },
+        Distance::ZERO,
+        Distance::meters(20.0),
+        timer,
+    );
+    // Light rail platforms may be far from the tracks
+    let sidewalk_pts = if route.is_bus {
+        HashMap::new()
+    } else {
+        match_points_to_lanes(
+            map.get_bounds(),
+            pts,
+            map.all_lanes(),
+            |l| l.is_walkable(),
+            Distance::ZERO,
+            Distance::meters(50.0),
+            timer,
+        )
+    };
+
+    let mut stops = Vec::new();
+    // Only for the stops that get snapped
+    let mut schedule = Vec::new();
+    for (idx, (name, pt)) in route.stops.into_iter().enumerate() {
+        let driving_pos = match driving_pts
+            .get(&pt.to_hashable())
+            .and_then(|pos| clear_of_border(*pos, map).ok())
+        {
+            Some(pos) => pos,
+            None => continue,
+        };
+        let sidewalk_pos = if route.is_bus {
+            match map.get_parent(driving_pos.lane()).find_closest_lane(
+                driving_pos.lane(),
+                |l| PathConstraints::Pedestrian.can_use(l, map),
+                map,
+            ) {
+                Some(l) => driving_pos.equiv_pos(l, map),
+                None => continue,
+            }
+        } else {
+            match sidewalk_pts.get(&pt.to_hashable()) {
+                Some(pos) => *pos,
+                None => continue,
+            }
+        };
+
+        let existing = map
+            .bus_stops
+            .values()
+            .find(|bs| {
+                bs.is_train_stop == !route.is_bus
+                    && bs.driving_pos.lane() == driving_pos.lane()
+                    && (bs.driving_pos.dist_along() - driving_pos.dist_along()).abs()
+                        <= Distance::meters(10.0)
+            })
+            .map(|bs| bs.id);
+        let id = existing
+            .unwrap_or_else(|| create_stop(map, name, sidewalk_pos, driving_pos, !route.is_bus));
+        // Consecutive stops close together might snap to the same place
+        if stops.last() != Some(&id) {
+            stops.push(id);
+            if let Some(ref sched) = route.schedule {
+                schedule.push(sched[idx]);
+            }
+        }
+    }
+    if stops.len() < 2 {
+        bail!("only {} stops are on the map", stops.len());
+    }
+
+    let start = pick_start_lane(map.get_bs(stops[0]).driving_pos, route_type, map)?;
+    add_route(
+        map,
+        BusRoute {
+            id: BusRouteID(map.bus_routes.len()),
+            full_name: route.full_name,
+            short_name: route.short_name,
+            osm_rel_id: route.osm_rel_id,
+            gtfs_trip_marker: route.gtfs_trip_marker,
+            stops,
+            route_type,
+            start,
+            end_border: None,
+            orig_spawn_times: route.spawn_times.clone(),
+            spawn_times: route.spawn_times,
+            capacity: None,
+            schedule: if route.schedule.is_some() {
+                Some(schedule)
+            } else {
+                None
+            },
+        },
+    )
 }
 
 struct Matcher {
@@ -284,7 +469,7 @@ impl Matcher {
         let (_, pt) = closest
             .closest_pt(stop.vehicle_pos.1, Distance::meters(10.0))
             .ok_or_else(|| anyhow!("{} isn't near {}", stop.vehicle_pos.0, l.id))?;
-        let mut driving_pos = Position::new(l.id, l.dist_along_of_point(pt).unwrap());
+        let driving_pos = Position::new(l.id, l.dist_along_of_point(pt).unwrap());
 
         let sidewalk_pos = if let Some(pt) = stop.ped_pos {
             *self
@@ -303,20 +488,23 @@ impl Matcher {
             driving_pos.equiv_pos(sidewalk, map)
         };
 
-        // If we're a stop right at an incoming border, make sure to be at least past where the bus
-        // will spawn from the border. pick_start_lane() can't do anything for borders.
-        if map
-            .get_i(map.get_l(driving_pos.lane()).src_i)
-            .is_incoming_border()
-        {
-            if let Some(pos) = driving_pos.min_dist(Distance::meters(1.0), map) {
-                driving_pos = pos;
-            } else {
-                bail!("too close to start of a border {}", driving_pos.lane());
-            }
+        Ok((sidewalk_pos, clear_of_border(driving_pos, map)?))
+    }
+}
+
+/// If a stop is right at an incoming border, make sure it's at least past where the bus will spawn
+/// from the border. pick_start_lane() can't do anything for borders.
+fn clear_of_border(driving_pos: Position, map: &Map) -> Result<Position> {
+    if map
+        .get_i(map.get_l(driving_pos.lane()).src_i)
+        .is_incoming_border()
+    {
+        if let Some(pos) = driving_pos.min_dist(Distance::meters(1.0), map) {
+            return Ok(pos);
         }
-        Ok((sidewalk_pos, driving_pos))
+        bail!("too close to start of a border {}", driving_pos.lane());
     }
+    Ok(driving_pos)
 }
 
 fn pick_start_lane(
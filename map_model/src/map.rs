@@ -10,13 +10,14 @@ use abstio::{CityName, MapName};
 use abstutil::{MultiMap, Tags, Timer};
 use geom::{Bounds, Distance, Duration, GPSBounds, Polygon, Pt2D, Ring, Time};
 
+use crate::pathfind::CreateEngine;
 use crate::raw::{OriginalRoad, RawMap};
 use crate::{
     osm, Area, AreaID, AreaType, Building, BuildingID, BuildingType, BusRoute, BusRouteID, BusStop,
-    BusStopID, ControlStopSign, ControlTrafficSignal, DirectedRoadID, Intersection, IntersectionID,
-    Lane, LaneID, LaneType, Map, MapEdits, MovementID, OffstreetParking, ParkingLot, ParkingLotID,
-    Path, PathConstraints, PathRequest, PathV2, Pathfinder, Position, Road, RoadID, RoutingParams,
-    Turn, TurnID, TurnType, Zone,
+    BusStopID, ControlStopSign, ControlTrafficSignal, DirectedRoadID, ExternalTransitRoute,
+    Intersection, IntersectionID, Lane, LaneID, LaneType, Map, MapEdits, MovementID,
+    OffstreetParking, ParkingLot, ParkingLotID, Path, PathConstraints, PathRequest, PathV2,
+    Pathfinder, Position, Road, RoadID, RoutingParams, Turn, TurnID, TurnType, Zone,
 };
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -582,6 +583,19 @@ impl Map {
             .pathfind_with_params(req.clone(), params, self)
             .ok_or_else(|| anyhow!("can't fulfill {}", req))
     }
+    /// Like `pathfind`, but never enter any of the specified roads. Only works for vehicles. This
+    /// is much slower than regular pathfinding.
+    pub fn pathfind_avoiding_roads(
+        &self,
+        req: PathRequest,
+        avoid: &BTreeSet<RoadID>,
+    ) -> Result<Path> {
+        assert!(!self.pathfinder_dirty);
+        self.pathfinder
+            .pathfind_avoiding_roads(req.clone(), avoid, self)
+            .ok_or_else(|| anyhow!("can't fulfill {} avoiding {} roads", req, avoid.len()))?
+            .into_v1(self)
+    }
     pub fn should_use_transit(
         &self,
         start: Position,
@@ -678,6 +692,28 @@ impl Map {
         self.bus_routes[br.0].spawn_times = times;
     }
 
+    /// Add a transit route that doesn't come from OSM, snapping its stops to the map. Afterwards,
+    /// call `hack_rebuild_pathfinding`, or pathfinding won't know about any new stops.
+    pub fn hack_add_transit_route(
+        &mut self,
+        route: ExternalTransitRoute,
+        timer: &mut Timer,
+    ) -> Result<BusRouteID> {
+        crate::make::add_external_route(self, route, timer)
+    }
+
+    /// Build pathfinding from scratch, with the same engine as before. This is needed after adding
+    /// transit stops, because map edits only update pathfinding for the stops that already exist.
+    pub fn hack_rebuild_pathfinding(&mut self, timer: &mut Timer) {
+        assert!(!self.pathfinder_dirty);
+        let engine = if self.pathfinder.uses_ch() {
+            CreateEngine::CH
+        } else {
+            CreateEngine::Dijkstra
+        };
+        self.pathfinder = Pathfinder::new(self, self.routing_params().clone(), engine, timer);
+    }
+
     pub fn hack_add_area(&mut self, area_type: AreaType, polygon: Polygon, osm_tags: Tags) {
         self.areas.push(Area {
             id: AreaID(self.areas.len()),
@@ -698,6 +734,20 @@ impl Map {
         self.recalculate_pathfinding_after_edits(timer);
     }
 
+    /// Make some movements take extra time for cars to cross, like when they're usually
+    /// congested. Car pathfinding keeps using these delays after map edits, until they're
+    /// replaced.
+    pub fn set_driving_delays(
+        &mut self,
+        delays: &HashMap<MovementID, Duration>,
+        timer: &mut Timer,
+    ) {
+        assert!(!self.pathfinder_dirty);
+        let mut pathfinder = std::mem::replace(&mut self.pathfinder, Pathfinder::empty());
+        pathfinder.set_driving_delays(self, delays, timer);
+        self.pathfinder = pathfinder;
+    }
+
     pub fn get_languages(&self) -> BTreeSet<&str> {
         let mut languages = BTreeSet::new();
         for r in self.all_roads() {
@@ -6,7 +6,7 @@ use std::fmt;
 use serde::{Deserialize, Serialize};
 
 use abstutil::{deserialize_usize, serialize_usize};
-use geom::Time;
+use geom::{Duration, Time};
 
 use crate::{osm, LaneID, Map, PathConstraints, PathRequest, Position};
 
@@ -67,6 +67,35 @@ pub struct BusRoute {
     /// Explicitly store whatever the original was, since this can't be reconstructed without side
     /// input.
     pub orig_spawn_times: Vec<Time>,
+    /// If set, overrides the default capacity of the route's vehicle type. The importer leaves
+    /// this unset; only `EditCmd::ChangeRouteCapacity` changes it.
+    pub capacity: Option<TransitCapacity>,
+    /// If known (usually from GTFS), when vehicles should reach each stop, in the same order as
+    /// `stops`. Offsets are relative to departing the first stop; the simulation adds the time
+    /// needed to reach it. Otherwise the simulation derives a schedule.
+    pub schedule: Option<Vec<ScheduledStop>>,
+}
+
+/// When a vehicle following a route should reach one of its stops
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct ScheduledStop {
+    /// Relative to when the vehicle leaves the start of the route
+    pub offset: Duration,
+    /// Vehicles ahead of schedule may hold at timepoints until the scheduled time.
+    pub timepoint: bool,
+}
+
+/// How many passengers a transit vehicle can carry
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct TransitCapacity {
+    pub seated: usize,
+    pub standing: usize,
+}
+
+impl TransitCapacity {
+    pub fn total(&self) -> usize {
+        self.seated + self.standing
+    }
 }
 
 impl BusRoute {
@@ -3,7 +3,7 @@ use std::fmt;
 use serde::{Deserialize, Serialize};
 
 use abstutil::{deserialize_usize, serialize_usize};
-use geom::{Angle, Line, PolyLine, Polygon, Pt2D};
+use geom::{Angle, Duration, Line, PolyLine, Polygon, Pt2D};
 
 use crate::{osm, Position};
 
@@ -47,6 +47,8 @@ pub struct ParkingLot {
     /// Lot to sidewalk
     pub sidewalk_line: Line,
     pub sidewalk_pos: Position,
+
+    pub parking_rules: ParkingRules,
 }
 
 impl ParkingLot {
@@ -54,3 +56,50 @@ impl ParkingLot {
         self.spots.len() + self.extra_spots
     }
 }
+
+/// Restrictions on parking along a road or in a lot. These apply to every on-street parking lane
+/// of a road. Private spots in buildings are always free.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ParkingRules {
+    /// In dollars
+    pub price_per_hour: f64,
+    /// Parking longer than this is a violation. Drivers don't know how long they'll stay, so they
+    /// don't avoid these spots; the simulation just counts the violations.
+    pub max_stay: Option<Duration>,
+    /// If set, only drivers heading to a building along a road in the same permit zone may park
+    /// here. This approximates residential permits, since everybody's trips start and end at home.
+    pub permit_zone: Option<String>,
+}
+
+impl ParkingRules {
+    /// Free, unlimited, and open to anybody
+    pub fn new() -> ParkingRules {
+        ParkingRules {
+            price_per_hour: 0.0,
+            max_stay: None,
+            permit_zone: None,
+        }
+    }
+
+    pub fn is_unrestricted(&self) -> bool {
+        *self == ParkingRules::new()
+    }
+
+    /// A short summary, like "$2.50/hour, 2 hour limit, permit zone A"
+    pub fn describe(&self) -> String {
+        if self.is_unrestricted() {
+            return "free".to_string();
+        }
+        let mut parts = Vec::new();
+        if self.price_per_hour > 0.0 {
+            parts.push(format!("${:.2}/hour", self.price_per_hour));
+        }
+        if let Some(max) = self.max_stay {
+            parts.push(format!("{} limit", max));
+        }
+        if let Some(ref zone) = self.permit_zone {
+            parts.push(format!("permit zone {}", zone));
+        }
+        parts.join(", ")
+    }
+}
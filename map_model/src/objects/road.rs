@@ -11,7 +11,7 @@ use geom::{Distance, PolyLine, Polygon, Speed};
 use crate::raw::{OriginalRoad, RestrictionType};
 use crate::{
     osm, AccessRestrictions, BusStopID, DrivingSide, IntersectionID, Lane, LaneID, LaneSpec,
-    LaneType, Map, PathConstraints, Zone,
+    LaneType, Map, ParkingRules, PathConstraints, Zone,
 };
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
@@ -142,6 +142,8 @@ pub struct Road {
     pub orig_id: OriginalRoad,
     pub speed_limit: Speed,
     pub access_restrictions: AccessRestrictions,
+    /// Applies to all of the road's parking lanes
+    pub parking_rules: ParkingRules,
     pub zorder: isize,
     /// [-1.0, 1.0] theoretically, but in practice, about [-0.25, 0.25]. 0 is flat,
     /// positive is uphill from src_i -> dst_i, negative is downhill.
@@ -468,6 +470,7 @@ impl Road {
         };
         AccessRestrictions {
             allow_through_traffic,
+            cap_vehicles_per_hour: None,
         }
     }
 
@@ -50,6 +50,25 @@ pub enum StageType {
     /// Delay is the elapsed time with no demand that ends a cycle.
     /// Additional is the additional duration for an extended cycle.
     Variable(Duration, Duration, Duration),
+    /// Detectors at the stop line of each approach lane call for the stage and extend it. The
+    /// stage is skipped entirely if no vehicle is waiting for one of its protected movements and
+    /// no pedestrian has pushed the button for one of its crosswalks. Once it starts, it lasts at
+    /// least `min_green`. Then every vehicle detected extends it by `passage_time`; if nothing
+    /// arrives in that gap, it ends ("gapping out"). It never lasts longer than `max_green`
+    /// ("maxing out").
+    Actuated {
+        min_green: Duration,
+        passage_time: Duration,
+        max_green: Duration,
+    },
+    /// At the start of every cycle, the time given to all adaptive stages is re-split between
+    /// them, in proportion to how many vehicles and pedestrians were queued for each one when it
+    /// last started. The total is the sum of the midpoints between each stage's minimum and
+    /// maximum.
+    Adaptive {
+        min_green: Duration,
+        max_green: Duration,
+    },
 }
 
 impl StageType {
@@ -58,6 +77,8 @@ impl StageType {
         match self {
             StageType::Fixed(d) => *d,
             StageType::Variable(duration, _, _) => *duration,
+            StageType::Actuated { min_green, .. } => *min_green,
+            StageType::Adaptive { min_green, .. } => *min_green,
         }
     }
 }
@@ -157,6 +178,48 @@ impl ControlTrafficSignal {
                     stage.stage_type.simple_duration()
                 );
             }
+            match stage.stage_type {
+                StageType::Actuated {
+                    min_green,
+                    passage_time,
+                    max_green,
+                } => {
+                    if min_green <= Duration::ZERO {
+                        bail!("Actuated stage {} needs a positive min green", stage_index);
+                    }
+                    if max_green < min_green {
+                        bail!(
+                            "Actuated stage {} has a max green {} shorter than its min green {}",
+                            stage_index,
+                            max_green,
+                            min_green
+                        );
+                    }
+                    if passage_time <= Duration::ZERO {
+                        bail!(
+                            "Actuated stage {} needs a positive passage time",
+                            stage_index
+                        );
+                    }
+                }
+                StageType::Adaptive {
+                    min_green,
+                    max_green,
+                } => {
+                    if min_green <= Duration::ZERO {
+                        bail!("Adaptive stage {} needs a positive min green", stage_index);
+                    }
+                    if max_green < min_green {
+                        bail!(
+                            "Adaptive stage {} has a max green {} shorter than its min green {}",
+                            stage_index,
+                            max_green,
+                            min_green
+                        );
+                    }
+                }
+                StageType::Fixed(_) | StageType::Variable(_, _, _) => {}
+            }
         }
         Ok(())
     }
@@ -393,6 +456,19 @@ impl Stage {
                 StageType::Variable(_, delay, additional) => {
                     StageType::Variable(time, delay, additional)
                 }
+                StageType::Actuated {
+                    passage_time,
+                    max_green,
+                    ..
+                } => StageType::Actuated {
+                    min_green: time,
+                    passage_time,
+                    max_green: max_green.max(time),
+                },
+                StageType::Adaptive { max_green, .. } => StageType::Adaptive {
+                    min_green: time,
+                    max_green: max_green.max(time),
+                },
             };
         }
     }
@@ -452,6 +528,22 @@ impl ControlTrafficSignal {
                                     additional.inner_seconds() as usize,
                                 )
                             }
+                            StageType::Actuated {
+                                min_green,
+                                passage_time,
+                                max_green,
+                            } => traffic_signal_data::StageType::Actuated {
+                                min_green_seconds: min_green.inner_seconds() as usize,
+                                passage_seconds: passage_time.inner_seconds() as usize,
+                                max_green_seconds: max_green.inner_seconds() as usize,
+                            },
+                            StageType::Adaptive {
+                                min_green,
+                                max_green,
+                            } => traffic_signal_data::StageType::Adaptive {
+                                min_green_seconds: min_green.inner_seconds() as usize,
+                                max_green_seconds: max_green.inner_seconds() as usize,
+                            },
                         },
                     })
                     .collect(),
@@ -507,6 +599,22 @@ impl ControlTrafficSignal {
                                 Duration::seconds(additional as f64),
                             )
                         }
+                        traffic_signal_data::StageType::Actuated {
+                            min_green_seconds,
+                            passage_seconds,
+                            max_green_seconds,
+                        } => StageType::Actuated {
+                            min_green: Duration::seconds(min_green_seconds as f64),
+                            passage_time: Duration::seconds(passage_seconds as f64),
+                            max_green: Duration::seconds(max_green_seconds as f64),
+                        },
+                        traffic_signal_data::StageType::Adaptive {
+                            min_green_seconds,
+                            max_green_seconds,
+                        } => StageType::Adaptive {
+                            min_green: Duration::seconds(min_green_seconds as f64),
+                            max_green: Duration::seconds(max_green_seconds as f64),
+                        },
                     },
                 });
             } else {
@@ -568,3 +676,51 @@ fn find_r(id: traffic_signal_data::DirectedRoad, map: &Map) -> Result<DirectedRo
         },
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signal(stage_type: StageType) -> ControlTrafficSignal {
+        let mut stage = Stage::new();
+        stage.stage_type = stage_type;
+        ControlTrafficSignal {
+            id: IntersectionID(0),
+            stages: vec![stage],
+            offset: Duration::ZERO,
+            movements: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_validate_actuated() {
+        let actuated = |min_green: f64, passage_time: f64, max_green: f64| {
+            signal(StageType::Actuated {
+                min_green: Duration::seconds(min_green),
+                passage_time: Duration::seconds(passage_time),
+                max_green: Duration::seconds(max_green),
+            })
+            .validate()
+        };
+        assert!(actuated(10.0, 3.0, 40.0).is_ok());
+        assert!(actuated(0.0, 3.0, 40.0).is_err());
+        assert!(actuated(0.0, 3.0, 0.0).is_err());
+        assert!(actuated(10.0, 0.0, 40.0).is_err());
+        assert!(actuated(10.0, 3.0, 5.0).is_err());
+    }
+
+    #[test]
+    fn test_validate_adaptive() {
+        let adaptive = |min_green: f64, max_green: f64| {
+            signal(StageType::Adaptive {
+                min_green: Duration::seconds(min_green),
+                max_green: Duration::seconds(max_green),
+            })
+            .validate()
+        };
+        assert!(adaptive(10.0, 40.0).is_ok());
+        assert!(adaptive(0.0, 40.0).is_err());
+        assert!(adaptive(0.0, 0.0).is_err());
+        assert!(adaptive(10.0, 5.0).is_err());
+    }
+}
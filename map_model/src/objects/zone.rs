@@ -15,12 +15,16 @@ use crate::{IntersectionID, Map, PathConstraints, RoadID};
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct AccessRestrictions {
     pub allow_through_traffic: EnumSet<PathConstraints>,
+    /// If set, only this many vehicles may enter the zone per hour. The simulation enforces this;
+    /// pathfinding ignores it.
+    pub cap_vehicles_per_hour: Option<usize>,
 }
 
 impl AccessRestrictions {
     pub fn new() -> AccessRestrictions {
         AccessRestrictions {
             allow_through_traffic: EnumSet::all(),
+            cap_vehicles_per_hour: None,
         }
     }
 }
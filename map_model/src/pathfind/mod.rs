@@ -9,7 +9,7 @@ pub use self::engine::CreateEngine;
 pub use self::pathfinder::Pathfinder;
 pub use self::v1::{Path, PathRequest, PathStep};
 pub use self::v2::{PathStepV2, PathV2};
-pub use self::vehicles::vehicle_cost;
+pub use self::vehicles::{vehicle_cost, VehiclePathfinder};
 pub use self::walking::WalkingNode;
 use crate::{osm, Lane, LaneID, LaneType, Map, MovementID, TurnType};
 
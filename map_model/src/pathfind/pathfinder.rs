@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 
 use serde::{Deserialize, Serialize};
 
@@ -9,8 +9,8 @@ use crate::pathfind::engine::CreateEngine;
 use crate::pathfind::vehicles::VehiclePathfinder;
 use crate::pathfind::walking::SidewalkPathfinder;
 use crate::{
-    BusRouteID, BusStopID, DirectedRoadID, Map, PathConstraints, PathRequest, PathV2, Position,
-    RoutingParams,
+    BusRouteID, BusStopID, DirectedRoadID, Map, MovementID, PathConstraints, PathRequest, PathV2,
+    Position, RoadID, RoutingParams,
 };
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -133,6 +133,25 @@ impl Pathfinder {
         }
     }
 
+    /// Finds a vehicle path that never enters any of the specified roads. This builds a one-off
+    /// graph without a contraction hierarchy, so it's slow; only use it rarely.
+    pub fn pathfind_avoiding_roads(
+        &self,
+        req: PathRequest,
+        avoid: &BTreeSet<RoadID>,
+        map: &Map,
+    ) -> Option<PathV2> {
+        assert_ne!(req.constraints, PathConstraints::Pedestrian);
+        VehiclePathfinder::new_avoiding_roads(
+            map,
+            req.constraints,
+            &self.params,
+            &CreateEngine::Dijkstra,
+            avoid,
+        )
+        .pathfind(req, map)
+    }
+
     pub fn all_costs_from(
         &self,
         req: PathRequest,
@@ -159,6 +178,23 @@ impl Pathfinder {
             .should_use_transit(map, start, end)
     }
 
+    /// Was this built with contraction hierarchies?
+    pub fn uses_ch(&self) -> bool {
+        !self.car_graph.engine.is_dijkstra()
+    }
+
+    /// Make some movements take extra time for cars. Only car pathfinding is affected.
+    pub fn set_driving_delays(
+        &mut self,
+        map: &Map,
+        delays: &HashMap<MovementID, Duration>,
+        timer: &mut Timer,
+    ) {
+        timer.start("apply delays to car pathfinding");
+        self.car_graph.apply_delays(map, delays);
+        timer.stop("apply delays to car pathfinding");
+    }
+
     pub fn apply_edits(&mut self, map: &Map, timer: &mut Timer) {
         timer.start("apply edits to car pathfinding");
         self.car_graph.apply_edits(map);
@@ -133,9 +133,13 @@ impl PathStep {
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Path {
     steps: VecDeque<PathStep>,
-    // The original request used to produce this path. Calling shift(), add(), modify_step(), etc
-    // will NOT affect this.
+    // The original request used to produce this path. Calling shift(), add(), modify_step(),
+    // reroute(), etc will NOT affect this.
     orig_req: PathRequest,
+    // If the rest of the path was replaced partway through, the request used to find the latest
+    // replacement.
+    #[serde(default)]
+    reroute_req: Option<PathRequest>,
 
     // Also track progress along the original path.
     total_length: Distance,
@@ -171,6 +175,7 @@ impl Path {
         let mut path = Path {
             steps: VecDeque::from(steps),
             orig_req,
+            reroute_req: None,
             total_length: Distance::ZERO,
             crossed_so_far: Distance::ZERO,
             uber_turns: uber_turns.into_iter().collect(),
@@ -228,6 +233,11 @@ impl Path {
         &self.orig_req
     }
 
+    /// If the path has been rerouted, the request used to find the latest replacement.
+    pub fn get_reroute_req(&self) -> Option<&PathRequest> {
+        self.reroute_req.as_ref()
+    }
+
     pub fn crossed_so_far(&self) -> Distance {
         self.crossed_so_far
     }
@@ -308,6 +318,21 @@ impl Path {
         // TODO Maybe need to amend uber_turns?
     }
 
+    /// Replace the rest of this path with a different way to get somewhere. The new path must
+    /// start with the current step. Progress along the original path and the original request are
+    /// kept.
+    pub fn reroute(&mut self, mut new_path: Path) {
+        assert_eq!(self.current_step(), new_path.current_step());
+        assert!(self.currently_inside_ut.is_none());
+        new_path.crossed_so_far = self.crossed_so_far;
+        new_path.total_length += self.crossed_so_far;
+        new_path.reroute_req = Some(std::mem::replace(
+            &mut new_path.orig_req,
+            self.orig_req.clone(),
+        ));
+        *self = new_path;
+    }
+
     pub fn is_upcoming_uber_turn_component(&self, t: TurnID) -> bool {
         self.uber_turns
             .front()
@@ -1,6 +1,6 @@
 //! Pathfinding for cars, bikes, buses, and trains using contraction hierarchies
 
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 
 use fast_paths::InputGraph;
 use serde::{Deserialize, Serialize};
@@ -15,7 +15,7 @@ use crate::pathfind::zone_cost;
 use crate::pathfind::{round, unround};
 use crate::{
     DirectedRoadID, Direction, DrivingSide, LaneType, Map, MovementID, PathConstraints,
-    PathRequest, PathV2, Position, RoutingParams, Traversable, TurnType,
+    PathRequest, PathV2, Position, RoadID, RoutingParams, Traversable, TurnType,
 };
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -26,6 +26,10 @@ pub struct VehiclePathfinder {
     constraints: PathConstraints,
     params: RoutingParams,
     pub engine: PathfindEngine,
+    /// Extra time for some movements, kept through map edits. These usually come from a
+    /// simulation, so they aren't saved with the map.
+    #[serde(skip_serializing, skip_deserializing)]
+    delays: HashMap<MovementID, Duration>,
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
@@ -42,6 +46,7 @@ impl VehiclePathfinder {
             constraints: PathConstraints::Car,
             params: RoutingParams::default(),
             engine: PathfindEngine::Empty,
+            delays: HashMap::new(),
         }
     }
 
@@ -50,6 +55,48 @@ impl VehiclePathfinder {
         constraints: PathConstraints,
         params: &RoutingParams,
         engine: &CreateEngine,
+    ) -> VehiclePathfinder {
+        VehiclePathfinder::new_avoiding_roads(map, constraints, params, engine, &BTreeSet::new())
+    }
+
+    /// Like `new`, but movements into any of the specified roads are left out of the graph
+    /// entirely. Note `apply_edits` will forget about the roads to avoid.
+    pub fn new_avoiding_roads(
+        map: &Map,
+        constraints: PathConstraints,
+        params: &RoutingParams,
+        engine: &CreateEngine,
+        avoid: &BTreeSet<RoadID>,
+    ) -> VehiclePathfinder {
+        VehiclePathfinder::create(map, constraints, params, engine, avoid, &HashMap::new())
+    }
+
+    /// Like `new`, but some movements take extra time, like when they're congested. Since
+    /// the delays are expected to change often, this always uses Dijkstra's algorithm, so building
+    /// it is fast but pathfinding is slow.
+    pub fn new_with_delays(
+        map: &Map,
+        constraints: PathConstraints,
+        params: &RoutingParams,
+        delays: &HashMap<MovementID, Duration>,
+    ) -> VehiclePathfinder {
+        VehiclePathfinder::create(
+            map,
+            constraints,
+            params,
+            &CreateEngine::Dijkstra,
+            &BTreeSet::new(),
+            delays,
+        )
+    }
+
+    fn create(
+        map: &Map,
+        constraints: PathConstraints,
+        params: &RoutingParams,
+        engine: &CreateEngine,
+        avoid: &BTreeSet<RoadID>,
+        delays: &HashMap<MovementID, Duration>,
     ) -> VehiclePathfinder {
         // Insert every road as a node.
         let mut nodes = NodeMap::new();
@@ -75,7 +122,8 @@ impl VehiclePathfinder {
             }
         }
 
-        let input_graph = make_input_graph(constraints, &nodes, &uber_turns, params, map);
+        let input_graph =
+            make_input_graph(constraints, &nodes, &uber_turns, params, avoid, delays, map);
         let engine = engine.create(input_graph);
 
         VehiclePathfinder {
@@ -84,6 +132,7 @@ impl VehiclePathfinder {
             constraints,
             params: params.clone(),
             engine,
+            delays: delays.clone(),
         }
     }
 
@@ -143,12 +192,21 @@ impl VehiclePathfinder {
             &self.nodes,
             &self.uber_turns,
             &self.params,
+            &BTreeSet::new(),
+            &self.delays,
             map,
         );
         let engine = self.engine.reuse_ordering().create(input_graph);
         self.engine = engine;
     }
 
+    /// Make some movements take extra time, replacing any previous delays. The delays are kept
+    /// through `apply_edits`.
+    pub fn apply_delays(&mut self, map: &Map, delays: &HashMap<MovementID, Duration>) {
+        self.delays = delays.clone();
+        self.apply_edits(map);
+    }
+
     pub fn all_costs_from(&self, start: Position, map: &Map) -> HashMap<DirectedRoadID, Duration> {
         let start = self
             .nodes
@@ -162,6 +220,8 @@ impl VehiclePathfinder {
                 &self.nodes,
                 &self.uber_turns,
                 &self.params,
+                &BTreeSet::new(),
+                &HashMap::new(),
                 map,
             );
             CreateEngine::Dijkstra
@@ -186,6 +246,8 @@ fn make_input_graph(
     nodes: &NodeMap<Node>,
     uber_turns: &[UberTurnV2],
     params: &RoutingParams,
+    avoid: &BTreeSet<RoadID>,
+    delays: &HashMap<MovementID, Duration>,
     map: &Map,
 ) -> InputGraph {
     let mut input_graph = InputGraph::new();
@@ -201,11 +263,9 @@ fn make_input_graph(
         // vehicle.
         // TODO Need to test editing lanes inside an IntersectionCluster very carefully. See Mercer
         // and Dexter.
-        if ut
-            .path
-            .iter()
-            .all(|mvmnt| !mvmnt.to.lanes(constraints, map).is_empty())
-        {
+        if ut.path.iter().all(|mvmnt| {
+            !mvmnt.to.lanes(constraints, map).is_empty() && !avoid.contains(&mvmnt.to.id)
+        }) {
             uber_turn_entrances.insert(ut.entry(), idx);
         }
     }
@@ -217,12 +277,16 @@ fn make_input_graph(
                 let indices = uber_turn_entrances.get(dr);
                 if indices.is_empty() {
                     for mvmnt in map.get_movements_for(dr, constraints) {
+                        if avoid.contains(&mvmnt.to.id) {
+                            continue;
+                        }
                         input_graph.add_edge(
                             from,
                             nodes.get(Node::Road(mvmnt.to)),
                             round(
                                 vehicle_cost(mvmnt.from, mvmnt, constraints, params, map)
-                                    + zone_cost(mvmnt, constraints, map),
+                                    + zone_cost(mvmnt, constraints, map)
+                                    + delays.get(&mvmnt).cloned().unwrap_or(Duration::ZERO),
                             ),
                         );
                     }
@@ -233,7 +297,8 @@ fn make_input_graph(
                         let mut sum_cost = Duration::ZERO;
                         for mvmnt in &ut.path {
                             sum_cost += vehicle_cost(mvmnt.from, *mvmnt, constraints, params, map)
-                                + zone_cost(*mvmnt, constraints, map);
+                                + zone_cost(*mvmnt, constraints, map)
+                                + delays.get(mvmnt).cloned().unwrap_or(Duration::ZERO);
                         }
                         input_graph.add_edge(
                             from,
@@ -1,63 +1,56 @@
+use std::collections::BTreeMap;
+
 use rand::Rng;
 use rand_xorshift::XorShiftRng;
 
 use geom::{Duration, Time};
 
-use crate::{Activity, CensusPerson, Config, PersonType, Schedule};
+use crate::{Activity, CensusPerson, Config, PersonType, Schedule, ScheduleStep, ScheduleTemplate};
 
 impl CensusPerson {
-    pub fn generate_schedule(&self, _config: &Config, rng: &mut XorShiftRng) -> Schedule {
-        // TODO How do we pick these categories based on census data?
-        let person_type = if rng.gen_bool(0.5) {
+    /// Classify someone based on their age and employment.
+    pub fn person_type(&self, config: &Config, rng: &mut XorShiftRng) -> PersonType {
+        if self.age < config.adult_age {
+            PersonType::Child
+        } else if self.employed {
+            if rng.gen_bool(config.part_time_rate) {
+                PersonType::PartTimeWorker
+            } else {
+                PersonType::Worker
+            }
+        } else if self.age >= config.retirement_age {
+            PersonType::Retiree
+        } else if self.age <= config.max_student_age {
             PersonType::Student
         } else {
-            PersonType::Worker
+            PersonType::Unemployed
+        }
+    }
+
+    pub fn generate_schedule(&self, config: &Config, rng: &mut XorShiftRng) -> Schedule {
+        let person_type = self.person_type(config, rng);
+        let defaults;
+        let template = match config.schedules.get(&person_type) {
+            Some(template) => template,
+            None => {
+                defaults = default_schedules();
+                &defaults[&person_type]
+            }
         };
 
         // Fill out a list of activities and how long the person should do the activity before
         // travelling to the next place.
+        let start_time =
+            Time::START_OF_DAY + rand_duration(rng, template.start_time.0, template.start_time.1);
         let mut plan = Vec::new();
-        let start_time;
-
-        match person_type {
-            PersonType::Student => {
-                // I'm probably channeling a college student here...
-                start_time = rand_time(rng, hours(8), hours(11));
-                if rng.gen_bool(0.95) {
-                    plan.push((Activity::Breakfast, minutes(30)));
-                }
-                plan.push((Activity::School, rand_duration(rng, hours(3), hours(6))));
-                if rng.gen_bool(0.3) {
-                    plan.push((
-                        Activity::Lunch,
-                        rand_duration(rng, minutes(20), minutes(40)),
-                    ));
-                }
-                plan.push((Activity::School, rand_duration(rng, hours(2), hours(4))));
-                if rng.gen_bool(0.6) {
-                    plan.push((Activity::Entertainment, hours(2)));
-                } else {
-                    plan.push((Activity::Errands, rand_duration(rng, minutes(15), hours(1))));
-                }
-                // The last duration doesn't matter
-                plan.push((Activity::Home, hours(8)));
-            }
-            PersonType::Worker => {
-                start_time = rand_time(rng, hours(6), hours(9));
-                if rng.gen_bool(0.8) {
-                    plan.push((Activity::Breakfast, minutes(15)));
-                }
-                plan.push((Activity::Work, rand_duration(rng, hours(4), hours(5))));
+        for step in &template.steps {
+            if rng.gen_bool(step.probability) {
                 plan.push((
-                    Activity::Lunch,
-                    rand_duration(rng, minutes(20), minutes(40)),
+                    step.activity,
+                    rand_duration(rng, step.duration.0, step.duration.1),
                 ));
-                plan.push((Activity::Work, hours(4)));
-                if rng.gen_bool(0.8) {
-                    plan.push((Activity::Errands, rand_duration(rng, minutes(15), hours(1))));
-                }
-                // The last duration doesn't matter
-                plan.push((Activity::Home, hours(8)));
+            } else if let Some((activity, (low, high))) = step.otherwise {
+                plan.push((activity, rand_duration(rng, low, high)));
             }
         }
 
@@ -76,13 +69,119 @@ impl CensusPerson {
     }
 }
 
-fn rand_duration(rng: &mut XorShiftRng, low: Duration, high: Duration) -> Duration {
-    assert!(high > low);
-    Duration::seconds(rng.gen_range(low.inner_seconds()..high.inner_seconds()))
+/// Made-up templates for each PersonType
+pub fn default_schedules() -> BTreeMap<PersonType, ScheduleTemplate> {
+    let mut schedules = BTreeMap::new();
+    // I'm probably channeling a college student here...
+    schedules.insert(
+        PersonType::Student,
+        ScheduleTemplate {
+            start_time: (hours(8), hours(11)),
+            steps: vec![
+                step(Activity::Breakfast, 0.95, minutes(30), minutes(30)),
+                step(Activity::School, 1.0, hours(3), hours(6)),
+                step(Activity::Lunch, 0.3, minutes(20), minutes(40)),
+                step(Activity::School, 1.0, hours(2), hours(4)),
+                step(Activity::Entertainment, 0.6, hours(2), hours(2)).otherwise(
+                    Activity::Errands,
+                    minutes(15),
+                    hours(1),
+                ),
+                // The last duration doesn't matter
+                step(Activity::Home, 1.0, hours(8), hours(8)),
+            ],
+        },
+    );
+    schedules.insert(
+        PersonType::Worker,
+        ScheduleTemplate {
+            start_time: (hours(6), hours(9)),
+            steps: vec![
+                step(Activity::Breakfast, 0.8, minutes(15), minutes(15)),
+                step(Activity::Work, 1.0, hours(4), hours(5)),
+                step(Activity::Lunch, 1.0, minutes(20), minutes(40)),
+                step(Activity::Work, 1.0, hours(4), hours(4)),
+                step(Activity::Errands, 0.8, minutes(15), hours(1)),
+                step(Activity::Home, 1.0, hours(8), hours(8)),
+            ],
+        },
+    );
+    schedules.insert(
+        PersonType::PartTimeWorker,
+        ScheduleTemplate {
+            start_time: (hours(8), hours(12)),
+            steps: vec![
+                step(Activity::Work, 1.0, hours(3), hours(5)),
+                step(Activity::Lunch, 0.5, minutes(20), minutes(40)),
+                step(Activity::Errands, 0.6, minutes(15), hours(1)),
+                step(Activity::Entertainment, 0.3, hours(1), hours(2)),
+                step(Activity::Home, 1.0, hours(8), hours(8)),
+            ],
+        },
+    );
+    schedules.insert(
+        PersonType::Child,
+        ScheduleTemplate {
+            start_time: (hours(7), minutes(8 * 60 + 30)),
+            steps: vec![
+                step(Activity::School, 1.0, hours(6), hours(7)),
+                step(Activity::Entertainment, 0.4, hours(1), hours(2)),
+                step(Activity::Home, 1.0, hours(8), hours(8)),
+            ],
+        },
+    );
+    schedules.insert(
+        PersonType::Retiree,
+        ScheduleTemplate {
+            start_time: (hours(9), hours(11)),
+            steps: vec![
+                step(Activity::Breakfast, 0.5, minutes(30), hours(1)),
+                step(Activity::Healthcare, 0.2, minutes(30), hours(1)),
+                step(Activity::Errands, 0.7, minutes(30), hours(2)),
+                step(Activity::Lunch, 0.3, minutes(30), hours(1)),
+                step(Activity::Entertainment, 0.4, hours(1), hours(3)),
+                step(Activity::Home, 1.0, hours(8), hours(8)),
+            ],
+        },
+    );
+    schedules.insert(
+        PersonType::Unemployed,
+        ScheduleTemplate {
+            start_time: (hours(9), hours(12)),
+            steps: vec![
+                step(Activity::Errands, 0.6, minutes(30), hours(2)),
+                step(Activity::Financial, 0.3, minutes(15), minutes(30)),
+                step(Activity::Lunch, 0.3, minutes(20), minutes(40)),
+                step(Activity::Entertainment, 0.5, hours(1), hours(3)),
+                step(Activity::Home, 1.0, hours(8), hours(8)),
+            ],
+        },
+    );
+    schedules
+}
+
+fn step(activity: Activity, probability: f64, low: Duration, high: Duration) -> ScheduleStep {
+    ScheduleStep {
+        activity,
+        probability,
+        duration: (low, high),
+        otherwise: None,
+    }
 }
 
-fn rand_time(rng: &mut XorShiftRng, low: Duration, high: Duration) -> Time {
-    Time::START_OF_DAY + rand_duration(rng, low, high)
+impl ScheduleStep {
+    fn otherwise(mut self, activity: Activity, low: Duration, high: Duration) -> ScheduleStep {
+        self.otherwise = Some((activity, (low, high)));
+        self
+    }
+}
+
+fn rand_duration(rng: &mut XorShiftRng, low: Duration, high: Duration) -> Duration {
+    assert!(high >= low);
+    if high == low {
+        return low;
+    }
+    Duration::seconds(rng.gen_range(low.inner_seconds()..high.inner_seconds()))
 }
 
 // TODO I thought we could just use geom::Duration::{hours, minutes};   but this doesn't work
@@ -92,3 +191,72 @@ fn minutes(x: usize) -> Duration {
 fn hours(x: usize) -> Duration {
     Duration::hours(x)
 }
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use map_model::BuildingID;
+
+    use super::*;
+
+    fn person(age: usize, employed: bool) -> CensusPerson {
+        CensusPerson {
+            home: BuildingID(0),
+            age,
+            employed,
+        }
+    }
+
+    #[test]
+    fn test_person_type() {
+        let config = Config::default();
+        let mut rng = XorShiftRng::seed_from_u64(42);
+        let mut person_type = |age, employed| person(age, employed).person_type(&config, &mut rng);
+
+        assert_eq!(person_type(config.adult_age - 1, false), PersonType::Child);
+        assert_eq!(person_type(config.adult_age, false), PersonType::Student);
+        assert_eq!(
+            person_type(config.max_student_age, false),
+            PersonType::Student
+        );
+        assert_eq!(
+            person_type(config.max_student_age + 1, false),
+            PersonType::Unemployed
+        );
+        assert_eq!(
+            person_type(config.retirement_age, false),
+            PersonType::Retiree
+        );
+
+        // Anybody employed works, no matter their age
+        for age in vec![config.adult_age, 40, config.retirement_age + 5] {
+            let result = person_type(age, true);
+            assert!(result == PersonType::Worker || result == PersonType::PartTimeWorker);
+        }
+    }
+
+    #[test]
+    fn test_student_entertainment_or_errands() {
+        let config = Config::default();
+        let mut rng = XorShiftRng::seed_from_u64(42);
+        let student = person(20, false);
+        assert_eq!(student.person_type(&config, &mut rng), PersonType::Student);
+
+        // After school, students do exactly one of these
+        let mut entertainment = 0;
+        for _ in 0..100 {
+            let schedule = student.generate_schedule(&config, &mut rng);
+            let count = |activity| {
+                schedule
+                    .activities
+                    .iter()
+                    .filter(|(_, a)| *a == activity)
+                    .count()
+            };
+            assert_eq!(count(Activity::Entertainment) + count(Activity::Errands), 1);
+            entertainment += count(Activity::Entertainment);
+        }
+        assert!(entertainment > 0 && entertainment < 100);
+    }
+}
@@ -0,0 +1,373 @@
+use std::collections::{BTreeMap, HashMap};
+
+use rand::seq::SliceRandom;
+use rand_xorshift::XorShiftRng;
+
+use geom::Duration;
+use map_model::connectivity::{
+    all_vehicle_costs_from, all_walking_costs_from, Spot, WalkingOptions,
+};
+use map_model::{Building, BuildingID, BuildingType, Map, PathConstraints};
+
+use crate::{Activity, DestinationChoice};
+
+impl DestinationChoice {
+    /// How attractive a building is for this activity. 0 means it doesn't satisfy the activity at
+    /// all.
+    pub fn attractiveness(&self, b: &Building) -> f64 {
+        let amenities = b
+            .amenities
+            .iter()
+            .filter(|amenity| self.amenities.contains(&amenity.amenity_type))
+            .count();
+        self.weigh(amenities, &b.bldg_type)
+    }
+
+    /// The attractiveness of a building with some number of matching amenities
+    fn weigh(&self, amenities: usize, bldg_type: &BuildingType) -> f64 {
+        let (jobs, weight) = match *bldg_type {
+            BuildingType::Residential { .. } => (0, self.residential_weight),
+            BuildingType::ResidentialCommercial(_, workers) => (workers, self.mixed_use_weight),
+            BuildingType::Commercial(workers) => (workers, self.commercial_weight),
+            BuildingType::Empty => (0, 0.0),
+        };
+        weight * (self.per_amenity * (amenities as f64) + self.per_job * (jobs as f64))
+    }
+
+    /// Every building that satisfies this activity, with its attractiveness
+    pub fn candidates(&self, map: &Map) -> Vec<(BuildingID, f64)> {
+        map.all_buildings()
+            .iter()
+            .filter_map(|b| {
+                let attractiveness = self.attractiveness(b);
+                if attractiveness > 0.0 {
+                    Some((b.id, attractiveness))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// How long it takes to reach buildings from somewhere, up to at least `max_travel_time`.
+    /// Pedestrian constraints measure walking time. Searching is expensive, so the results are
+    /// remembered in the cache.
+    pub fn travel_times<'a>(
+        &self,
+        map: &Map,
+        from: BuildingID,
+        constraints: PathConstraints,
+        cache: &'a mut TravelTimeCache,
+    ) -> &'a HashMap<BuildingID, Duration> {
+        cache.get(map, from, constraints, self.max_travel_time)
+    }
+
+    /// Randomly pick one of the candidates, weighted by attractiveness and how hard it is to get
+    /// there. Candidates farther than `max_travel_time` are skipped. Without travel times, or if
+    /// none of the candidates are reachable, only attractiveness matters.
+    pub fn pick(
+        &self,
+        candidates: &[(BuildingID, f64)],
+        travel_times: Option<&HashMap<BuildingID, Duration>>,
+        rng: &mut XorShiftRng,
+    ) -> Option<BuildingID> {
+        if let Some(travel_times) = travel_times {
+            let reachable: Vec<(BuildingID, f64)> = candidates
+                .iter()
+                .filter_map(|(b, attractiveness)| {
+                    let time = *travel_times.get(b)?;
+                    if time > self.max_travel_time {
+                        return None;
+                    }
+                    let minutes = time.inner_seconds() / 60.0;
+                    Some((*b, attractiveness / (1.0 + minutes).powf(self.impedance)))
+                })
+                .collect();
+            if let Ok((b, _)) = reachable.choose_weighted(rng, |(_, weight)| *weight) {
+                return Some(*b);
+            }
+        }
+        candidates
+            .choose_weighted(rng, |(_, weight)| *weight)
+            .ok()
+            .map(|(b, _)| *b)
+    }
+}
+
+/// Remembers the travel times from buildings already searched, since many trips leave from the
+/// same place. Each search can reach much of the map, so keep the cache around only while the
+/// same origins are likely to come up again.
+#[derive(Default)]
+pub struct TravelTimeCache {
+    /// For each origin and mode, how far the search went and what it reached
+    searches: BTreeMap<(BuildingID, PathConstraints), (Duration, HashMap<BuildingID, Duration>)>,
+}
+
+impl TravelTimeCache {
+    pub fn new() -> TravelTimeCache {
+        TravelTimeCache::default()
+    }
+
+    fn get(
+        &mut self,
+        map: &Map,
+        from: BuildingID,
+        constraints: PathConstraints,
+        max_travel_time: Duration,
+    ) -> &HashMap<BuildingID, Duration> {
+        let key = (from, constraints);
+        // A search that went at least as far can be reused
+        let searched = self
+            .searches
+            .get(&key)
+            .map(|(limit, _)| *limit >= max_travel_time)
+            .unwrap_or(false);
+        if !searched {
+            let times = if constraints == PathConstraints::Pedestrian {
+                all_walking_costs_from(
+                    map,
+                    vec![Spot::Building(from)],
+                    max_travel_time,
+                    WalkingOptions::default(),
+                )
+            } else {
+                all_vehicle_costs_from(
+                    map,
+                    vec![Spot::Building(from)],
+                    max_travel_time,
+                    constraints,
+                )
+            };
+            self.searches.insert(key, (max_travel_time, times));
+        }
+        &self.searches[&key].1
+    }
+}
+
+/// Where people go for each activity. Errands and meals stay close to where somebody starts, while
+/// people travel farther for work, and most work happens in commercial areas.
+pub fn default_destinations() -> BTreeMap<Activity, DestinationChoice> {
+    let mut destinations = BTreeMap::new();
+    destinations.insert(
+        Activity::Breakfast,
+        choice(vec!["cafe"], 0.0, 2.0, minutes(20)),
+    );
+    destinations.insert(
+        Activity::Lunch,
+        choice(
+            vec!["pub", "food_court", "fast_food"],
+            0.0,
+            2.0,
+            minutes(20),
+        ),
+    );
+    destinations.insert(
+        Activity::Dinner,
+        choice(
+            vec!["restaurant", "theatre", "biergarten"],
+            0.0,
+            1.5,
+            minutes(30),
+        ),
+    );
+    destinations.insert(
+        Activity::School,
+        choice(
+            vec![
+                "college",
+                "kindergarten",
+                "language_school",
+                "library",
+                "music_school",
+                "university",
+            ],
+            0.0,
+            1.5,
+            minutes(45),
+        ),
+    );
+    destinations.insert(
+        Activity::Entertainment,
+        choice(
+            vec![
+                "arts_centre",
+                "casino",
+                "cinema",
+                "community_centre",
+                "fountain",
+                "gambling",
+                "nightclub",
+                "planetarium",
+                "public_bookcase",
+                "pool",
+                "dojo",
+                "social_centre",
+                "studio",
+                "theatre",
+                "bar",
+                "bbq",
+                "bicycle_rental",
+                "boat_rental",
+                "boat_sharing",
+                "dive_centre",
+                "internet_cafe",
+            ],
+            0.0,
+            1.5,
+            minutes(30),
+        ),
+    );
+    destinations.insert(
+        Activity::Errands,
+        choice(
+            vec![
+                "marketplace",
+                "post_box",
+                "photo_booth",
+                "recycling",
+                "townhall",
+            ],
+            0.0,
+            2.0,
+            minutes(20),
+        ),
+    );
+    destinations.insert(
+        Activity::Financial,
+        choice(
+            vec!["bank", "atm", "bureau_de_change"],
+            0.0,
+            2.0,
+            minutes(20),
+        ),
+    );
+    destinations.insert(
+        Activity::Healthcare,
+        choice(
+            vec![
+                "baby_hatch",
+                "clinic",
+                "dentist",
+                "doctors",
+                "hospital",
+                "nursing_home",
+                "pharmacy",
+                "social_facility",
+                "veterinary",
+                "childcare",
+            ],
+            0.0,
+            1.0,
+            minutes(45),
+        ),
+    );
+    // Mostly based on the number of jobs, but a few amenities hint at workplaces too
+    destinations.insert(
+        Activity::Work,
+        choice(vec!["bank", "clinic"], 1.0, 1.0, minutes(60)),
+    );
+    destinations
+}
+
+fn choice(
+    amenities: Vec<&str>,
+    per_job: f64,
+    impedance: f64,
+    max_travel_time: Duration,
+) -> DestinationChoice {
+    DestinationChoice {
+        amenities: amenities.into_iter().map(|x| x.to_string()).collect(),
+        per_amenity: 1.0,
+        per_job,
+        residential_weight: 0.5,
+        mixed_use_weight: 1.0,
+        commercial_weight: 2.0,
+        impedance,
+        max_travel_time,
+    }
+}
+
+fn minutes(x: usize) -> Duration {
+    Duration::minutes(x)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn test_attractiveness() {
+        let work = choice(vec!["bank"], 1.0, 1.0, minutes(60));
+        assert_eq!(work.weigh(0, &BuildingType::Empty), 0.0);
+        assert_eq!(work.weigh(3, &BuildingType::Empty), 0.0);
+        // More jobs and amenities help, and commercial areas count the most
+        assert!(
+            work.weigh(0, &BuildingType::Commercial(20))
+                > work.weigh(0, &BuildingType::Commercial(10))
+        );
+        assert!(
+            work.weigh(1, &BuildingType::Commercial(10))
+                > work.weigh(0, &BuildingType::Commercial(10))
+        );
+        assert!(
+            work.weigh(0, &BuildingType::Commercial(10))
+                > work.weigh(0, &BuildingType::ResidentialCommercial(5, 10))
+        );
+    }
+
+    #[test]
+    fn test_pick() {
+        let errands = choice(vec!["marketplace"], 0.0, 2.0, minutes(20));
+        let mut rng = XorShiftRng::seed_from_u64(42);
+        let (near, far, unreachable) = (BuildingID(0), BuildingID(1), BuildingID(2));
+        let mut travel_times = HashMap::new();
+        travel_times.insert(near, minutes(2));
+        travel_times.insert(far, minutes(15));
+        // Farther than max_travel_time
+        travel_times.insert(unreachable, minutes(30));
+
+        // The closer building wins most of the time, and buildings too far away never win
+        let counts = pick_many(
+            &errands,
+            &[(near, 1.0), (far, 1.0), (unreachable, 100.0)],
+            &travel_times,
+            &mut rng,
+        );
+        assert!(counts[&near] > 900);
+        assert!(!counts.contains_key(&unreachable));
+
+        // If two buildings are equally close, the more attractive wins more often
+        travel_times.insert(far, minutes(2));
+        let counts = pick_many(
+            &errands,
+            &[(near, 1.0), (far, 3.0)],
+            &travel_times,
+            &mut rng,
+        );
+        assert!(counts[&far] > 2 * counts[&near]);
+
+        // Nothing's reachable, so only attractiveness matters
+        let only_unreachable = [(unreachable, 1.0)];
+        assert_eq!(
+            errands.pick(&only_unreachable, Some(&travel_times), &mut rng),
+            Some(unreachable)
+        );
+    }
+
+    /// How many times each candidate is picked, out of 1000
+    fn pick_many(
+        choice: &DestinationChoice,
+        candidates: &[(BuildingID, f64)],
+        travel_times: &HashMap<BuildingID, Duration>,
+        rng: &mut XorShiftRng,
+    ) -> BTreeMap<BuildingID, usize> {
+        let mut counts = BTreeMap::new();
+        for _ in 0..1000 {
+            let b = choice.pick(candidates, Some(travel_times), rng).unwrap();
+            *counts.entry(b).or_insert(0) += 1;
+        }
+        counts
+    }
+}
@@ -5,31 +5,110 @@ use rand_xorshift::XorShiftRng;
 use abstutil::prettyprint_usize;
 use map_model::{BuildingID, Map};
 
-use crate::{CensusArea, CensusPerson, Config};
+use crate::{AgeBand, CensusArea, CensusHousehold, CensusPerson, Config};
 
 pub fn assign_people_to_houses(
     areas: Vec<CensusArea>,
     map: &Map,
     rng: &mut XorShiftRng,
-    _config: &Config,
-) -> Vec<CensusPerson> {
-    let mut people = Vec::new();
+    config: &Config,
+) -> Vec<CensusHousehold> {
+    let mut households = Vec::new();
     for area in areas {
-        for (home, n) in distribute_population_to_homes(area.polygon, area.population, map, rng) {
-            for _ in 0..n {
-                people.push(CensusPerson {
+        let age_bands = area
+            .age_bands
+            .unwrap_or_else(|| config.default_age_bands.clone());
+        let employment_rate = area
+            .employment_rate
+            .unwrap_or(config.default_employment_rate);
+        let car_ownership = area.car_ownership.unwrap_or(config.default_car_ownership);
+        let household_sizes = area
+            .household_sizes
+            .unwrap_or_else(|| config.default_household_sizes.clone());
+
+        for (home, mut n) in distribute_population_to_homes(area.polygon, area.population, map, rng)
+        {
+            // Split everybody living in one building into households
+            while n > 0 {
+                let size = pick_household_size(&household_sizes, rng).min(n);
+                n -= size;
+
+                let mut members = Vec::new();
+                for idx in 0..size {
+                    // Somebody has to be the grown-up
+                    let min_age = if idx == 0 { config.adult_age } else { 0 };
+                    let age = pick_age(&age_bands, min_age, rng);
+                    let working_age = age >= config.adult_age && age < config.retirement_age;
+                    members.push(CensusPerson {
+                        home,
+                        age,
+                        employed: working_age && rng.gen_bool(employment_rate),
+                    });
+                }
+
+                let mut num_vehicles = 0;
+                if rng.gen_bool(car_ownership) {
+                    num_vehicles = 1;
+                    let adults = members
+                        .iter()
+                        .filter(|person| person.age >= config.adult_age)
+                        .count();
+                    for _ in 1..adults {
+                        if rng.gen_bool(config.extra_vehicle_rate) {
+                            num_vehicles += 1;
+                        }
+                    }
+                }
+
+                households.push(CensusHousehold {
                     home,
-                    // TODO Making this up for now. We can either move this to Config or see if we
-                    // can extract it from the census. Also, not even sure which of these
-                    // attributes are useful later in the pipeline.
-                    age: rng.gen_range(5..95),
-                    employed: rng.gen_bool(0.7),
-                    owns_car: rng.gen_bool(0.5),
+                    members,
+                    num_vehicles,
                 });
             }
         }
     }
-    people
+    households
+}
+
+/// Pick an age band weighted by the fraction of people in it, then any age within that band. Only
+/// ages of at least min_age are considered.
+fn pick_age(age_bands: &[AgeBand], min_age: usize, rng: &mut XorShiftRng) -> usize {
+    let bands: Vec<(usize, usize, f64)> = age_bands
+        .iter()
+        .filter(|band| band.max_age > min_age)
+        .map(|band| (band.min_age.max(min_age), band.max_age, band.fraction))
+        .collect();
+    let total: f64 = bands.iter().map(|(_, _, fraction)| fraction).sum();
+    if total <= 0.0 {
+        // Missing data; just make something up
+        return rng.gen_range(min_age.max(5)..95);
+    }
+    let mut choice = rng.gen_range(0.0..total);
+    for (low, high, fraction) in &bands {
+        if choice < *fraction {
+            return rng.gen_range(*low..*high);
+        }
+        choice -= fraction;
+    }
+    let (low, high, _) = bands.last().unwrap();
+    rng.gen_range(*low..*high)
+}
+
+/// Household sizes are weighted by the fraction of households with 1, 2, 3, ... people.
+fn pick_household_size(household_sizes: &[f64], rng: &mut XorShiftRng) -> usize {
+    let total: f64 = household_sizes.iter().sum();
+    if total <= 0.0 {
+        return 1;
+    }
+    let mut choice = rng.gen_range(0.0..total);
+    for (idx, fraction) in household_sizes.iter().enumerate() {
+        if choice < *fraction {
+            return idx + 1;
+        }
+        choice -= fraction;
+    }
+    household_sizes.len()
 }
 
 /// Starting from some number of total people living in a polygonal area, randomly distribute them
@@ -1,9 +1,11 @@
+use std::collections::HashMap;
+
 use anyhow::Result;
 use geo::algorithm::intersects::Intersects;
 
 use geom::{GPSBounds, Polygon};
 
-use crate::CensusArea;
+use crate::{AgeBand, CensusArea};
 
 impl CensusArea {
     pub async fn fetch_all_for_map(
@@ -83,6 +85,10 @@ impl CensusArea {
                 results.push(CensusArea {
                     polygon,
                     population,
+                    age_bands: parse_age_bands(&props)?,
+                    employment_rate: parse_fraction(&props, "employment_rate")?,
+                    car_ownership: parse_fraction(&props, "car_ownership")?,
+                    household_sizes: parse_household_sizes(&props)?,
                 });
             } else {
                 warn!("skipping unexpected geometry");
@@ -93,3 +99,143 @@ impl CensusArea {
         Ok(results)
     }
 }
+
+// Besides population, areas may have some optional properties:
+// - `age_18_25` and similar, counting people at least 18 and younger than 25
+// - `employment_rate` and `car_ownership`, as fractions between 0 and 1
+// - `household_size_1`, `household_size_2`, etc, counting households with that many people
+
+fn parse_age_bands(props: &HashMap<String, String>) -> Result<Option<Vec<AgeBand>>> {
+    let mut bands = Vec::new();
+    for (key, value) in props {
+        let parts: Vec<&str> = key.split('_').collect();
+        if parts.len() != 3 || parts[0] != "age" {
+            continue;
+        }
+        let (min_age, max_age) = match (parts[1].parse::<usize>(), parts[2].parse::<usize>()) {
+            (Ok(min_age), Ok(max_age)) => (min_age, max_age),
+            _ => {
+                warn!("skipping unknown census property {}", key);
+                continue;
+            }
+        };
+        if min_age >= max_age {
+            bail!("bad age band {}", key);
+        }
+        bands.push(AgeBand {
+            min_age,
+            max_age,
+            fraction: value.parse::<usize>()? as f64,
+        });
+    }
+    let total: f64 = bands.iter().map(|band| band.fraction).sum();
+    if total == 0.0 {
+        return Ok(None);
+    }
+    bands.sort_by_key(|band| band.min_age);
+    for band in &mut bands {
+        band.fraction /= total;
+    }
+    Ok(Some(bands))
+}
+
+fn parse_fraction(props: &HashMap<String, String>, key: &str) -> Result<Option<f64>> {
+    match props.get(key) {
+        Some(value) => {
+            let x: f64 = value.parse()?;
+            if !(0.0..=1.0).contains(&x) {
+                bail!("{} = {} isn't between 0 and 1", key, x);
+            }
+            Ok(Some(x))
+        }
+        None => Ok(None),
+    }
+}
+
+fn parse_household_sizes(props: &HashMap<String, String>) -> Result<Option<Vec<f64>>> {
+    let mut counts = Vec::new();
+    while let Some(value) = props.get(&format!("household_size_{}", counts.len() + 1)) {
+        counts.push(value.parse::<usize>()? as f64);
+    }
+    let total: f64 = counts.iter().sum();
+    if total == 0.0 {
+        return Ok(None);
+    }
+    Ok(Some(counts.into_iter().map(|x| x / total).collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn props(pairs: Vec<(&str, &str)>) -> HashMap<String, String> {
+        pairs
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_age_bands() {
+        let bands = parse_age_bands(&props(vec![
+            ("population", "100"),
+            ("age_65_95", "20"),
+            ("age_0_18", "20"),
+            ("age_18_65", "60"),
+            // Not an age band, so it's skipped
+            ("age_of_bldgs", "50"),
+        ]))
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            bands,
+            vec![
+                AgeBand {
+                    min_age: 0,
+                    max_age: 18,
+                    fraction: 0.2,
+                },
+                AgeBand {
+                    min_age: 18,
+                    max_age: 65,
+                    fraction: 0.6,
+                },
+                AgeBand {
+                    min_age: 65,
+                    max_age: 95,
+                    fraction: 0.2,
+                },
+            ]
+        );
+
+        assert_eq!(
+            parse_age_bands(&props(vec![("population", "100")])).unwrap(),
+            None
+        );
+        assert_eq!(
+            parse_age_bands(&props(vec![("age_0_18", "0")])).unwrap(),
+            None
+        );
+        assert!(parse_age_bands(&props(vec![("age_18_5", "10")])).is_err());
+        assert!(parse_age_bands(&props(vec![("age_0_18", "lots")])).is_err());
+    }
+
+    #[test]
+    fn test_parse_household_sizes() {
+        // Sizes have to be consecutive, starting from 1
+        assert_eq!(
+            parse_household_sizes(&props(vec![
+                ("household_size_1", "30"),
+                ("household_size_2", "50"),
+                ("household_size_4", "20"),
+            ]))
+            .unwrap(),
+            Some(vec![0.375, 0.625])
+        );
+        assert_eq!(
+            parse_household_sizes(&props(vec![("household_size_2", "50")])).unwrap(),
+            None
+        );
+        assert!(parse_household_sizes(&props(vec![("household_size_1", "-3")])).is_err());
+    }
+}
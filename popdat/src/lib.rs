@@ -10,28 +10,32 @@
 //!
 //! 1) For a given map, find some census data that describes how many people live in different
 //!    areas of the city. (CensusArea)
-//! 2) Take the CensusAreas and turn them into individual CensusPersons, by randomly choosing a
-//!    specific building on the map as their home, and assigning specific attributes based on the
-//!    census data's distribution.
+//! 2) Take the CensusAreas and turn them into CensusHouseholds of individual CensusPersons, by
+//!    randomly choosing a specific building on the map as their home, and assigning specific
+//!    attributes based on the census data's distribution.
 //! 3) For each CensusPerson, classify them into a PersonType, then generate a Schedule of
 //!    different Activities throughout the day.
-//! 4) Pick specific buildings to visit to satisfy the Schedule.
+//! 4) Pick specific buildings to visit to satisfy the Schedule, using a DestinationChoice model.
+//!    Some adults escort children from their household.
 
 #[macro_use]
 extern crate anyhow;
 #[macro_use]
 extern crate log;
 
+use std::collections::BTreeMap;
+
 use rand_xorshift::XorShiftRng;
 
 use abstutil::Timer;
-use geom::{Distance, Time};
+use geom::{Distance, Duration, Time};
 use map_model::{BuildingID, Map};
 use sim::Scenario;
 
 pub use self::distribute_people::distribute_population_to_homes;
 
 mod activities;
+mod destinations;
 mod distribute_people;
 mod import_census;
 mod make_person;
@@ -41,11 +45,38 @@ pub mod od;
 /// blocks, depending what data we find. All of the areas should roughly partition the map -- we
 /// probably don't need to guarantee we cover every single building, but we definitely shouldn't
 /// have two overlapping areas.
+///
+/// Besides population, census data for an area may be missing any of the other attributes. Then
+/// the defaults from `Config` are used instead.
 #[derive(Debug, PartialEq)]
 pub struct CensusArea {
     pub polygon: geo::Polygon<f64>,
     pub population: usize,
-    // TODO Not sure what goes here, whatever census data actually has that could be useful
+    /// How many people are in different age ranges
+    pub age_bands: Option<Vec<AgeBand>>,
+    /// The fraction of working-age adults who're employed
+    pub employment_rate: Option<f64>,
+    /// The fraction of households with at least one vehicle
+    pub car_ownership: Option<f64>,
+    /// The fraction of households with 1, 2, 3, ... people
+    pub household_sizes: Option<Vec<f64>>,
+}
+
+/// Some fraction of an area's population is in this age range.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AgeBand {
+    pub min_age: usize,
+    /// Exclusive
+    pub max_age: usize,
+    pub fraction: f64,
+}
+
+/// People living together, sharing some vehicles
+pub struct CensusHousehold {
+    pub home: BuildingID,
+    pub members: Vec<CensusPerson>,
+    /// Any adult member can drive one of these, if nobody else is using it
+    pub num_vehicles: usize,
 }
 
 /// Demographic information for a single person
@@ -53,14 +84,23 @@ pub struct CensusPerson {
     pub home: BuildingID,
     pub age: usize,
     pub employed: bool,
-    pub owns_car: bool,
 }
 
 /// It might be useful to classify a CensusPerson into different categories to figure out their
 /// Schedule.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum PersonType {
+    /// Young adults not working
     Student,
+    /// Full-time
     Worker,
+    PartTimeWorker,
+    /// Anybody under `Config::adult_age`, who goes to school
+    Child,
+    /// Anybody at least `Config::retirement_age` and not working
+    Retiree,
+    /// Adults not working or studying
+    Unemployed,
 }
 
 /// A single person's daily schedule. It's assumed that someone always starts at home. And for most
@@ -69,9 +109,30 @@ pub struct Schedule {
     pub activities: Vec<(Time, Activity)>,
 }
 
+/// A typical day for one type of person. Activities happen in order, but each one might be
+/// skipped.
+#[derive(Clone, Debug)]
+pub struct ScheduleTemplate {
+    /// When the person first leaves home is randomly chosen from this range, since midnight.
+    pub start_time: (Duration, Duration),
+    pub steps: Vec<ScheduleStep>,
+}
+
+/// One activity in a ScheduleTemplate
+#[derive(Clone, Debug)]
+pub struct ScheduleStep {
+    pub activity: Activity,
+    /// The chance that this activity happens at all
+    pub probability: f64,
+    /// How long the activity lasts is randomly chosen from this range.
+    pub duration: (Duration, Duration),
+    /// If the activity doesn't happen, do this one instead, lasting for some time in the range
+    pub otherwise: Option<(Activity, (Duration, Duration))>,
+}
+
 /// Different things people might do in the day. Maybe it's more clear to call this a
 /// DestinationType or similar.
-#[derive(Hash, PartialEq, Eq, Clone, Copy, Debug)]
+#[derive(Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
 pub enum Activity {
     Breakfast,
     Lunch,
@@ -85,18 +146,116 @@ pub enum Activity {
     Work,
 }
 
+/// A gravity model for picking a building to satisfy some Activity. Each candidate building is
+/// weighted by its attractiveness, divided by `(1 + minutes to get there) ^ impedance`.
+#[derive(Clone, Debug)]
+pub struct DestinationChoice {
+    /// OpenStreetMap amenity types that satisfy the activity
+    pub amenities: Vec<String>,
+    /// How much each matching amenity in a building adds to its attractiveness
+    pub per_amenity: f64,
+    /// How much each estimated worker in a building adds to its attractiveness
+    pub per_job: f64,
+    /// Attractiveness is scaled by one of these, depending on the BuildingType.
+    pub residential_weight: f64,
+    pub mixed_use_weight: f64,
+    pub commercial_weight: f64,
+    /// How strongly travel time discourages people. 0 means only attractiveness matters.
+    pub impedance: f64,
+    /// Buildings farther away than this are only considered when nothing closer works.
+    pub max_travel_time: Duration,
+}
+
 /// Any arbitrarily chosen parameters needed should be put here, so they can be controlled from the
 /// UI or tuned for different cities.
 pub struct Config {
     pub walk_for_distances_shorter_than: Distance,
     pub walk_or_bike_for_distances_shorter_than: Distance,
+
+    /// People younger than this go to school and don't work or drive.
+    pub adult_age: usize,
+    /// People this old or older who aren't working are retired.
+    pub retirement_age: usize,
+    /// The fraction of employed people who work part-time
+    pub part_time_rate: f64,
+    /// Young adults up to this age who aren't working are students.
+    pub max_student_age: usize,
+
+    /// Used when a CensusArea is missing these attributes
+    pub default_age_bands: Vec<AgeBand>,
+    pub default_employment_rate: f64,
+    pub default_car_ownership: f64,
+    pub default_household_sizes: Vec<f64>,
+
+    /// In a household with at least one vehicle, each adult besides the first has this chance of
+    /// adding another.
+    pub extra_vehicle_rate: f64,
+    /// The chance that a child is taken to their first activity by an adult from their household
+    pub escort_rate: f64,
+    /// An adult escorting a child has to be free for this long after the child leaves home.
+    pub escort_duration: Duration,
+
+    /// What each type of person does during the day. Types missing here use the default template.
+    pub schedules: BTreeMap<PersonType, ScheduleTemplate>,
+    /// Where people go for each activity. Activities missing here are satisfied off-map, except for
+    /// Activity::Home.
+    pub destinations: BTreeMap<Activity, DestinationChoice>,
 }
 
 impl Config {
+    /// The defaults for demographics are roughly based on the US as a whole.
     pub fn default() -> Config {
         Config {
             walk_for_distances_shorter_than: Distance::miles(0.5),
             walk_or_bike_for_distances_shorter_than: Distance::miles(3.0),
+
+            adult_age: 18,
+            retirement_age: 65,
+            part_time_rate: 0.2,
+            max_student_age: 24,
+
+            default_age_bands: vec![
+                AgeBand {
+                    min_age: 0,
+                    max_age: 5,
+                    fraction: 0.06,
+                },
+                AgeBand {
+                    min_age: 5,
+                    max_age: 18,
+                    fraction: 0.16,
+                },
+                AgeBand {
+                    min_age: 18,
+                    max_age: 25,
+                    fraction: 0.10,
+                },
+                AgeBand {
+                    min_age: 25,
+                    max_age: 45,
+                    fraction: 0.26,
+                },
+                AgeBand {
+                    min_age: 45,
+                    max_age: 65,
+                    fraction: 0.25,
+                },
+                AgeBand {
+                    min_age: 65,
+                    max_age: 95,
+                    fraction: 0.17,
+                },
+            ],
+            default_employment_rate: 0.7,
+            default_car_ownership: 0.9,
+            default_household_sizes: vec![0.28, 0.35, 0.15, 0.13, 0.09],
+
+            extra_vehicle_rate: 0.5,
+            escort_rate: 0.5,
+            escort_duration: Duration::hours(1),
+
+            schedules: activities::default_schedules(),
+            destinations: destinations::default_destinations(),
         }
     }
 }
@@ -115,14 +274,14 @@ pub fn generate_scenario(
     // find_data_for_map may return an error. If so, just plumb it back to the caller using the ?
     // operator
     timer.start("assigning people to houses");
-    let people = distribute_people::assign_people_to_houses(areas, map, rng, &config);
+    let households = distribute_people::assign_people_to_houses(areas, map, rng, &config);
     timer.stop("assigning people to houses");
 
     let mut scenario = Scenario::empty(map, scenario_name);
     timer.start("building people");
-    scenario.people.extend(make_person::make_people(
-        people, map, &mut timer, rng, &config,
-    ));
+    let (people, households) = make_person::make_people(households, map, &mut timer, rng, &config);
+    scenario.people.extend(people);
+    scenario.households.extend(households);
     timer.stop("building people");
 
     timer.start("removing weird schedules");
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 
 use rand::seq::SliceRandom;
 use rand::Rng;
@@ -6,17 +6,18 @@ use rand_xorshift::XorShiftRng;
 
 use abstutil::Timer;
 use map_model::{BuildingID, IntersectionID, Map, PathConstraints, PathRequest};
-use sim::{IndividTrip, PersonSpec, TripEndpoint, TripMode, TripPurpose};
+use sim::{HouseholdSpec, IndividTrip, PersonSpec, TripEndpoint, TripMode, TripPurpose};
 
-use crate::{Activity, CensusPerson, Config};
+use crate::destinations::TravelTimeCache;
+use crate::{Activity, CensusHousehold, CensusPerson, Config};
 
 pub fn make_people(
-    people: Vec<CensusPerson>,
+    households: Vec<CensusHousehold>,
     map: &Map,
     timer: &mut Timer,
     rng: &mut XorShiftRng,
     config: &Config,
-) -> Vec<PersonSpec> {
+) -> (Vec<PersonSpec>, Vec<HouseholdSpec>) {
     // Only consider two-way intersections, so the agent can return the same way
     // they came.
     // TODO: instead, if it's not a two-way border, we should find an intersection
@@ -33,153 +34,131 @@ pub fn make_people(
     // TODO Where should we validate that at least one border exists? Probably in
     // generate_scenario, at minimum.
 
-    let person_factory = PersonFactory::new(map);
-    let make_person_inputs = people
+    let person_factory = PersonFactory::new(map, config);
+    let make_household_inputs = households
         .into_iter()
-        .map(|person| (person, sim::fork_rng(rng)))
+        .map(|household| (household, sim::fork_rng(rng)))
         .collect();
-    timer.parallelize(
+    let results = timer.parallelize(
         "making people in parallel",
-        make_person_inputs,
-        |(person, mut rng)| {
-            person_factory.make_person(person, map, &commuter_borders, &mut rng, config)
+        make_household_inputs,
+        |(household, mut rng)| {
+            person_factory.make_household(household, map, &commuter_borders, &mut rng, config)
         },
-    )
+    );
+
+    // Each household refers to its members and carpool drivers starting from 0, so shift them
+    let mut people = Vec::new();
+    let mut households = Vec::new();
+    for (mut household, members) in results {
+        let offset = people.len();
+        for idx in &mut household.members {
+            *idx += offset;
+        }
+        for mut person in members {
+            for trip in &mut person.trips {
+                trip.carpool_driver = trip
+                    .carpool_driver
+                    .map(|(driver, trip)| (driver + offset, trip));
+            }
+            people.push(person);
+        }
+        households.push(household);
+    }
+    (people, households)
+}
+
+/// What's needed to make the people in one household
+struct HouseholdCtx<'a> {
+    map: &'a Map,
+    config: &'a Config,
+    /// Everybody leaves from the same home, so share travel times within the household
+    travel_times: TravelTimeCache,
 }
 
 struct PersonFactory {
-    activity_to_buildings: HashMap<Activity, Vec<BuildingID>>,
+    /// Every building satisfying each activity, with its attractiveness
+    activity_to_buildings: HashMap<Activity, Vec<(BuildingID, f64)>>,
 }
 
 impl PersonFactory {
-    fn new(map: &Map) -> Self {
-        let activity_to_buildings = Self::activity_to_buildings(map);
+    fn new(map: &Map, config: &Config) -> Self {
+        let activity_to_buildings = config
+            .destinations
+            .iter()
+            .map(|(activity, choice)| (*activity, choice.candidates(map)))
+            .collect();
         Self {
             activity_to_buildings,
         }
     }
 
-    fn activity_to_buildings(map: &Map) -> HashMap<Activity, Vec<BuildingID>> {
-        // What types of OpenStreetMap amenities will satisfy each activity?
-        let categories = vec![
-            (Activity::Breakfast, vec!["cafe"]),
-            (Activity::Lunch, vec!["pub", "food_court", "fast_food"]),
-            (
-                Activity::Dinner,
-                vec!["restaurant", "theatre", "biergarten"],
-            ),
-            (
-                Activity::School,
-                vec![
-                    "college",
-                    "kindergarten",
-                    "language_school",
-                    "library",
-                    "music_school",
-                    "university",
-                ],
-            ),
-            (
-                Activity::Entertainment,
-                vec![
-                    "arts_centre",
-                    "casino",
-                    "cinema",
-                    "community_centre",
-                    "fountain",
-                    "gambling",
-                    "nightclub",
-                    "planetarium",
-                    "public_bookcase",
-                    "pool",
-                    "dojo",
-                    "social_centre",
-                    "social_centre",
-                    "studio",
-                    "theatre",
-                    "bar",
-                    "bbq",
-                    "bicycle_rental",
-                    "boat_rental",
-                    "boat_sharing",
-                    "dive_centre",
-                    "internet_cafe",
-                ],
-            ),
-            (
-                Activity::Errands,
-                vec![
-                    "marketplace",
-                    "post_box",
-                    "photo_booth",
-                    "recycling",
-                    "townhall",
-                ],
-            ),
-            (Activity::Financial, vec!["bank", "atm", "bureau_de_change"]),
-            (
-                Activity::Healthcare,
-                vec![
-                    "baby_hatch",
-                    "clinic",
-                    "dentist",
-                    "doctors",
-                    "hospital",
-                    "nursing_home",
-                    "pharmacy",
-                    "social_facility",
-                    "veterinary",
-                    "childcare",
-                ],
-            ),
-            (Activity::Work, vec!["bank", "clinic"]),
-        ];
-
-        // Find all buildings with a matching amenity
-        let mut candidates: HashMap<Activity, Vec<BuildingID>> = HashMap::new();
-        for b in map.all_buildings() {
-            for (activity, categories) in &categories {
-                for amenity in &b.amenities {
-                    if categories.contains(&amenity.amenity_type.as_str()) {
-                        candidates
-                            .entry(*activity)
-                            .and_modify(|v| v.push(b.id))
-                            .or_insert_with(|| vec![b.id]);
-                    }
-                }
-            }
-        }
-        candidates
-    }
-
     fn find_building_for_activity(
         &self,
         activity: Activity,
-        _start: TripEndpoint,
-        _map: &Map,
+        start: TripEndpoint,
+        can_drive: bool,
+        ctx: &mut HouseholdCtx,
         rng: &mut XorShiftRng,
     ) -> Option<BuildingID> {
-        // TODO If there are several choices of building that satisfy an activity, which one will
-        // someone choose? One simple approach could just calculate the difficulty of going from the
-        // previous location (starting from home) to that place, using some mode of travel. Then
-        // either pick the closest choice, or even better, randomize, but weight based on
-        // the cost of getting there. map.pathfind() may be helpful.
-
-        // For now, just pick a random one
-        self.activity_to_buildings
-            .get(&activity)
-            .and_then(|buildings| buildings.choose(rng).cloned())
+        let choice = ctx.config.destinations.get(&activity)?;
+        let candidates = self.activity_to_buildings.get(&activity)?;
+        // We don't know the mode yet, so just guess based on whether they could drive
+        let travel_times = match start {
+            TripEndpoint::Bldg(b) if choice.impedance > 0.0 => Some(choice.travel_times(
+                ctx.map,
+                b,
+                if can_drive {
+                    PathConstraints::Car
+                } else {
+                    PathConstraints::Pedestrian
+                },
+                &mut ctx.travel_times,
+            )),
+            _ => None,
+        };
+        choice.pick(candidates, travel_times, rng)
     }
 
-    pub fn make_person(
+    fn make_household(
         &self,
-        person: CensusPerson,
+        household: CensusHousehold,
         map: &Map,
         commuter_borders: &[IntersectionID],
         rng: &mut XorShiftRng,
         config: &Config,
+    ) -> (HouseholdSpec, Vec<PersonSpec>) {
+        let mut ctx = HouseholdCtx {
+            map,
+            config,
+            travel_times: TravelTimeCache::new(),
+        };
+        let mut people = Vec::new();
+        for person in &household.members {
+            let can_drive = household.num_vehicles > 0 && person.age >= config.adult_age;
+            people.push(self.make_person(person, can_drive, commuter_borders, &mut ctx, rng));
+        }
+        escort_children(&household, &mut people, rng, config);
+
+        (
+            HouseholdSpec {
+                home: household.home,
+                members: (0..people.len()).collect(),
+                num_vehicles: household.num_vehicles,
+            },
+            people,
+        )
+    }
+
+    fn make_person(
+        &self,
+        person: &CensusPerson,
+        can_drive: bool,
+        commuter_borders: &[IntersectionID],
+        ctx: &mut HouseholdCtx,
+        rng: &mut XorShiftRng,
     ) -> PersonSpec {
-        let schedule = person.generate_schedule(config, rng);
+        let schedule = person.generate_schedule(ctx.config, rng);
 
         let mut output = PersonSpec {
             orig_id: None,
@@ -192,8 +171,13 @@ impl PersonFactory {
             // better.
             let purpose = TripPurpose::Shopping;
 
-            let goto = if let Some(destination) =
-                self.find_building_for_activity(activity, current_location, map, rng)
+            let goto = if activity == Activity::Home {
+                if current_location == TripEndpoint::Bldg(person.home) {
+                    continue;
+                }
+                TripEndpoint::Bldg(person.home)
+            } else if let Some(destination) =
+                self.find_building_for_activity(activity, current_location, can_drive, ctx, rng)
             {
                 TripEndpoint::Bldg(destination)
             } else if let Some(i) = commuter_borders.choose(rng) {
@@ -204,7 +188,7 @@ impl PersonFactory {
                 continue;
             };
 
-            let mode = pick_mode(current_location, goto, map, rng, config);
+            let mode = pick_mode(current_location, goto, can_drive, ctx.map, rng, ctx.config);
             output.trips.push(IndividTrip::new(
                 departure_time,
                 purpose,
@@ -220,9 +204,80 @@ impl PersonFactory {
     }
 }
 
+/// Some children go to their first activity of the day with an adult from their household, who
+/// then returns home before continuing their own day. If the household has a car, the child rides
+/// along; otherwise they walk together.
+fn escort_children(
+    household: &CensusHousehold,
+    people: &mut [PersonSpec],
+    rng: &mut XorShiftRng,
+    config: &Config,
+) {
+    let home = TripEndpoint::Bldg(household.home);
+    let is_adult = |idx: usize| household.members[idx].age >= config.adult_age;
+    // Each adult only escorts one child
+    let mut escorts = BTreeSet::new();
+    for child in 0..people.len() {
+        if is_adult(child) || !rng.gen_bool(config.escort_rate) {
+            continue;
+        }
+        let (depart, destination) = match people[child].trips.get(0) {
+            Some(trip)
+                if trip.origin == home && matches!(trip.destination, TripEndpoint::Bldg(_)) =>
+            {
+                (trip.depart, trip.destination)
+            }
+            _ => continue,
+        };
+        // The adult has to be home long enough to drop off the child and come back
+        let adult = match (0..people.len()).find(|idx| {
+            is_adult(*idx)
+                && !escorts.contains(idx)
+                && people[*idx]
+                    .trips
+                    .get(0)
+                    .map(|trip| trip.depart >= depart + config.escort_duration)
+                    .unwrap_or(true)
+        }) {
+            Some(idx) => idx,
+            None => continue,
+        };
+        escorts.insert(adult);
+
+        let mode = if household.num_vehicles > 0 {
+            TripMode::Drive
+        } else {
+            TripMode::Walk
+        };
+        people[adult].trips.insert(
+            0,
+            IndividTrip::new(depart, TripPurpose::Escort, home, destination, mode),
+        );
+        people[adult].trips.insert(
+            1,
+            IndividTrip::new(
+                depart + config.escort_duration / 2.0,
+                TripPurpose::Home,
+                destination,
+                home,
+                mode,
+            ),
+        );
+
+        let trip = &mut people[child].trips[0];
+        if mode == TripMode::Drive {
+            trip.mode = TripMode::Carpool;
+            trip.carpool_driver = Some((adult, 0));
+        } else {
+            trip.mode = TripMode::Walk;
+        }
+    }
+}
+
 fn pick_mode(
     from: TripEndpoint,
     to: TripEndpoint,
+    can_drive: bool,
     map: &Map,
     rng: &mut XorShiftRng,
     config: &Config,
@@ -284,7 +339,7 @@ fn pick_mode(
         return TripMode::Bike;
     }
     // Try transit if available, or fallback to walking
-    if rng.gen_bool(0.3) {
+    if !can_drive || rng.gen_bool(0.3) {
         return TripMode::Transit;
     }
 
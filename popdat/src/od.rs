@@ -13,9 +13,12 @@ use rand_xorshift::XorShiftRng;
 
 use abstutil::Timer;
 use geom::{Duration, Polygon, Time};
-use map_model::{BuildingID, BuildingType, Map};
+use map_model::{BuildingID, BuildingType, Map, PathConstraints};
 use sim::{IndividTrip, MapBorders, PersonSpec, TripEndpoint, TripMode, TripPurpose};
 
+use crate::destinations::TravelTimeCache;
+use crate::{Activity, DestinationChoice};
+
 /// This describes some number of commuters living in some named zone, working in another (or the
 /// same zone), and commuting using some mode.
 #[derive(Debug)]
@@ -33,6 +36,8 @@ pub struct Options {
     pub departure_time: NormalDistribution,
     /// How long should somebody work before returning home?
     pub work_duration: NormalDistribution,
+    /// How to pick a workplace within the work zone. Travel times are measured from home.
+    pub workplace_choice: DestinationChoice,
 }
 
 impl Options {
@@ -43,6 +48,9 @@ impl Options {
                 Duration::minutes(30),
             ),
             work_duration: NormalDistribution::new(Duration::hours(9), Duration::hours(1)),
+            workplace_choice: crate::destinations::default_destinations()
+                .remove(&Activity::Work)
+                .unwrap(),
         }
     }
 }
@@ -50,7 +58,8 @@ impl Options {
 /// Generates a scenario from aggregated origin/destination data (DesireLines). The input describes
 /// an exact number of people, who live in one zone and work in another (possibly the same) and
 /// commute using some mode. For each of them, we just need to pick a specific home and workplace
-/// from the zones, and use the Options to pick departure times. We'll wind up creating people who
+/// from the zones, and use the Options to pick departure times. Homes are weighted by the number
+/// of residents, and workplaces by `Options::workplace_choice`. We'll wind up creating people who
 /// just take two trips daily: home -> work -> home.
 ///
 /// The home and workplace may be a specific building, or they're snapped to a map border,
@@ -71,7 +80,7 @@ pub fn disaggregate(
     // First decide which zones are relevant for our map. Match homes, shops, and border
     // intersections to each zone.
     timer.start("match zones");
-    let zones = create_zones(map, zones);
+    let zones = create_zones(map, zones, &opts.workplace_choice);
     timer.stop("match zones");
 
     let mut people = Vec::new();
@@ -83,13 +92,23 @@ pub fn disaggregate(
         }
         let home_zone = &zones[&desire.home_zone];
         let work_zone = &zones[&desire.work_zone];
+        // Many of these commuters live in the same buildings
+        let mut cache = TravelTimeCache::new();
 
         for _ in 0..desire.number_commuters {
             // Pick a specific home and workplace. It might be off-map, depending on how much the
             // zone overlaps the map.
-            if let (Some((leave_home, goto_home)), Some((leave_work, goto_work))) = (
-                home_zone.pick_home(desire.mode, map, rng),
-                work_zone.pick_workplace(desire.mode, map, rng),
+            let (leave_home, goto_home) = match home_zone.pick_home(desire.mode, map, rng) {
+                Some(pair) => pair,
+                None => continue,
+            };
+            if let Some((leave_work, goto_work)) = work_zone.pick_workplace(
+                desire.mode,
+                leave_home,
+                &opts.workplace_choice,
+                map,
+                &mut cache,
+                rng,
             ) {
                 // Create their schedule
                 let goto_work_time = Time::START_OF_DAY + opts.departure_time.sample(rng);
@@ -127,11 +146,15 @@ struct Zone {
     // value doesn't matter; it's just a relative weighting. This way, we can use a weighted sample
     // and match more people to larger homes/stores.
     homes: Vec<(BuildingID, usize)>,
-    workplaces: Vec<(BuildingID, usize)>,
+    workplaces: Vec<(BuildingID, f64)>,
     borders: MapBorders,
 }
 
-fn create_zones(map: &Map, input: HashMap<String, Polygon>) -> HashMap<String, Zone> {
+fn create_zones(
+    map: &Map,
+    input: HashMap<String, Polygon>,
+    workplace_choice: &DestinationChoice,
+) -> HashMap<String, Zone> {
     let all_borders = MapBorders::new(map);
     let mut zones = HashMap::new();
     for (name, polygon) in input {
@@ -170,20 +193,15 @@ fn create_zones(map: &Map, input: HashMap<String, Polygon>) -> HashMap<String, Z
             match b.bldg_type {
                 // The current heuristics for num_residents sometimes assign 0 people to a
                 // building. We never want that, so just scale them all up.
-                BuildingType::Residential { num_residents, .. } => {
+                BuildingType::Residential { num_residents, .. }
+                | BuildingType::ResidentialCommercial(num_residents, _) => {
                     zone.homes.push((b.id, num_residents + 1));
                 }
-                BuildingType::ResidentialCommercial(num_residents, _) => {
-                    zone.homes.push((b.id, num_residents + 1));
-                    // We know how many different stores are located in each building, according to
-                    // OSM. A big mall might have 10 amenities, while standalone
-                    // shops just have 1.
-                    zone.workplaces.push((b.id, b.amenities.len()));
-                }
-                BuildingType::Commercial(_) => {
-                    zone.workplaces.push((b.id, b.amenities.len()));
-                }
-                BuildingType::Empty => {}
+                BuildingType::Commercial(_) | BuildingType::Empty => {}
+            }
+            let attractiveness = workplace_choice.attractiveness(b);
+            if attractiveness > 0.0 {
+                zone.workplaces.push((b.id, attractiveness));
             }
         }
     }
@@ -227,12 +245,28 @@ impl Zone {
     fn pick_workplace(
         &self,
         mode: TripMode,
+        home: TripEndpoint,
+        choice: &DestinationChoice,
         map: &Map,
+        cache: &mut TravelTimeCache,
         rng: &mut XorShiftRng,
     ) -> Option<(TripEndpoint, TripEndpoint)> {
         if rng.gen_bool(self.pct_overlap) && !self.workplaces.is_empty() {
-            let b = self.workplaces.choose_weighted(rng, |(_, n)| *n).unwrap().0;
-            return Some((TripEndpoint::Bldg(b), TripEndpoint::Bldg(b)));
+            // Prefer workplaces closer to home
+            let travel_times = match home {
+                TripEndpoint::Bldg(b) if choice.impedance > 0.0 => {
+                    // TODO Transit isn't handled yet; walking times are pessimistic
+                    let constraints = match mode {
+                        TripMode::Transit => PathConstraints::Pedestrian,
+                        _ => mode.to_constraints(),
+                    };
+                    Some(choice.travel_times(map, b, constraints, cache))
+                }
+                _ => None,
+            };
+            if let Some(b) = choice.pick(&self.workplaces, travel_times, rng) {
+                return Some((TripEndpoint::Bldg(b), TripEndpoint::Bldg(b)));
+            }
         }
         self.pick_borders(mode, map, rng)
     }
@@ -3,16 +3,24 @@ use std::collections::{BTreeMap, BTreeSet, VecDeque};
 use serde::{Deserialize, Serialize};
 
 use abstutil::Counter;
-use geom::{Duration, Time};
+use geom::{Distance, Duration, Time};
 use map_model::{
-    BusRouteID, BusStopID, CompressedMovementID, IntersectionID, LaneID, Map, MovementID,
-    ParkingLotID, Path, PathRequest, RoadID, Traversable, TurnID, TurnType,
+    vehicle_cost, BuildingID, BusRouteID, BusStopID, CompressedMovementID, IntersectionID, LaneID,
+    Map, MovementID, ParkingLotID, Path, PathConstraints, PathRequest, RoadID, Traversable, TurnID,
+    TurnType,
 };
 
 use crate::{
-    AgentID, AgentType, AlertLocation, CarID, Event, ParkingSpot, TripID, TripMode, TripPhaseType,
+    AgentID, AgentType, AlertLocation, CapOutcome, CarID, CruisingOutcome, Emissions, Event,
+    ParkingSpot, SignalPriorityAction, TripID, TripMode, TripPhaseType, VehicleType,
 };
 
+/// A transit vehicle reaching a stop no earlier or later than this is on time.
+const ON_TIME_EARLY: Duration = Duration::const_seconds(-60.0);
+const ON_TIME_LATE: Duration = Duration::const_seconds(5.0 * 60.0);
+/// Outside of permit zones, cruising for parking is grouped into squares about this wide.
+const PARKING_AREA_SIZE: Distance = Distance::const_meters(500.0);
+
 /// As a simulation runs, different pieces emit Events. The Analytics object listens to these,
 /// organizing and storing some information from them. The UI queries Analytics to draw time-series
 /// and display statistics.
@@ -39,6 +47,22 @@ pub struct Analytics {
     /// For each passenger boarding, how long did they wait at the stop?
     pub passengers_boarding: BTreeMap<BusStopID, Vec<(Time, BusRouteID, Duration)>>,
     pub passengers_alighting: BTreeMap<BusStopID, Vec<(Time, BusRouteID)>>,
+    /// Every time a passenger couldn't board a full vehicle
+    pub passengers_denied: BTreeMap<BusStopID, Vec<(Time, BusRouteID)>>,
+    /// Every time a transit vehicle reaches a stop, how late was it? Negative means early.
+    pub schedule_deviations: BTreeMap<BusRouteID, Vec<(Time, BusStopID, Duration)>>,
+    /// Every time a transit vehicle reaches a stop after some previous vehicle on the same route,
+    /// what was the actual and scheduled headway?
+    pub headways: BTreeMap<BusRouteID, Vec<(Time, BusStopID, Duration, Duration)>>,
+    /// Every time a carpool leaves: the driver's trip, their car, and the number of passengers,
+    /// not counting the driver.
+    pub carpools: Vec<(Time, TripID, CarID, usize)>,
+    /// Every ride-hailing pickup: the rider's trip, the vehicle, and how long they waited since
+    /// requesting it.
+    pub ride_hail_pickups: Vec<(Time, TripID, CarID, Duration)>,
+    /// Every time a ride-hailing vehicle parks: the vehicle, if it was carrying a rider (or
+    /// driving empty to pick somebody up), and the distance and time spent driving.
+    pub ride_hail_legs: Vec<(Time, CarID, bool, Distance, Duration)>,
 
     pub started_trips: BTreeMap<TripID, Time>,
     /// Finish time, ID, mode, trip duration if successful (or None if cancelled)
@@ -57,6 +81,61 @@ pub struct Analytics {
     /// Per parking lane or lot, when does a spot become filled (true) or free (false)
     pub parking_lane_changes: BTreeMap<LaneID, Vec<(Time, bool)>>,
     pub parking_lot_changes: BTreeMap<ParkingLotID, Vec<(Time, bool)>>,
+    /// Every time a car left a priced or time-limited spot: when it left, the spot, when it
+    /// arrived, the price paid, and if it stayed longer than the limit. Cars parked since they
+    /// were seeded at the start of the simulation don't count; they never arrived or paid.
+    pub parking_stays: Vec<(Time, ParkingSpot, Time, f64, bool)>,
+    /// When and where each car currently parked somewhere arrived. Seeded cars aren't tracked.
+    parked_since: BTreeMap<CarID, (Time, ParkingSpot)>,
+    /// For trips that drove around looking for parking: the destination, the time and distance
+    /// spent cruising, and how the search ended
+    pub cruising_for_parking: BTreeMap<TripID, (BuildingID, Duration, Distance, CruisingOutcome)>,
+
+    /// Every time a vehicle passed somebody using the oncoming lane: the vehicle, who they passed,
+    /// and the lane where it happened.
+    pub oncoming_overtakes: Vec<(Time, CarID, CarID, LaneID)>,
+
+    /// Every time a traffic signal changed for a bus or emergency vehicle: where, the bus route
+    /// (None for emergency vehicles), what changed, the estimated time saved by the vehicle, and
+    /// the estimated delay imposed on everybody else waiting at the signal.
+    pub signal_priority: Vec<(
+        Time,
+        IntersectionID,
+        Option<BusRouteID>,
+        SignalPriorityAction,
+        Duration,
+        Duration,
+    )>,
+
+    /// Every time a driver switched to a faster route: which vehicle, where they were, and the
+    /// estimated time saved.
+    pub reroutes: Vec<(Time, CarID, LaneID, Duration)>,
+
+    /// Every time a driver would've exceeded a zone's vehicle cap: when, which trip, some road in
+    /// the capped zone, and what happened to the trip.
+    pub capped_trips: Vec<(Time, TripID, RoadID, CapOutcome)>,
+
+    /// Estimated emissions and energy use of each trip's vehicle. Transit vehicles aren't
+    /// included.
+    pub trip_emissions: BTreeMap<TripID, Emissions>,
+    /// Emissions of all vehicles, per road. Turns aren't included.
+    pub road_emissions: BTreeMap<RoadID, Emissions>,
+    /// Emissions of all vehicles, per hour since midnight
+    pub hourly_emissions: Vec<Emissions>,
+
+    /// The highest pedestrian density seen on each sidewalk or shoulder, in people per square
+    /// meter. Indexed by hour since midnight.
+    pub sidewalk_density: BTreeMap<LaneID, Vec<f64>>,
+
+    /// For cars, the total time spent crossing a road and then a movement at the end of it, and
+    /// the number of crossings. Crossings starting partway along a road aren't included. Only
+    /// recorded if `SimOptions::record_movement_times` is set.
+    pub movement_times: BTreeMap<MovementID, (Duration, usize)>,
+    /// For each trip driving a car: when it entered its current road, whether it started at the
+    /// beginning of that road, and the turn it's on now. Removed when the trip ends, so time spent
+    /// parked never counts.
+    car_crossings: BTreeMap<TripID, (Time, bool, Option<TurnID>)>,
+    pub(crate) record_movement_times: bool,
 
     pub(crate) alerts: Vec<(Time, AlertLocation, String)>,
 
@@ -74,6 +153,8 @@ pub enum Problem {
     ArterialIntersectionCrossing(TurnID),
     /// Another vehicle wanted to over-take this cyclist somewhere on this lane or turn.
     OvertakeDesired(Traversable),
+    /// A transit rider couldn't board a vehicle at this stop, because it was full.
+    DeniedBoarding(BusStopID),
 }
 
 impl Analytics {
@@ -86,6 +167,12 @@ impl Analytics {
             bus_arrivals: Vec::new(),
             passengers_boarding: BTreeMap::new(),
             passengers_alighting: BTreeMap::new(),
+            passengers_denied: BTreeMap::new(),
+            schedule_deviations: BTreeMap::new(),
+            headways: BTreeMap::new(),
+            carpools: Vec::new(),
+            ride_hail_pickups: Vec::new(),
+            ride_hail_legs: Vec::new(),
             started_trips: BTreeMap::new(),
             finished_trips: Vec::new(),
             problems_per_trip: BTreeMap::new(),
@@ -93,6 +180,20 @@ impl Analytics {
             intersection_delays: BTreeMap::new(),
             parking_lane_changes: BTreeMap::new(),
             parking_lot_changes: BTreeMap::new(),
+            parking_stays: Vec::new(),
+            parked_since: BTreeMap::new(),
+            cruising_for_parking: BTreeMap::new(),
+            oncoming_overtakes: Vec::new(),
+            signal_priority: Vec::new(),
+            reroutes: Vec::new(),
+            capped_trips: Vec::new(),
+            trip_emissions: BTreeMap::new(),
+            road_emissions: BTreeMap::new(),
+            hourly_emissions: Vec::new(),
+            sidewalk_density: BTreeMap::new(),
+            movement_times: BTreeMap::new(),
+            car_crossings: BTreeMap::new(),
+            record_movement_times: false,
             alerts: Vec::new(),
             record_anything,
         }
@@ -105,29 +206,25 @@ impl Analytics {
 
         // Throughput
         if let Event::AgentEntersTraversable(a, _, to, passengers) = ev {
+            let rider = if a.to_type() == AgentType::Car {
+                AgentType::CarPassenger
+            } else {
+                AgentType::TransitRider
+            };
             match to {
                 Traversable::Lane(l) => {
                     self.road_thruput
                         .record(time, map.get_l(l).parent, a.to_type(), 1);
                     if let Some(n) = passengers {
-                        self.road_thruput.record(
-                            time,
-                            map.get_l(l).parent,
-                            AgentType::TransitRider,
-                            n,
-                        );
+                        self.road_thruput
+                            .record(time, map.get_l(l).parent, rider, n);
                     }
                 }
                 Traversable::Turn(t) => {
                     self.intersection_thruput
                         .record(time, t.parent, a.to_type(), 1);
                     if let Some(n) = passengers {
-                        self.intersection_thruput.record(
-                            time,
-                            t.parent,
-                            AgentType::TransitRider,
-                            n,
-                        );
+                        self.intersection_thruput.record(time, t.parent, rider, n);
                     }
 
                     if let Some(id) = map.get_movement(t) {
@@ -136,13 +233,25 @@ impl Analytics {
                         let m = map.get_traffic_signal(t.parent).compressed_id(t);
                         self.traffic_signal_thruput.record(time, m, a.to_type(), 1);
                         if let Some(n) = passengers {
-                            self.traffic_signal_thruput
-                                .record(time, m, AgentType::TransitRider, n);
+                            self.traffic_signal_thruput.record(time, m, rider, n);
                         }
                     }
                 }
             };
         }
+        if self.record_movement_times {
+            match ev {
+                Event::AgentEntersTraversable(AgentID::Car(car), Some(trip), to, _)
+                    if car.vehicle_type == VehicleType::Car =>
+                {
+                    self.record_crossing(time, trip, to, map);
+                }
+                Event::TripFinished { trip, .. } | Event::TripCancelled(trip, _) => {
+                    self.car_crossings.remove(&trip);
+                }
+                _ => {}
+            }
+        }
         match ev {
             Event::PersonLeavesMap(_, Some(a), i) => {
                 // Ignore cancelled trips
@@ -172,6 +281,143 @@ impl Analytics {
                 .or_insert_with(Vec::new)
                 .push((time, route));
         }
+        if let Event::PassengerDeniedBoarding(trip, _, _, route, stop) = ev {
+            self.passengers_denied
+                .entry(stop)
+                .or_insert_with(Vec::new)
+                .push((time, route));
+            self.problems_per_trip
+                .entry(trip)
+                .or_insert_with(Vec::new)
+                .push((time, Problem::DeniedBoarding(stop)));
+        }
+        if let Event::BusScheduleAdherence {
+            route,
+            stop,
+            deviation,
+            headway,
+            ..
+        } = ev
+        {
+            self.schedule_deviations
+                .entry(route)
+                .or_insert_with(Vec::new)
+                .push((time, stop, deviation));
+            if let Some((actual, scheduled)) = headway {
+                self.headways
+                    .entry(route)
+                    .or_insert_with(Vec::new)
+                    .push((time, stop, actual, scheduled));
+            }
+        }
+
+        if let Event::OncomingOvertake {
+            vehicle,
+            overtaken,
+            lane,
+            ..
+        } = ev
+        {
+            self.oncoming_overtakes
+                .push((time, vehicle, overtaken, lane));
+        }
+
+        if let Event::SignalPriority {
+            intersection,
+            route,
+            action,
+            time_saved,
+            delay_imposed,
+            ..
+        } = ev
+        {
+            self.signal_priority.push((
+                time,
+                intersection,
+                route,
+                action,
+                time_saved,
+                delay_imposed,
+            ));
+        }
+
+        if let Event::VehicleRerouted {
+            vehicle,
+            lane,
+            time_saved,
+            ..
+        } = ev
+        {
+            self.reroutes.push((time, vehicle, lane, time_saved));
+        }
+
+        if let Event::CruisedForParking {
+            trip: Some(trip),
+            target,
+            duration,
+            distance,
+            outcome,
+            ..
+        } = ev
+        {
+            // A trip only parks once, but just in case, add up everything
+            let entry = self.cruising_for_parking.entry(trip).or_insert((
+                target,
+                Duration::ZERO,
+                Distance::ZERO,
+                outcome,
+            ));
+            entry.1 += duration;
+            entry.2 += distance;
+            entry.3 = outcome;
+        }
+
+        if let Event::CarpoolDeparted(trip, car, passengers) = ev {
+            self.carpools.push((time, trip, car, passengers));
+        }
+
+        // Emissions
+        if let Event::VehicleEmissions(_, trip, on, emissions) = ev {
+            if let Some(trip) = trip {
+                *self.trip_emissions.entry(trip).or_insert(Emissions::ZERO) += emissions;
+            }
+            if let Traversable::Lane(l) = on {
+                *self
+                    .road_emissions
+                    .entry(map.get_l(l).parent)
+                    .or_insert(Emissions::ZERO) += emissions;
+            }
+            let hour = time.get_hours();
+            if self.hourly_emissions.len() <= hour {
+                self.hourly_emissions.resize(hour + 1, Emissions::ZERO);
+            }
+            self.hourly_emissions[hour] += emissions;
+        }
+
+        // Pedestrian crowding. Crosswalks are usually brief, so just track sidewalks.
+        if let Event::PedestrianDensity(Traversable::Lane(l), density) = ev {
+            let hour = time.get_hours();
+            let per_hour = self.sidewalk_density.entry(l).or_insert_with(Vec::new);
+            if per_hour.len() <= hour {
+                per_hour.resize(hour + 1, 0.0);
+            }
+            per_hour[hour] = per_hour[hour].max(density);
+        }
+
+        // Ride-hailing
+        if let Event::RideHailPickup(trip, car, wait) = ev {
+            self.ride_hail_pickups.push((time, trip, car, wait));
+        } else if let Event::RideHailLegFinished {
+            car,
+            occupied,
+            distance,
+            duration,
+            ..
+        } = ev
+        {
+            self.ride_hail_legs
+                .push((time, car, occupied, distance, duration));
+        }
 
         // Started trips
         if let Event::TripPhaseStarting(id, _, _, _) = ev {
@@ -222,7 +468,15 @@ impl Analytics {
         }
 
         // Parking spot changes
-        if let Event::CarReachedParkingSpot(_, spot) = ev {
+        let parked = match ev {
+            Event::CarReachedParkingSpot(car, spot) => {
+                self.parked_since.insert(car, (time, spot));
+                Some(spot)
+            }
+            Event::CarSeededInParkingSpot(_, spot) => Some(spot),
+            _ => None,
+        };
+        if let Some(spot) = parked {
             if let ParkingSpot::Onstreet(l, _) = spot {
                 self.parking_lane_changes
                     .entry(l)
@@ -235,7 +489,18 @@ impl Analytics {
                     .push((time, true));
             }
         }
-        if let Event::CarLeftParkingSpot(_, spot) = ev {
+        if let Event::CarLeftParkingSpot(car, spot) = ev {
+            if let (Some((arrived, _)), Some(rules)) =
+                (self.parked_since.remove(&car), spot.rules(map))
+            {
+                if !rules.is_unrestricted() {
+                    let stay = time - arrived;
+                    let price = rules.price_per_hour * stay.inner_seconds() / 3600.0;
+                    let overstayed = rules.max_stay.map(|max| stay > max).unwrap_or(false);
+                    self.parking_stays
+                        .push((time, spot, arrived, price, overstayed));
+                }
+            }
             if let ParkingSpot::Onstreet(l, _) = spot {
                 self.parking_lane_changes
                     .entry(l)
@@ -249,6 +514,11 @@ impl Analytics {
             }
         }
 
+        // Congestion capping
+        if let Event::TripCapped(trip, r, outcome) = ev {
+            self.capped_trips.push((time, trip, r, outcome));
+        }
+
         // Safety metrics
         if let Event::AgentEntersTraversable(a, Some(trip), Traversable::Turn(t), _) = ev {
             if a.to_type() == AgentType::Bike && map.get_i(t.parent).roads.len() > 4 {
@@ -449,6 +719,321 @@ impl Analytics {
         pts
     }
 
+    /// How many trips so far were rerouted, delayed, or cancelled due to congestion caps?
+    pub fn capped_trip_outcomes(&self, now: Time) -> Counter<CapOutcome> {
+        let mut cnt = Counter::new();
+        for (t, _, _, outcome) in &self.capped_trips {
+            if *t > now {
+                break;
+            }
+            cnt.inc(*outcome);
+        }
+        cnt
+    }
+
+    /// What fraction of stop arrivals so far on this route were on time -- no more than 1 minute
+    /// early or 5 minutes late? None if there haven't been any arrivals yet.
+    pub fn on_time_performance(&self, route: BusRouteID, now: Time) -> Option<f64> {
+        let mut on_time = 0;
+        let mut total = 0;
+        for (t, _, deviation) in self.schedule_deviations.get(&route)? {
+            if *t > now {
+                break;
+            }
+            total += 1;
+            if *deviation >= ON_TIME_EARLY && *deviation <= ON_TIME_LATE {
+                on_time += 1;
+            }
+        }
+        if total == 0 {
+            return None;
+        }
+        Some((on_time as f64) / (total as f64))
+    }
+
+    /// Of the stop arrivals so far on this route that followed another vehicle, how many were
+    /// bunched -- arriving less than half of the scheduled headway after the previous one? Returns
+    /// (bunched, total).
+    pub fn bunched_arrivals(&self, route: BusRouteID, now: Time) -> (usize, usize) {
+        let mut bunched = 0;
+        let mut total = 0;
+        if let Some(list) = self.headways.get(&route) {
+            for (t, _, actual, scheduled) in list {
+                if *t > now {
+                    break;
+                }
+                total += 1;
+                if *actual < *scheduled / 2.0 {
+                    bunched += 1;
+                }
+            }
+        }
+        (bunched, total)
+    }
+
+    /// Sums up the signal priority granted so far at some intersection or along some route (or
+    /// anywhere, if both are None). Returns (number of times, total time saved by the vehicles,
+    /// total delay imposed on everybody else).
+    pub fn signal_priority_summary(
+        &self,
+        intersection: Option<IntersectionID>,
+        route: Option<BusRouteID>,
+        now: Time,
+    ) -> (usize, Duration, Duration) {
+        let mut count = 0;
+        let mut saved = Duration::ZERO;
+        let mut imposed = Duration::ZERO;
+        for (t, i, r, _, time_saved, delay_imposed) in &self.signal_priority {
+            if *t > now {
+                break;
+            }
+            if intersection.map(|x| x != *i).unwrap_or(false)
+                || route.map(|x| Some(x) != *r).unwrap_or(false)
+            {
+                continue;
+            }
+            count += 1;
+            saved += *time_saved;
+            imposed += *delay_imposed;
+        }
+        (count, saved, imposed)
+    }
+
+    fn record_crossing(&mut self, time: Time, trip: TripID, to: Traversable, map: &Map) {
+        match to {
+            Traversable::Lane(l) => {
+                let via_turn = match self.car_crossings.get(&trip) {
+                    Some((entered, full, Some(t))) if t.dst == l => {
+                        if *full {
+                            let entry = self
+                                .movement_times
+                                .entry(t.to_movement(map))
+                                .or_insert((Duration::ZERO, 0));
+                            entry.0 += time - *entered;
+                            entry.1 += 1;
+                        }
+                        true
+                    }
+                    // Starting to drive
+                    _ => false,
+                };
+                self.car_crossings.insert(trip, (time, via_turn, None));
+            }
+            Traversable::Turn(t) => {
+                if let Some(crossing) = self.car_crossings.get_mut(&trip) {
+                    crossing.2 = Some(t);
+                }
+            }
+        }
+    }
+
+    /// For cars, how much longer than expected did crossing each road and movement take, on
+    /// average? Only movements crossed at least once are included, and crossings faster than
+    /// expected count as no delay.
+    pub fn movement_delays(&self, map: &Map) -> BTreeMap<MovementID, Duration> {
+        self.movement_times
+            .iter()
+            .map(|(mvmnt, (total, count))| {
+                let expected = vehicle_cost(
+                    mvmnt.from,
+                    *mvmnt,
+                    PathConstraints::Car,
+                    map.routing_params(),
+                    map,
+                );
+                let delay = (*total / (*count as f64) - expected).max(Duration::ZERO);
+                (*mvmnt, delay)
+            })
+            .collect()
+    }
+
+    /// How many people, including the driver, were in the average car that's started driving so
+    /// far? None if nobody's driven yet.
+    pub fn average_car_occupancy(&self, now: Time) -> Option<f64> {
+        let mut cars = 0;
+        for (t, _, _, phase) in &self.trip_log {
+            if *t > now {
+                break;
+            }
+            if *phase == TripPhaseType::Driving {
+                cars += 1;
+            }
+        }
+        if cars == 0 {
+            return None;
+        }
+        let passengers: usize = self
+            .carpools
+            .iter()
+            .take_while(|(t, _, _, _)| *t <= now)
+            .map(|(_, _, _, n)| *n)
+            .sum();
+        Some(((cars + passengers) as f64) / (cars as f64))
+    }
+
+    /// Everything emitted by vehicles so far
+    pub fn total_emissions(&self) -> Emissions {
+        let mut total = Emissions::ZERO;
+        for x in &self.hourly_emissions {
+            total += *x;
+        }
+        total
+    }
+
+    /// Parking revenue per hour since midnight, splitting each stay across the hours it covered.
+    /// Cars still parked have paid for their stay until `now`. Cars seeded at the start of the
+    /// simulation never pay.
+    pub fn parking_revenue_by_hour(&self, now: Time, map: &Map) -> Vec<f64> {
+        let mut result = Vec::new();
+        for (left, _, arrived, price, _) in &self.parking_stays {
+            if left > arrived {
+                let hours = (*left - *arrived) / Duration::hours(1);
+                add_by_hour(&mut result, *arrived, *left, price / hours);
+            }
+        }
+        for (arrived, spot) in self.parked_since.values() {
+            if let Some(rules) = spot.rules(map) {
+                add_by_hour(&mut result, *arrived, now, rules.price_per_hour);
+            }
+        }
+        result
+    }
+
+    /// For each parking lane and lot, the average number of occupied spots during each hour since
+    /// midnight, up to `now`. Cars seeded at the start of the simulation and cars still parked
+    /// count.
+    pub fn parking_occupancy_by_hour(
+        &self,
+        now: Time,
+    ) -> (BTreeMap<LaneID, Vec<f64>>, BTreeMap<ParkingLotID, Vec<f64>>) {
+        (
+            self.parking_lane_changes
+                .iter()
+                .map(|(l, changes)| (*l, occupancy_by_hour(changes, now)))
+                .collect(),
+            self.parking_lot_changes
+                .iter()
+                .map(|(pl, changes)| (*pl, occupancy_by_hour(changes, now)))
+                .collect(),
+        )
+    }
+
+    /// Time and distance spent cruising for parking, grouped by the area around each driver's
+    /// destination. Only trips that couldn't park on the lane closest to their destination are
+    /// counted.
+    pub fn cruising_per_area(&self, map: &Map) -> Vec<CruisingSummary> {
+        let mut result: BTreeMap<ParkingArea, (CruisingSummary, Counter<RoadID>)> = BTreeMap::new();
+        for (b, duration, distance, outcome) in self.cruising_for_parking.values() {
+            let area = ParkingArea::containing(*b, map);
+            let (summary, roads) = result.entry(area.clone()).or_insert_with(|| {
+                (
+                    CruisingSummary {
+                        area,
+                        busiest_road: RoadID(0),
+                        trips: 0,
+                        total_time: Duration::ZERO,
+                        total_distance: Distance::ZERO,
+                        max_time: Duration::ZERO,
+                        parked_illegally: 0,
+                        gave_up: 0,
+                    },
+                    Counter::new(),
+                )
+            });
+            summary.trips += 1;
+            summary.total_time += *duration;
+            summary.total_distance += *distance;
+            summary.max_time = summary.max_time.max(*duration);
+            match outcome {
+                CruisingOutcome::Parked => {}
+                CruisingOutcome::ParkedIllegally => {
+                    summary.parked_illegally += 1;
+                }
+                CruisingOutcome::GaveUp => {
+                    summary.gave_up += 1;
+                }
+            }
+            roads.inc(map.get_l(map.get_b(*b).sidewalk()).parent);
+        }
+        result
+            .into_iter()
+            .map(|(_, (mut summary, roads))| {
+                summary.busiest_road = roads.highest_n(1)[0].0;
+                summary
+            })
+            .collect()
+    }
+
+    /// How crowded did a sidewalk or shoulder get during one hour since midnight? None if nobody
+    /// walked along it, or if pedestrian crowding is disabled.
+    pub fn pedestrian_los(&self, l: LaneID, hour: usize) -> Option<PedestrianLOS> {
+        let density = *self.sidewalk_density.get(&l)?.get(hour)?;
+        if density == 0.0 {
+            return None;
+        }
+        Some(PedestrianLOS::from_density(density))
+    }
+
+    /// The most crowded a sidewalk or shoulder has been all day
+    pub fn worst_pedestrian_los(&self, l: LaneID) -> Option<PedestrianLOS> {
+        let density = self
+            .sidewalk_density
+            .get(&l)?
+            .iter()
+            .cloned()
+            .fold(0.0, f64::max);
+        if density == 0.0 {
+            return None;
+        }
+        Some(PedestrianLOS::from_density(density))
+    }
+
+    /// How has a ride-hailing fleet of some size performed so far? Vehicles still driving
+    /// somewhere don't count towards utilization or distance yet.
+    pub fn ride_hail_summary(&self, now: Time, fleet_size: usize) -> RideHailSummary {
+        let mut summary = RideHailSummary {
+            fleet_size,
+            rides: 0,
+            utilization: 0.0,
+            occupied_distance: Distance::ZERO,
+            empty_distance: Distance::ZERO,
+            average_wait: None,
+            max_wait: None,
+        };
+
+        let mut total_wait = Duration::ZERO;
+        for (t, _, _, wait) in &self.ride_hail_pickups {
+            if *t > now {
+                break;
+            }
+            summary.rides += 1;
+            total_wait += *wait;
+            summary.max_wait = Some(summary.max_wait.map(|w| w.max(*wait)).unwrap_or(*wait));
+        }
+        if summary.rides > 0 {
+            summary.average_wait = Some(total_wait / (summary.rides as f64));
+        }
+
+        let mut busy = Duration::ZERO;
+        for (t, _, occupied, dist, dt) in &self.ride_hail_legs {
+            if *t > now {
+                break;
+            }
+            busy += *dt;
+            if *occupied {
+                summary.occupied_distance += *dist;
+            } else {
+                summary.empty_distance += *dist;
+            }
+        }
+        let available = (fleet_size as f64) * (now - Time::START_OF_DAY);
+        if available > Duration::ZERO {
+            summary.utilization = busy / available;
+        }
+
+        summary
+    }
+
     /// Returns the free spots over time
     pub fn parking_lane_availability(
         &self,
@@ -516,6 +1101,96 @@ impl Default for Analytics {
     }
 }
 
+/// Performance of the ride-hailing fleet
+#[derive(Clone, Debug, Serialize)]
+pub struct RideHailSummary {
+    pub fleet_size: usize,
+    /// How many people have been picked up
+    pub rides: usize,
+    /// The fraction of time vehicles spent driving, either to a pickup or with a rider
+    pub utilization: f64,
+    pub occupied_distance: Distance,
+    /// Distance driven empty to pick somebody up
+    pub empty_distance: Distance,
+    /// How long riders waited from requesting a vehicle until it arrived
+    pub average_wait: Option<Duration>,
+    pub max_wait: Option<Duration>,
+}
+
+/// Where drivers were trying to park. Destinations in a parking permit zone are grouped by zone,
+/// since drivers can only use spots in their own zone. Everywhere else is split into a grid.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum ParkingArea {
+    PermitZone(String),
+    /// The column and row of a square `PARKING_AREA_SIZE` wide
+    Grid(usize, usize),
+}
+
+impl ParkingArea {
+    pub fn containing(b: BuildingID, map: &Map) -> ParkingArea {
+        let bldg = map.get_b(b);
+        if let Some(zone) = &map.get_parent(bldg.sidewalk()).parking_rules.permit_zone {
+            return ParkingArea::PermitZone(zone.clone());
+        }
+        let pt = bldg.polygon.center();
+        let size = PARKING_AREA_SIZE.inner_meters();
+        ParkingArea::Grid((pt.x() / size) as usize, (pt.y() / size) as usize)
+    }
+}
+
+/// Drivers looking for parking in one area
+#[derive(Clone, Debug, Serialize)]
+pub struct CruisingSummary {
+    pub area: ParkingArea,
+    /// The road in this area that the most drivers were heading to, to help describe it
+    pub busiest_road: RoadID,
+    /// How many trips had to look beyond the lane closest to their destination
+    pub trips: usize,
+    pub total_time: Duration,
+    pub total_distance: Distance,
+    /// The longest anybody spent looking
+    pub max_time: Duration,
+    /// How many drivers took a spot in a permit zone they don't belong to
+    pub parked_illegally: usize,
+    /// How many drivers couldn't find any spot and abandoned their trip
+    pub gave_up: usize,
+}
+
+/// Pedestrian level-of-service, following the Highway Capacity Manual's thresholds for the space
+/// available to each person on a walkway.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum PedestrianLOS {
+    /// Free to walk wherever, at any speed
+    A,
+    B,
+    C,
+    /// Speed and passing are restricted
+    D,
+    E,
+    /// Everybody shuffles along, with frequent contact
+    F,
+}
+
+impl PedestrianLOS {
+    /// From a density in people per square meter
+    pub fn from_density(density: f64) -> PedestrianLOS {
+        let space = 1.0 / density;
+        if space > 5.6 {
+            PedestrianLOS::A
+        } else if space > 3.7 {
+            PedestrianLOS::B
+        } else if space > 2.2 {
+            PedestrianLOS::C
+        } else if space > 1.4 {
+            PedestrianLOS::D
+        } else if space > 0.75 {
+            PedestrianLOS::E
+        } else {
+            PedestrianLOS::F
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct TripPhase {
     pub start_time: Time,
@@ -696,3 +1371,109 @@ impl SlidingWindow {
         }
     }
 }
+
+/// Splits a time interval by the hours since midnight it covers.
+/// Spread something happening at a constant rate per hour over the hours since midnight between
+/// two times
+fn add_by_hour(result: &mut Vec<f64>, start: Time, end: Time, per_hour: f64) {
+    for (hour, dt) in split_by_hour(start, end) {
+        if result.len() <= hour {
+            result.resize(hour + 1, 0.0);
+        }
+        result[hour] += per_hour * (dt / Duration::hours(1));
+    }
+}
+
+/// Given when spots became filled (true) or free (false), the average number occupied during each
+/// hour since midnight, up to `now`
+fn occupancy_by_hour(changes: &[(Time, bool)], now: Time) -> Vec<f64> {
+    let mut result = Vec::new();
+    let mut occupied = 0.0;
+    let mut last_t = Time::START_OF_DAY;
+    for (t, filled) in changes {
+        if *t > now {
+            break;
+        }
+        add_by_hour(&mut result, last_t, *t, occupied);
+        last_t = *t;
+        occupied += if *filled { 1.0 } else { -1.0 };
+    }
+    add_by_hour(&mut result, last_t, now, occupied);
+    result
+}
+
+fn split_by_hour(start: Time, end: Time) -> Vec<(usize, Duration)> {
+    let mut result = Vec::new();
+    let mut t = start;
+    while t < end {
+        let hour = t.get_hours();
+        let next = (Time::START_OF_DAY + Duration::hours(hour + 1)).min(end);
+        result.push((hour, next - t));
+        t = next;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hours(h: f64) -> Time {
+        Time::START_OF_DAY + Duration::seconds(h * 3600.0)
+    }
+
+    #[test]
+    fn test_split_by_hour() {
+        assert_eq!(
+            split_by_hour(hours(7.5), hours(9.25)),
+            vec![
+                (7, Duration::minutes(30)),
+                (8, Duration::hours(1)),
+                (9, Duration::minutes(15)),
+            ]
+        );
+        assert_eq!(
+            split_by_hour(hours(8.0), hours(8.5)),
+            vec![(8, Duration::minutes(30))]
+        );
+        assert!(split_by_hour(hours(8.0), hours(8.0)).is_empty());
+    }
+
+    #[test]
+    fn test_parking_revenue_by_hour() {
+        let mut analytics = Analytics::new(true);
+        let spot = ParkingSpot::Offstreet(BuildingID(0), 0);
+        // $3 for 1.5 hours, starting halfway through 7am
+        analytics
+            .parking_stays
+            .push((hours(9.0), spot, hours(7.5), 3.0, false));
+        // $1 for 30 minutes during 8am
+        analytics
+            .parking_stays
+            .push((hours(8.5), spot, hours(8.0), 1.0, false));
+
+        let revenue = analytics.parking_revenue_by_hour(hours(10.0), &Map::blank());
+        assert_eq!(revenue.len(), 9);
+        assert!(revenue[0..7].iter().all(|x| *x == 0.0));
+        assert!((revenue[7] - 1.0).abs() < 1e-6);
+        assert!((revenue[8] - 3.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_occupancy_by_hour() {
+        let changes = vec![
+            // Seeded
+            (Time::START_OF_DAY, true),
+            (hours(7.5), false),
+            (hours(8.0), true),
+            (hours(8.25), true),
+            (hours(8.75), false),
+        ];
+        assert_eq!(
+            occupancy_by_hour(&changes, hours(10.0)),
+            vec![1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 0.5, 1.5, 1.0]
+        );
+        // Changes after now don't count yet
+        assert_eq!(occupancy_by_hour(&changes, hours(1.5)), vec![1.0, 0.5]);
+    }
+}
@@ -0,0 +1,168 @@
+//! Iterative traffic assignment, approximating user equilibrium. Normally every driver picks the
+//! route that'd be fastest with no traffic at all, so in busy scenarios, everybody piles onto the
+//! same arterials. Instead, simulate the whole day, measure how long crossing each road and
+//! movement actually took, route the next day using those times, and repeat until nobody could
+//! do much better by switching routes.
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use abstutil::Timer;
+use geom::{Duration, Time};
+use map_model::{vehicle_cost, Map, MovementID, PathConstraints, PathStepV2, PathV2};
+
+use crate::{Analytics, Scenario, Sim, SimFlags, TripPhaseType};
+
+/// The result of iterative assignment for one scenario. Saved at
+/// `abstio::path_assignment_costs`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct AssignmentCosts {
+    pub scenario_name: String,
+    /// How many days were simulated
+    pub iterations: usize,
+    /// After the last day, how much time drivers could've saved in total by switching to the best
+    /// route, relative to their total time. Zero means nobody could've done better.
+    pub relative_gap: f64,
+    /// For cars, the extra time needed to cross a road and then a movement, beyond the free-flow
+    /// time
+    pub delays: BTreeMap<MovementID, Duration>,
+}
+
+impl AssignmentCosts {
+    /// Route cars on this map using these costs. They're kept through map edits.
+    pub fn apply(&self, map: &mut Map, timer: &mut Timer) {
+        map.set_driving_delays(&to_hashmap(&self.delays), timer);
+    }
+}
+
+/// Simulate the scenario for a full day at a time, feeding the travel times experienced by cars
+/// back into routing, until the relative gap drops to `target_gap` or `max_iterations` days have
+/// been simulated. The final costs are left applied to the map.
+pub fn iterative_assignment(
+    map: &mut Map,
+    scenario: &Scenario,
+    flags: &SimFlags,
+    max_iterations: usize,
+    target_gap: f64,
+    timer: &mut Timer,
+) -> AssignmentCosts {
+    assert!(max_iterations > 0);
+    let mut opts = flags.opts.clone();
+    opts.record_movement_times = true;
+    let mut delays = BTreeMap::new();
+    let mut iterations = 0;
+    let mut relative_gap = 1.0;
+    while iterations < max_iterations {
+        iterations += 1;
+        timer.start(format!("simulate day {}", iterations));
+        map.set_driving_delays(&to_hashmap(&delays), timer);
+        let mut sim = Sim::new(map, opts.clone());
+        // Every day is the same, except for the routes chosen
+        let mut rng = flags.make_rng();
+        scenario.instantiate(&mut sim, map, &mut rng, timer);
+        // Like prebaking, run a few hours past the end of the day, so late trips finish.
+        sim.timed_step(
+            map,
+            sim.get_end_of_day() - Time::START_OF_DAY + Duration::hours(3),
+            &mut None,
+            timer,
+        );
+        timer.stop(format!("simulate day {}", iterations));
+
+        let experienced = sim.get_analytics().movement_delays(map);
+        relative_gap = calculate_relative_gap(sim.get_analytics(), &experienced, map, timer);
+        info!(
+            "After day {} of {}, the relative gap is {:.4}",
+            iterations, scenario.scenario_name, relative_gap
+        );
+
+        // The method of successive averages: blend in each day's measurements with a shrinking
+        // weight, so routes don't flip back and forth between days.
+        let weight = 1.0 / (iterations as f64);
+        let mut blended = BTreeMap::new();
+        for mvmnt in delays.keys().chain(experienced.keys()) {
+            let old = delays.get(mvmnt).cloned().unwrap_or(Duration::ZERO);
+            let new = experienced.get(mvmnt).cloned().unwrap_or(Duration::ZERO);
+            blended.insert(*mvmnt, old * (1.0 - weight) + new * weight);
+        }
+        blended.retain(|_, delay| *delay > Duration::ZERO);
+        delays = blended;
+
+        if relative_gap <= target_gap {
+            break;
+        }
+    }
+    map.set_driving_delays(&to_hashmap(&delays), timer);
+
+    AssignmentCosts {
+        scenario_name: scenario.scenario_name.clone(),
+        iterations,
+        relative_gap,
+        delays,
+    }
+}
+
+/// Compare the cost of the route each driver took to the best route they could've taken, both
+/// using the experienced delays. The map must still have the delays used to pick the routes.
+fn calculate_relative_gap(
+    analytics: &Analytics,
+    experienced: &BTreeMap<MovementID, Duration>,
+    map: &mut Map,
+    timer: &mut Timer,
+) -> f64 {
+    let requests: Vec<_> = analytics
+        .trip_log
+        .iter()
+        .filter_map(|(_, _, req, phase)| {
+            let req = req.as_ref()?;
+            if *phase == TripPhaseType::Driving && req.constraints == PathConstraints::Car {
+                Some(req.clone())
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    // Ignoring parking and rerouting, this is the path each driver took
+    let used: Vec<Option<PathV2>> = requests
+        .iter()
+        .map(|req| map.pathfind_v2(req.clone()).ok())
+        .collect();
+    map.set_driving_delays(&to_hashmap(experienced), timer);
+
+    let mut total_used = Duration::ZERO;
+    let mut total_best = Duration::ZERO;
+    for (req, used) in requests.into_iter().zip(used) {
+        if let (Some(used), Ok(best)) = (used, map.pathfind_v2(req)) {
+            total_used += path_cost(&used, experienced, map);
+            total_best += path_cost(&best, experienced, map);
+        }
+    }
+    if total_used == Duration::ZERO {
+        return 0.0;
+    }
+    // Round-off could make the best path look slightly worse
+    ((total_used - total_best) / total_used).max(0.0)
+}
+
+/// The cost of a car following the path, given extra delays for some movements
+fn path_cost(path: &PathV2, delays: &BTreeMap<MovementID, Duration>, map: &Map) -> Duration {
+    let mut total = Duration::ZERO;
+    for step in path.get_steps() {
+        if let PathStepV2::Movement(mvmnt) = step {
+            total += vehicle_cost(
+                mvmnt.from,
+                *mvmnt,
+                PathConstraints::Car,
+                map.routing_params(),
+                map,
+            ) + delays.get(mvmnt).cloned().unwrap_or(Duration::ZERO);
+        }
+    }
+    total
+}
+
+fn to_hashmap(delays: &BTreeMap<MovementID, Duration>) -> HashMap<MovementID, Duration> {
+    delays.iter().map(|(k, v)| (*k, *v)).collect()
+}
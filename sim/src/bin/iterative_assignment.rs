@@ -0,0 +1,40 @@
+//! Runs iterative traffic assignment on a scenario, then saves the costs that cars should use to
+//! route. Later, pass `--assignment_costs` when loading the same scenario to use them.
+//!
+//! > cargo run --release --bin iterative_assignment -- \
+//!     data/system/us/seattle/scenarios/montlake/weekday.bin --max_iterations=10 --target_gap=0.01
+
+fn main() {
+    let mut args = abstutil::CmdArgs::new();
+    let max_iterations = args
+        .optional_parse("--max_iterations", |s| s.parse::<usize>())
+        .unwrap_or(10);
+    let target_gap = args
+        .optional_parse("--target_gap", |s| s.parse::<f64>())
+        .unwrap_or(0.01);
+    let mut flags = sim::SimFlags::from_args(&mut args);
+    args.done();
+    flags.opts.alerts = sim::AlertHandler::Silence;
+
+    let mut timer = abstutil::Timer::new("iterative assignment");
+    let mut scenario: sim::Scenario = abstio::must_read_object(flags.load.clone(), &mut timer);
+    let mut map = map_model::Map::load_synchronously(scenario.map_name.path(), &mut timer);
+    for m in &flags.modifiers {
+        scenario = m.apply(&map, scenario);
+    }
+
+    let costs = sim::iterative_assignment(
+        &mut map,
+        &scenario,
+        &flags,
+        max_iterations,
+        target_gap,
+        &mut timer,
+    );
+    let path = abstio::path_assignment_costs(&scenario.map_name, &scenario.scenario_name);
+    abstio::write_binary(path.clone(), &costs);
+    println!(
+        "Wrote {} after {} days, with a relative gap of {:.4}",
+        path, costs.iterations, costs.relative_gap
+    );
+}
@@ -0,0 +1,311 @@
+//! Some zones have a cap on the number of vehicles that may enter per hour. CapSimState tracks how
+//! many drivers have entered each capped zone, and decides what happens to drivers who would exceed
+//! the cap: reroute around the zone if possible, otherwise delay or cancel the trip.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use geom::{Duration, Time};
+use map_model::{LaneID, Map, Path, PathRequest, PathStep, RoadID};
+
+use crate::{CarID, Event, TripID};
+
+/// A delayed driver waits at most this many hours for a capped zone to have room. After that, the
+/// trip is cancelled.
+const MAX_DELAY_HOURS: usize = 24;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct CapSimState {
+    /// Only lanes belonging to a capped zone are here. The value indexes into `zones`.
+    lane_to_zone: BTreeMap<LaneID, usize>,
+    zones: Vec<CappedZone>,
+    cancel_capped_trips: bool,
+
+    events: Vec<Event>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct CappedZone {
+    members: BTreeSet<RoadID>,
+    cap: usize,
+    /// Hour of the day -> the vehicles that entered (or are scheduled to enter) the zone then
+    entered_per_hour: BTreeMap<usize, BTreeSet<CarID>>,
+}
+
+impl CappedZone {
+    fn has_room(&self, hour: usize) -> bool {
+        let entered = self
+            .entered_per_hour
+            .get(&hour)
+            .map(|cars| cars.len())
+            .unwrap_or(0);
+        entered < self.cap
+    }
+}
+
+/// What happened to a driver who would have exceeded a zone's cap
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum CapOutcome {
+    /// A path avoiding the full zones was found
+    Rerouted,
+    /// No alternate path exists, so the driver waits until the zone has room again
+    Delayed,
+    /// No alternate path exists, and `SimOptions::cancel_capped_trips` is set
+    Cancelled,
+}
+
+/// The result of `CapSimState::validate_path`
+pub(crate) enum CapResult {
+    /// Start the vehicle now with this path. It may differ from the original.
+    Ok(Path),
+    /// Start the vehicle at this later time, with the original path.
+    Delay(Path, Time),
+    Cancel(String),
+}
+
+impl CapSimState {
+    pub fn new(map: &Map, cancel_capped_trips: bool) -> CapSimState {
+        let mut cap = CapSimState {
+            lane_to_zone: BTreeMap::new(),
+            zones: Vec::new(),
+            cancel_capped_trips,
+            events: Vec::new(),
+        };
+        cap.rebuild_zones(map);
+        cap
+    }
+
+    fn rebuild_zones(&mut self, map: &Map) {
+        self.lane_to_zone.clear();
+        self.zones.clear();
+        for z in map.all_zones() {
+            // Like the UI, treat a cap of 0 as no cap at all. Otherwise nobody could ever enter.
+            if let Some(cap) = z.restrictions.cap_vehicles_per_hour.filter(|cap| *cap > 0) {
+                let idx = self.zones.len();
+                for r in &z.members {
+                    for l in map.get_r(*r).all_lanes() {
+                        self.lane_to_zone.insert(l, idx);
+                    }
+                }
+                self.zones.push(CappedZone {
+                    members: z.members.clone(),
+                    cap,
+                    entered_per_hour: BTreeMap::new(),
+                });
+            }
+        }
+    }
+
+    /// Zones and caps may have changed. Counts of vehicles that already entered a zone are kept
+    /// for zones with the exact same members.
+    pub fn handle_live_edits(&mut self, map: &Map) {
+        let mut old_counts: BTreeMap<BTreeSet<RoadID>, BTreeMap<usize, BTreeSet<CarID>>> = self
+            .zones
+            .drain(..)
+            .map(|z| (z.members, z.entered_per_hour))
+            .collect();
+        self.rebuild_zones(map);
+        for z in &mut self.zones {
+            if let Some(counts) = old_counts.remove(&z.members) {
+                z.entered_per_hour = counts;
+            }
+        }
+    }
+
+    /// Returns the indices of every capped zone this path enters from outside. A trip starting
+    /// inside a zone doesn't count as entering it.
+    fn zones_entered(&self, path: &Path) -> Vec<usize> {
+        let mut zones = Vec::new();
+        for step in path.get_steps() {
+            if let PathStep::Turn(t) = step {
+                if let Some(z) = self.lane_to_zone.get(&t.dst) {
+                    if self.lane_to_zone.get(&t.src) != Some(z) && !zones.contains(z) {
+                        zones.push(*z);
+                    }
+                }
+            }
+        }
+        zones
+    }
+
+    /// Before a car starts driving, check if its path would exceed any zone's cap. If so, try to
+    /// find an alternative. Bikes and transit vehicles aren't subject to caps; callers shouldn't
+    /// pass them in.
+    ///
+    /// A car already `underway` is switching to a new path partway through its trip. If no
+    /// alternative exists, the result is `Cancel`, meaning the car should keep its current path;
+    /// the trip itself isn't delayed or cancelled.
+    pub fn validate_path(
+        &mut self,
+        req: &PathRequest,
+        path: Path,
+        now: Time,
+        car: CarID,
+        trip: TripID,
+        underway: bool,
+        map: &Map,
+    ) -> CapResult {
+        if self.zones.is_empty() {
+            return CapResult::Ok(path);
+        }
+
+        let hour = now.get_hours();
+        let entered = self.zones_entered(&path);
+        let full: Vec<usize> = entered
+            .iter()
+            .copied()
+            .filter(|z| !self.zones[*z].has_room(hour))
+            .collect();
+        if full.is_empty() {
+            self.enter_zones(&entered, hour, car);
+            return CapResult::Ok(path);
+        }
+
+        let mut avoid = BTreeSet::new();
+        for z in &full {
+            avoid.extend(self.zones[*z].members.clone());
+        }
+        let first_road = *self.zones[full[0]].members.iter().next().unwrap();
+        if let Ok(new_path) = map.pathfind_avoiding_roads(req.clone(), &avoid) {
+            // The new path might enter other capped zones, but those must have room, or the new
+            // path would've avoided them too.
+            let entered = self.zones_entered(&new_path);
+            if entered.iter().all(|z| self.zones[*z].has_room(hour)) {
+                self.enter_zones(&entered, hour, car);
+                self.events
+                    .push(Event::TripCapped(trip, first_road, CapOutcome::Rerouted));
+                return CapResult::Ok(new_path);
+            }
+        }
+
+        if underway {
+            return CapResult::Cancel(format!(
+                "new path enters {} full congestion-capped zones",
+                full.len()
+            ));
+        }
+
+        if self.cancel_capped_trips {
+            self.events
+                .push(Event::TripCapped(trip, first_road, CapOutcome::Cancelled));
+            return CapResult::Cancel(format!(
+                "no path avoiding {} full congestion-capped zones",
+                full.len()
+            ));
+        }
+
+        // Wait for the earliest hour when every zone on the original path has room.
+        if let Some(later_hour) = self.first_hour_with_room(&entered, hour + 1) {
+            self.enter_zones(&entered, later_hour, car);
+            self.events
+                .push(Event::TripCapped(trip, first_road, CapOutcome::Delayed));
+            return CapResult::Delay(path, Time::START_OF_DAY + Duration::hours(later_hour));
+        }
+        self.events
+            .push(Event::TripCapped(trip, first_road, CapOutcome::Cancelled));
+        CapResult::Cancel(format!(
+            "{} congestion-capped zones are full for the next {} hours",
+            full.len(),
+            MAX_DELAY_HOURS
+        ))
+    }
+
+    /// Starting from some hour, find the first one when all of the zones have room. Gives up after
+    /// MAX_DELAY_HOURS.
+    fn first_hour_with_room(&self, zones: &[usize], start_hour: usize) -> Option<usize> {
+        (start_hour..start_hour + MAX_DELAY_HOURS)
+            .find(|hour| zones.iter().all(|z| self.zones[*z].has_room(*hour)))
+    }
+
+    fn enter_zones(&mut self, zones: &[usize], hour: usize, car: CarID) {
+        for z in zones {
+            self.zones[*z]
+                .entered_per_hour
+                .entry(hour)
+                .or_insert_with(BTreeSet::new)
+                .insert(car);
+        }
+    }
+
+    /// For each capped zone, returns (the member roads, the cap, the number of vehicles entering
+    /// during this hour).
+    pub fn get_zone_counts(&self, now: Time) -> Vec<(&BTreeSet<RoadID>, usize, usize)> {
+        let hour = now.get_hours();
+        self.zones
+            .iter()
+            .map(|z| {
+                (
+                    &z.members,
+                    z.cap,
+                    z.entered_per_hour.get(&hour).map(|x| x.len()).unwrap_or(0),
+                )
+            })
+            .collect()
+    }
+
+    pub fn collect_events(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VehicleType;
+
+    fn car(id: usize) -> CarID {
+        CarID {
+            id,
+            vehicle_type: VehicleType::Car,
+        }
+    }
+
+    fn zone(cap: usize) -> CappedZone {
+        CappedZone {
+            members: BTreeSet::new(),
+            cap,
+            entered_per_hour: BTreeMap::new(),
+        }
+    }
+
+    fn state(zones: Vec<CappedZone>) -> CapSimState {
+        CapSimState {
+            lane_to_zone: BTreeMap::new(),
+            zones,
+            cancel_capped_trips: false,
+            events: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_has_room() {
+        let mut z = zone(2);
+        assert!(z.has_room(8));
+        z.entered_per_hour
+            .insert(8, vec![car(0)].into_iter().collect());
+        assert!(z.has_room(8));
+        z.entered_per_hour.get_mut(&8).unwrap().insert(car(1));
+        assert!(!z.has_room(8));
+        // Other hours aren't affected
+        assert!(z.has_room(9));
+
+        // Nobody at all fits in a zone with a cap of 0, even during an hour nobody's entered yet
+        assert!(!zone(0).has_room(8));
+    }
+
+    #[test]
+    fn test_delay_until_room() {
+        let mut cap = state(vec![zone(1), zone(1)]);
+        cap.enter_zones(&[0], 8, car(0));
+        cap.enter_zones(&[1], 9, car(1));
+        // The first zone is full at 8, the second at 9
+        assert_eq!(cap.first_hour_with_room(&[0], 8), Some(9));
+        assert_eq!(cap.first_hour_with_room(&[0, 1], 8), Some(10));
+        assert_eq!(cap.first_hour_with_room(&[1], 8), Some(8));
+
+        // The search gives up, instead of looping forever
+        let cap = state(vec![zone(0)]);
+        assert_eq!(cap.first_hour_with_room(&[0], 8), None);
+    }
+}
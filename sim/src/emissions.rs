@@ -0,0 +1,282 @@
+//! Rough estimates of the fuel, energy, and pollution that vehicles produce. Each vehicle's
+//! emissions are estimated after it finishes crossing a lane or turn, based on the distance
+//! driven, the road's incline, how fast it went, how much it sped up, and how long it spent
+//! stopped. Only tailpipe emissions are counted; electricity for trains shows up as energy use,
+//! not CO2.
+
+use std::ops::{Add, AddAssign, Mul};
+
+use serde::{Deserialize, Serialize};
+
+use geom::{Distance, Duration, Speed};
+
+use crate::VehicleType;
+
+/// Some amount of pollution and energy use
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Emissions {
+    /// In grams
+    pub co2: f64,
+    /// In grams
+    pub nox: f64,
+    /// Particulate matter, in grams
+    pub pm: f64,
+    /// Energy used, in kilowatt-hours
+    pub kwh: f64,
+}
+
+impl Emissions {
+    pub const ZERO: Emissions = Emissions {
+        co2: 0.0,
+        nox: 0.0,
+        pm: 0.0,
+        kwh: 0.0,
+    };
+
+    const fn new(co2: f64, nox: f64, pm: f64, kwh: f64) -> Emissions {
+        Emissions { co2, nox, pm, kwh }
+    }
+
+    /// A short summary, like "1.2kg CO2, 3.4g NOx, 0.1g PM, 5.6 kWh"
+    pub fn describe(&self) -> String {
+        let co2 = if self.co2 >= 1000.0 {
+            format!("{:.1}kg", self.co2 / 1000.0)
+        } else {
+            format!("{:.0}g", self.co2)
+        };
+        format!(
+            "{} CO2, {:.1}g NOx, {:.2}g PM, {:.1} kWh",
+            co2, self.nox, self.pm, self.kwh
+        )
+    }
+}
+
+impl Add for Emissions {
+    type Output = Emissions;
+
+    fn add(self, other: Emissions) -> Emissions {
+        Emissions {
+            co2: self.co2 + other.co2,
+            nox: self.nox + other.nox,
+            pm: self.pm + other.pm,
+            kwh: self.kwh + other.kwh,
+        }
+    }
+}
+
+impl AddAssign for Emissions {
+    fn add_assign(&mut self, other: Emissions) {
+        *self = *self + other;
+    }
+}
+
+impl Mul<f64> for Emissions {
+    type Output = Emissions;
+
+    fn mul(self, factor: f64) -> Emissions {
+        Emissions {
+            co2: self.co2 * factor,
+            nox: self.nox * factor,
+            pm: self.pm * factor,
+            kwh: self.kwh * factor,
+        }
+    }
+}
+
+/// How much one type of vehicle emits
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EmissionRates {
+    /// Per kilometer driven on flat ground at the most efficient speed
+    pub per_km: Emissions,
+    /// Per hour spent stopped with the engine running
+    pub per_idle_hour: Emissions,
+    /// Extra for speeding up from a stop to 10m/s. This scales with the kinetic energy gained, so
+    /// speeding up from 10m/s to 20m/s costs 3 times as much.
+    pub per_acceleration: Emissions,
+    /// How much each percent of uphill grade increases the per-kilometer rate. Downhill decreases
+    /// it, to a minimum of zero.
+    pub incline_factor: f64,
+    /// Driving slower or faster than this on average uses more per kilometer...
+    pub efficient_speed: Speed,
+    /// ... growing with the square of the relative difference, scaled by this.
+    pub speed_factor: f64,
+}
+
+impl EmissionRates {
+    fn zero() -> EmissionRates {
+        EmissionRates {
+            per_km: Emissions::ZERO,
+            per_idle_hour: Emissions::ZERO,
+            per_acceleration: Emissions::ZERO,
+            incline_factor: 0.0,
+            efficient_speed: Speed::ZERO,
+            speed_factor: 0.0,
+        }
+    }
+}
+
+/// Emission rates for every type of vehicle. Callers can pass in their own with
+/// `--emission_table=path.json`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EmissionTable {
+    pub car: EmissionRates,
+    pub bus: EmissionRates,
+    pub train: EmissionRates,
+    pub bike: EmissionRates,
+}
+
+impl std::default::Default for EmissionTable {
+    /// Ballpark figures for a gasoline car, a diesel bus, and an electric light rail vehicle.
+    fn default() -> EmissionTable {
+        EmissionTable {
+            car: EmissionRates {
+                per_km: Emissions::new(150.0, 0.05, 0.004, 0.57),
+                per_idle_hour: Emissions::new(1400.0, 0.5, 0.02, 5.3),
+                per_acceleration: Emissions::new(8.0, 0.004, 0.0004, 0.03),
+                incline_factor: 0.08,
+                efficient_speed: Speed::km_per_hour(60.0),
+                speed_factor: 1.0,
+            },
+            bus: EmissionRates {
+                per_km: Emissions::new(1200.0, 1.8, 0.045, 4.5),
+                per_idle_hour: Emissions::new(4000.0, 8.0, 0.2, 15.0),
+                per_acceleration: Emissions::new(50.0, 0.08, 0.0025, 0.18),
+                incline_factor: 0.1,
+                efficient_speed: Speed::km_per_hour(40.0),
+                speed_factor: 1.0,
+            },
+            train: EmissionRates {
+                per_km: Emissions::new(0.0, 0.0, 0.01, 4.5),
+                per_idle_hour: Emissions::new(0.0, 0.0, 0.0, 20.0),
+                per_acceleration: Emissions::new(0.0, 0.0, 0.001, 0.4),
+                incline_factor: 0.1,
+                efficient_speed: Speed::km_per_hour(50.0),
+                speed_factor: 0.5,
+            },
+            bike: EmissionRates::zero(),
+        }
+    }
+}
+
+impl EmissionTable {
+    fn rates(&self, vehicle_type: VehicleType) -> &EmissionRates {
+        match vehicle_type {
+            // Emergency vehicles are assumed to be about the same as cars
+            VehicleType::Car | VehicleType::Emergency => &self.car,
+            VehicleType::Bus => &self.bus,
+            VehicleType::Train => &self.train,
+            VehicleType::Bike => &self.bike,
+        }
+    }
+
+    /// Estimate emissions for a vehicle crossing some distance with an average incline (0.08 is
+    /// an 8% uphill grade). It spends `moving` time driving and `stopped` time idling, and starts
+    /// at `start_speed`. If it ends up faster on average, it had to accelerate.
+    pub fn estimate(
+        &self,
+        vehicle_type: VehicleType,
+        dist: Distance,
+        percent_incline: f64,
+        moving: Duration,
+        stopped: Duration,
+        start_speed: Speed,
+    ) -> Emissions {
+        let rates = self.rates(vehicle_type);
+        let incline = (1.0 + rates.incline_factor * percent_incline * 100.0).max(0.0);
+        let speed = if moving > Duration::ZERO {
+            dist.inner_meters() / moving.inner_seconds()
+        } else {
+            0.0
+        };
+        let efficient = rates.efficient_speed.inner_meters_per_second();
+        let inefficiency = if efficient > 0.0 && speed > 0.0 {
+            1.0 + rates.speed_factor * ((speed - efficient) / efficient).powi(2)
+        } else {
+            1.0
+        };
+
+        let mut total = rates.per_km * (dist.inner_meters() / 1000.0 * incline * inefficiency);
+        let start = start_speed.inner_meters_per_second();
+        if speed > start {
+            total += rates.per_acceleration * ((speed.powi(2) - start.powi(2)) / 100.0);
+        }
+        if stopped > Duration::ZERO {
+            total += rates.per_idle_hour * (stopped.inner_seconds() / 3600.0);
+        }
+        total
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Cross 1km at some average speed
+    fn km_at(table: &EmissionTable, speed: Speed, start_speed: Speed) -> Emissions {
+        let dist = Distance::meters(1000.0);
+        table.estimate(
+            VehicleType::Car,
+            dist,
+            0.0,
+            dist / speed,
+            Duration::ZERO,
+            start_speed,
+        )
+    }
+
+    fn assert_close(a: Emissions, b: Emissions) {
+        for (x, y) in [(a.co2, b.co2), (a.nox, b.nox), (a.pm, b.pm), (a.kwh, b.kwh)] {
+            assert!((x - y).abs() < 1e-3, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn test_speed() {
+        let table = EmissionTable::default();
+        let efficient = table.car.efficient_speed;
+        let cruising = km_at(&table, efficient, efficient);
+        assert_close(cruising, table.car.per_km);
+
+        // Crawling and speeding both use more
+        let slow = Speed::km_per_hour(15.0);
+        let fast = Speed::km_per_hour(120.0);
+        assert!(km_at(&table, slow, slow).co2 > cruising.co2);
+        assert!(km_at(&table, fast, fast).co2 > cruising.co2);
+    }
+
+    #[test]
+    fn test_acceleration() {
+        let table = EmissionTable::default();
+        let speed = Speed::meters_per_second(10.0);
+        let cruising = km_at(&table, speed, speed);
+        let from_stop = km_at(&table, speed, Speed::ZERO);
+        assert_close(from_stop, cruising + table.car.per_acceleration);
+        // Slowing down doesn't cost anything extra
+        assert_eq!(km_at(&table, speed, speed * 2.0), cruising);
+    }
+
+    #[test]
+    fn test_idling() {
+        let table = EmissionTable::default();
+        let idle = table.estimate(
+            VehicleType::Car,
+            Distance::ZERO,
+            0.0,
+            Duration::ZERO,
+            Duration::hours(1),
+            Speed::ZERO,
+        );
+        assert_eq!(idle, table.car.per_idle_hour);
+        assert_eq!(
+            table.estimate(
+                VehicleType::Bike,
+                Distance::meters(1000.0),
+                0.1,
+                Duration::minutes(3),
+                Duration::minutes(1),
+                Speed::ZERO,
+            ),
+            Emissions::ZERO
+        );
+    }
+}
@@ -1,12 +1,15 @@
 use serde::{Deserialize, Serialize};
 
-use geom::Duration;
+use geom::{Distance, Duration};
 use map_model::{
-    BuildingID, BusRouteID, BusStopID, IntersectionID, LaneID, Map, Path, PathRequest, Traversable,
-    TurnID,
+    BuildingID, BusRouteID, BusStopID, IntersectionID, LaneID, Map, Path, PathRequest, RoadID,
+    Traversable, TurnID,
 };
 
-use crate::{AgentID, CarID, ParkingSpot, PedestrianID, PersonID, Problem, TripID, TripMode};
+use crate::{
+    AgentID, CapOutcome, CarID, CruisingOutcome, Emissions, ParkingSpot, PedestrianID, PersonID,
+    Problem, SignalPriorityAction, TripID, TripMode,
+};
 
 /// As a simulation runs, different systems emit Events. This cleanly separates the internal
 /// mechanics of the simulation from consumers that just want to know what's happening.
@@ -19,6 +22,8 @@ use crate::{AgentID, CarID, ParkingSpot, PedestrianID, PersonID, Problem, TripID
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum Event {
     CarReachedParkingSpot(CarID, ParkingSpot),
+    /// A car starts the simulation parked here, instead of arriving during a trip.
+    CarSeededInParkingSpot(CarID, ParkingSpot),
     CarLeftParkingSpot(CarID, ParkingSpot),
 
     BusArrivedAtStop(CarID, BusRouteID, BusStopID),
@@ -26,6 +31,19 @@ pub enum Event {
     /// How long waiting at the stop?
     PassengerBoardsTransit(PersonID, CarID, BusRouteID, BusStopID, Duration),
     PassengerAlightsTransit(PersonID, CarID, BusRouteID, BusStopID),
+    /// The vehicle was full, so the passenger keeps waiting for the next one.
+    PassengerDeniedBoarding(TripID, PersonID, CarID, BusRouteID, BusStopID),
+    /// A transit vehicle reached a stop, compared to its schedule.
+    BusScheduleAdherence {
+        bus: CarID,
+        route: BusRouteID,
+        stop: BusStopID,
+        /// Positive if late, negative if early
+        deviation: Duration,
+        /// Actual and scheduled time since the previous vehicle on this route reached this stop.
+        /// None for the first vehicle.
+        headway: Option<(Duration, Duration)>,
+    },
 
     PersonEntersBuilding(PersonID, BuildingID),
     PersonLeavesBuilding(PersonID, BuildingID),
@@ -34,16 +52,72 @@ pub enum Event {
     PersonEntersMap(PersonID, AgentID, IntersectionID),
 
     PedReachedParkingSpot(PedestrianID, ParkingSpot),
+    /// A carpool driver's car is leaving with some number of passengers, not counting the driver.
+    CarpoolDeparted(TripID, CarID, usize),
+    /// A ride-hailing vehicle picked somebody up. How long did they wait since requesting it?
+    RideHailPickup(TripID, CarID, Duration),
+    /// A ride-hailing vehicle parked after driving somewhere, either empty to a pickup or carrying
+    /// the rider.
+    RideHailLegFinished {
+        car: CarID,
+        trip: TripID,
+        occupied: bool,
+        distance: Distance,
+        duration: Duration,
+    },
 
     BikeStoppedAtSidewalk(CarID, LaneID),
+    /// A vehicle passed somebody on this lane by briefly using the adjacent oncoming lane.
+    OncomingOvertake {
+        vehicle: CarID,
+        overtaken: CarID,
+        lane: LaneID,
+        oncoming: LaneID,
+    },
+    /// A traffic signal changed for an approaching bus or emergency vehicle. The time saved by
+    /// the vehicle and the delay imposed on everybody else (summed over each agent waiting) are
+    /// rough estimates.
+    SignalPriority {
+        intersection: IntersectionID,
+        vehicle: CarID,
+        route: Option<BusRouteID>,
+        action: SignalPriorityAction,
+        time_saved: Duration,
+        delay_imposed: Duration,
+    },
+    /// A driver switched to a route that's estimated to be faster with current traffic. The trip
+    /// is None for ride-hailing vehicles driving empty.
+    VehicleRerouted {
+        vehicle: CarID,
+        trip: Option<TripID>,
+        lane: LaneID,
+        time_saved: Duration,
+    },
+    /// A driver found no free spot on the lane they were heading to, so they drove around looking
+    /// for one. Emitted once they start parking or give up. The trip is None for ride-hailing
+    /// vehicles.
+    CruisedForParking {
+        car: CarID,
+        trip: Option<TripID>,
+        target: BuildingID,
+        duration: Duration,
+        distance: Distance,
+        outcome: CruisingOutcome,
+    },
 
     ProblemEncountered(TripID, Problem),
 
-    /// If the agent is a transit vehicle, then include a count of how many passengers are on
-    /// board.
+    /// If the agent is a transit vehicle or a carpool, then include a count of how many passengers
+    /// are on board.
     AgentEntersTraversable(AgentID, Option<TripID>, Traversable, Option<usize>),
     /// TripID, TurnID (Where the delay was encountered), Time spent waiting at that turn
     IntersectionDelayMeasured(TripID, TurnID, AgentID, Duration),
+    /// Estimated emissions of a vehicle on some lane or turn it just finished. The trip is None
+    /// for transit vehicles.
+    VehicleEmissions(CarID, Option<TripID>, Traversable, Emissions),
+    /// How crowded a sidewalk, shoulder, or crosswalk was when a pedestrian started walking along
+    /// it, in people per square meter.
+    PedestrianDensity(Traversable, f64),
 
     TripFinished {
         trip: TripID,
@@ -53,6 +127,8 @@ pub enum Event {
     },
     TripCancelled(TripID, TripMode),
     TripPhaseStarting(TripID, PersonID, Option<PathRequest>, TripPhaseType),
+    /// A driver would have exceeded the vehicle cap of the zone containing this road.
+    TripCapped(TripID, RoadID, CapOutcome),
 
     /// Just use for parking replanning. Not happy about copying the full path in here, but the way
     /// to plumb info into Analytics is Event.
@@ -78,6 +154,10 @@ pub enum TripPhaseType {
     WaitingForBus(BusRouteID, BusStopID),
     /// What stop did they board at?
     RidingBus(BusRouteID, BusStopID, CarID),
+    /// A carpool driver or passenger waiting for everyone else to show up
+    WaitingForCarpool,
+    RidingCar(CarID),
+    WaitingForRideHail,
     Cancelled,
     Finished,
     DelayedStart,
@@ -94,6 +174,9 @@ impl TripPhaseType {
                 format!("Waiting for bus {}", map.get_br(r).full_name)
             }
             TripPhaseType::RidingBus(r, _, _) => format!("Riding bus {}", map.get_br(r).full_name),
+            TripPhaseType::WaitingForCarpool => "Waiting for the rest of the carpool".to_string(),
+            TripPhaseType::RidingCar(c) => format!("Riding in {}", c),
+            TripPhaseType::WaitingForRideHail => "Waiting for a ride-hailing vehicle".to_string(),
             TripPhaseType::Cancelled => "Trip was cancelled due to some bug".to_string(),
             TripPhaseType::Finished => "Trip finished".to_string(),
             TripPhaseType::DelayedStart => "Delayed by a previous trip taking too long".to_string(),
@@ -0,0 +1,251 @@
+//! A fleet of ride-hailing vehicles that nobody owns. Idle vehicles wait in parking spots. When
+//! somebody requests a ride, the nearest idle vehicle drives there empty ("deadheading"), picks
+//! them up, drops them off near their destination, and then waits wherever it parked for the next
+//! request.
+//!
+//! This only tracks the state of each vehicle and request; TripManager moves the riders and
+//! vehicles around.
+
+use std::collections::{BTreeMap, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use abstutil::{deserialize_btreemap, serialize_btreemap};
+use geom::{Distance, Duration, Time};
+use map_model::{BuildingID, Map};
+
+use crate::{CarID, ParkingSim, ParkingSimState, TripID};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct FleetSimState {
+    /// How many vehicles to seed when a scenario is instantiated
+    size: usize,
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    vehicles: BTreeMap<CarID, FleetVehicle>,
+    /// Riders who haven't been picked up yet
+    requests: BTreeMap<TripID, RideRequest>,
+    /// Riders without an assigned vehicle, in the order they asked
+    queue: VecDeque<TripID>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct FleetVehicle {
+    state: FleetVehicleState,
+    /// When the current state began
+    since: Time,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub(crate) enum FleetVehicleState {
+    /// Parked somewhere, waiting for a request
+    Idle,
+    /// Driving empty to pick somebody up
+    EnRoute(TripID),
+    /// Carrying a rider to their destination
+    Occupied(TripID),
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct RideRequest {
+    pickup: BuildingID,
+    requested_at: Time,
+}
+
+impl FleetSimState {
+    pub fn new(size: usize) -> FleetSimState {
+        FleetSimState {
+            size,
+            vehicles: BTreeMap::new(),
+            requests: BTreeMap::new(),
+            queue: VecDeque::new(),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn num_vehicles(&self) -> usize {
+        self.vehicles.len()
+    }
+
+    /// Vehicles currently driving, either empty or with a rider
+    pub fn num_busy(&self) -> usize {
+        self.vehicles
+            .values()
+            .filter(|v| v.state != FleetVehicleState::Idle)
+            .count()
+    }
+
+    /// The vehicle must already be parked somewhere.
+    pub fn add_vehicle(&mut self, car: CarID, now: Time) {
+        self.vehicles.insert(
+            car,
+            FleetVehicle {
+                state: FleetVehicleState::Idle,
+                since: now,
+            },
+        );
+    }
+
+    /// Somebody wants a ride. If a vehicle is free, returns the one that should go pick them up.
+    /// Otherwise they wait in line.
+    pub fn request(
+        &mut self,
+        now: Time,
+        trip: TripID,
+        pickup: BuildingID,
+        parking: &ParkingSimState,
+        map: &Map,
+    ) -> Option<CarID> {
+        self.requests.insert(
+            trip,
+            RideRequest {
+                pickup,
+                requested_at: now,
+            },
+        );
+        let car = self.assign(now, trip, parking, map);
+        if car.is_none() {
+            self.queue.push_back(trip);
+        }
+        car
+    }
+
+    /// The vehicle headed to pick somebody up disappeared, so find another one. If there are no
+    /// free vehicles, they go to the front of the line.
+    pub fn reassign(
+        &mut self,
+        now: Time,
+        trip: TripID,
+        parking: &ParkingSimState,
+        map: &Map,
+    ) -> Option<CarID> {
+        // Maybe they already gave up
+        if !self.requests.contains_key(&trip) {
+            return None;
+        }
+        let car = self.assign(now, trip, parking, map);
+        if car.is_none() {
+            self.queue.push_front(trip);
+        }
+        car
+    }
+
+    fn assign(
+        &mut self,
+        now: Time,
+        trip: TripID,
+        parking: &ParkingSimState,
+        map: &Map,
+    ) -> Option<CarID> {
+        let pickup_pt = map.get_b(self.requests[&trip].pickup).polygon.center();
+
+        // Idle vehicles should always be parked, unless live map edits evicted them.
+        let mut evicted = Vec::new();
+        let mut best: Option<(CarID, Distance)> = None;
+        for (car, v) in &self.vehicles {
+            if v.state != FleetVehicleState::Idle {
+                continue;
+            }
+            if let Some(parked_car) = parking.lookup_parked_car(*car) {
+                let dist = parking
+                    .spot_to_sidewalk_pos(parked_car.spot, map)
+                    .pt(map)
+                    .dist_to(pickup_pt);
+                if best.map(|(_, d)| dist < d).unwrap_or(true) {
+                    best = Some((*car, dist));
+                }
+            } else {
+                evicted.push(*car);
+            }
+        }
+        for car in evicted {
+            self.vehicles.remove(&car);
+        }
+
+        let (car, _) = best?;
+        let v = self.vehicles.get_mut(&car).unwrap();
+        v.state = FleetVehicleState::EnRoute(trip);
+        v.since = now;
+        Some(car)
+    }
+
+    /// Who should the vehicle pick up, and where?
+    pub fn assignment(&self, car: CarID) -> (TripID, BuildingID) {
+        match self.vehicles[&car].state {
+            FleetVehicleState::EnRoute(trip) => (trip, self.requests[&trip].pickup),
+            state => panic!("{} isn't on its way to a pickup; it's {:?}", car, state),
+        }
+    }
+
+    /// The vehicle finished driving somewhere. Returns what it was doing, and for how long. It's
+    /// idle until told otherwise.
+    pub fn vehicle_parked(&mut self, now: Time, car: CarID) -> (FleetVehicleState, Duration) {
+        let v = self.vehicles.get_mut(&car).unwrap();
+        let prev = (v.state, now - v.since);
+        v.state = FleetVehicleState::Idle;
+        v.since = now;
+        prev
+    }
+
+    /// The vehicle reached its assigned rider. Returns how long they waited since asking for the
+    /// ride. If they've since given up, returns None and the vehicle stays idle.
+    pub fn pick_up(&mut self, now: Time, car: CarID, trip: TripID) -> Option<Duration> {
+        let req = self.requests.remove(&trip)?;
+        let v = self.vehicles.get_mut(&car).unwrap();
+        v.state = FleetVehicleState::Occupied(trip);
+        v.since = now;
+        Some(now - req.requested_at)
+    }
+
+    /// The vehicle couldn't take the rider anywhere after all.
+    pub fn abort_ride(&mut self, now: Time, car: CarID) {
+        let v = self.vehicles.get_mut(&car).unwrap();
+        v.state = FleetVehicleState::Idle;
+        v.since = now;
+    }
+
+    /// An idle vehicle should serve the next rider in line, if there is one.
+    pub fn next_request(&mut self, now: Time, car: CarID) -> Option<TripID> {
+        while let Some(trip) = self.queue.pop_front() {
+            // Skip anybody who gave up
+            if self.requests.contains_key(&trip) {
+                let v = self.vehicles.get_mut(&car).unwrap();
+                v.state = FleetVehicleState::EnRoute(trip);
+                v.since = now;
+                return Some(trip);
+            }
+        }
+        None
+    }
+
+    /// The rider's trip was cancelled before they were picked up. If a vehicle is already on the
+    /// way, it'll find nobody there.
+    pub fn cancel_request(&mut self, trip: TripID) {
+        self.requests.remove(&trip);
+    }
+
+    pub fn has_vehicle(&self, car: CarID) -> bool {
+        self.vehicles.contains_key(&car)
+    }
+
+    /// The vehicle vanished from the simulation. Returns what it was doing.
+    pub fn remove_vehicle(&mut self, car: CarID) -> FleetVehicleState {
+        self.vehicles.remove(&car).unwrap().state
+    }
+
+    /// Everybody waiting in line, who'll never get a ride if there are no vehicles left.
+    pub fn drain_queue(&mut self) -> Vec<TripID> {
+        let mut trips = Vec::new();
+        for trip in self.queue.drain(..) {
+            if self.requests.remove(&trip).is_some() {
+                trips.push(trip);
+            }
+        }
+        trips
+    }
+}
@@ -6,8 +6,9 @@
 //! glue together individual goals executed by the agents.
 //!
 //! Helpful terminology:
-//! - sov = single occupancy vehicle, a car with just a driver and no passengers. (Car passengers
-//!   are not currently modelled)
+//! - sov = single occupancy vehicle, a car with just a driver and no passengers.
+//! - carpool = a car with a driver and some passengers, who each have their own trip
+//! - deadheading = a ride-hailing vehicle driving empty to pick somebody up
 
 // Disable some noisy clippy warnings
 #![allow(clippy::type_complexity, clippy::too_many_arguments)]
@@ -24,8 +25,8 @@ use serde::{Deserialize, Serialize};
 use abstutil::{deserialize_usize, serialize_usize};
 use geom::{Distance, Speed, Time};
 use map_model::{
-    BuildingID, BusRouteID, BusStopID, IntersectionID, LaneID, Map, ParkingLotID, Path,
-    PathConstraints, Position,
+    BuildingID, BusRouteID, BusStopID, IntersectionID, LaneID, Map, ParkingLotID, ParkingRules,
+    Path, PathConstraints, Position, TransitCapacity,
 };
 
 pub use crate::render::{
@@ -33,21 +34,31 @@ pub use crate::render::{
     UnzoomedAgent,
 };
 
-pub use self::analytics::{Analytics, Problem, SlidingWindow, TripPhase};
-pub(crate) use self::events::Event;
-pub use self::events::{AlertLocation, TripPhaseType};
+pub use self::analytics::{
+    Analytics, CruisingSummary, ParkingArea, PedestrianLOS, Problem, RideHailSummary,
+    SlidingWindow, TripPhase,
+};
+pub use self::assignment::{iterative_assignment, AssignmentCosts};
+pub use self::cap::CapOutcome;
+pub(crate) use self::cap::{CapResult, CapSimState};
+pub use self::emissions::{EmissionRates, EmissionTable, Emissions};
+pub use self::events::{AlertLocation, Event, TripPhaseType};
+pub(crate) use self::fleet::{FleetSimState, FleetVehicleState};
 pub use self::make::{
-    fork_rng, BorderSpawnOverTime, ExternalPerson, ExternalTrip, ExternalTripEndpoint, IndividTrip,
-    MapBorders, PersonSpec, Scenario, ScenarioGenerator, ScenarioModifier, SimFlags, SpawnOverTime,
-    TripEndpoint, TripPurpose,
+    fork_rng, BorderSpawnOverTime, ExternalPerson, ExternalTrip, ExternalTripEndpoint,
+    HouseholdSpec, IndividTrip, MapBorders, ModeChoiceModel, PersonSpec, Scenario,
+    ScenarioGenerator, ScenarioModifier, SimFlags, SpawnOverTime, TripEndpoint, TripPurpose,
 };
 pub(crate) use self::make::{StartTripArgs, TripSpec};
 pub(crate) use self::mechanics::{
     DrivingSimState, IntersectionSimState, ParkingSim, ParkingSimState, WalkingSimState,
 };
+pub use self::mechanics::{SignalPriorityAction, TransitSignalPriority};
+pub use self::noise::building_noise;
 pub(crate) use self::pandemic::PandemicModel;
 pub(crate) use self::recorder::TrafficRecorder;
 pub(crate) use self::router::{ActionAtEnd, Router};
+pub use self::router::{CruisingOutcome, ParkingStrategy, ReroutingPolicy};
 pub(crate) use self::scheduler::{Command, Scheduler};
 pub use self::sim::{AgentProperties, AlertHandler, DelayCause, Sim, SimCallback, SimOptions};
 pub(crate) use self::transit::TransitSimState;
@@ -56,9 +67,14 @@ pub use self::trips::{CommutersVehiclesCounts, Person, PersonState, TripInfo, Tr
 pub(crate) use self::trips::{TripLeg, TripManager};
 
 mod analytics;
+mod assignment;
+mod cap;
+mod emissions;
 mod events;
+mod fleet;
 mod make;
 mod mechanics;
+mod noise;
 mod pandemic;
 mod recorder;
 mod render;
@@ -84,6 +100,9 @@ pub(crate) const FOLLOWING_DISTANCE: Distance = Distance::const_meters(1.0);
 /// Getting too close to EPSILON_DIST can lead to get_draw_car having no geometry at all.
 pub(crate) const SPAWN_DIST: Distance = Distance::const_meters(0.05);
 
+/// When choosing a parking spot, drivers will walk this much farther to save a dollar per hour.
+const WALK_TO_SAVE_A_DOLLAR: Distance = Distance::const_meters(100.0);
+
 // TODO Implement Eq, Hash, Ord manually to guarantee this.
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct CarID {
@@ -104,6 +123,7 @@ impl fmt::Display for CarID {
             VehicleType::Bus => write!(f, "Bus #{}", self.id),
             VehicleType::Train => write!(f, "Train #{}", self.id),
             VehicleType::Bike => write!(f, "Bike #{}", self.id),
+            VehicleType::Emergency => write!(f, "Emergency vehicle #{}", self.id),
         }
     }
 }
@@ -127,7 +147,7 @@ impl fmt::Display for PedestrianID {
 pub enum AgentID {
     Car(CarID),
     Pedestrian(PedestrianID),
-    // TODO Rename...
+    // TODO Rename... this covers carpool passengers too.
     BusPassenger(PersonID, CarID),
 }
 
@@ -142,13 +162,19 @@ impl AgentID {
     pub fn to_type(self) -> AgentType {
         match self {
             AgentID::Car(c) => match c.vehicle_type {
-                VehicleType::Car => AgentType::Car,
+                VehicleType::Car | VehicleType::Emergency => AgentType::Car,
                 VehicleType::Bike => AgentType::Bike,
                 VehicleType::Bus => AgentType::Bus,
                 VehicleType::Train => AgentType::Train,
             },
             AgentID::Pedestrian(_) => AgentType::Pedestrian,
-            AgentID::BusPassenger(_, _) => AgentType::TransitRider,
+            AgentID::BusPassenger(_, c) => {
+                if c.vehicle_type == VehicleType::Car {
+                    AgentType::CarPassenger
+                } else {
+                    AgentType::TransitRider
+                }
+            }
         }
     }
 
@@ -188,6 +214,7 @@ pub enum AgentType {
     Train,
     Pedestrian,
     TransitRider,
+    CarPassenger,
 }
 
 impl AgentType {
@@ -199,6 +226,7 @@ impl AgentType {
             AgentType::Train,
             AgentType::Pedestrian,
             AgentType::TransitRider,
+            AgentType::CarPassenger,
         ]
     }
 
@@ -210,6 +238,7 @@ impl AgentType {
             AgentType::Train => "Train",
             AgentType::Pedestrian => "Pedestrian",
             AgentType::TransitRider => "Transit rider",
+            AgentType::CarPassenger => "Car passenger",
         }
     }
 
@@ -221,6 +250,7 @@ impl AgentType {
             AgentType::Train => "trains",
             AgentType::Pedestrian => "pedestrians",
             AgentType::TransitRider => "transit riders",
+            AgentType::CarPassenger => "car passengers",
         }
     }
 
@@ -231,6 +261,7 @@ impl AgentType {
             AgentType::Bus | AgentType::Train => unreachable!(),
             AgentType::Pedestrian => "walking",
             AgentType::TransitRider => "riding transit",
+            AgentType::CarPassenger => "riding in a car",
         }
     }
 }
@@ -287,6 +318,8 @@ pub enum VehicleType {
     Bus,
     Train,
     Bike,
+    /// Drives like a car, but can pre-empt traffic signals
+    Emergency,
 }
 
 impl fmt::Display for VehicleType {
@@ -296,6 +329,7 @@ impl fmt::Display for VehicleType {
             VehicleType::Bus => write!(f, "bus"),
             VehicleType::Train => write!(f, "train"),
             VehicleType::Bike => write!(f, "bike"),
+            VehicleType::Emergency => write!(f, "emergency vehicle"),
         }
     }
 }
@@ -303,7 +337,7 @@ impl fmt::Display for VehicleType {
 impl VehicleType {
     pub fn to_constraints(self) -> PathConstraints {
         match self {
-            VehicleType::Car => PathConstraints::Car,
+            VehicleType::Car | VehicleType::Emergency => PathConstraints::Car,
             VehicleType::Bus => PathConstraints::Bus,
             VehicleType::Train => PathConstraints::Train,
             VehicleType::Bike => PathConstraints::Bike,
@@ -316,6 +350,23 @@ impl VehicleType {
             VehicleType::Bus => true,
             VehicleType::Train => true,
             VehicleType::Bike => false,
+            VehicleType::Emergency => false,
+        }
+    }
+
+    /// How many passengers a transit vehicle of this type carries, unless its route overrides it.
+    /// The numbers are rough figures for a standard 40' bus and a light rail car.
+    pub fn default_transit_capacity(self) -> Option<TransitCapacity> {
+        match self {
+            VehicleType::Car | VehicleType::Bike | VehicleType::Emergency => None,
+            VehicleType::Bus => Some(TransitCapacity {
+                seated: 40,
+                standing: 30,
+            }),
+            VehicleType::Train => Some(TransitCapacity {
+                seated: 74,
+                standing: 126,
+            }),
         }
     }
 }
@@ -358,6 +409,40 @@ pub enum ParkingSpot {
     Lot(ParkingLotID, usize),
 }
 
+impl ParkingSpot {
+    /// Private spots in buildings don't have any rules.
+    pub fn rules<'a>(&self, map: &'a Map) -> Option<&'a ParkingRules> {
+        match self {
+            ParkingSpot::Onstreet(l, _) => Some(&map.get_parent(*l).parking_rules),
+            ParkingSpot::Offstreet(_, _) => None,
+            ParkingSpot::Lot(pl, _) => Some(&map.get_pl(*pl).parking_rules),
+        }
+    }
+
+    /// Can a driver heading to this building park here? Permit zones only admit drivers going to
+    /// a building along a road in the same zone.
+    pub fn permits(&self, target: BuildingID, map: &Map) -> bool {
+        match self.rules(map).and_then(|r| r.permit_zone.as_ref()) {
+            Some(zone) => {
+                map.get_parent(map.get_b(target).sidewalk())
+                    .parking_rules
+                    .permit_zone
+                    .as_ref()
+                    == Some(zone)
+            }
+            None => true,
+        }
+    }
+
+    /// Drivers trade off the price of a spot against walking distance.
+    pub(crate) fn price_as_distance(&self, map: &Map) -> Distance {
+        match self.rules(map) {
+            Some(rules) => WALK_TO_SAVE_A_DOLLAR * rules.price_per_hour,
+            None => Distance::ZERO,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct ParkedCar {
     pub vehicle: Vehicle,
@@ -610,9 +695,12 @@ pub(crate) struct CreateCar {
     pub vehicle: Vehicle,
     pub router: Router,
     pub maybe_parked_car: Option<ParkedCar>,
-    /// None for buses
+    /// None for buses and ride-hailing vehicles
     pub trip_and_person: Option<(TripID, PersonID)>,
-    pub maybe_route: Option<BusRouteID>,
+    /// For transit vehicles, the route and when they were scheduled to start it
+    pub maybe_route: Option<(BusRouteID, Time)>,
+    /// How many carpool or ride-hailing passengers ride along, not counting the driver
+    pub passengers: usize,
 }
 
 impl CreateCar {
@@ -628,6 +716,7 @@ impl CreateCar {
             maybe_parked_car: None,
             trip_and_person: Some((trip, person)),
             maybe_route: None,
+            passengers: 0,
         }
     }
 
@@ -637,6 +726,7 @@ impl CreateCar {
         router: Router,
         trip: TripID,
         person: PersonID,
+        passengers: usize,
     ) -> CreateCar {
         CreateCar {
             vehicle: parked_car.vehicle.clone(),
@@ -644,6 +734,18 @@ impl CreateCar {
             maybe_parked_car: Some(parked_car),
             trip_and_person: Some((trip, person)),
             maybe_route: None,
+            passengers,
+        }
+    }
+
+    pub fn for_ride_hail(parked_car: ParkedCar, router: Router, passengers: usize) -> CreateCar {
+        CreateCar {
+            vehicle: parked_car.vehicle.clone(),
+            router,
+            maybe_parked_car: Some(parked_car),
+            trip_and_person: None,
+            maybe_route: None,
+            passengers,
         }
     }
 }
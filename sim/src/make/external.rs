@@ -188,7 +188,9 @@ impl MapBorders {
     ) {
         match mode {
             TripMode::Walk | TripMode::Transit => (&self.incoming_walking, &self.outgoing_walking),
-            TripMode::Drive => (&self.incoming_driving, &self.outgoing_driving),
+            TripMode::Drive | TripMode::Carpool | TripMode::RideHail => {
+                (&self.incoming_driving, &self.outgoing_driving)
+            }
             TripMode::Bike => (&self.incoming_biking, &self.outgoing_biking),
         }
     }
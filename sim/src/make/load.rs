@@ -5,7 +5,7 @@ use abstio::MapName;
 use abstutil::CmdArgs;
 use map_model::{Map, MapEdits};
 
-use crate::{Scenario, ScenarioModifier, Sim, SimOptions};
+use crate::{AssignmentCosts, Scenario, ScenarioModifier, Sim, SimOptions};
 
 /// SimFlags specifies a simulation to setup.
 #[derive(Clone)]
@@ -18,6 +18,9 @@ pub struct SimFlags {
     pub modifiers: Vec<ScenarioModifier>,
     pub rng_seed: u64,
     pub opts: SimOptions,
+    /// When loading a scenario, route cars using the costs previously found by iterative traffic
+    /// assignment.
+    pub use_assignment_costs: bool,
 }
 
 impl SimFlags {
@@ -38,7 +41,11 @@ impl SimFlags {
                 .unwrap_or_else(|| MapName::seattle("montlake").path()),
             modifiers,
             rng_seed,
-            opts: SimOptions::from_args(args, rng_seed),
+            opts: match SimOptions::from_args(args, rng_seed) {
+                Ok(opts) => opts,
+                Err(err) => panic!("{}", err),
+            },
+            use_assignment_costs: args.enabled("--assignment_costs"),
         }
     }
 
@@ -49,6 +56,7 @@ impl SimFlags {
             modifiers: Vec::new(),
             rng_seed: SimFlags::RNG_SEED,
             opts: SimOptions::new(run_name),
+            use_assignment_costs: false,
         }
     }
 
@@ -88,7 +96,14 @@ impl SimFlags {
 
             let mut scenario: Scenario = abstio::must_read_object(self.load.clone(), timer);
 
-            let map = Map::load_synchronously(scenario.map_name.path(), timer);
+            let mut map = Map::load_synchronously(scenario.map_name.path(), timer);
+            if self.use_assignment_costs {
+                let costs: AssignmentCosts = abstio::must_read_object(
+                    abstio::path_assignment_costs(&scenario.map_name, &scenario.scenario_name),
+                    timer,
+                );
+                costs.apply(&mut map, timer);
+            }
 
             for m in &self.modifiers {
                 scenario = m.apply(&map, scenario);
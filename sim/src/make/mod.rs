@@ -7,8 +7,9 @@ use rand_xorshift::XorShiftRng;
 pub use self::external::{ExternalPerson, ExternalTrip, ExternalTripEndpoint, MapBorders};
 pub use self::generator::{BorderSpawnOverTime, ScenarioGenerator, SpawnOverTime};
 pub use self::load::SimFlags;
+pub use self::mode_choice::ModeChoiceModel;
 pub use self::modifier::ScenarioModifier;
-pub use self::scenario::{IndividTrip, PersonSpec, Scenario, TripPurpose};
+pub use self::scenario::{HouseholdSpec, IndividTrip, PersonSpec, Scenario, TripPurpose};
 pub use self::spawner::TripEndpoint;
 pub(crate) use self::spawner::{StartTripArgs, TripSpec};
 
@@ -16,6 +17,7 @@ mod activity_model;
 mod external;
 mod generator;
 mod load;
+mod mode_choice;
 mod modifier;
 mod scenario;
 mod spawner;
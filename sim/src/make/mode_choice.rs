@@ -0,0 +1,474 @@
+//! A simple utility-based mode choice model. Instead of changing some fixed percentage of trips to
+//! another mode, estimate how long and how expensive each person's day would be walking, biking,
+//! taking transit, or driving on the current map, then pick one mode per person with a
+//! multinomial logit. After map edits like new bike lanes, some people will switch on their own.
+//! People may also leave earlier or later to avoid the busiest times for driving.
+
+use std::collections::BTreeMap;
+
+use rand::{Rng, SeedableRng};
+use rand_xorshift::XorShiftRng;
+use serde::{Deserialize, Serialize};
+
+use geom::{Distance, Duration, Speed, Time};
+use map_model::{BuildingID, BusRouteID, BusStopID, LaneType, Map, PathRequest};
+
+use crate::{IndividTrip, Scenario, TripEndpoint, TripMode, TripPurpose};
+
+/// The modes that people can choose between. Carpools and ride-hailing need more coordination
+/// than one person's choice.
+const CHOICES: [TripMode; 4] = [
+    TripMode::Walk,
+    TripMode::Bike,
+    TripMode::Transit,
+    TripMode::Drive,
+];
+
+/// Parameters for choosing a mode and when to leave. The utility of a mode for somebody's whole day
+/// is `constant + time_coefficient * minutes + cost_coefficient * dollars`, minus a penalty for
+/// every trip that ends somewhere without parking nearby, if they drive. Shifting the whole day
+/// earlier or later adds `schedule_delay_coefficient` per minute shifted, but might avoid
+/// congestion.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ModeChoiceModel {
+    /// Captures everything about a mode not otherwise modeled, like comfort or safety
+    pub constants: BTreeMap<TripMode, f64>,
+    /// Per minute spent traveling. Should be negative.
+    pub time_coefficient: f64,
+    /// Per dollar spent. Should be negative.
+    pub cost_coefficient: f64,
+    /// The cost of gas and wear on the car
+    pub driving_cost_per_km: f64,
+    /// The cost of parking at the end of each driving trip
+    pub parking_cost: f64,
+    /// Subtracted from the utility of driving for every trip ending somewhere without parking
+    pub no_parking_penalty: f64,
+    /// The cost of each transit trip
+    pub transit_fare: f64,
+    /// Added to each transit trip, since riders don't show up exactly when the bus does
+    pub transit_wait: Duration,
+    /// Including time spent at stops
+    pub transit_speed: Speed,
+    /// Average speeds, since the people in the scenario don't have their own speeds yet
+    pub walking_speed: Speed,
+    pub biking_speed: Speed,
+    /// Ways to shift all of somebody's trips earlier or later. Only zero disables departure time
+    /// choice.
+    pub departure_shifts: Vec<Duration>,
+    /// Per minute that somebody's day is shifted from what they originally wanted. Should be
+    /// negative.
+    pub schedule_delay_coefficient: f64,
+    /// Driving trips are grouped by departure time into windows this long.
+    pub congestion_window: Duration,
+    /// Driving takes this much longer in the window when the most people start driving, as a
+    /// fraction of the uncongested time. Other windows scale linearly with how many people start
+    /// driving then.
+    pub peak_congestion: f64,
+}
+
+impl std::default::Default for ModeChoiceModel {
+    /// Ballpark coefficients, not calibrated against any real survey.
+    fn default() -> ModeChoiceModel {
+        let mut constants = BTreeMap::new();
+        constants.insert(TripMode::Walk, 0.0);
+        constants.insert(TripMode::Bike, -1.0);
+        constants.insert(TripMode::Transit, -0.5);
+        constants.insert(TripMode::Drive, 0.5);
+        ModeChoiceModel {
+            constants,
+            time_coefficient: -0.05,
+            cost_coefficient: -0.2,
+            driving_cost_per_km: 0.2,
+            parking_cost: 2.0,
+            no_parking_penalty: 1.0,
+            transit_fare: 2.75,
+            transit_wait: Duration::minutes(5),
+            transit_speed: Speed::miles_per_hour(10.0),
+            walking_speed: Speed::miles_per_hour(3.0),
+            biking_speed: Speed::miles_per_hour(10.0),
+            departure_shifts: vec![
+                Duration::minutes(30) * -1.0,
+                Duration::minutes(15) * -1.0,
+                Duration::ZERO,
+                Duration::minutes(15),
+                Duration::minutes(30),
+            ],
+            schedule_delay_coefficient: -0.02,
+            congestion_window: Duration::minutes(15),
+            peak_congestion: 0.5,
+        }
+    }
+}
+
+impl ModeChoiceModel {
+    /// Re-assign modes and departure times for everybody whose day consists only of simple trips
+    /// between buildings. Travel times are lower bounds from pathfinding on the current map, so
+    /// this should happen after any map edits. Driving is slower when more people in the original
+    /// scenario start driving around the same time. Each person picks one mode for all of their
+    /// trips, so nobody leaves their car or bike somewhere and can't get it back, and shifts all of
+    /// their trips by the same amount.
+    pub fn apply(&self, map: &Map, mut s: Scenario) -> Scenario {
+        let demand = DrivingDemand::new(
+            s.people
+                .iter()
+                .flat_map(|person| person.trips.iter())
+                .filter(|trip| trip.mode == TripMode::Drive && !trip.cancelled)
+                .map(|trip| trip.depart),
+            self.congestion_window,
+        );
+
+        let mut is_carpool_driver = vec![false; s.people.len()];
+        for person in &s.people {
+            for trip in &person.trips {
+                if let Some((driver, _)) = trip.carpool_driver {
+                    is_carpool_driver[driver] = true;
+                }
+            }
+        }
+
+        let mut changes: BTreeMap<(TripMode, TripMode), usize> = BTreeMap::new();
+        let mut shifted = 0;
+        for (idx, person) in s.people.iter_mut().enumerate() {
+            if is_carpool_driver[idx]
+                || person.trips.is_empty()
+                || !person.trips.iter().all(can_choose_mode)
+            {
+                continue;
+            }
+            // Nobody can leave before midnight
+            let earliest_shift = Time::START_OF_DAY - person.trips[0].depart;
+            let mut utilities: Vec<((TripMode, Duration), f64)> = Vec::new();
+            for mode in CHOICES.iter() {
+                let costs = match self.trip_costs(map, &person.trips, *mode) {
+                    Some(costs) => costs,
+                    None => {
+                        continue;
+                    }
+                };
+                for shift in &self.departure_shifts {
+                    if *shift >= earliest_shift {
+                        let utility = self.utility(*mode, &person.trips, &costs, *shift, &demand);
+                        utilities.push(((*mode, *shift), utility));
+                    }
+                }
+            }
+            if utilities.is_empty() {
+                continue;
+            }
+            // The same person makes the same choice, as long as the options don't change
+            let mut rng = XorShiftRng::seed_from_u64(idx as u64);
+            let (mode, shift) = logit(&utilities, &mut rng);
+
+            if shift != Duration::ZERO {
+                shifted += 1;
+            }
+            for trip in &mut person.trips {
+                if trip.mode != mode {
+                    *changes.entry((trip.mode, mode)).or_insert(0) += 1;
+                    trip.mode = mode;
+                    trip.modified = true;
+                }
+                if shift != Duration::ZERO {
+                    trip.depart += shift;
+                    trip.modified = true;
+                }
+            }
+        }
+        info!("{} people left earlier or later", shifted);
+        for ((from, to), count) in changes {
+            info!(
+                "{} trips switched from {} to {}",
+                count,
+                from.noun(),
+                to.noun()
+            );
+        }
+        s
+    }
+
+    /// The uncongested time, cost, and penalty of each trip using this mode, or None if some trip
+    /// isn't possible.
+    fn trip_costs(
+        &self,
+        map: &Map,
+        trips: &[IndividTrip],
+        mode: TripMode,
+    ) -> Option<Vec<TripCost>> {
+        let mut costs = Vec::new();
+        for trip in trips {
+            let mut time = Duration::ZERO;
+            let mut dollars = 0.0;
+            let mut penalty = 0.0;
+            match mode {
+                TripMode::Walk | TripMode::Bike | TripMode::Drive => {
+                    let req = TripEndpoint::path_req(trip.origin, trip.destination, mode, map)?;
+                    let path = map.pathfind(req).ok()?;
+                    time += path.estimate_duration(
+                        map,
+                        match mode {
+                            TripMode::Walk => Some(self.walking_speed),
+                            TripMode::Bike => Some(self.biking_speed),
+                            _ => None,
+                        },
+                    );
+                    if mode == TripMode::Drive {
+                        dollars += self.driving_cost_per_km * path.total_length().inner_meters()
+                            / 1000.0
+                            + self.parking_cost;
+                        if let TripEndpoint::Bldg(b) = trip.destination {
+                            if parking_spots_near(map, b) == 0 {
+                                penalty += self.no_parking_penalty;
+                            }
+                        }
+                    }
+                }
+                TripMode::Transit => {
+                    let req =
+                        TripEndpoint::path_req(trip.origin, trip.destination, TripMode::Walk, map)?;
+                    time += self.transit_time(map, req)?;
+                    dollars += self.transit_fare;
+                }
+                TripMode::Carpool | TripMode::RideHail => unreachable!(),
+            }
+            costs.push(TripCost {
+                time,
+                dollars,
+                penalty,
+            });
+        }
+        Some(costs)
+    }
+
+    /// The utility of using this mode for all of the trips, after shifting their departure times.
+    fn utility(
+        &self,
+        mode: TripMode,
+        trips: &[IndividTrip],
+        costs: &[TripCost],
+        shift: Duration,
+        demand: &DrivingDemand,
+    ) -> f64 {
+        let mut time = Duration::ZERO;
+        let mut dollars = 0.0;
+        let mut penalty = 0.0;
+        for (trip, cost) in trips.iter().zip(costs) {
+            time += if mode == TripMode::Drive {
+                cost.time * (1.0 + self.peak_congestion * demand.busyness(trip.depart + shift))
+            } else {
+                cost.time
+            };
+            dollars += cost.dollars;
+            penalty += cost.penalty;
+        }
+        self.constants.get(&mode).cloned().unwrap_or(0.0)
+            + self.time_coefficient * time.inner_seconds() / 60.0
+            + self.cost_coefficient * dollars
+            + self.schedule_delay_coefficient * shift.inner_seconds().abs() / 60.0
+            - penalty
+    }
+
+    /// Walk to a stop, wait, ride, then walk to the destination. None if transit doesn't make
+    /// sense for this trip.
+    fn transit_time(&self, map: &Map, req: PathRequest) -> Option<Duration> {
+        let (stop1, maybe_stop2, route) = map.should_use_transit(req.start, req.end)?;
+        // Riding to the end of a route means leaving the map; there's no sense in choosing that
+        let stop2 = maybe_stop2?;
+        let walk1 = map
+            .pathfind(PathRequest::walking(
+                req.start,
+                map.get_bs(stop1).sidewalk_pos,
+            ))
+            .ok()?;
+        let walk2 = map
+            .pathfind(PathRequest::walking(
+                map.get_bs(stop2).sidewalk_pos,
+                req.end,
+            ))
+            .ok()?;
+        let ride_dist = ride_dist(map, route, stop1, stop2)?;
+        Some(
+            walk1.estimate_duration(map, Some(self.walking_speed))
+                + self.transit_wait
+                + ride_dist / self.transit_speed
+                + walk2.estimate_duration(map, Some(self.walking_speed)),
+        )
+    }
+}
+
+struct TripCost {
+    time: Duration,
+    dollars: f64,
+    penalty: f64,
+}
+
+/// How many people start driving during each window of the day
+struct DrivingDemand {
+    window: Duration,
+    counts: BTreeMap<usize, usize>,
+    max: usize,
+}
+
+impl DrivingDemand {
+    fn new<I: Iterator<Item = Time>>(departures: I, window: Duration) -> DrivingDemand {
+        let mut counts = BTreeMap::new();
+        for t in departures {
+            *counts.entry(DrivingDemand::bucket(t, window)).or_insert(0) += 1;
+        }
+        let max = counts.values().max().cloned().unwrap_or(0);
+        DrivingDemand {
+            window,
+            counts,
+            max,
+        }
+    }
+
+    fn bucket(t: Time, window: Duration) -> usize {
+        ((t - Time::START_OF_DAY) / window) as usize
+    }
+
+    /// From 0 to 1, how busy is the window containing this time, relative to the busiest one?
+    fn busyness(&self, t: Time) -> f64 {
+        if self.max == 0 {
+            return 0.0;
+        }
+        let count = self
+            .counts
+            .get(&DrivingDemand::bucket(t, self.window))
+            .cloned()
+            .unwrap_or(0);
+        (count as f64) / (self.max as f64)
+    }
+}
+
+/// How far a vehicle following the route travels between two of its stops
+fn ride_dist(map: &Map, route: BusRouteID, stop1: BusStopID, stop2: BusStopID) -> Option<Distance> {
+    let route = map.get_br(route);
+    let start = route.stops.iter().position(|s| *s == stop1)?;
+    let end = start + route.stops[start..].iter().position(|s| *s == stop2)?;
+    // The first step goes from the start of the route to the first stop
+    let mut dist = Distance::ZERO;
+    for req in route
+        .all_steps(map)
+        .into_iter()
+        .skip(start + 1)
+        .take(end - start)
+    {
+        dist += map.pathfind(req).ok()?.total_length();
+    }
+    Some(dist)
+}
+
+/// Only simple trips between buildings can switch modes. Trips to and from borders represent
+/// somebody traveling a long way from off the map.
+fn can_choose_mode(trip: &IndividTrip) -> bool {
+    !trip.cancelled
+        && trip.carpool_driver.is_none()
+        && CHOICES.contains(&trip.mode)
+        && !matches!(
+            trip.purpose,
+            TripPurpose::EmergencyResponse | TripPurpose::ParkAndRideTransfer
+        )
+        && matches!(
+            (trip.origin, trip.destination),
+            (TripEndpoint::Bldg(_), TripEndpoint::Bldg(_))
+        )
+}
+
+/// Off-street spots at the building, plus on-street spots along its road
+fn parking_spots_near(map: &Map, b: BuildingID) -> usize {
+    let bldg = map.get_b(b);
+    let mut total = bldg.num_parking_spots();
+    for (l, _, lt) in map.get_parent(bldg.sidewalk()).lanes_ltr() {
+        if lt == LaneType::Parking {
+            total += map.get_l(l).number_parking_spots(map.get_config());
+        }
+    }
+    total
+}
+
+/// Randomly pick an option, with probability proportional to `e^utility`.
+fn logit<T: Copy>(utilities: &[(T, f64)], rng: &mut XorShiftRng) -> T {
+    // Subtract the max before exponentiating to avoid overflow
+    let max = utilities
+        .iter()
+        .map(|(_, u)| *u)
+        .fold(std::f64::NEG_INFINITY, f64::max);
+    let weights: Vec<f64> = utilities.iter().map(|(_, u)| (u - max).exp()).collect();
+    let mut choice = rng.gen_range(0.0..weights.iter().sum::<f64>());
+    for ((mode, _), weight) in utilities.iter().zip(weights) {
+        if choice < weight {
+            return *mode;
+        }
+        choice -= weight;
+    }
+    utilities.last().unwrap().0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hours(h: f64) -> Time {
+        Time::START_OF_DAY + Duration::seconds(h * 3600.0)
+    }
+
+    #[test]
+    fn test_busyness() {
+        let demand = DrivingDemand::new(
+            vec![hours(8.0), hours(8.1), hours(8.2), hours(17.0)].into_iter(),
+            Duration::minutes(15),
+        );
+        assert_eq!(demand.busyness(hours(8.0)), 1.0);
+        assert!((demand.busyness(hours(17.1)) - 1.0 / 3.0).abs() < 1e-6);
+        assert_eq!(demand.busyness(hours(12.0)), 0.0);
+
+        let empty = DrivingDemand::new(Vec::new().into_iter(), Duration::minutes(15));
+        assert_eq!(empty.busyness(hours(8.0)), 0.0);
+    }
+
+    #[test]
+    fn test_leave_early_to_avoid_congestion() {
+        let mut model = ModeChoiceModel::default();
+        model.peak_congestion = 1.0;
+        let demand = DrivingDemand::new(
+            std::iter::repeat(hours(8.0)).take(100),
+            Duration::minutes(15),
+        );
+        let trips = vec![IndividTrip::new(
+            hours(8.0),
+            TripPurpose::Work,
+            TripEndpoint::Bldg(BuildingID(0)),
+            TripEndpoint::Bldg(BuildingID(1)),
+            TripMode::Drive,
+        )];
+        let costs = vec![TripCost {
+            time: Duration::minutes(30),
+            dollars: 0.0,
+            penalty: 0.0,
+        }];
+        let drive =
+            |shift: Duration| model.utility(TripMode::Drive, &trips, &costs, shift, &demand);
+        let walk = |shift: Duration| model.utility(TripMode::Walk, &trips, &costs, shift, &demand);
+
+        // Leaving 15 minutes early doubles as a way to skip the traffic
+        assert!(drive(Duration::minutes(15) * -1.0) > drive(Duration::ZERO));
+        // Walkers aren't affected by congestion, so shifting only hurts
+        assert!(walk(Duration::ZERO) > walk(Duration::minutes(15) * -1.0));
+        assert!(walk(Duration::ZERO) > walk(Duration::minutes(15)));
+    }
+
+    #[test]
+    fn test_logit() {
+        let mut rng = XorShiftRng::seed_from_u64(42);
+        // An overwhelmingly better option always wins
+        for _ in 0..100 {
+            assert_eq!(logit(&[("bad", -50.0), ("good", 50.0)], &mut rng), "good");
+        }
+        // Equally good options are each picked sometimes
+        let picks: Vec<&str> = (0..100)
+            .map(|_| logit(&[("a", 1.0), ("b", 1.0)], &mut rng))
+            .collect();
+        assert!(picks.contains(&"a"));
+        assert!(picks.contains(&"b"));
+    }
+}
@@ -1,12 +1,18 @@
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 
 use serde::{Deserialize, Serialize};
 
 use abstutil::Timer;
-use geom::{Duration, Time};
-use map_model::Map;
+use geom::{Distance, Duration, Pt2D, Time};
+use map_model::{BuildingID, Map};
 
-use crate::{Scenario, TripMode};
+use crate::{ModeChoiceModel, Scenario, TripEndpoint, TripMode};
+
+/// Two trips can share a car if they start and end within this distance of each other...
+const CARPOOL_MAX_DIST: Distance = Distance::const_meters(400.0);
+/// ... and leave around the same time.
+const CARPOOL_MAX_DEPARTURE_DIFF: Duration = Duration::const_seconds(15.0 * 60.0);
+const CARPOOL_MAX_PASSENGERS: usize = 3;
 
 /// Transforms an existing Scenario before instantiating it.
 #[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize)]
@@ -21,6 +27,14 @@ pub enum ScenarioModifier {
     },
     /// Scenario name
     AddExtraTrips(String),
+    /// Some percent of people driving by themselves instead ride along with another driver
+    /// leaving from and going to somewhere nearby, around the same time.
+    FormCarpools {
+        pct_ppl: usize,
+    },
+    /// Re-assign modes and departure times using the default `ModeChoiceModel`, based on travel
+    /// times with the current map edits.
+    ChooseModes,
 }
 
 impl ScenarioModifier {
@@ -59,6 +73,7 @@ impl ScenarioModifier {
                         if let Some(to_mode) = *to_mode {
                             trip.mode = to_mode;
                             trip.modified = true;
+                            trip.carpool_driver = None;
                         } else {
                             trip.modified = true;
                             trip.cancelled = true;
@@ -76,14 +91,26 @@ impl ScenarioModifier {
                     abstio::path_scenario(map.get_name(), name),
                     &mut Timer::throwaway(),
                 );
+                let offset = s.people.len();
                 for mut p in other.people {
                     for trip in &mut p.trips {
                         trip.modified = true;
+                        trip.carpool_driver = trip
+                            .carpool_driver
+                            .map(|(person, trip)| (person + offset, trip));
                     }
                     s.people.push(p);
                 }
+                for mut household in other.households {
+                    for idx in &mut household.members {
+                        *idx += offset;
+                    }
+                    s.households.push(household);
+                }
                 s
             }
+            ScenarioModifier::FormCarpools { pct_ppl } => form_carpools(map, s, *pct_ppl),
+            ScenarioModifier::ChooseModes => ModeChoiceModel::default().apply(map, s),
         }
     }
 
@@ -105,6 +132,13 @@ impl ScenarioModifier {
                 to_mode.map(|m| m.verb())
             ),
             ScenarioModifier::AddExtraTrips(name) => format!("Add extra trips from {}", name),
+            ScenarioModifier::FormCarpools { pct_ppl } => format!(
+                "{}% of people driving alone carpool with somebody else",
+                pct_ppl
+            ),
+            ScenarioModifier::ChooseModes => {
+                "choose modes and departure times based on travel time and cost".to_string()
+            }
         }
     }
 }
@@ -119,14 +153,19 @@ impl ScenarioModifier {
 // needing LOTS of cars.
 fn repeat_days(mut s: Scenario, days: usize) -> Scenario {
     s.scenario_name = format!("{} (repeated {} days)", s.scenario_name, days);
+    // Carpool passengers ride with the driver's copy of the trip on the same day
+    let trips_per_person: Vec<usize> = s.people.iter().map(|p| p.trips.len()).collect();
     for person in &mut s.people {
         let mut trips = Vec::new();
         let mut offset = Duration::ZERO;
-        for _ in 0..days {
+        for day in 0..days {
             for trip in &person.trips {
                 let mut new = trip.clone();
                 new.depart += offset;
                 new.modified = true;
+                new.carpool_driver = trip
+                    .carpool_driver
+                    .map(|(p, t)| (p, t + day * trips_per_person[p]));
                 trips.push(new);
             }
             offset += Duration::hours(24);
@@ -135,3 +174,288 @@ fn repeat_days(mut s: Scenario, days: usize) -> Scenario {
     }
     s
 }
+
+fn form_carpools(map: &Map, s: Scenario, pct_ppl: usize) -> Scenario {
+    form_carpools_near(s, pct_ppl, |b| map.get_b(b).polygon.center())
+}
+
+type Cell = (isize, isize);
+
+/// `pos` says where a building is.
+fn form_carpools_near<F: Fn(BuildingID) -> Pt2D>(
+    mut s: Scenario,
+    pct_ppl: usize,
+    pos: F,
+) -> Scenario {
+    // Bucket potential drivers by roughly where they start and end, so passengers only have to
+    // check nearby buckets.
+    let mut drivers: BTreeMap<(Cell, Cell), Vec<(usize, usize)>> = BTreeMap::new();
+    // A passenger's own car stays parked wherever it was, so they can't drive any part of the tour
+    // until they're back where they started. Either every driving trip of a tour becomes a
+    // carpool, or none of them do.
+    let mut tours: Vec<Vec<(usize, usize, BuildingID, BuildingID)>> = Vec::new();
+    // Drivers may already have some passengers
+    let mut seats_taken: BTreeMap<(usize, usize), usize> = BTreeMap::new();
+    for (person_idx, person) in s.people.iter().enumerate() {
+        // Like ChangeMode, this is stable as the percentage increases.
+        let passenger = person_idx % 100 < pct_ppl;
+        let mut tour = Vec::new();
+        let mut tour_start = None;
+        let mut can_carpool = true;
+        for (trip_idx, trip) in person.trips.iter().enumerate() {
+            if let Some(driver) = trip.carpool_driver {
+                *seats_taken.entry(driver).or_insert(0) += 1;
+            }
+            if trip.cancelled {
+                continue;
+            }
+            if tour_start.is_none() {
+                tour_start = Some(trip.origin);
+            }
+            if trip.mode == TripMode::Drive {
+                match (trip.origin, trip.destination) {
+                    (TripEndpoint::Bldg(from), TripEndpoint::Bldg(to)) => {
+                        if passenger {
+                            tour.push((person_idx, trip_idx, from, to));
+                        } else {
+                            drivers
+                                .entry((cell(pos(from)), cell(pos(to))))
+                                .or_insert_with(Vec::new)
+                                .push((person_idx, trip_idx));
+                        }
+                    }
+                    _ => {
+                        can_carpool = false;
+                    }
+                }
+            }
+            if tour_start == Some(trip.destination) {
+                let finished = std::mem::take(&mut tour);
+                if can_carpool && !finished.is_empty() {
+                    tours.push(finished);
+                }
+                tour_start = None;
+                can_carpool = true;
+            }
+        }
+        // The last tour might not return
+        if can_carpool && !tour.is_empty() {
+            tours.push(tour);
+        }
+    }
+
+    let mut num_carpools = 0;
+    for tour in tours {
+        let mut matches = Vec::new();
+        for (person_idx, trip_idx, from, to) in &tour {
+            let depart = s.people[*person_idx].trips[*trip_idx].depart;
+            match find_driver(&s, &drivers, &seats_taken, &pos, depart, *from, *to) {
+                Some(driver) => {
+                    *seats_taken.entry(driver).or_insert(0) += 1;
+                    matches.push(driver);
+                }
+                None => break,
+            }
+        }
+        if matches.len() < tour.len() {
+            for driver in matches {
+                *seats_taken.get_mut(&driver).unwrap() -= 1;
+            }
+            continue;
+        }
+
+        for ((person_idx, trip_idx, _, _), driver) in tour.into_iter().zip(matches) {
+            s.people[driver.0].trips[driver.1].modified = true;
+            let trip = &mut s.people[person_idx].trips[trip_idx];
+            trip.mode = TripMode::Carpool;
+            trip.modified = true;
+            trip.carpool_driver = Some(driver);
+            num_carpools += 1;
+        }
+    }
+    info!("{} solo driving trips are now carpools", num_carpools);
+    s
+}
+
+/// Finds a driver with a free seat, going from and to somewhere nearby. The closest departure
+/// time wins.
+fn find_driver<F: Fn(BuildingID) -> Pt2D>(
+    s: &Scenario,
+    drivers: &BTreeMap<(Cell, Cell), Vec<(usize, usize)>>,
+    seats_taken: &BTreeMap<(usize, usize), usize>,
+    pos: &F,
+    depart: Time,
+    from: BuildingID,
+    to: BuildingID,
+) -> Option<(usize, usize)> {
+    let (from_x, from_y) = cell(pos(from));
+    let (to_x, to_y) = cell(pos(to));
+    let mut best: Option<(Duration, (usize, usize))> = None;
+    for (dx1, dy1, dx2, dy2) in neighbors() {
+        let key = ((from_x + dx1, from_y + dy1), (to_x + dx2, to_y + dy2));
+        for driver in drivers.get(&key).into_iter().flatten() {
+            if seats_taken.get(driver).cloned().unwrap_or(0) >= CARPOOL_MAX_PASSENGERS {
+                continue;
+            }
+            let trip = &s.people[driver.0].trips[driver.1];
+            let diff = (trip.depart - depart).abs();
+            if diff > CARPOOL_MAX_DEPARTURE_DIFF
+                || best
+                    .map(|(best_diff, _)| diff >= best_diff)
+                    .unwrap_or(false)
+            {
+                continue;
+            }
+            if let (TripEndpoint::Bldg(driver_from), TripEndpoint::Bldg(driver_to)) =
+                (trip.origin, trip.destination)
+            {
+                if close_enough(pos(from), pos(driver_from))
+                    && close_enough(pos(to), pos(driver_to))
+                {
+                    best = Some((diff, *driver));
+                }
+            }
+        }
+    }
+    best.map(|(_, driver)| driver)
+}
+
+fn cell(pt: Pt2D) -> Cell {
+    (
+        (pt.x() / CARPOOL_MAX_DIST.inner_meters()).floor() as isize,
+        (pt.y() / CARPOOL_MAX_DIST.inner_meters()).floor() as isize,
+    )
+}
+
+fn neighbors() -> Vec<(isize, isize, isize, isize)> {
+    let mut result = Vec::new();
+    for dx1 in -1..=1 {
+        for dy1 in -1..=1 {
+            for dx2 in -1..=1 {
+                for dy2 in -1..=1 {
+                    result.push((dx1, dy1, dx2, dy2));
+                }
+            }
+        }
+    }
+    result
+}
+
+fn close_enough(pt1: Pt2D, pt2: Pt2D) -> bool {
+    pt1.dist_to(pt2) <= CARPOOL_MAX_DIST
+}
+
+#[cfg(test)]
+mod tests {
+    use abstio::MapName;
+
+    use super::*;
+    use crate::{IndividTrip, PersonSpec, TripPurpose};
+
+    // Two clusters of buildings, far apart
+    fn pos(b: BuildingID) -> Pt2D {
+        if b.0 < 10 {
+            Pt2D::new(10.0 * (b.0 as f64), 0.0)
+        } else {
+            Pt2D::new(5000.0 + 10.0 * (b.0 as f64), 0.0)
+        }
+    }
+
+    fn drive(depart: Time, from: usize, to: usize) -> IndividTrip {
+        IndividTrip::new(
+            depart,
+            TripPurpose::Work,
+            TripEndpoint::Bldg(BuildingID(from)),
+            TripEndpoint::Bldg(BuildingID(to)),
+            TripMode::Drive,
+        )
+    }
+
+    fn scenario(people: Vec<Vec<IndividTrip>>) -> Scenario {
+        Scenario {
+            scenario_name: "carpools".to_string(),
+            map_name: MapName::seattle("montlake"),
+            people: people
+                .into_iter()
+                .map(|trips| PersonSpec {
+                    orig_id: None,
+                    trips,
+                })
+                .collect(),
+            households: Vec::new(),
+            only_seed_buses: None,
+        }
+    }
+
+    fn hours(h: usize) -> Time {
+        Time::START_OF_DAY + Duration::hours(h)
+    }
+
+    #[test]
+    fn test_carpool_whole_tour() {
+        // Person 0 is the passenger, and person 1 drives both ways
+        let s = form_carpools_near(
+            scenario(vec![
+                vec![drive(hours(8), 1, 11), drive(hours(17), 11, 1)],
+                vec![
+                    drive(hours(8) + Duration::minutes(5), 2, 12),
+                    drive(hours(17), 12, 2),
+                ],
+            ]),
+            1,
+            pos,
+        );
+        let trips = &s.people[0].trips;
+        assert_eq!(trips[0].mode, TripMode::Carpool);
+        assert_eq!(trips[0].carpool_driver, Some((1, 0)));
+        assert_eq!(trips[1].mode, TripMode::Carpool);
+        assert_eq!(trips[1].carpool_driver, Some((1, 1)));
+        assert!(s.people[1].trips.iter().all(|t| t.mode == TripMode::Drive));
+    }
+
+    #[test]
+    fn test_no_carpool_without_a_ride_home() {
+        // The driver only goes one way, so the passenger would be stuck at work without a car
+        let s = form_carpools_near(
+            scenario(vec![
+                vec![drive(hours(8), 1, 11), drive(hours(17), 11, 1)],
+                vec![drive(hours(8), 2, 12)],
+            ]),
+            1,
+            pos,
+        );
+        assert!(s.people[0]
+            .trips
+            .iter()
+            .all(|t| t.mode == TripMode::Drive && t.carpool_driver.is_none()));
+    }
+
+    #[test]
+    fn test_tours_carpool_separately() {
+        // The morning tour returns home before the evening one starts. Only the morning one has a
+        // matching driver, and the passenger's car is still at home for the evening.
+        let s = form_carpools_near(
+            scenario(vec![
+                vec![
+                    drive(hours(8), 1, 11),
+                    drive(hours(12), 11, 1),
+                    drive(hours(18), 1, 13),
+                    drive(hours(22), 13, 1),
+                ],
+                vec![drive(hours(8), 2, 12), drive(hours(12), 12, 2)],
+            ]),
+            1,
+            pos,
+        );
+        let modes: Vec<TripMode> = s.people[0].trips.iter().map(|t| t.mode).collect();
+        assert_eq!(
+            modes,
+            vec![
+                TripMode::Carpool,
+                TripMode::Carpool,
+                TripMode::Drive,
+                TripMode::Drive
+            ]
+        );
+    }
+}
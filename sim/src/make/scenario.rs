@@ -25,6 +25,8 @@ pub struct Scenario {
     pub map_name: MapName,
 
     pub people: Vec<PersonSpec>,
+    /// Groups of people sharing a home and some cars
+    pub households: Vec<HouseholdSpec>,
     /// None means seed all buses. Otherwise the route name must be present here.
     pub only_seed_buses: Option<BTreeSet<String>>,
 }
@@ -39,6 +41,17 @@ pub struct PersonSpec {
     pub trips: Vec<IndividTrip>,
 }
 
+/// Several people living together, sharing a fixed number of cars. The cars start parked near
+/// home. When a member drives from home, they take whichever car is still parked there; if there
+/// isn't one, they use transit instead.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct HouseholdSpec {
+    pub home: BuildingID,
+    /// Indices into the scenario's people. Everybody should start their day at home.
+    pub members: Vec<usize>,
+    pub num_vehicles: usize,
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct IndividTrip {
     pub depart: Time,
@@ -49,6 +62,10 @@ pub struct IndividTrip {
     pub cancelled: bool,
     /// Did a ScenarioModifier affect this?
     pub modified: bool,
+    /// For carpool passengers, the trip they ride along with, as an index into the scenario's
+    /// people, then into that person's trips. The driver's trip must be a driving trip starting
+    /// at a building.
+    pub carpool_driver: Option<(usize, usize)>,
 }
 
 impl IndividTrip {
@@ -67,6 +84,7 @@ impl IndividTrip {
             purpose,
             cancelled: false,
             modified: false,
+            carpool_driver: None,
         }
     }
 }
@@ -85,6 +103,8 @@ pub enum TripPurpose {
     Recreation,
     Medical,
     ParkAndRideTransfer,
+    /// Driving trips with this purpose use an emergency vehicle instead of a personal car.
+    EmergencyResponse,
 }
 
 impl fmt::Display for TripPurpose {
@@ -105,6 +125,7 @@ impl fmt::Display for TripPurpose {
                 TripPurpose::Recreation => "recreation",
                 TripPurpose::Medical => "medical",
                 TripPurpose::ParkAndRideTransfer => "park-and-ride transfer",
+                TripPurpose::EmergencyResponse => "emergency response",
             }
         )
     }
@@ -146,16 +167,22 @@ impl Scenario {
         timer.start_iter("trips for People", self.people.len());
         let mut parked_cars: Vec<(Vehicle, BuildingID)> = Vec::new();
         let mut schedule_trips = Vec::new();
-        for p in &self.people {
+        // Where each person's trips start in schedule_trips
+        let mut first_trip_per_person = Vec::new();
+        let household_homes = self.household_homes();
+        let mut person_ids = Vec::new();
+        for (p, household_home) in self.people.iter().zip(household_homes) {
             timer.next();
+            first_trip_per_person.push(schedule_trips.len());
 
             if let Err(err) = p.check_schedule() {
                 panic!("{}", err);
             }
 
             let (vehicle_specs, cars_initially_parked_at, vehicle_foreach_trip) =
-                p.get_vehicles(rng);
+                p.get_vehicles(household_home, rng);
             let person = sim.new_person(p.orig_id, Scenario::rand_ped_speed(rng), vehicle_specs);
+            person_ids.push(person.id);
             for (idx, b) in cars_initially_parked_at {
                 parked_cars.push((person.vehicles[idx].clone(), b));
             }
@@ -183,11 +210,58 @@ impl Scenario {
             }
         }
 
+        for household in &self.households {
+            if household.members.is_empty() {
+                continue;
+            }
+            let vehicle_specs = (0..household.num_vehicles)
+                .map(|_| Scenario::rand_car(rng))
+                .collect();
+            for vehicle in sim.new_household(
+                household.home,
+                household
+                    .members
+                    .iter()
+                    .map(|idx| person_ids[*idx])
+                    .collect(),
+                vehicle_specs,
+            ) {
+                parked_cars.push((vehicle, household.home));
+            }
+        }
+
+        // (driver, passenger), both indices into schedule_trips
+        let mut carpools = Vec::new();
+        for (person_idx, p) in self.people.iter().enumerate() {
+            for (trip_idx, trip) in p.trips.iter().enumerate() {
+                if let Some((driver_person, driver_trip)) = trip.carpool_driver {
+                    let driver_mode = self
+                        .people
+                        .get(driver_person)
+                        .and_then(|driver| driver.trips.get(driver_trip))
+                        .map(|driver| driver.mode);
+                    if trip.mode == TripMode::Carpool && driver_mode == Some(TripMode::Drive) {
+                        carpools.push((
+                            first_trip_per_person[driver_person] + driver_trip,
+                            first_trip_per_person[person_idx] + trip_idx,
+                        ));
+                    } else {
+                        // The passenger's trip will be cancelled when it starts
+                        warn!(
+                            "Person {} trip {} can't ride along with person {} trip {}",
+                            person_idx, trip_idx, driver_person, driver_trip
+                        );
+                    }
+                }
+            }
+        }
+
         // parked_cars is stable over map edits, so don't fork.
         parked_cars.shuffle(rng);
         seed_parked_cars(parked_cars, sim, map, rng, timer);
+        seed_ride_hail_fleet(sim, map, rng);
 
-        sim.spawn_trips(schedule_trips, map, timer);
+        sim.spawn_trips(schedule_trips, carpools, map, timer);
         timer.stop(format!("Instantiating {}", self.scenario_name));
     }
 
@@ -203,6 +277,7 @@ impl Scenario {
             scenario_name: name.to_string(),
             map_name: map.get_name().clone(),
             people: Vec::new(),
+            households: Vec::new(),
             only_seed_buses: Some(BTreeSet::new()),
         }
     }
@@ -216,6 +291,16 @@ impl Scenario {
         }
     }
 
+    fn rand_emergency_vehicle(rng: &mut XorShiftRng) -> VehicleSpec {
+        // Somewhere between a police car and an ambulance
+        let length = Scenario::rand_dist(rng, MAX_CAR_LENGTH, Distance::meters(7.0));
+        VehicleSpec {
+            vehicle_type: VehicleType::Emergency,
+            length,
+            max_speed: None,
+        }
+    }
+
     fn rand_bike(rng: &mut XorShiftRng) -> VehicleSpec {
         let max_speed = Some(Scenario::rand_speed(
             rng,
@@ -253,24 +338,63 @@ impl Scenario {
         let mut per_bldg = Counter::new();
         // Pass in a dummy RNG
         let mut rng = XorShiftRng::seed_from_u64(0);
-        for p in &self.people {
-            let (_, cars_initially_parked_at, _) = p.get_vehicles(&mut rng);
+        for (p, household_home) in self.people.iter().zip(self.household_homes()) {
+            let (_, cars_initially_parked_at, _) = p.get_vehicles(household_home, &mut rng);
             for (_, b) in cars_initially_parked_at {
                 per_bldg.inc(b);
             }
         }
+        for household in &self.households {
+            per_bldg.add(household.home, household.num_vehicles);
+        }
         per_bldg
     }
 
+    /// For each person, the home of their household, if they belong to one
+    fn household_homes(&self) -> Vec<Option<BuildingID>> {
+        let mut homes = vec![None; self.people.len()];
+        for household in &self.households {
+            for idx in &household.members {
+                homes[*idx] = Some(household.home);
+            }
+        }
+        homes
+    }
+
     pub fn remove_weird_schedules(mut self) -> Scenario {
         let orig = self.people.len();
+        // Households and carpools refer to people by index, so track where everybody winds up
+        let mut new_idx: Vec<Option<usize>> = Vec::new();
+        let mut kept = 0;
         self.people.retain(|person| match person.check_schedule() {
-            Ok(()) => true,
+            Ok(()) => {
+                new_idx.push(Some(kept));
+                kept += 1;
+                true
+            }
             Err(err) => {
                 println!("{}", err);
+                new_idx.push(None);
                 false
             }
         });
+        for household in &mut self.households {
+            household.members = household
+                .members
+                .iter()
+                .filter_map(|idx| new_idx[*idx])
+                .collect();
+        }
+        self.households
+            .retain(|household| !household.members.is_empty());
+        for person in &mut self.people {
+            for trip in &mut person.trips {
+                // If the driver was removed, the passenger's trip will be cancelled
+                trip.carpool_driver = trip
+                    .carpool_driver
+                    .and_then(|(person, trip)| new_idx[person].map(|idx| (idx, trip)));
+            }
+        }
         warn!(
             "{} of {} people have nonsense schedules",
             prettyprint_usize(orig - self.people.len()),
@@ -357,6 +481,46 @@ fn seed_parked_cars(
     }
 }
 
+/// Scatter the ride-hailing fleet around the map, in spots that anybody can use.
+fn seed_ride_hail_fleet(sim: &mut Sim, map: &Map, base_rng: &mut XorShiftRng) {
+    let size = sim.ride_hail_fleet_size();
+    if size == 0 {
+        return;
+    }
+    // Do this after seeding parked cars, so it doesn't affect them
+    let mut rng = fork_rng(base_rng);
+
+    let mut spots: Vec<ParkingSpot> = if sim.infinite_parking() {
+        map.all_buildings()
+            .iter()
+            .filter_map(|b| sim.get_free_offstreet_spots(b.id).pop())
+            .collect()
+    } else {
+        sim.get_all_parking_spots()
+            .1
+            .into_iter()
+            .filter(|spot| match spot {
+                ParkingSpot::Offstreet(b, _) => {
+                    matches!(map.get_b(*b).parking, OffstreetParking::PublicGarage(_, _))
+                }
+                ParkingSpot::Onstreet(_, _) | ParkingSpot::Lot(_, _) => true,
+            })
+            .collect()
+    };
+    if spots.len() < size {
+        warn!(
+            "Only room for {} of {} ride-hailing vehicles",
+            prettyprint_usize(spots.len()),
+            prettyprint_usize(size)
+        );
+    }
+    spots.shuffle(&mut rng);
+    for spot in spots.into_iter().take(size) {
+        let spec = Scenario::rand_car(&mut rng);
+        sim.seed_ride_hail_vehicle(spec, spot);
+    }
+}
+
 // Pick a parking spot for this building. If the building's road has a free spot, use it. If not,
 // start BFSing out from the road in a deterministic way until finding a nearby road with an open
 // spot.
@@ -443,8 +607,11 @@ impl PersonSpec {
         Ok(())
     }
 
+    /// Members of a household don't get their own car for driving from home; the simulation picks
+    /// one of the household's cars when the trip starts.
     fn get_vehicles(
         &self,
+        household_home: Option<BuildingID>,
         rng: &mut XorShiftRng,
     ) -> (
         Vec<VehicleSpec>,
@@ -458,11 +625,13 @@ impl PersonSpec {
         let mut bike_idx = None;
         // For each indexed car, is it parked somewhere, or off-map?
         let mut car_locations: Vec<(usize, Option<BuildingID>)> = Vec::new();
+        // If this person is using a household car, where is it parked?
+        let mut household_car_at: Option<BuildingID> = None;
 
         // TODO If the trip is cancelled, this should be affected...
         for trip in &self.trips {
             let use_for_trip = match trip.mode {
-                TripMode::Walk | TripMode::Transit => None,
+                TripMode::Walk | TripMode::Transit | TripMode::Carpool | TripMode::RideHail => None,
                 TripMode::Bike => {
                     if bike_idx.is_none() {
                         bike_idx = Some(vehicle_specs.len());
@@ -475,18 +644,47 @@ impl PersonSpec {
                         TripEndpoint::Bldg(b) => Some(b),
                         _ => None,
                     };
+                    let vehicle_type = if matches!(trip.purpose, TripPurpose::EmergencyResponse) {
+                        VehicleType::Emergency
+                    } else {
+                        VehicleType::Car
+                    };
 
                     // Any available cars in the right spot?
-                    let idx = if let Some(idx) = car_locations
+                    let available = car_locations
                         .iter()
-                        .find(|(_, parked_at)| *parked_at == need_parked_at)
-                        .map(|(idx, _)| *idx)
-                    {
+                        .find(|(idx, parked_at)| {
+                            *parked_at == need_parked_at
+                                && vehicle_specs[*idx].vehicle_type == vehicle_type
+                        })
+                        .map(|(idx, _)| *idx);
+                    let use_household_car = available.is_none()
+                        && vehicle_type == VehicleType::Car
+                        && need_parked_at.is_some()
+                        && match household_car_at {
+                            Some(b) => need_parked_at == Some(b),
+                            None => need_parked_at == household_home,
+                        };
+                    if use_household_car {
+                        household_car_at = match trip.destination {
+                            TripEndpoint::Bldg(b) if Some(b) != household_home => Some(b),
+                            // Back home or off-map, so this person is done with the car
+                            _ => None,
+                        };
+                        vehicle_foreach_trip.push(None);
+                        continue;
+                    }
+
+                    let idx = if let Some(idx) = available {
                         idx
                     } else {
                         // Need a new car, starting in the right spot
                         let idx = vehicle_specs.len();
-                        vehicle_specs.push(Scenario::rand_car(rng));
+                        vehicle_specs.push(if vehicle_type == VehicleType::Emergency {
+                            Scenario::rand_emergency_vehicle(rng)
+                        } else {
+                            Scenario::rand_car(rng)
+                        });
                         if let Some(b) = need_parked_at {
                             cars_initially_parked_at.push((idx, b));
                         }
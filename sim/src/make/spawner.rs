@@ -9,7 +9,10 @@ use map_model::{
     BuildingID, BusRouteID, BusStopID, IntersectionID, Map, PathConstraints, PathRequest, Position,
 };
 
-use crate::{CarID, DrivingGoal, SidewalkSpot, TripLeg, TripMode, VehicleType, SPAWN_DIST};
+use crate::{
+    CarID, DrivingGoal, SidewalkPOI, SidewalkSpot, TripID, TripLeg, TripMode, VehicleType,
+    SPAWN_DIST,
+};
 
 /// We need to remember a few things from scenario instantiation that're used for starting the
 /// trip.
@@ -57,6 +60,15 @@ pub(crate) enum TripSpec {
         stop1: BusStopID,
         maybe_stop2: Option<BusStopID>,
     },
+    JoiningCarpool {
+        start: SidewalkSpot,
+        /// Where the driver's trip starts
+        pickup: BuildingID,
+        driver: TripID,
+        goal: SidewalkSpot,
+    },
+    /// Wait at the building for a ride-hailing vehicle
+    UsingRideHail { start: BuildingID, goal: BuildingID },
 }
 
 impl TripSpec {
@@ -203,6 +215,22 @@ impl TripSpec {
                     legs = vec![TripLeg::Walk(walk_to), TripLeg::RideBus(*route, None)];
                 }
             }
+            TripSpec::JoiningCarpool {
+                start,
+                pickup,
+                driver,
+                goal,
+            } => {
+                if start.connection != SidewalkPOI::Building(*pickup) {
+                    legs.push(TripLeg::Walk(SidewalkSpot::building(*pickup, map)));
+                }
+                legs.push(TripLeg::RideCar(*driver));
+                legs.push(TripLeg::Walk(goal.clone()));
+            }
+            TripSpec::UsingRideHail { goal, .. } => {
+                legs.push(TripLeg::RideHail(*goal));
+                legs.push(TripLeg::Walk(SidewalkSpot::building(*goal, map)));
+            }
         };
 
         (self, legs)
@@ -290,6 +318,46 @@ impl TripSpec {
                     TripSpec::JustWalking { start, goal }
                 }
             }
+            TripMode::Carpool => bail!("carpool passengers need to know who's driving"),
+            TripMode::RideHail => match (from, to) {
+                (TripEndpoint::Bldg(start), TripEndpoint::Bldg(goal)) => {
+                    TripSpec::UsingRideHail { start, goal }
+                }
+                _ => bail!(
+                    "ride-hailing trips have to start and end at buildings, not {:?} and {:?}",
+                    from,
+                    to
+                ),
+            },
+        })
+    }
+
+    /// Ride along with somebody else's trip, meeting them where they start.
+    pub fn joining_carpool(
+        from: TripEndpoint,
+        to: TripEndpoint,
+        driver: TripID,
+        driver_start: TripEndpoint,
+        map: &Map,
+    ) -> Result<TripSpec> {
+        if !matches!(from, TripEndpoint::Bldg(_)) {
+            bail!(
+                "carpool passengers have to start at a building, not {:?}",
+                from
+            );
+        }
+        let pickup = match driver_start {
+            TripEndpoint::Bldg(b) => b,
+            _ => bail!(
+                "carpool drivers have to start at a building, not {:?}",
+                driver_start
+            ),
+        };
+        Ok(TripSpec::JoiningCarpool {
+            start: from.start_sidewalk_spot(map)?,
+            pickup,
+            driver,
+            goal: to.end_sidewalk_spot(map)?,
         })
     }
 }
@@ -320,7 +388,7 @@ impl TripEndpoint {
             TripMode::Walk | TripMode::Transit => PathRequest::walking(start, end),
             TripMode::Bike => PathRequest::vehicle(start, end, PathConstraints::Bike),
             // Only cars leaving from a building might turn out from the driveway in a special way
-            TripMode::Drive => {
+            TripMode::Drive | TripMode::Carpool | TripMode::RideHail => {
                 if matches!(from, TripEndpoint::Bldg(_)) {
                     PathRequest::leave_from_driveway(start, end, PathConstraints::Car, map)
                 } else {
@@ -377,7 +445,7 @@ impl TripEndpoint {
             })
             .ok()
             .map(|spot| spot.sidewalk_pos),
-            TripMode::Drive | TripMode::Bike => {
+            TripMode::Drive | TripMode::Bike | TripMode::Carpool | TripMode::RideHail => {
                 if from {
                     match self {
                         // Fall through and use DrivingGoal also to start.
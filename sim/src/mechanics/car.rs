@@ -2,8 +2,8 @@ use std::collections::{BTreeSet, VecDeque};
 
 use serde::{Deserialize, Serialize};
 
-use geom::{Distance, Duration, PolyLine, Time, EPSILON_DIST};
-use map_model::{Direction, LaneID, Map, Traversable};
+use geom::{Distance, Duration, PolyLine, Speed, Time, EPSILON_DIST};
+use map_model::{Direction, LaneID, Map, PathStep, Traversable};
 
 use crate::{
     CarID, CarStatus, DistanceInterval, DrawCarInput, Intent, ParkingSpot, PersonID, Router,
@@ -21,6 +21,9 @@ pub(crate) struct Car {
     pub trip_and_person: Option<(TripID, PersonID)>,
     pub started_at: Time,
     pub total_blocked_time: Duration,
+    /// Carpool passengers riding along, not counting the driver. Transit passengers are tracked
+    /// separately.
+    pub passengers: usize,
 
     /// In reverse order -- most recently left is first. The sum length of these must be >=
     /// vehicle.length.
@@ -29,11 +32,39 @@ pub(crate) struct Car {
     /// Since lane over-taking isn't implemented yet, a vehicle tends to be stuck behind a slow
     /// leader for a while. Avoid duplicate events.
     pub wants_to_overtake: BTreeSet<CarID>,
+
+    /// Gradually speed up and brake, instead of instantly moving at the max speed
+    pub accelerates: bool,
+    /// How fast was the vehicle going when its last Crossing state ended? Only used when
+    /// accelerating.
+    pub exit_speed: Speed,
+
+    /// Where, when, and how fast the vehicle started on the current step, and its
+    /// total_blocked_time at that point. Used to estimate emissions.
+    pub step_start_dist: Distance,
+    pub step_start_time: Time,
+    pub step_start_speed: Speed,
+    pub step_blocked_time: Duration,
+
+    /// For drivers who look for faster routes, when they'll next do so
+    pub next_reroute: Option<Time>,
 }
 
 impl Car {
     /// Assumes the current head of the path is the thing to cross.
     pub fn crossing_state(&self, start_dist: Distance, start_time: Time, map: &Map) -> CarState {
+        self.crossing_state_behind(start_dist, start_time, None, map)
+    }
+
+    /// Like `crossing_state`, but following a leader who plans to cruise at some speed. When
+    /// accelerating, the vehicle won't plan to go any faster than its leader.
+    pub fn crossing_state_behind(
+        &self,
+        start_dist: Distance,
+        start_time: Time,
+        leader_speed: Option<Speed>,
+        map: &Map,
+    ) -> CarState {
         let dist_int = DistanceInterval::new_driving(
             start_dist,
             if self.router.last_step() {
@@ -42,14 +73,32 @@ impl Car {
                 self.router.head().get_polyline(map).length()
             },
         );
-        self.crossing_state_with_end_dist(dist_int, start_time, map)
+        let end_speed = if self.accelerates {
+            self.speed_at_end_of_step(map)
+        } else {
+            None
+        };
+        self.crossing_state_with_speeds(dist_int, start_time, end_speed, leader_speed, map)
     }
 
+    /// The interval might end partway along the current step, so this doesn't slow down by the
+    /// end.
     pub fn crossing_state_with_end_dist(
         &self,
         dist_int: DistanceInterval,
         start_time: Time,
         map: &Map,
+    ) -> CarState {
+        self.crossing_state_with_speeds(dist_int, start_time, None, None, map)
+    }
+
+    fn crossing_state_with_speeds(
+        &self,
+        dist_int: DistanceInterval,
+        start_time: Time,
+        end_speed: Option<Speed>,
+        leader_speed: Option<Speed>,
+        map: &Map,
     ) -> CarState {
         let (speed, percent_incline) = self
             .router
@@ -60,11 +109,111 @@ impl Car {
                 self.vehicle.vehicle_type.to_constraints(),
                 map,
             );
-        let dt = (dist_int.end - dist_int.start) / speed;
+        let steep_uphill = percent_incline >= 0.08;
+
+        if !self.accelerates {
+            let dt = (dist_int.end - dist_int.start) / speed;
+            return CarState::Crossing {
+                time_int: TimeInterval::new(start_time, start_time + dt),
+                dist_int,
+                steep_uphill,
+                profile: None,
+            };
+        }
+
+        // Don't plan to catch up to a leader. A leader that's stopped doesn't count; the vehicle
+        // will drive up behind them and wait, like usual.
+        let cruise = match leader_speed {
+            Some(leader) if leader > Speed::ZERO => speed.min(leader),
+            _ => speed,
+        };
+        let (accel, decel) = acceleration_limits(self.vehicle.vehicle_type);
+        let profile = SpeedProfile::new(
+            dist_int.end - dist_int.start,
+            self.current_speed(start_time),
+            cruise,
+            end_speed.unwrap_or(speed),
+            accel,
+            decel,
+        );
         CarState::Crossing {
-            time_int: TimeInterval::new(start_time, start_time + dt),
+            time_int: TimeInterval::new(start_time, start_time + profile.duration()),
             dist_int,
-            steep_uphill: percent_incline >= 0.08,
+            steep_uphill,
+            profile: Some(profile),
+        }
+    }
+
+    /// How fast should the vehicle be going by the end of the current step? None means there's
+    /// no need to slow down.
+    fn speed_at_end_of_step(&self, map: &Map) -> Option<Speed> {
+        let constraints = self.vehicle.vehicle_type.to_constraints();
+        match self.router.get_path().maybe_next_step() {
+            None => {
+                // Vehicles leaving the map don't stop. Everybody else is about to park, reach a
+                // bus stop, etc.
+                let lane = map.get_l(self.router.head().as_lane());
+                if map.get_i(lane.dst_i).is_border() && self.router.get_end_dist() == lane.length()
+                {
+                    None
+                } else {
+                    Some(Speed::ZERO)
+                }
+            }
+            Some(step) => {
+                if let PathStep::Turn(t) = step {
+                    let i = map.get_i(t.parent);
+                    if i.is_stop_sign()
+                        && map.get_stop_sign(i.id).roads[&map.get_l(t.src).parent].must_stop
+                    {
+                        return Some(Speed::ZERO);
+                    }
+                }
+                // Don't anticipate traffic signals. If the light's red, the vehicle will just
+                // accelerate from a stop later.
+                Some(step.max_speed_along(self.vehicle.max_speed, constraints, map))
+            }
+        }
+    }
+
+    /// How fast is the vehicle going right now? Without acceleration, this is just the average
+    /// speed of the current movement.
+    pub fn current_speed(&self, now: Time) -> Speed {
+        match self.state {
+            CarState::Crossing {
+                ref time_int,
+                ref dist_int,
+                ref profile,
+                ..
+            } => match profile {
+                Some(p) => p.speed_at(time_int.percent_clamp_end(now)),
+                None => average_speed(dist_int, time_int),
+            },
+            CarState::ChangingLanes {
+                ref new_time,
+                ref new_dist,
+                ..
+            } => average_speed(new_dist, new_time),
+            // If the vehicle just finished crossing something and hasn't been blocked, it's still
+            // moving.
+            CarState::Queued { blocked_since, .. }
+            | CarState::WaitingToAdvance { blocked_since }
+                if blocked_since == now =>
+            {
+                self.exit_speed
+            }
+            _ => Speed::ZERO,
+        }
+    }
+
+    /// If the vehicle is accelerating through the current step, the fastest it plans to go.
+    pub fn planned_speed(&self) -> Option<Speed> {
+        match self.state {
+            CarState::Crossing {
+                profile: Some(ref p),
+                ..
+            } => Some(p.peak_speed()),
+            _ => None,
         }
     }
 
@@ -145,14 +294,17 @@ impl Car {
                 from,
                 to,
                 ref lc_time,
+                oncoming,
                 ..
             } => {
                 let percent_time = 1.0 - lc_time.percent(now);
+                // When overtaking, start in the oncoming lane
+                let from = oncoming.unwrap_or(from);
                 // TODO Can probably simplify this! Lifted from the parking case
                 let r = map.get_parent(from);
                 // The car's body is already at 'to', so shift back
                 let mut diff = (r.offset(to) as isize) - (r.offset(from) as isize);
-                if map.get_l(from).dir == Direction::Fwd {
+                if map.get_l(to).dir == Direction::Fwd {
                     diff *= -1;
                 }
                 // TODO Careful with this width math
@@ -305,6 +457,19 @@ impl Car {
         }
     }
 
+    /// Is the vehicle still in the oncoming lane, passing somebody? It's already ahead of them in
+    /// the queue, but might not have physically caught up yet.
+    pub fn is_overtaking(&self, now: Time) -> bool {
+        match self.state {
+            CarState::ChangingLanes {
+                oncoming: Some(_),
+                ref lc_time,
+                ..
+            } => now < lc_time.end,
+            _ => false,
+        }
+    }
+
     pub fn is_parking(&self) -> bool {
         if let CarState::Parking(_, _, _) = self.state {
             return true;
@@ -321,6 +486,8 @@ pub(crate) enum CarState {
         time_int: TimeInterval,
         dist_int: DistanceInterval,
         steep_uphill: bool,
+        /// None if the vehicle moves at a constant speed
+        profile: Option<SpeedProfile>,
     },
     ChangingLanes {
         from: LaneID,
@@ -330,6 +497,10 @@ pub(crate) enum CarState {
         new_dist: DistanceInterval,
         // How long does the lane-changing itself last? This must end before new_time_int does.
         lc_time: TimeInterval,
+        /// If the vehicle is overtaking somebody by briefly using the oncoming lane, then `from`
+        /// and `to` are the same, and this is the oncoming lane. The vehicle has already moved
+        /// ahead of whoever it passed, and merges back into `to` during lc_time.
+        oncoming: Option<LaneID>,
     },
     Queued {
         blocked_since: Time,
@@ -371,3 +542,191 @@ impl CarState {
         }
     }
 }
+
+/// How a vehicle's speed changes while it crosses some distance: speed up from its starting speed
+/// to some peak, cruise, then brake to some ending speed. If the distance is short, the vehicle
+/// might not reach the cruising speed. Everything is in meters and seconds.
+///
+/// This isn't a continuous car-following model like IDM. The profile is planned once per step,
+/// reacting to a leader only by not cruising faster than them, so a vehicle catching up to a
+/// stopped queue still stops abruptly.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub(crate) struct SpeedProfile {
+    length: f64,
+    start_speed: f64,
+    peak_speed: f64,
+    end_speed: f64,
+    accel: f64,
+    decel: f64,
+    accel_time: f64,
+    cruise_time: f64,
+    decel_time: f64,
+}
+
+impl SpeedProfile {
+    fn new(
+        length: Distance,
+        start: Speed,
+        cruise: Speed,
+        end: Speed,
+        accel: f64,
+        decel: f64,
+    ) -> SpeedProfile {
+        let length = length.inner_meters();
+        let v0 = start.inner_meters_per_second();
+        // Don't brake at the start; the vehicle should've slowed down by the end of the previous
+        // step.
+        let cruise = cruise.inner_meters_per_second().max(v0);
+        // Aim for the end speed, as long as it's reachable over this distance.
+        let v1 = end
+            .inner_meters_per_second()
+            .min(cruise)
+            .max((v0 * v0 - 2.0 * decel * length).max(0.0).sqrt())
+            .min((v0 * v0 + 2.0 * accel * length).sqrt());
+        // Without enough room to reach the cruising speed, accelerate to some peak and then
+        // immediately brake.
+        let peak = ((length + v0 * v0 / (2.0 * accel) + v1 * v1 / (2.0 * decel))
+            / (1.0 / (2.0 * accel) + 1.0 / (2.0 * decel)))
+            .sqrt()
+            .min(cruise)
+            .max(v0)
+            .max(v1);
+
+        let accel_time = (peak - v0) / accel;
+        let decel_time = (peak - v1) / decel;
+        let accel_dist = (v0 + peak) / 2.0 * accel_time;
+        let decel_dist = (peak + v1) / 2.0 * decel_time;
+        let cruise_time = if peak > 0.0 {
+            ((length - accel_dist - decel_dist) / peak).max(0.0)
+        } else {
+            0.0
+        };
+
+        SpeedProfile {
+            length,
+            start_speed: v0,
+            peak_speed: peak,
+            end_speed: v1,
+            accel,
+            decel,
+            accel_time,
+            cruise_time,
+            decel_time,
+        }
+    }
+
+    fn duration(&self) -> Duration {
+        Duration::seconds(self.total_seconds())
+    }
+
+    fn peak_speed(&self) -> Speed {
+        Speed::meters_per_second(self.peak_speed)
+    }
+
+    fn total_seconds(&self) -> f64 {
+        self.accel_time + self.cruise_time + self.decel_time
+    }
+
+    /// Given the percent of time spent crossing so far, returns the percent of distance covered.
+    pub fn percent_dist(&self, percent_time: f64) -> f64 {
+        if self.length == 0.0 || percent_time >= 1.0 {
+            return 1.0;
+        }
+        let mut t = percent_time * self.total_seconds();
+        if t < self.accel_time {
+            return (self.start_speed * t + 0.5 * self.accel * t * t) / self.length;
+        }
+        let mut dist = (self.start_speed + self.peak_speed) / 2.0 * self.accel_time;
+        t -= self.accel_time;
+        if t < self.cruise_time {
+            return ((dist + self.peak_speed * t) / self.length).min(1.0);
+        }
+        dist += self.peak_speed * self.cruise_time;
+        t = (t - self.cruise_time).min(self.decel_time);
+        dist += self.peak_speed * t - 0.5 * self.decel * t * t;
+        (dist / self.length).clamp(0.0, 1.0)
+    }
+
+    fn speed_at(&self, percent_time: f64) -> Speed {
+        let mut t = percent_time * self.total_seconds();
+        let speed = if t < self.accel_time {
+            self.start_speed + self.accel * t
+        } else {
+            t -= self.accel_time;
+            if t < self.cruise_time {
+                self.peak_speed
+            } else {
+                t = (t - self.cruise_time).min(self.decel_time);
+                (self.peak_speed - self.decel * t).max(self.end_speed)
+            }
+        };
+        Speed::meters_per_second(speed)
+    }
+}
+
+/// In meters per second squared, how quickly different vehicles comfortably speed up and brake.
+fn acceleration_limits(vehicle_type: VehicleType) -> (f64, f64) {
+    match vehicle_type {
+        VehicleType::Car | VehicleType::Emergency => (2.5, 3.0),
+        VehicleType::Bus => (1.2, 1.5),
+        VehicleType::Train => (1.0, 1.2),
+        VehicleType::Bike => (1.0, 2.0),
+    }
+}
+
+pub(crate) fn average_speed(dist_int: &DistanceInterval, time_int: &TimeInterval) -> Speed {
+    let dt = time_int.end - time_int.start;
+    if dt == Duration::ZERO {
+        return Speed::ZERO;
+    }
+    Speed::meters_per_second(dist_int.length().inner_meters() / dt.inner_seconds())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(length: f64, start: f64, cruise: f64, end: f64) -> SpeedProfile {
+        SpeedProfile::new(
+            Distance::meters(length),
+            Speed::meters_per_second(start),
+            Speed::meters_per_second(cruise),
+            Speed::meters_per_second(end),
+            2.0,
+            3.0,
+        )
+    }
+
+    #[test]
+    fn test_speed_profile() {
+        // Long enough to reach the cruising speed: 25m to accelerate, 16.67m to brake
+        let p = profile(100.0, 0.0, 10.0, 0.0);
+        assert!((p.total_seconds() - (5.0 + 5.83333 + 3.33333)).abs() < 0.001);
+        assert_eq!(p.percent_dist(0.0), 0.0);
+        assert_eq!(p.percent_dist(1.0), 1.0);
+        assert_eq!(p.speed_at(0.0), Speed::ZERO);
+        assert_eq!(p.speed_at(0.5), Speed::meters_per_second(10.0));
+
+        // Too short to reach the cruising speed
+        let p = profile(10.0, 0.0, 10.0, 0.0);
+        assert!(p.peak_speed < 10.0);
+        assert_eq!(p.percent_dist(1.0), 1.0);
+
+        // Can't stop in time, so end up going as slow as possible
+        let p = profile(1.0, 10.0, 10.0, 0.0);
+        assert!(p.end_speed > 9.0);
+
+        // Already moving, no need to slow down
+        let p = profile(100.0, 10.0, 10.0, 10.0);
+        assert!((p.total_seconds() - 10.0).abs() < 0.001);
+
+        // Distance only ever increases
+        let p = profile(50.0, 3.0, 12.0, 0.0);
+        let mut last = 0.0;
+        for i in 0..=100 {
+            let x = p.percent_dist((i as f64) / 100.0);
+            assert!(x >= last);
+            last = x;
+        }
+    }
+}
@@ -1,28 +1,37 @@
 use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 
+use rand::{Rng, SeedableRng};
+use rand_xorshift::XorShiftRng;
 use serde::{Deserialize, Serialize};
 
 use abstutil::{deserialize_hashmap, serialize_hashmap, FixedMap, IndexableKey};
-use geom::{Distance, Duration, PolyLine, Time};
-use map_model::{DrivingSide, IntersectionID, LaneID, Map, Path, PathStep, Position, Traversable};
+use geom::{Distance, Duration, PolyLine, Speed, Time};
+use map_model::{
+    DirectedRoadID, Direction, DrivingSide, IntersectionID, LaneID, Map, Path, PathConstraints,
+    PathStep, Position, Traversable, VehiclePathfinder,
+};
 
-use crate::mechanics::car::{Car, CarState};
+use crate::mechanics::car::{average_speed, Car, CarState};
 use crate::mechanics::queue::{Queue, QueueEntry, Queued};
 use crate::sim::Ctx;
 use crate::{
-    ActionAtEnd, AgentID, AgentProperties, CarID, CarStatus, Command, CreateCar, DelayCause,
-    DistanceInterval, DrawCarInput, Event, IntersectionSimState, ParkedCar, ParkingSim,
-    ParkingSpot, PersonID, Problem, SimOptions, TimeInterval, TransitSimState, TripID, TripManager,
-    UnzoomedAgent, Vehicle, VehicleType, WalkingSimState, FOLLOWING_DISTANCE, MAX_CAR_LENGTH,
+    ActionAtEnd, AgentID, AgentProperties, CapResult, CarID, CarStatus, Command, CreateCar,
+    DelayCause, DistanceInterval, DrawCarInput, EmissionTable, Emissions, Event, FleetSimState,
+    IntersectionSimState, ParkedCar, ParkingSim, ParkingSpot, ParkingStrategy, PersonID, Problem,
+    ReroutingPolicy, SimOptions, TimeInterval, TransitSimState, TripID, TripManager, UnzoomedAgent,
+    Vehicle, VehicleType, WalkingSimState, FOLLOWING_DISTANCE, MAX_CAR_LENGTH,
 };
 
-const TIME_TO_WAIT_AT_BUS_STOP: Duration = Duration::const_seconds(10.0);
 const TIME_TO_CHANGE_LANES: Duration = Duration::const_seconds(1.0);
 
 // TODO Do something else.
 pub const BLIND_RETRY_TO_CREEP_FORWARDS: Duration = Duration::const_seconds(0.1);
 pub const BLIND_RETRY_TO_REACH_END_DIST: Duration = Duration::const_seconds(5.0);
 
+/// When estimating current traffic, each vehicle stopped in a lane delays everybody behind it by
+/// roughly this much.
+const QUEUE_DISCHARGE_HEADWAY: Duration = Duration::const_seconds(2.0);
+
 /// Simulates vehicles!
 #[derive(Serialize, Deserialize, Clone)]
 pub(crate) struct DrivingSimState {
@@ -45,6 +54,14 @@ pub(crate) struct DrivingSimState {
 
     recalc_lanechanging: bool,
     handle_uber_turns: bool,
+    acceleration: bool,
+    oncoming_overtakes: bool,
+    emissions: EmissionTable,
+    rerouting: Option<ReroutingPolicy>,
+    parking_strategy: ParkingStrategy,
+    /// Only used for rerouting, and rebuilt as needed
+    #[serde(skip_serializing, skip_deserializing)]
+    live_traffic: Option<LiveTraffic>,
 
     time_to_unpark_onstreet: Duration,
     time_to_park_onstreet: Duration,
@@ -61,6 +78,12 @@ impl DrivingSimState {
             events: Vec::new(),
             recalc_lanechanging: opts.recalc_lanechanging,
             handle_uber_turns: opts.handle_uber_turns,
+            acceleration: opts.acceleration,
+            oncoming_overtakes: opts.oncoming_overtakes,
+            emissions: opts.emission_table.clone(),
+            rerouting: opts.rerouting.clone(),
+            parking_strategy: opts.parking_strategy,
+            live_traffic: None,
             waiting_to_spawn: BTreeMap::new(),
 
             time_to_unpark_onstreet: Duration::seconds(10.0),
@@ -187,8 +210,26 @@ impl DrivingSimState {
                 started_at: now,
                 total_blocked_time: Duration::ZERO,
                 trip_and_person: params.trip_and_person,
+                passengers: params.passengers,
                 wants_to_overtake: BTreeSet::new(),
+                accelerates: self.acceleration,
+                exit_speed: Speed::ZERO,
+                step_start_dist: start_dist,
+                step_start_time: now,
+                step_start_speed: Speed::ZERO,
+                step_blocked_time: Duration::ZERO,
+                next_reroute: None,
             };
+            if let Some(ref policy) = self.rerouting {
+                // Deterministically decide who's informed
+                let mut rng = XorShiftRng::seed_from_u64(car.vehicle.id.id as u64);
+                if car.vehicle.vehicle_type.to_constraints() == PathConstraints::Car
+                    && !car.vehicle.vehicle_type.is_transit()
+                    && rng.gen_bool(policy.informed_fraction)
+                {
+                    car.next_reroute = Some(now + policy.every);
+                }
+            }
             if let Some(p) = params.maybe_parked_car {
                 let delay = match p.spot {
                     ParkingSpot::Onstreet(_, _) => self.time_to_unpark_onstreet,
@@ -229,9 +270,11 @@ impl DrivingSimState {
                 // Have to do this early
                 if car.router.last_step() {
                     match car.router.maybe_handle_end(
+                        now,
                         start_dist,
                         &car.vehicle,
                         ctx.parking,
+                        self.parking_strategy,
                         ctx.map,
                         car.trip_and_person,
                         &mut self.events,
@@ -369,6 +412,7 @@ impl DrivingSimState {
     ) -> bool {
         match car.state {
             CarState::Crossing { .. } => {
+                car.exit_speed = car.current_speed(now);
                 car.state = CarState::Queued {
                     blocked_since: now,
                     want_to_change_lanes: None,
@@ -405,7 +449,14 @@ impl DrivingSimState {
                         ));
                     }
 
-                    if let Some(target_lane) = self.pick_overtaking_lane(car, ctx.map) {
+                    let target_lane = self.pick_overtaking_lane(car, ctx.map).or_else(|| {
+                        if self.oncoming_overtakes {
+                            self.pick_oncoming_lane(car, ctx.map)
+                        } else {
+                            None
+                        }
+                    });
+                    if let Some(target_lane) = target_lane {
                         // We need the current position of the car to see if lane-changing is
                         // actually feasible right now, so record our intention and trigger
                         // update_car_with_distances.
@@ -447,9 +498,11 @@ impl DrivingSimState {
                     // the next loop will pick that up. Just trigger the side effect of choosing an
                     // end_dist.
                     car.router.maybe_handle_end(
+                        now,
                         front,
                         &car.vehicle,
                         ctx.parking,
+                        self.parking_strategy,
                         ctx.map,
                         car.trip_and_person,
                         &mut self.events,
@@ -500,23 +553,47 @@ impl DrivingSimState {
                 // We do NOT need to update the follower. If they were Queued, they'll remain that
                 // way, until laggy_head is None.
 
+                self.record_emissions(
+                    car,
+                    now,
+                    from.get_polyline(ctx.map).length(),
+                    car.total_blocked_time + (now - blocked_since),
+                    ctx.map,
+                );
+
                 let last_step = car.router.advance(
+                    now,
                     &car.vehicle,
                     ctx.parking,
+                    self.parking_strategy,
                     ctx.map,
                     car.trip_and_person,
                     &mut self.events,
                 );
                 car.total_blocked_time += now - blocked_since;
-                car.state = car.crossing_state(Distance::ZERO, now, ctx.map);
+                car.step_start_dist = Distance::ZERO;
+                car.step_start_time = now;
+                car.step_start_speed = car.current_speed(now);
+                car.step_blocked_time = car.total_blocked_time;
+                if let Traversable::Lane(_) = goto {
+                    self.maybe_reroute(car, now, ctx);
+                }
+                let leader_speed = self.queues[&goto]
+                    .get_last_car()
+                    .and_then(|leader| self.cars.get(&leader))
+                    .and_then(|leader| leader.planned_speed());
+                car.state = car.crossing_state_behind(Distance::ZERO, now, leader_speed, ctx.map);
                 ctx.scheduler
                     .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
+                approach_signal(car, now, transit, ctx);
                 self.events.push(Event::AgentEntersTraversable(
                     AgentID::Car(car.vehicle.id),
                     car.trip_and_person.map(|(t, _)| t),
                     goto,
                     if car.vehicle.vehicle_type.is_transit() {
                         Some(transit.get_passengers(car.vehicle.id).len())
+                    } else if car.passengers > 0 {
+                        Some(car.passengers)
                     } else {
                         None
                     },
@@ -550,6 +627,7 @@ impl DrivingSimState {
                 from,
                 new_time,
                 new_dist,
+                oncoming,
                 ..
             } => {
                 // The car is already in the target queue. Just set them in the crossing state; we
@@ -558,24 +636,28 @@ impl DrivingSimState {
                     time_int: new_time,
                     dist_int: new_dist,
                     steep_uphill: false,
+                    profile: None,
                 };
                 ctx.scheduler
                     .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
 
-                // And remove the blockage from the old queue. Similar to the note in this function
-                // for Unparking, we calculate distances in that OTHER queue.
-                let dists = self.queues[&Traversable::Lane(from)].get_car_positions(
-                    now,
-                    &self.cars,
-                    &self.queues,
-                );
-                let idx = dists.iter().position(|entry| matches!(entry.member, Queued::DynamicBlockage { cause, ..} if cause == car.vehicle.id)).unwrap();
-                self.update_follower(idx, &dists, now, ctx);
+                // Overtaking using an oncoming lane doesn't leave a blockage behind
+                if oncoming.is_none() {
+                    // And remove the blockage from the old queue. Similar to the note in this
+                    // function for Unparking, we calculate distances in that OTHER queue.
+                    let dists = self.queues[&Traversable::Lane(from)].get_car_positions(
+                        now,
+                        &self.cars,
+                        &self.queues,
+                    );
+                    let idx = dists.iter().position(|entry| matches!(entry.member, Queued::DynamicBlockage { cause, ..} if cause == car.vehicle.id)).unwrap();
+                    self.update_follower(idx, &dists, now, ctx);
 
-                self.queues
-                    .get_mut(&Traversable::Lane(from))
-                    .unwrap()
-                    .clear_dynamic_blockage(car.vehicle.id, idx);
+                    self.queues
+                        .get_mut(&Traversable::Lane(from))
+                        .unwrap()
+                        .clear_dynamic_blockage(car.vehicle.id, idx);
+                }
             }
             CarState::Queued { .. } => unreachable!(),
             CarState::Parking(_, _, _) => unreachable!(),
@@ -610,20 +692,29 @@ impl DrivingSimState {
                 // Two totally different reasons we'll wind up here: we want to lane-change, and
                 // we're on our last step.
                 if let Some(target_lane) = want_to_change_lanes {
-                    self.try_start_lc(car, our_dist, idx, target_lane, now, ctx);
+                    if ctx.map.get_l(target_lane).dir
+                        == ctx.map.get_l(car.router.head().as_lane()).dir
+                    {
+                        self.try_start_lc(car, our_dist, idx, target_lane, now, ctx);
+                    } else {
+                        self.try_overtake_oncoming(car, dists, idx, target_lane, now, ctx);
+                    }
                     return true;
                 }
 
                 match car.router.maybe_handle_end(
+                    now,
                     our_dist,
                     &car.vehicle,
                     ctx.parking,
+                    self.parking_strategy,
                     ctx.map,
                     car.trip_and_person,
                     &mut self.events,
                 ) {
                     Some(ActionAtEnd::VanishAtBorder(i)) => {
                         car.total_blocked_time += now - blocked_since;
+                        self.record_emissions(car, now, our_dist, car.total_blocked_time, ctx.map);
                         // Don't do this for buses
                         if car.trip_and_person.is_some() {
                             trips.car_or_bike_reached_border(
@@ -639,19 +730,25 @@ impl DrivingSimState {
                     }
                     Some(ActionAtEnd::GiveUpOnParking) => {
                         car.total_blocked_time += now - blocked_since;
-                        trips.cancel_trip(
-                            now,
-                            car.trip_and_person.unwrap().0,
-                            "no available parking anywhere".to_string(),
-                            // If we couldn't find parking normally, doesn't make sense to warp the
-                            // car to the destination. There's no parking!
-                            None,
-                            ctx,
-                        );
+                        self.record_emissions(car, now, our_dist, car.total_blocked_time, ctx.map);
+                        if let Some((trip, _)) = car.trip_and_person {
+                            trips.cancel_trip(
+                                now,
+                                trip,
+                                "no available parking anywhere".to_string(),
+                                // If we couldn't find parking normally, doesn't make sense to warp
+                                // the car to the destination. There's no parking!
+                                None,
+                                ctx,
+                            );
+                        } else {
+                            trips.ride_hail_vehicle_lost(now, car.vehicle.id, ctx);
+                        }
                         false
                     }
                     Some(ActionAtEnd::StartParking(spot)) => {
                         car.total_blocked_time += now - blocked_since;
+                        self.record_emissions(car, now, our_dist, car.total_blocked_time, ctx.map);
                         let delay = match spot {
                             ParkingSpot::Onstreet(_, _) => self.time_to_park_onstreet,
                             ParkingSpot::Offstreet(_, _) | ParkingSpot::Lot(_, _) => {
@@ -677,6 +774,7 @@ impl DrivingSimState {
                     }
                     Some(ActionAtEnd::StopBiking(bike_rack)) => {
                         car.total_blocked_time += now - blocked_since;
+                        self.record_emissions(car, now, our_dist, car.total_blocked_time, ctx.map);
                         trips.bike_reached_end(
                             now,
                             car.vehicle.id,
@@ -689,11 +787,11 @@ impl DrivingSimState {
                     }
                     Some(ActionAtEnd::BusAtStop) => {
                         car.total_blocked_time += now - blocked_since;
-                        if transit.bus_arrived_at_stop(now, car.vehicle.id, trips, walking, ctx) {
-                            car.state = CarState::IdlingAtStop(
-                                our_dist,
-                                TimeInterval::new(now, now + TIME_TO_WAIT_AT_BUS_STOP),
-                            );
+                        if let Some(depart) =
+                            transit.bus_arrived_at_stop(now, car.vehicle.id, trips, walking, ctx)
+                        {
+                            car.state =
+                                CarState::IdlingAtStop(our_dist, TimeInterval::new(now, depart));
                             ctx.scheduler
                                 .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
                             true
@@ -737,23 +835,44 @@ impl DrivingSimState {
                     spot,
                     parked_since: now,
                 });
-                trips.car_reached_parking_spot(
-                    now,
-                    car.vehicle.id,
-                    spot,
-                    car.total_blocked_time,
-                    car.router.get_path().total_length(),
-                    ctx,
-                );
+                // Buses don't park, so the only vehicles without a trip are ride-hailing
+                if car.trip_and_person.is_some() {
+                    trips.car_reached_parking_spot(
+                        now,
+                        car.vehicle.id,
+                        spot,
+                        car.total_blocked_time,
+                        car.router.get_path().total_length(),
+                        ctx,
+                    );
+                } else {
+                    trips.ride_hail_vehicle_parked(
+                        now,
+                        car.vehicle.id,
+                        spot,
+                        car.total_blocked_time,
+                        car.router.get_path().total_length(),
+                        ctx,
+                    );
+                }
                 false
             }
-            CarState::IdlingAtStop(dist, _) => {
+            CarState::IdlingAtStop(dist, time_int) => {
+                // Count the approach to the stop. The engine idles at the stop, so count that like
+                // being blocked for the rest of the step.
+                self.record_emissions(car, time_int.start, dist, car.total_blocked_time, ctx.map);
+                car.step_start_dist = dist;
+                car.step_start_time = time_int.start;
+                car.step_start_speed = Speed::ZERO;
+                car.step_blocked_time = car.total_blocked_time - (now - time_int.start);
+
                 car.router = transit.bus_departed_from_stop(car.vehicle.id, ctx.map);
                 self.events
                     .push(Event::PathAmended(car.router.get_path().clone()));
                 car.state = car.crossing_state(dist, now, ctx.map);
                 ctx.scheduler
                     .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
+                approach_signal(car, now, transit, ctx);
 
                 self.update_follower(idx, dists, now, ctx);
 
@@ -762,6 +881,169 @@ impl DrivingSimState {
         }
     }
 
+    /// If it's time for this driver to look for a faster route, do so.
+    fn maybe_reroute(&mut self, car: &mut Car, now: Time, ctx: &mut Ctx) {
+        let map = ctx.map;
+        match car.next_reroute {
+            Some(t) if now >= t => {}
+            _ => {
+                return;
+            }
+        }
+        let policy = self.rerouting.as_ref().unwrap();
+        car.next_reroute = Some(now + policy.every);
+
+        // Everybody looking around the same time shares one estimate of traffic
+        if self
+            .live_traffic
+            .as_ref()
+            .map(|x| now - x.measured_at >= policy.every)
+            .unwrap_or(true)
+        {
+            let delays = self.live_road_delays(map);
+            // The delay applies to every way out of the road
+            let mut movement_delays = HashMap::new();
+            for (dr, delay) in &delays {
+                for mvmnt in map.get_movements_for(*dr, PathConstraints::Car) {
+                    movement_delays.insert(mvmnt, *delay);
+                }
+            }
+            let pathfinder = VehiclePathfinder::new_with_delays(
+                map,
+                PathConstraints::Car,
+                map.routing_params(),
+                &movement_delays,
+            );
+            self.live_traffic = Some(LiveTraffic {
+                measured_at: now,
+                delays,
+                pathfinder,
+            });
+        }
+
+        let live = self.live_traffic.as_ref().unwrap();
+        let (new_path, time_saved) = match car.router.find_reroute(
+            &live.pathfinder,
+            &live.delays,
+            policy.min_time_saved,
+            &car.vehicle,
+            map,
+        ) {
+            Some(pair) => pair,
+            None => {
+                return;
+            }
+        };
+        // The new route could enter a zone with a vehicle cap. If the zone is full and there's no
+        // way around it, stick to the current route.
+        let new_path = match car.trip_and_person {
+            Some((trip, _)) if car.vehicle.vehicle_type == VehicleType::Car => {
+                let req = new_path.get_req().clone();
+                match ctx
+                    .cap
+                    .validate_path(&req, new_path, now, car.vehicle.id, trip, true, map)
+                {
+                    CapResult::Ok(path) => path,
+                    CapResult::Delay(_, _) | CapResult::Cancel(_) => {
+                        return;
+                    }
+                }
+            }
+            _ => new_path,
+        };
+        car.router.reroute(new_path);
+        self.events
+            .push(Event::PathAmended(car.router.get_path().clone()));
+        self.events.push(Event::VehicleRerouted {
+            vehicle: car.vehicle.id,
+            trip: car.trip_and_person.map(|(t, _)| t),
+            lane: car.router.head().as_lane(),
+            time_saved,
+        });
+    }
+
+    /// Roughly estimate how much longer than usual it takes to cross each road right now, based
+    /// on how many vehicles are stopped in its lanes.
+    fn live_road_delays(&self, map: &Map) -> HashMap<DirectedRoadID, Duration> {
+        // Per road, the number of stopped vehicles and lanes
+        let mut counts: HashMap<DirectedRoadID, (usize, usize)> = HashMap::new();
+        for queue in self.queues.values() {
+            if let Traversable::Lane(l) = queue.id {
+                let lane = map.get_l(l);
+                if !PathConstraints::Car.can_use(lane, map) {
+                    continue;
+                }
+                let stopped = queue
+                    .get_active_cars()
+                    .into_iter()
+                    .filter(|c| {
+                        // The vehicle being updated right now isn't here
+                        self.cars
+                            .get(c)
+                            .map(|car| {
+                                matches!(
+                                    car.state,
+                                    CarState::Queued { .. } | CarState::WaitingToAdvance { .. }
+                                )
+                            })
+                            .unwrap_or(false)
+                    })
+                    .count();
+                let entry = counts.entry(lane.get_directed_parent()).or_insert((0, 0));
+                entry.0 += stopped;
+                entry.1 += 1;
+            }
+        }
+        counts
+            .into_iter()
+            .filter(|(_, (stopped, _))| *stopped > 0)
+            .map(|(dr, (stopped, lanes))| {
+                (
+                    dr,
+                    QUEUE_DISCHARGE_HEADWAY * (stopped as f64 / lanes as f64),
+                )
+            })
+            .collect()
+    }
+
+    /// Estimate the emissions of a vehicle on its current step, from where it started until some
+    /// distance and time. blocked_time is the vehicle's total so far.
+    fn record_emissions(
+        &mut self,
+        car: &Car,
+        now: Time,
+        end_dist: Distance,
+        blocked_time: Duration,
+        map: &Map,
+    ) {
+        let (_, percent_incline) = car
+            .router
+            .get_path()
+            .current_step()
+            .max_speed_and_incline_along(
+                car.vehicle.max_speed,
+                car.vehicle.vehicle_type.to_constraints(),
+                map,
+            );
+        let stopped = blocked_time - car.step_blocked_time;
+        let emissions = self.emissions.estimate(
+            car.vehicle.vehicle_type,
+            (end_dist - car.step_start_dist).max(Distance::ZERO),
+            percent_incline,
+            (now - car.step_start_time - stopped).max(Duration::ZERO),
+            stopped,
+            car.step_start_speed,
+        );
+        if emissions != Emissions::ZERO {
+            self.events.push(Event::VehicleEmissions(
+                car.vehicle.id,
+                car.trip_and_person.map(|(t, _)| t),
+                car.router.head(),
+                emissions,
+            ));
+        }
+    }
+
     /// Abruptly remove a vehicle from the simulation. They may be in any arbitrary state, like in
     /// the middle of a turn or parking.
     pub fn delete_car(&mut self, c: CarID, now: Time, ctx: &mut Ctx) -> Vehicle {
@@ -778,6 +1060,18 @@ impl DrivingSimState {
             .unwrap();
         let mut car = self.cars.remove(&c).unwrap();
 
+        // Count whatever part of the current step the vehicle covered. Parking already counted
+        // the whole step.
+        if !matches!(car.state, CarState::Parking(..)) {
+            self.record_emissions(
+                &car,
+                now,
+                dists[idx].front,
+                car.total_blocked_time + car.state.time_spent_waiting(now),
+                ctx.map,
+            );
+        }
+
         // Hacks to delete cars that're mid-turn
         if let Traversable::Turn(t) = car.router.head() {
             let queue = self.queues.get_mut(&car.router.head()).unwrap();
@@ -855,6 +1149,10 @@ impl DrivingSimState {
 
         if let Queued::Vehicle(follower_id) = dists[idx_follower].member {
             let follower_dist = dists[idx_follower].front;
+            let leader_speed = match dists[idx_leader].member {
+                Queued::Vehicle(leader) => self.cars.get(&leader).and_then(|c| c.planned_speed()),
+                Queued::StaticBlockage { .. } | Queued::DynamicBlockage { .. } => None,
+            };
 
             // If we're going to delete the follower soon, don't bother waking them up.
             if let Some(ref deleting_agents) = ctx.handling_live_edits {
@@ -874,7 +1172,8 @@ impl DrivingSimState {
 
                     // Prevent them from jumping forwards.
                     follower.total_blocked_time += now - blocked_since;
-                    follower.state = follower.crossing_state(follower_dist, now, ctx.map);
+                    follower.state =
+                        follower.crossing_state_behind(follower_dist, now, leader_speed, ctx.map);
                     ctx.scheduler.update(
                         follower.state.get_end_time(),
                         Command::UpdateCar(follower_id),
@@ -884,22 +1183,27 @@ impl DrivingSimState {
                     // If the follower was still Crossing, they might not've been blocked by the
                     // leader yet. But recalculating their Crossing state isn't necessarily a no-op
                     // -- this could prevent them from suddenly warping past a blockage.
-                    follower.state = follower.crossing_state(follower_dist, now, ctx.map);
+                    follower.state =
+                        follower.crossing_state_behind(follower_dist, now, leader_speed, ctx.map);
                     ctx.scheduler.update(
                         follower.state.get_end_time(),
                         Command::UpdateCar(follower_id),
                     );
                 }
                 CarState::ChangingLanes {
-                    from, to, lc_time, ..
+                    from,
+                    to,
+                    lc_time,
+                    oncoming,
+                    ..
                 } => {
                     // This is a fun case -- something stopped blocking somebody that was in the
                     // process of lane-changing! Similar to the Crossing case above, we just have
                     // to update the distance/time intervals, but otherwise leave them in the
-                    // middle of their lane-changing. It's guaranteed that lc_time will continue to
-                    // finish before the new time interval, because there's no possible way
-                    // recalculating this crossing state here will speed things up from the
-                    // original estimate.
+                    // middle of their lane-changing. Without acceleration, it's guaranteed that
+                    // lc_time will continue to finish before the new time interval, because
+                    // there's no possible way recalculating this crossing state here will speed
+                    // things up from the original estimate.
                     let (new_time, new_dist) = match follower.crossing_state_with_end_dist(
                         DistanceInterval::new_driving(follower_dist, ctx.map.get_l(to).length()),
                         now,
@@ -910,13 +1214,16 @@ impl DrivingSimState {
                         } => (time_int, dist_int),
                         _ => unreachable!(),
                     };
-                    assert!(new_time.end >= lc_time.end);
+                    // With acceleration, the vehicle might cover a short remaining distance a
+                    // little faster than originally estimated. Just finish the lane-change first.
+                    let new_time = TimeInterval::new(new_time.start, new_time.end.max(lc_time.end));
                     follower.state = CarState::ChangingLanes {
                         from,
                         to,
                         new_time,
                         new_dist,
                         lc_time,
+                        oncoming,
                     };
                 }
                 // They weren't blocked
@@ -1126,6 +1433,207 @@ impl DrivingSimState {
         None
     }
 
+    /// If the car can't overtake using a lane in the same direction, can it briefly use the
+    /// adjacent oncoming lane? For DrivingSide::Right, that's on the driver's left.
+    fn pick_oncoming_lane(&self, car: &Car, map: &Map) -> Option<LaneID> {
+        let current_lane = map.get_l(car.router.head().maybe_lane()?);
+        let road = map.get_r(current_lane.parent);
+        let idx = road.offset(current_lane.id);
+        let lanes_ltr = road.lanes_ltr();
+
+        let towards_left = (current_lane.dir == Direction::Fwd)
+            == (map.get_config().driving_side == DrivingSide::Right);
+        let target_lane = if towards_left {
+            if idx == 0 {
+                return None;
+            }
+            map.get_l(lanes_ltr[idx - 1].0)
+        } else {
+            map.get_l(lanes_ltr.get(idx + 1)?.0)
+        };
+
+        if target_lane.dir == current_lane.dir
+            || !car
+                .vehicle
+                .vehicle_type
+                .to_constraints()
+                .can_use(target_lane, map)
+        {
+            return None;
+        }
+        Some(target_lane.id)
+    }
+
+    /// Try to pass the vehicle immediately in front by briefly using an oncoming lane. There must
+    /// be room to pull back in ahead of them, and nobody coming the other way for as long as that
+    /// takes.
+    fn try_overtake_oncoming(
+        &mut self,
+        car: &mut Car,
+        dists: &[QueueEntry],
+        idx: usize,
+        oncoming: LaneID,
+        now: Time,
+        ctx: &mut Ctx,
+    ) {
+        // Don't start if our back is still sticking into another lane or turn, or if we're about
+        // to stop anyway.
+        if !car.last_steps.is_empty() || car.router.last_step() || idx == 0 {
+            return;
+        }
+        let overtaken = match dists[idx - 1].member {
+            Queued::Vehicle(id) => id,
+            _ => {
+                return;
+            }
+        };
+        // If they're stuck behind somebody, they'd jump forwards when we leave. And don't pass
+        // somebody who's passing somebody else.
+        if matches!(
+            self.cars[&overtaken].state,
+            CarState::Queued { .. } | CarState::WaitingToAdvance { .. }
+        ) || self.cars[&overtaken].is_overtaking(now)
+        {
+            return;
+        }
+
+        // How fast would we go, and how fast are they going?
+        let current_lane = car.router.head().as_lane();
+        let lane_len = ctx.map.get_l(current_lane).length();
+        let our_front = dists[idx].front;
+        let (new_time, new_dist) = match car.crossing_state_with_end_dist(
+            DistanceInterval::new_driving(our_front, lane_len),
+            now,
+            ctx.map,
+        ) {
+            CarState::Crossing {
+                time_int, dist_int, ..
+            } => (time_int, dist_int),
+            _ => unreachable!(),
+        };
+        // While passing, the car moves at a constant speed
+        let speed = average_speed(&new_dist, &new_time);
+        let their_speed = self.cars[&overtaken].current_speed(now);
+        if speed <= their_speed {
+            return;
+        }
+
+        // Where would we pull back in? They keep moving while we pass them.
+        let gap = dists[idx - 1].front + FOLLOWING_DISTANCE + car.vehicle.length - our_front;
+        let pass_time = gap / (speed - their_speed);
+        let new_front = our_front + speed * pass_time;
+        let limit = if idx >= 2 {
+            dists[idx - 2].back - FOLLOWING_DISTANCE
+        } else if self.queues[&car.router.head()].laggy_head.is_some() {
+            return;
+        } else {
+            lane_len
+        };
+        if new_front >= limit || new_front >= lane_len {
+            return;
+        }
+
+        let pass_time = pass_time.max(TIME_TO_CHANGE_LANES);
+        let lc_time = TimeInterval::new(now, now + pass_time);
+        // Like lane-changing, we have to finish before the end of the lane.
+        if lc_time.end >= new_time.end {
+            return;
+        }
+
+        // Is anybody coming the other way? Using distances along the oncoming lane, check from our
+        // back to wherever somebody going the speed limit would be by the time we're done.
+        let oncoming_back = Position::new(
+            current_lane,
+            (our_front - car.vehicle.length).max(Distance::ZERO),
+        )
+        .equiv_pos(oncoming, ctx.map)
+        .dist_along();
+        let oncoming_front = Position::new(current_lane, new_front)
+            .equiv_pos(oncoming, ctx.map)
+            .dist_along();
+        let oncoming_speed = ctx.map.get_parent(oncoming).speed_limit;
+        let danger_start = oncoming_front.min(oncoming_back) - oncoming_speed * pass_time;
+        let danger_end = oncoming_front.max(oncoming_back);
+        if self.queues[&Traversable::Lane(oncoming)]
+            .get_car_positions(now, &self.cars, &self.queues)
+            .into_iter()
+            .any(|entry| entry.back < danger_end && entry.front > danger_start)
+        {
+            return;
+        }
+        if danger_start < Distance::ZERO {
+            if let Some(dist) = self.closest_vehicle_entering(oncoming, current_lane, now, ctx.map)
+            {
+                if dist < -danger_start {
+                    return;
+                }
+            }
+        }
+
+        // Go for it! We move ahead of them in the queue immediately, but they aren't held back by
+        // us until we've finished passing. Anybody stuck behind us shouldn't jump forwards.
+        self.update_follower(idx, dists, now, ctx);
+        self.queues
+            .get_mut(&car.router.head())
+            .unwrap()
+            .swap_with_leader(car.vehicle.id, idx);
+        car.state = CarState::ChangingLanes {
+            from: current_lane,
+            to: current_lane,
+            new_time,
+            new_dist,
+            lc_time,
+            oncoming: Some(oncoming),
+        };
+        ctx.scheduler
+            .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
+        self.events.push(Event::OncomingOvertake {
+            vehicle: car.vehicle.id,
+            overtaken,
+            lane: current_lane,
+            oncoming,
+        });
+    }
+
+    /// How far away is the closest vehicle about to enter a lane, either in the middle of a turn
+    /// onto it, or on a lane leading to one of those turns? Vehicles on `skip` aren't considered.
+    fn closest_vehicle_entering(
+        &self,
+        lane: LaneID,
+        skip: LaneID,
+        now: Time,
+        map: &Map,
+    ) -> Option<Distance> {
+        let mut closest: Option<Distance> = None;
+        for turn in map.get_turns_to_lane(lane) {
+            let turn_len = turn.geom.length();
+            for entry in self.queues[&Traversable::Turn(turn.id)].get_car_positions(
+                now,
+                &self.cars,
+                &self.queues,
+            ) {
+                let dist = turn_len - entry.front;
+                closest = Some(closest.map_or(dist, |x| x.min(dist)));
+            }
+
+            // Somebody making a U-turn behind us isn't a threat.
+            if turn.id.src == skip {
+                continue;
+            }
+            let src = Traversable::Lane(turn.id.src);
+            let src_len = self.queues[&src].geom_len;
+            for entry in self.queues[&src].get_car_positions(now, &self.cars, &self.queues) {
+                if let Queued::Vehicle(id) = entry.member {
+                    if self.cars[&id].router.maybe_next() == Some(Traversable::Turn(turn.id)) {
+                        let dist = src_len - entry.front + turn_len;
+                        closest = Some(closest.map_or(dist, |x| x.min(dist)));
+                    }
+                }
+            }
+        }
+        closest
+    }
+
     fn try_start_lc(
         &mut self,
         car: &mut Car,
@@ -1212,6 +1720,7 @@ impl DrivingSimState {
                 new_time,
                 new_dist,
                 lc_time,
+                oncoming: None,
             };
             ctx.scheduler
                 .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
@@ -1223,6 +1732,9 @@ impl DrivingSimState {
     }
 
     pub fn handle_live_edits(&mut self, map: &Map) {
+        // The roads might've changed
+        self.live_traffic = None;
+
         // Calculate all queues that should exist now.
         let mut new_queues = HashSet::new();
         for l in map.all_lanes().values() {
@@ -1506,38 +2018,65 @@ impl DrivingSimState {
         self.queues[&Traversable::Lane(l)].target_lane_penalty()
     }
 
+    /// Returns the trips affected, and separately, ride-hailing vehicles, which don't belong to a
+    /// trip.
     pub fn find_trips_to_edited_parking(
         &self,
         spots: BTreeSet<ParkingSpot>,
-    ) -> Vec<(AgentID, TripID)> {
+        fleet: &FleetSimState,
+    ) -> (Vec<(AgentID, TripID)>, Vec<CarID>) {
         let mut affected = Vec::new();
+        let mut ride_hail = Vec::new();
         for car in self.cars.values() {
             if let Some(spot) = car.router.get_parking_spot_goal() {
                 if !spots.contains(spot) {
-                    // Buses don't park
-                    affected.push((AgentID::Car(car.vehicle.id), car.trip_and_person.unwrap().0));
+                    // Buses don't park.
+                    if let Some((trip, _)) = car.trip_and_person {
+                        affected.push((AgentID::Car(car.vehicle.id), trip));
+                    } else if fleet.has_vehicle(car.vehicle.id) {
+                        ride_hail.push(car.vehicle.id);
+                    }
                 }
             }
         }
-        affected
+        (affected, ride_hail)
     }
 
-    /// Finds vehicles that're laggy heads on affected parts of the map.
+    /// Finds vehicles that're laggy heads on affected parts of the map. Ride-hailing vehicles
+    /// aren't part of any trip that checks its path, so they're also returned if their remaining
+    /// path is affected.
     pub fn find_vehicles_affected_by_live_edits(
         &self,
         closed_intersections: &HashSet<IntersectionID>,
         edited_lanes: &BTreeSet<LaneID>,
-    ) -> Vec<(AgentID, TripID)> {
+        fleet: &FleetSimState,
+    ) -> (Vec<(AgentID, TripID)>, Vec<CarID>) {
+        let is_affected = |step: &Traversable| match step {
+            Traversable::Lane(l) => edited_lanes.contains(l),
+            Traversable::Turn(t) => {
+                closed_intersections.contains(&t.parent)
+                    || edited_lanes.contains(&t.src)
+                    || edited_lanes.contains(&t.dst)
+            }
+        };
+
         let mut affected = Vec::new();
+        let mut ride_hail = Vec::new();
         for car in self.cars.values() {
-            if car.last_steps.iter().any(|step| match step {
-                Traversable::Lane(l) => edited_lanes.contains(l),
-                Traversable::Turn(t) => {
-                    closed_intersections.contains(&t.parent)
-                        || edited_lanes.contains(&t.src)
-                        || edited_lanes.contains(&t.dst)
+            if car.trip_and_person.is_none() && fleet.has_vehicle(car.vehicle.id) {
+                if car.last_steps.iter().any(is_affected)
+                    || car
+                        .router
+                        .get_path()
+                        .get_steps()
+                        .iter()
+                        .any(|step| is_affected(&step.as_traversable()))
+                {
+                    ride_hail.push(car.vehicle.id);
                 }
-            }) {
+                continue;
+            }
+            if car.last_steps.iter().any(is_affected) {
                 // TODO Buses aren't handled yet! Mostly not a big deal, because they're pretty
                 // much never created anyway.
                 if let Some((trip, _)) = car.trip_and_person {
@@ -1545,7 +2084,7 @@ impl DrivingSimState {
                 }
             }
         }
-        affected
+        (affected, ride_hail)
     }
 
     pub fn all_waiting_people(&self, now: Time, delays: &mut BTreeMap<PersonID, Duration>) {
@@ -1618,17 +2157,23 @@ impl DrivingSimState {
         let queue = &self.queues[&car.router.head()];
         let leader = &self.cars[&queue.get_leader(car.vehicle.id)?];
 
-        // Are we faster than them?
-        // TODO This shouldn't be a blocking check; we also want to pass parking cars and buses
-        // waiting at stops.
-        let their_speed = leader.vehicle.max_speed?;
-        if car
-            .vehicle
-            .max_speed
-            .map(|s| s <= their_speed)
-            .unwrap_or(false)
-        {
-            return None;
+        // Always try to pass buses waiting at stops and vehicles parking, if we're allowed to use
+        // the oncoming lane.
+        let stopped = matches!(
+            leader.state,
+            CarState::IdlingAtStop(_, _) | CarState::Parking(_, _, _) | CarState::Unparking { .. }
+        );
+        if !(stopped && self.oncoming_overtakes) {
+            // Are we faster than them?
+            let their_speed = leader.vehicle.max_speed?;
+            if car
+                .vehicle
+                .max_speed
+                .map(|s| s <= their_speed)
+                .unwrap_or(false)
+            {
+                return None;
+            }
         }
 
         // Are they moving slowly or also stuck behind someone?
@@ -1645,8 +2190,8 @@ impl DrivingSimState {
         // TODO Check if there's room in front of them to pass. Handle passing two bikes?
         // TODO Check remaining distance before next intersection
         // TODO Check relative speed difference, and time to pass them
-        // TODO Eventually, check if there's room to do the maneuever (lane-changing to
-        // the left only, or popping into an oncoming lane!
+        // TODO Check if there's room to do the maneuever when lane-changing. Passing using an
+        // oncoming lane checks later.
 
         Some(leader.vehicle.id)
     }
@@ -1659,3 +2204,36 @@ impl IndexableKey for CarID {
         self.id
     }
 }
+
+/// A snapshot of traffic, used by drivers looking for faster routes
+#[derive(Clone)]
+struct LiveTraffic {
+    measured_at: Time,
+    delays: HashMap<DirectedRoadID, Duration>,
+    pathfinder: VehiclePathfinder,
+}
+
+/// Buses and emergency vehicles heading towards a traffic signal might get priority there. Buses
+/// only ask once they've left any stop on the way.
+fn approach_signal(car: &Car, now: Time, transit: &TransitSimState, ctx: &mut Ctx) {
+    let route = match car.vehicle.vehicle_type {
+        VehicleType::Bus => Some(transit.bus_route(car.vehicle.id)),
+        VehicleType::Emergency => None,
+        _ => {
+            return;
+        }
+    };
+    if let (Traversable::Lane(_), Some(Traversable::Turn(t))) =
+        (car.router.head(), car.router.maybe_next())
+    {
+        ctx.intersections.vehicle_approaching_signal(
+            now,
+            car.vehicle.id,
+            route,
+            t,
+            car.state.get_end_time(),
+            ctx.map,
+            ctx.scheduler,
+        );
+    }
+}
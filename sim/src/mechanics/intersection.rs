@@ -5,18 +5,50 @@ use serde::{Deserialize, Serialize};
 use abstutil::{deserialize_btreemap, prettyprint_usize, serialize_btreemap, FixedMap};
 use geom::{Duration, Time};
 use map_model::{
-    ControlStopSign, ControlTrafficSignal, Intersection, IntersectionID, LaneID, Map, StageType,
-    Traversable, TurnID, TurnPriority, TurnType, UberTurn,
+    BusRouteID, ControlStopSign, ControlTrafficSignal, Intersection, IntersectionID, LaneID, Map,
+    Stage, StageType, Traversable, TurnID, TurnPriority, TurnType, UberTurn,
 };
 
 use crate::mechanics::car::{Car, CarState};
 use crate::mechanics::Queue;
 use crate::{
     AgentID, AlertLocation, CarID, Command, DelayCause, Event, Scheduler, SimOptions, Speed,
+    VehicleType,
 };
 
 const WAIT_AT_STOP_SIGN: Duration = Duration::const_seconds(0.5);
 const WAIT_BEFORE_YIELD_AT_TRAFFIC_SIGNAL: Duration = Duration::const_seconds(0.2);
+/// When a bus asks for a green extension, leave it this much extra time to make it through.
+const PRIORITY_MARGIN: Duration = Duration::const_seconds(2.0);
+/// While an emergency vehicle holds a signal, check this often if it's gone through.
+const PREEMPTION_CHECK: Duration = Duration::const_seconds(5.0);
+/// If an emergency vehicle never makes it through the signal, give up holding it after this long.
+const MAX_PREEMPTION: Duration = Duration::const_seconds(120.0);
+
+/// Buses on some routes can ask traffic signals to stay green a little longer, or to end a red
+/// early, so they don't have to stop. Only fixed-time and adaptive stages respond; actuated and
+/// variable stages already react to demand.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TransitSignalPriority {
+    /// The short names of routes whose buses ask for priority
+    pub routes: BTreeSet<String>,
+    /// A stage letting the bus through can be extended by up to this much in total, no matter how
+    /// many buses ask for it.
+    pub max_extension: Duration,
+    /// A stage that doesn't let the bus through can be cut short by up to this much, if the next
+    /// stage does. Stages always last long enough for pedestrians to finish crossing.
+    pub max_truncation: Duration,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum SignalPriorityAction {
+    /// The current stage was extended, so an approaching bus could make it through.
+    ExtendGreen,
+    /// The current stage was cut short, so the next stage serving a bus started sooner.
+    TruncateRed,
+    /// An emergency vehicle switched the signal to a stage serving it, and held it there.
+    Preempt,
+}
 
 /// Manages conflicts at intersections. When an agent has reached the end of a lane, they call
 /// maybe_start_turn to make a Request. Based on the intersection type (stop sign, traffic signal,
@@ -34,6 +66,8 @@ pub(crate) struct IntersectionSimState {
     break_turn_conflict_cycles: bool,
     handle_uber_turns: bool,
     disable_turn_conflicts: bool,
+    transit_signal_priority: Option<TransitSignalPriority>,
+    emergency_preemption: bool,
     // (x, y) means x is blocked by y. It's a many-to-many relationship. TODO Better data
     // structure.
     blocked_by: BTreeSet<(CarID, CarID)>,
@@ -78,6 +112,24 @@ struct SignalState {
     stage_ends_at: Time,
     // The number of times a variable signal has been extended during the current stage.
     extensions_count: usize,
+    // When the current stage started. Actuated stages measure their min and max green from this.
+    stage_started_at: Time,
+    // How much the current stage has been extended for approaching buses
+    priority_extension: Duration,
+    // Each approach lane has a detector at the stop line. This is the last time a vehicle was
+    // detected there.
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    detections: BTreeMap<LaneID, Time>,
+    // Indexed by stage, how many agents were waiting for a protected movement when the stage last
+    // started. Adaptive stages are re-split using this.
+    measured_queues: Vec<usize>,
+    // Indexed by stage, how long each adaptive stage lasts during the current cycle.
+    adaptive_splits: Vec<Duration>,
+    // An emergency vehicle holding the current stage, and when it started
+    preempted_by: Option<(CarID, Time)>,
 }
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Clone, Debug)]
@@ -96,6 +148,8 @@ impl IntersectionSimState {
             break_turn_conflict_cycles: opts.break_turn_conflict_cycles,
             handle_uber_turns: opts.handle_uber_turns,
             disable_turn_conflicts: opts.disable_turn_conflicts,
+            transit_signal_priority: opts.transit_signal_priority.clone(),
+            emergency_preemption: opts.emergency_preemption,
             blocked_by: BTreeSet::new(),
             events: Vec::new(),
 
@@ -184,6 +238,15 @@ impl IntersectionSimState {
     /// turn.
     pub fn vehicle_gone(&mut self, car: CarID) {
         self.blocked_by.retain(|(c1, c2)| *c1 != car && *c2 != car);
+        if car.vehicle_type == VehicleType::Emergency {
+            for state in self.state.values_mut() {
+                if let Some(ref mut signal_state) = state.signal {
+                    if signal_state.preempted_by.map(|(c, _)| c) == Some(car) {
+                        signal_state.preempted_by = None;
+                    }
+                }
+            }
+        }
     }
 
     pub fn agent_deleted_mid_turn(&mut self, agent: AgentID, turn: TurnID) {
@@ -279,31 +342,11 @@ impl IntersectionSimState {
         map: &Map,
         scheduler: &mut Scheduler,
     ) {
-        // trivial function that advances the signal stage and returns duration
-        fn advance(
-            signal_state: &mut SignalState,
-            signal: &ControlTrafficSignal,
-            allow_crosswalk_skip: bool,
-        ) -> Duration {
-            signal_state.current_stage = (signal_state.current_stage + 1) % signal.stages.len();
-            let stage = &signal.stages[signal_state.current_stage];
-            // only skip for variable all-walk crosswalk
-            if let StageType::Variable(_, _, _) = stage.stage_type {
-                if allow_crosswalk_skip && stage.max_crosswalk_time(&signal.movements).is_some() {
-                    // we can skip this stage, as its all walk and we're allowed to skip (no
-                    // pedestrian waiting).
-                    signal_state.current_stage =
-                        (signal_state.current_stage + 1) % signal.stages.len();
-                }
-            }
-            signal.stages[signal_state.current_stage]
-                .stage_type
-                .simple_duration()
-        }
         let state = self.state.get_mut(&id).unwrap();
+        let waiting = &state.waiting;
         let signal_state = state.signal.as_mut().unwrap();
         let signal = map.get_traffic_signal(id);
-        let ped_waiting = state.waiting.keys().any(|req| {
+        let ped_waiting = waiting.keys().any(|req| {
             if let AgentID::Pedestrian(_) = req.agent {
                 return true;
             }
@@ -312,10 +355,19 @@ impl IntersectionSimState {
         let duration: Duration;
         // Switch to a new stage?
         assert_eq!(now, signal_state.stage_ends_at);
+        if let Some((_, since)) = signal_state.preempted_by {
+            if now - since < MAX_PREEMPTION {
+                signal_state.stage_ends_at = now + PREEMPTION_CHECK;
+                scheduler.push(signal_state.stage_ends_at, Command::UpdateIntersection(id));
+                return;
+            }
+            // The emergency vehicle never made it through. Carry on as usual.
+            signal_state.preempted_by = None;
+        }
         let old_stage = &signal.stages[signal_state.current_stage];
         match old_stage.stage_type {
-            StageType::Fixed(_) => {
-                duration = advance(signal_state, signal, !ped_waiting);
+            StageType::Fixed(_) | StageType::Adaptive { .. } => {
+                duration = signal_state.advance(now, signal, waiting, !ped_waiting);
             }
             StageType::Variable(min, delay, additional) => {
                 // test if anyone is waiting in current stage, and if so, extend the signal cycle.
@@ -333,9 +385,9 @@ impl IntersectionSimState {
                             min, delay, additional, signal_state.extensions_count
                         ),
                     ));
-                    duration = advance(signal_state, signal, !ped_waiting);
+                    duration = signal_state.advance(now, signal, waiting, !ped_waiting);
                     signal_state.extensions_count = 0;
-                } else if state.waiting.keys().all(|req| {
+                } else if waiting.keys().all(|req| {
                     if let AgentID::Pedestrian(_) = req.agent {
                         return true;
                     }
@@ -344,7 +396,7 @@ impl IntersectionSimState {
                     old_stage.get_priority_of_turn(req.turn, signal) != TurnPriority::Protected
                 }) {
                     signal_state.extensions_count = 0;
-                    duration = advance(signal_state, signal, !ped_waiting);
+                    duration = signal_state.advance(now, signal, waiting, !ped_waiting);
                 } else {
                     signal_state.extensions_count += 1;
                     duration = delay;
@@ -357,6 +409,44 @@ impl IntersectionSimState {
                     ));
                 }
             }
+            StageType::Actuated {
+                passage_time,
+                max_green,
+                ..
+            } => {
+                let max_ends_at = signal_state.stage_started_at + max_green;
+                // Vehicles still waiting at the stop line are sitting on the detector.
+                let occupied = waiting.keys().any(|req| {
+                    matches!(req.agent, AgentID::Car(_))
+                        && old_stage.get_priority_of_turn(req.turn, signal)
+                            == TurnPriority::Protected
+                });
+                let gap_ends_at = if occupied {
+                    Some(now + passage_time)
+                } else {
+                    signal_state
+                        .last_detection(old_stage, signal)
+                        .map(|t| t + passage_time)
+                        .filter(|t| *t > now)
+                };
+
+                if now >= max_ends_at {
+                    self.events.push(Event::Alert(
+                        AlertLocation::Intersection(id),
+                        format!(
+                            "Actuated stage {} maxed out after {}",
+                            signal_state.current_stage + 1,
+                            max_green
+                        ),
+                    ));
+                    duration = signal_state.advance(now, signal, waiting, !ped_waiting);
+                } else if let Some(t) = gap_ends_at {
+                    duration = t.min(max_ends_at) - now;
+                } else {
+                    // Gap out
+                    duration = signal_state.advance(now, signal, waiting, !ped_waiting);
+                }
+            }
         }
 
         signal_state.stage_ends_at = now + duration;
@@ -364,6 +454,163 @@ impl IntersectionSimState {
         self.wakeup_waiting(now, id, scheduler, map);
     }
 
+    /// A bus or emergency vehicle just started down a lane towards a traffic signal, expecting to
+    /// reach it at `eta` and then make some turn. Maybe change the signal for it.
+    pub fn vehicle_approaching_signal(
+        &mut self,
+        now: Time,
+        car: CarID,
+        route: Option<BusRouteID>,
+        turn: TurnID,
+        eta: Time,
+        map: &Map,
+        scheduler: &mut Scheduler,
+    ) {
+        let signal = match map.maybe_get_traffic_signal(turn.parent) {
+            Some(signal) => signal,
+            None => {
+                return;
+            }
+        };
+        if self.use_freeform_policy_everywhere {
+            return;
+        }
+        let state = self.state.get_mut(&turn.parent).unwrap();
+        let waiting = &state.waiting;
+        let signal_state = state.signal.as_mut().unwrap();
+        if signal_state.preempted_by.is_some() {
+            return;
+        }
+        let movement = signal.turn_to_movement(turn);
+        let serves = |idx: usize| {
+            signal.stages[idx].get_priority_of_movement(movement) != TurnPriority::Banned
+        };
+        // Rough estimate of how many agents are affected by changing the signal
+        let num_waiting = |served: bool, idx: usize| {
+            waiting
+                .keys()
+                .filter(|req| {
+                    (signal.stages[idx].get_priority_of_turn(req.turn, signal)
+                        != TurnPriority::Banned)
+                        == served
+                })
+                .count() as f64
+        };
+        let current = signal_state.current_stage;
+
+        let (action, time_saved, delay_imposed) = if car.vehicle_type == VehicleType::Emergency {
+            if !self.emergency_preemption {
+                return;
+            }
+            // Prefer a stage where the turn is protected
+            let target = match (0..signal.stages.len())
+                .find(|idx| {
+                    signal.stages[*idx].get_priority_of_movement(movement)
+                        == TurnPriority::Protected
+                })
+                .or_else(|| (0..signal.stages.len()).find(|idx| serves(*idx)))
+            {
+                Some(idx) => idx,
+                None => {
+                    return;
+                }
+            };
+            // How long would the vehicle have waited for its stage?
+            let time_saved = if target == current && eta < signal_state.stage_ends_at {
+                Duration::ZERO
+            } else {
+                (signal_state.time_until_stage(now, signal, target) - (eta - now))
+                    .max(Duration::ZERO)
+            };
+            signal_state.preempted_by = Some((car, now));
+            if target != current {
+                signal_state.current_stage = target;
+                signal_state.stage_started_at = now;
+                signal_state.extensions_count = 0;
+                signal_state.priority_extension = Duration::ZERO;
+            }
+            signal_state.stage_ends_at = now + PREEMPTION_CHECK;
+            (
+                SignalPriorityAction::Preempt,
+                time_saved,
+                time_saved * num_waiting(false, target),
+            )
+        } else {
+            let tsp = match (&self.transit_signal_priority, route) {
+                (Some(tsp), Some(r)) if tsp.routes.contains(&map.get_br(r).short_name) => tsp,
+                _ => {
+                    return;
+                }
+            };
+            match signal.stages[current].stage_type {
+                StageType::Fixed(_) | StageType::Adaptive { .. } => {}
+                StageType::Variable(_, _, _) | StageType::Actuated { .. } => {
+                    return;
+                }
+            }
+
+            if serves(current) {
+                // The bus would make it anyway
+                if eta < signal_state.stage_ends_at {
+                    return;
+                }
+                // Without the extension, the bus would've waited for the stage to come around
+                // again.
+                let time_saved = (signal_state.time_until_stage(now, signal, current)
+                    - (eta - now))
+                    .max(Duration::ZERO);
+                let extension = match signal_state
+                    .extend_for_priority(eta + PRIORITY_MARGIN, tsp.max_extension)
+                {
+                    Some(extension) => extension,
+                    None => {
+                        return;
+                    }
+                };
+                (
+                    SignalPriorityAction::ExtendGreen,
+                    time_saved,
+                    extension * num_waiting(false, current),
+                )
+            } else {
+                let next = (current + 1) % signal.stages.len();
+                if !serves(next) || eta >= signal_state.stage_ends_at {
+                    return;
+                }
+                let new_end = (now
+                    + (signal_state.stage_ends_at - now - tsp.max_truncation).max(Duration::ZERO))
+                .max(eta)
+                .max(signal_state.stage_started_at + signal.get_min_crossing_time(current));
+                if new_end >= signal_state.stage_ends_at {
+                    return;
+                }
+                let time_saved = signal_state.stage_ends_at - new_end;
+                signal_state.stage_ends_at = new_end;
+                (
+                    SignalPriorityAction::TruncateRed,
+                    time_saved,
+                    time_saved * num_waiting(true, current),
+                )
+            }
+        };
+
+        scheduler.update(
+            signal_state.stage_ends_at,
+            Command::UpdateIntersection(turn.parent),
+        );
+        self.events.push(Event::SignalPriority {
+            intersection: turn.parent,
+            vehicle: car,
+            route,
+            action,
+            time_saved,
+            delay_imposed,
+        });
+        if action == SignalPriorityAction::Preempt {
+            self.wakeup_waiting(now, turn.parent, scheduler, map);
+        }
+    }
+
     /// For cars: The head car calls this when they're at the end of the lane WaitingToAdvance. If
     /// this returns true, then the head car MUST actually start this turn.
     /// For peds: Likewise -- only called when the ped is at the start of the turn. They must
@@ -403,6 +650,11 @@ impl IntersectionSimState {
             false
         };
         entry.or_insert((now, urgent));
+        if let AgentID::Car(_) = agent {
+            if let Some(ref mut signal_state) = self.state.get_mut(&turn.parent).unwrap().signal {
+                signal_state.detections.insert(turn.src, now);
+            }
+        }
 
         if repeat_request {
             self.total_repeat_requests += 1;
@@ -557,6 +809,12 @@ impl IntersectionSimState {
         let state = self.state.get_mut(&turn.parent).unwrap();
         state.waiting.remove(&req).unwrap();
         state.accepted.insert(req);
+        if let Some(ref mut signal_state) = state.signal {
+            // The emergency vehicle is through, so the signal can resume its usual cycle.
+            if signal_state.preempted_by.map(|(c, _)| AgentID::Car(c)) == Some(agent) {
+                signal_state.preempted_by = None;
+            }
+        }
         if self.break_turn_conflict_cycles {
             if let AgentID::Car(car) = agent {
                 self.blocked_by.retain(|(c, _)| *c != car);
@@ -581,6 +839,7 @@ impl IntersectionSimState {
                 state.signal.as_mut(),
             ) {
                 (Some(ts), Some(signal_state)) => {
+                    signal_state.reset_adaptive_splits(ts);
                     if signal_state.current_stage >= ts.stages.len() {
                         // Just jump back to the first one. Shrug.
                         signal_state.current_stage = 0;
@@ -1034,9 +1293,16 @@ impl SignalState {
             current_stage: 0,
             stage_ends_at: now,
             extensions_count: 0,
+            stage_started_at: now,
+            priority_extension: Duration::ZERO,
+            detections: BTreeMap::new(),
+            measured_queues: Vec::new(),
+            adaptive_splits: Vec::new(),
+            preempted_by: None,
         };
 
         let signal = map.get_traffic_signal(id);
+        state.reset_adaptive_splits(signal);
         // What stage are we starting with?
         let mut offset = (now - Time::START_OF_DAY) + signal.offset;
         loop {
@@ -1057,6 +1323,138 @@ impl SignalState {
         scheduler.push(state.stage_ends_at, Command::UpdateIntersection(id));
         state
     }
+
+    /// Move to the next stage that should run, and return how long until the signal should be
+    /// checked again.
+    fn advance(
+        &mut self,
+        now: Time,
+        signal: &ControlTrafficSignal,
+        waiting: &BTreeMap<Request, (Time, bool)>,
+        allow_crosswalk_skip: bool,
+    ) -> Duration {
+        // If no stage has demand, we wind up resting in the current one.
+        for _ in 0..signal.stages.len() {
+            self.current_stage = (self.current_stage + 1) % signal.stages.len();
+            if self.current_stage == 0 {
+                self.resplit_adaptive(signal);
+            }
+            let stage = &signal.stages[self.current_stage];
+            let skip = match stage.stage_type {
+                // Only skip a variable all-walk stage, if no pedestrian is waiting
+                StageType::Variable(_, _, _) => {
+                    allow_crosswalk_skip && stage.max_crosswalk_time(&signal.movements).is_some()
+                }
+                // Nobody's waiting on the detectors or pushed the crosswalk button
+                StageType::Actuated { .. } => !waiting.keys().any(|req| {
+                    stage.get_priority_of_turn(req.turn, signal) == TurnPriority::Protected
+                }),
+                StageType::Fixed(_) | StageType::Adaptive { .. } => false,
+            };
+            if !skip {
+                break;
+            }
+        }
+
+        let stage = &signal.stages[self.current_stage];
+        self.stage_started_at = now;
+        self.priority_extension = Duration::ZERO;
+        self.measured_queues[self.current_stage] = waiting
+            .keys()
+            .filter(|req| stage.get_priority_of_turn(req.turn, signal) == TurnPriority::Protected)
+            .count();
+        match stage.stage_type {
+            StageType::Adaptive { .. } => self.adaptive_splits[self.current_stage],
+            _ => stage.stage_type.simple_duration(),
+        }
+    }
+
+    /// Extend the current stage until some time, so an approaching bus makes it through. Buses
+    /// following each other can't chain extensions past `max_extension` for the stage. Returns how
+    /// much the stage was extended by, if it was.
+    fn extend_for_priority(&mut self, until: Time, max_extension: Duration) -> Option<Duration> {
+        let extension = until - self.stage_ends_at;
+        if self.priority_extension + extension > max_extension {
+            return None;
+        }
+        self.priority_extension += extension;
+        self.stage_ends_at = until;
+        Some(extension)
+    }
+
+    /// Following the usual cycle, how long until some stage starts next?
+    fn time_until_stage(
+        &self,
+        now: Time,
+        signal: &ControlTrafficSignal,
+        target: usize,
+    ) -> Duration {
+        let mut total = self.stage_ends_at - now;
+        let mut idx = (self.current_stage + 1) % signal.stages.len();
+        while idx != target {
+            total += match signal.stages[idx].stage_type {
+                StageType::Adaptive { .. } => self.adaptive_splits[idx],
+                ref stage_type => stage_type.simple_duration(),
+            };
+            idx = (idx + 1) % signal.stages.len();
+        }
+        total
+    }
+
+    /// The last time a vehicle was detected on any lane approaching a protected movement of the
+    /// stage.
+    fn last_detection(&self, stage: &Stage, signal: &ControlTrafficSignal) -> Option<Time> {
+        stage
+            .protected_movements
+            .iter()
+            .filter(|m| !m.crosswalk)
+            .flat_map(|m| signal.movements[m].members.iter())
+            .filter_map(|t| self.detections.get(&t.src))
+            .max()
+            .cloned()
+    }
+
+    /// Split the time for adaptive stages in proportion to the queues measured during the last
+    /// cycle.
+    fn resplit_adaptive(&mut self, signal: &ControlTrafficSignal) {
+        let mut budget = Duration::ZERO;
+        let mut total_min = Duration::ZERO;
+        let mut total_queue = 0;
+        for (idx, stage) in signal.stages.iter().enumerate() {
+            if let StageType::Adaptive {
+                min_green,
+                max_green,
+            } = stage.stage_type
+            {
+                budget += (min_green + max_green) / 2.0;
+                total_min += min_green;
+                total_queue += self.measured_queues[idx];
+            }
+        }
+        let spare = budget - total_min;
+
+        for (idx, stage) in signal.stages.iter().enumerate() {
+            if let StageType::Adaptive {
+                min_green,
+                max_green,
+            } = stage.stage_type
+            {
+                self.adaptive_splits[idx] = if total_queue == 0 {
+                    (min_green + max_green) / 2.0
+                } else {
+                    let share = self.measured_queues[idx] as f64 / total_queue as f64;
+                    (min_green + spare * share).min(max_green)
+                };
+            }
+        }
+    }
+
+    /// Forget about measured queues, and give every adaptive stage its usual time.
+    fn reset_adaptive_splits(&mut self, signal: &ControlTrafficSignal) {
+        self.measured_queues = vec![0; signal.stages.len()];
+        self.adaptive_splits = vec![Duration::ZERO; signal.stages.len()];
+        self.resplit_adaptive(signal);
+    }
 }
 
 fn allow_block_the_box(i: &Intersection) -> bool {
@@ -1108,3 +1506,48 @@ fn allow_block_the_box(i: &Intersection) -> bool {
     }
     false
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extend_for_priority() {
+        let start = Time::START_OF_DAY;
+        let mut state = SignalState {
+            current_stage: 0,
+            stage_ends_at: start + Duration::seconds(30.0),
+            extensions_count: 0,
+            stage_started_at: start,
+            priority_extension: Duration::ZERO,
+            detections: BTreeMap::new(),
+            measured_queues: Vec::new(),
+            adaptive_splits: Vec::new(),
+            preempted_by: None,
+        };
+        let max = Duration::seconds(10.0);
+
+        // One bus after another can extend the stage, until the total hits the cap
+        assert_eq!(
+            state.extend_for_priority(start + Duration::seconds(36.0), max),
+            Some(Duration::seconds(6.0))
+        );
+        assert_eq!(
+            state.extend_for_priority(start + Duration::seconds(40.0), max),
+            Some(Duration::seconds(4.0))
+        );
+        assert_eq!(
+            state.extend_for_priority(start + Duration::seconds(41.0), max),
+            None
+        );
+        assert_eq!(state.stage_ends_at, start + Duration::seconds(40.0));
+
+        // A bus asking for too much up front doesn't get a partial extension either
+        state.priority_extension = Duration::ZERO;
+        assert_eq!(
+            state.extend_for_priority(start + Duration::seconds(55.0), max),
+            None
+        );
+        assert_eq!(state.stage_ends_at, start + Duration::seconds(40.0));
+    }
+}
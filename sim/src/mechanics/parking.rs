@@ -34,6 +34,9 @@ pub trait ParkingSim {
     fn unreserve_spot(&mut self, car: CarID);
     fn remove_parked_car(&mut self, p: ParkedCar);
     fn add_parked_car(&mut self, p: ParkedCar);
+    /// Like add_parked_car, but for cars that start the simulation parked, instead of arriving
+    /// somewhere.
+    fn seed_parked_car(&mut self, p: ParkedCar);
     fn get_draw_cars(&self, id: LaneID, map: &Map) -> Vec<DrawCarInput>;
     fn get_draw_cars_in_lots(&self, id: LaneID, map: &Map) -> Vec<DrawCarInput>;
     fn get_draw_car(&self, id: CarID, map: &Map) -> Option<DrawCarInput>;
@@ -53,6 +56,15 @@ pub trait ParkingSim {
         target: BuildingID,
         map: &Map,
     ) -> Vec<(ParkingSpot, Position)>;
+    /// Like `get_all_free_spots`, but including spots in permit zones the target doesn't belong
+    /// to.
+    fn get_all_free_spots_ignoring_permits(
+        &self,
+        driving_pos: Position,
+        vehicle: &Vehicle,
+        target: BuildingID,
+        map: &Map,
+    ) -> Vec<(ParkingSpot, Position)>;
     fn spot_to_driving_pos(&self, spot: ParkingSpot, vehicle: &Vehicle, map: &Map) -> Position;
     fn spot_to_sidewalk_pos(&self, spot: ParkingSpot, map: &Map) -> Position;
     fn get_owner_of_car(&self, id: CarID) -> Option<PersonID>;
@@ -72,6 +84,15 @@ pub trait ParkingSim {
         target: BuildingID,
         map: &Map,
     ) -> Option<(Vec<PathStep>, ParkingSpot, Position)>;
+    /// Like `path_to_free_parking_spot`, but including spots in permit zones the target doesn't
+    /// belong to.
+    fn path_to_free_parking_spot_ignoring_permits(
+        &self,
+        start: LaneID,
+        vehicle: &Vehicle,
+        target: BuildingID,
+        map: &Map,
+    ) -> Option<(Vec<PathStep>, ParkingSpot, Position)>;
     fn collect_events(&mut self) -> Vec<Event>;
     fn all_parked_car_positions(&self, map: &Map) -> Vec<(Position, PersonID)>;
     fn bldg_to_parked_cars(&self, b: BuildingID) -> Vec<CarID>;
@@ -211,6 +232,149 @@ impl NormalParkingSimState {
 
         sim
     }
+
+    fn park(&mut self, p: ParkedCar) {
+        assert_eq!(self.reserved_spots.remove(&p.spot), Some(p.vehicle.id));
+
+        assert!(!self.occupants.contains_key(&p.spot));
+        self.occupants.insert(p.spot, p.vehicle.id);
+
+        assert!(!self.parked_cars.contains_key(&p.vehicle.id));
+        self.parked_cars.insert(p.vehicle.id, p);
+    }
+
+    /// Free spots ahead of the vehicle, ignoring permit zones
+    fn free_spots(
+        &self,
+        driving_pos: Position,
+        vehicle: &Vehicle,
+        // Either the building where a seeded car starts or the target of a trip. For filtering
+        // private spots.
+        target: BuildingID,
+        map: &Map,
+    ) -> Vec<ParkingSpot> {
+        let mut candidates = Vec::new();
+
+        for l in self.driving_to_parking_lanes.get(driving_pos.lane()) {
+            for spot in self.onstreet_lanes[l].spots() {
+                if self.is_free(spot)
+                    && driving_pos.dist_along()
+                        <= self.spot_to_driving_pos(spot, vehicle, map).dist_along()
+                {
+                    candidates.push(spot);
+                }
+            }
+        }
+
+        for (b, bldg_dist) in self.driving_to_offstreet.get(driving_pos.lane()) {
+            if let OffstreetParking::Private(_, _) = map.get_b(*b).parking {
+                if target != *b {
+                    continue;
+                }
+            }
+            if driving_pos.dist_along() < *bldg_dist {
+                for idx in 0..self.num_spots_per_offstreet[b] {
+                    let spot = ParkingSpot::Offstreet(*b, idx);
+                    if self.is_free(spot) {
+                        candidates.push(spot);
+                    }
+                }
+            }
+        }
+
+        for pl in self.driving_to_lots.get(driving_pos.lane()) {
+            let lot_dist = map.get_pl(*pl).driving_pos.dist_along();
+            if driving_pos.dist_along() < lot_dist {
+                for idx in 0..self.num_spots_per_lot[pl] {
+                    let spot = ParkingSpot::Lot(*pl, idx);
+                    if self.is_free(spot) {
+                        candidates.push(spot);
+                    }
+                }
+            }
+        }
+
+        candidates
+    }
+
+    fn path_to_free_spot(
+        &self,
+        start: LaneID,
+        vehicle: &Vehicle,
+        target: BuildingID,
+        ignore_permits: bool,
+        map: &Map,
+    ) -> Option<(Vec<PathStep>, ParkingSpot, Position)> {
+        let mut backrefs: HashMap<LaneID, TurnID> = HashMap::new();
+        // Don't travel far.
+        // This is a max-heap, so negate all distances. Tie breaker is lane ID, arbitrary but
+        // deterministic.
+        let mut queue: BinaryHeap<(Distance, LaneID)> = BinaryHeap::new();
+        queue.push((Distance::ZERO, start));
+
+        // We need a source of randomness between different cars, but it needs to be deterministic
+        // across repeated runs of the exact same simulation. This also shouldn't be the same
+        // starting seed for one vehicle across different decisions through the simulation, because
+        // then they might always prefer the first or third turn the most or whatever.
+        let mut rng = XorShiftRng::seed_from_u64((vehicle.id.id + start.0) as u64);
+
+        while !queue.is_empty() {
+            let (dist_so_far, current) = queue.pop().unwrap();
+            // If the current lane has a spot open, we wouldn't be asking. This can happen if a spot
+            // opens up on the 'start' lane, but behind the car.
+            if current != start {
+                // Pick the closest to the start of the lane, since that's closest to where we came
+                // from, but consider the price too
+                let spots = if ignore_permits {
+                    self.get_all_free_spots_ignoring_permits(
+                        Position::start(current),
+                        vehicle,
+                        target,
+                        map,
+                    )
+                } else {
+                    self.get_all_free_spots(Position::start(current), vehicle, target, map)
+                };
+                if let Some((spot, pos)) = spots
+                    .into_iter()
+                    .min_by_key(|(spot, pos)| pos.dist_along() + spot.price_as_distance(map))
+                {
+                    let mut steps = vec![PathStep::Lane(current)];
+                    let mut current = current;
+                    loop {
+                        if current == start {
+                            // Don't include PathStep::Lane(start)
+                            steps.pop();
+                            steps.reverse();
+                            return Some((steps, spot, pos));
+                        }
+                        let turn = backrefs[&current];
+                        steps.push(PathStep::Turn(turn));
+                        steps.push(PathStep::Lane(turn.src));
+                        current = turn.src;
+                    }
+                }
+            }
+            for turn in map.get_turns_for(current, PathConstraints::Car) {
+                if let Entry::Vacant(e) = backrefs.entry(turn.id.dst) {
+                    let dist_this_step = turn.geom.length() + map.get_l(current).length();
+                    // When vehicles search away from the first lane for a spot, don't all go in
+                    // the same direction! Do this by jittering which turn they explore.
+                    // At worst, they consider a route to be 10% of its true length, so somebody
+                    // might go up to 10x farther than necessary. From some quick tests, these
+                    // worst cases aren't happening -- because it'd be unlikely to roll a higher
+                    // number here many times in a row, and if there are only a few lanes away, it
+                    // doesn't matter that much anyway.
+                    let jitter = rng.gen_range(0.1..0.9);
+                    e.insert(turn.id);
+                    // Remember, keep things negative
+                    queue.push((dist_so_far - jitter * dist_this_step, turn.id.dst));
+                }
+            }
+        }
+
+        None
+    }
 }
 
 impl ParkingSim for NormalParkingSimState {
@@ -236,6 +400,7 @@ impl ParkingSim for NormalParkingSimState {
                 // If the spot isn't occupied, it must be reserved; a car is in the process of
                 // parking in it. That'll be handled below.
                 if let Some(car) = self.occupants.remove(&spot) {
+                    self.events.push(Event::CarLeftParkingSpot(car, spot));
                     evicted.push(self.parked_cars.remove(&car).unwrap());
                 }
             }
@@ -324,14 +489,13 @@ impl ParkingSim for NormalParkingSimState {
     fn add_parked_car(&mut self, p: ParkedCar) {
         self.events
             .push(Event::CarReachedParkingSpot(p.vehicle.id, p.spot));
+        self.park(p);
+    }
 
-        assert_eq!(self.reserved_spots.remove(&p.spot), Some(p.vehicle.id));
-
-        assert!(!self.occupants.contains_key(&p.spot));
-        self.occupants.insert(p.spot, p.vehicle.id);
-
-        assert!(!self.parked_cars.contains_key(&p.vehicle.id));
-        self.parked_cars.insert(p.vehicle.id, p);
+    fn seed_parked_car(&mut self, p: ParkedCar) {
+        self.events
+            .push(Event::CarSeededInParkingSpot(p.vehicle.id, p.spot));
+        self.park(p);
     }
 
     fn get_draw_cars(&self, id: LaneID, map: &Map) -> Vec<DrawCarInput> {
@@ -443,53 +607,24 @@ impl ParkingSim for NormalParkingSimState {
         &self,
         driving_pos: Position,
         vehicle: &Vehicle,
-        // Either the building where a seeded car starts or the target of a trip. For filtering
-        // private spots.
         target: BuildingID,
         map: &Map,
     ) -> Vec<(ParkingSpot, Position)> {
-        let mut candidates = Vec::new();
-
-        for l in self.driving_to_parking_lanes.get(driving_pos.lane()) {
-            for spot in self.onstreet_lanes[l].spots() {
-                if self.is_free(spot)
-                    && driving_pos.dist_along()
-                        <= self.spot_to_driving_pos(spot, vehicle, map).dist_along()
-                {
-                    candidates.push(spot);
-                }
-            }
-        }
-
-        for (b, bldg_dist) in self.driving_to_offstreet.get(driving_pos.lane()) {
-            if let OffstreetParking::Private(_, _) = map.get_b(*b).parking {
-                if target != *b {
-                    continue;
-                }
-            }
-            if driving_pos.dist_along() < *bldg_dist {
-                for idx in 0..self.num_spots_per_offstreet[b] {
-                    let spot = ParkingSpot::Offstreet(*b, idx);
-                    if self.is_free(spot) {
-                        candidates.push(spot);
-                    }
-                }
-            }
-        }
-
-        for pl in self.driving_to_lots.get(driving_pos.lane()) {
-            let lot_dist = map.get_pl(*pl).driving_pos.dist_along();
-            if driving_pos.dist_along() < lot_dist {
-                for idx in 0..self.num_spots_per_lot[pl] {
-                    let spot = ParkingSpot::Lot(*pl, idx);
-                    if self.is_free(spot) {
-                        candidates.push(spot);
-                    }
-                }
-            }
-        }
+        self.free_spots(driving_pos, vehicle, target, map)
+            .into_iter()
+            .filter(|spot| spot.permits(target, map))
+            .map(|spot| (spot, self.spot_to_driving_pos(spot, vehicle, map)))
+            .collect()
+    }
 
-        candidates
+    fn get_all_free_spots_ignoring_permits(
+        &self,
+        driving_pos: Position,
+        vehicle: &Vehicle,
+        target: BuildingID,
+        map: &Map,
+    ) -> Vec<(ParkingSpot, Position)> {
+        self.free_spots(driving_pos, vehicle, target, map)
             .into_iter()
             .map(|spot| (spot, self.spot_to_driving_pos(spot, vehicle, map)))
             .collect()
@@ -565,66 +700,17 @@ impl ParkingSim for NormalParkingSimState {
         target: BuildingID,
         map: &Map,
     ) -> Option<(Vec<PathStep>, ParkingSpot, Position)> {
-        let mut backrefs: HashMap<LaneID, TurnID> = HashMap::new();
-        // Don't travel far.
-        // This is a max-heap, so negate all distances. Tie breaker is lane ID, arbitrary but
-        // deterministic.
-        let mut queue: BinaryHeap<(Distance, LaneID)> = BinaryHeap::new();
-        queue.push((Distance::ZERO, start));
-
-        // We need a source of randomness between different cars, but it needs to be deterministic
-        // across repeated runs of the exact same simulation. This also shouldn't be the same
-        // starting seed for one vehicle across different decisions through the simulation, because
-        // then they might always prefer the first or third turn the most or whatever.
-        let mut rng = XorShiftRng::seed_from_u64((vehicle.id.id + start.0) as u64);
-
-        while !queue.is_empty() {
-            let (dist_so_far, current) = queue.pop().unwrap();
-            // If the current lane has a spot open, we wouldn't be asking. This can happen if a spot
-            // opens up on the 'start' lane, but behind the car.
-            if current != start {
-                // Pick the closest to the start of the lane, since that's closest to where we came
-                // from
-                if let Some((spot, pos)) = self
-                    .get_all_free_spots(Position::start(current), vehicle, target, map)
-                    .into_iter()
-                    .min_by_key(|(_, pos)| pos.dist_along())
-                {
-                    let mut steps = vec![PathStep::Lane(current)];
-                    let mut current = current;
-                    loop {
-                        if current == start {
-                            // Don't include PathStep::Lane(start)
-                            steps.pop();
-                            steps.reverse();
-                            return Some((steps, spot, pos));
-                        }
-                        let turn = backrefs[&current];
-                        steps.push(PathStep::Turn(turn));
-                        steps.push(PathStep::Lane(turn.src));
-                        current = turn.src;
-                    }
-                }
-            }
-            for turn in map.get_turns_for(current, PathConstraints::Car) {
-                if let Entry::Vacant(e) = backrefs.entry(turn.id.dst) {
-                    let dist_this_step = turn.geom.length() + map.get_l(current).length();
-                    // When vehicles search away from the first lane for a spot, don't all go in
-                    // the same direction! Do this by jittering which turn they explore.
-                    // At worst, they consider a route to be 10% of its true length, so somebody
-                    // might go up to 10x farther than necessary. From some quick tests, these
-                    // worst cases aren't happening -- because it'd be unlikely to roll a higher
-                    // number here many times in a row, and if there are only a few lanes away, it
-                    // doesn't matter that much anyway.
-                    let jitter = rng.gen_range(0.1..0.9);
-                    e.insert(turn.id);
-                    // Remember, keep things negative
-                    queue.push((dist_so_far - jitter * dist_this_step, turn.id.dst));
-                }
-            }
-        }
+        self.path_to_free_spot(start, vehicle, target, false, map)
+    }
 
-        None
+    fn path_to_free_parking_spot_ignoring_permits(
+        &self,
+        start: LaneID,
+        vehicle: &Vehicle,
+        target: BuildingID,
+        map: &Map,
+    ) -> Option<(Vec<PathStep>, ParkingSpot, Position)> {
+        self.path_to_free_spot(start, vehicle, target, true, map)
     }
 
     fn collect_events(&mut self) -> Vec<Event> {
@@ -632,13 +718,13 @@ impl ParkingSim for NormalParkingSimState {
     }
 
     fn all_parked_car_positions(&self, map: &Map) -> Vec<(Position, PersonID)> {
+        // Ride-hailing vehicles have no owner
         self.parked_cars
             .values()
-            .map(|p| {
-                (
-                    self.spot_to_sidewalk_pos(p.spot, map),
-                    p.vehicle.owner.unwrap(),
-                )
+            .filter_map(|p| {
+                p.vehicle
+                    .owner
+                    .map(|owner| (self.spot_to_sidewalk_pos(p.spot, map), owner))
             })
             .collect()
     }
@@ -830,6 +916,20 @@ impl InfiniteParkingSimState {
             i += 1;
         }
     }
+
+    fn park(&mut self, p: ParkedCar) {
+        assert_eq!(self.reserved_spots.remove(&p.spot), Some(p.vehicle.id));
+
+        assert!(!self.occupants.contains_key(&p.spot));
+        self.occupants.insert(p.spot, p.vehicle.id);
+
+        if let ParkingSpot::Offstreet(b, _) = p.spot {
+            *self.num_occupants_per_offstreet.entry(b).or_insert(0) += 1;
+        }
+
+        assert!(!self.parked_cars.contains_key(&p.vehicle.id));
+        self.parked_cars.insert(p.vehicle.id, p);
+    }
 }
 
 impl ParkingSim for InfiniteParkingSimState {
@@ -882,18 +982,13 @@ impl ParkingSim for InfiniteParkingSimState {
     fn add_parked_car(&mut self, p: ParkedCar) {
         self.events
             .push(Event::CarReachedParkingSpot(p.vehicle.id, p.spot));
+        self.park(p);
+    }
 
-        assert_eq!(self.reserved_spots.remove(&p.spot), Some(p.vehicle.id));
-
-        assert!(!self.occupants.contains_key(&p.spot));
-        self.occupants.insert(p.spot, p.vehicle.id);
-
-        if let ParkingSpot::Offstreet(b, _) = p.spot {
-            *self.num_occupants_per_offstreet.entry(b).or_insert(0) += 1;
-        }
-
-        assert!(!self.parked_cars.contains_key(&p.vehicle.id));
-        self.parked_cars.insert(p.vehicle.id, p);
+    fn seed_parked_car(&mut self, p: ParkedCar) {
+        self.events
+            .push(Event::CarSeededInParkingSpot(p.vehicle.id, p.spot));
+        self.park(p);
     }
 
     fn get_draw_cars(&self, _: LaneID, _: &Map) -> Vec<DrawCarInput> {
@@ -959,6 +1054,17 @@ impl ParkingSim for InfiniteParkingSimState {
         }
     }
 
+    // Buildings don't belong to permit zones
+    fn get_all_free_spots_ignoring_permits(
+        &self,
+        driving_pos: Position,
+        vehicle: &Vehicle,
+        target: BuildingID,
+        map: &Map,
+    ) -> Vec<(ParkingSpot, Position)> {
+        self.get_all_free_spots(driving_pos, vehicle, target, map)
+    }
+
     fn spot_to_driving_pos(&self, spot: ParkingSpot, _: &Vehicle, map: &Map) -> Position {
         match spot {
             ParkingSpot::Offstreet(b, _) => map.get_b(b).driving_connection(map).unwrap().0,
@@ -1041,18 +1147,28 @@ impl ParkingSim for InfiniteParkingSimState {
         None
     }
 
+    fn path_to_free_parking_spot_ignoring_permits(
+        &self,
+        start: LaneID,
+        vehicle: &Vehicle,
+        target: BuildingID,
+        map: &Map,
+    ) -> Option<(Vec<PathStep>, ParkingSpot, Position)> {
+        self.path_to_free_parking_spot(start, vehicle, target, map)
+    }
+
     fn collect_events(&mut self) -> Vec<Event> {
         std::mem::take(&mut self.events)
     }
 
     fn all_parked_car_positions(&self, map: &Map) -> Vec<(Position, PersonID)> {
+        // Ride-hailing vehicles have no owner
         self.parked_cars
             .values()
-            .map(|p| {
-                (
-                    self.spot_to_sidewalk_pos(p.spot, map),
-                    p.vehicle.owner.unwrap(),
-                )
+            .filter_map(|p| {
+                p.vehicle
+                    .owner
+                    .map(|owner| (self.spot_to_sidewalk_pos(p.spot, map), owner))
             })
             .collect()
     }
@@ -128,8 +128,11 @@ impl Queue {
         // TODO Consider simplifying this loop's structure. Calculate the bound here before
         // starting the loop, handling the laggy head case.
         let mut previous: Option<QueueEntry> = None;
+        // Usually the same as previous, except for vehicles still passing somebody through an
+        // oncoming lane. They don't hold back the vehicle they're passing.
+        let mut bounded_by: Option<QueueEntry> = None;
         for queued in self.members.iter().cloned() {
-            let bound = match previous {
+            let bound = match bounded_by {
                 Some(entry) => entry.back - FOLLOWING_DISTANCE,
                 None => match self.laggy_head {
                     Some(id) => {
@@ -231,11 +234,17 @@ impl Queue {
                         CarState::Crossing {
                             ref time_int,
                             ref dist_int,
+                            ref profile,
                             ..
                         } => {
                             // TODO Why percent_clamp_end? We process car updates in any order, so we might
                             // calculate this before moving this car from Crossing to another state.
-                            dist_int.lerp(time_int.percent_clamp_end(now)).min(bound)
+                            let percent_time = time_int.percent_clamp_end(now);
+                            let percent_dist = match profile {
+                                Some(p) => p.percent_dist(percent_time),
+                                None => percent_time,
+                            };
+                            dist_int.lerp(percent_dist).min(bound)
                         }
                         CarState::ChangingLanes {
                             ref new_time,
@@ -276,6 +285,13 @@ impl Queue {
             if let Some(ref mut intermediate_results) = intermediate_results {
                 intermediate_results.push(entry.clone());
             }
+            let overtaking = match entry.member {
+                Queued::Vehicle(id) => cars[&id].is_overtaking(now),
+                _ => false,
+            };
+            if !overtaking {
+                bounded_by = Some(entry.clone());
+            }
             previous = Some(entry);
         }
         // Enable to detect possible bugs, but save time otherwise
@@ -460,6 +476,15 @@ impl Queue {
         None
     }
 
+    /// Find the vehicle that a car entering the end of this queue would follow. None if the queue
+    /// is empty or ends with a blockage.
+    pub fn get_last_car(&self) -> Option<CarID> {
+        match self.members.back() {
+            Some(Queued::Vehicle(car)) => Some(*car),
+            _ => None,
+        }
+    }
+
     /// Record that a car is blocking a static portion of the queue (from front to back). Must use
     /// the index from can_block_from_driveway.
     pub fn add_static_blockage(
@@ -549,6 +574,13 @@ impl Queue {
         assert_eq!(self.members.remove(idx), Some(Queued::Vehicle(car)));
     }
 
+    /// A car at some index overtook the vehicle immediately in front of it, so swap their order.
+    pub fn swap_with_leader(&mut self, car: CarID, idx: usize) {
+        assert_eq!(self.members[idx], Queued::Vehicle(car));
+        assert!(matches!(self.members[idx - 1], Queued::Vehicle(_)));
+        self.members.swap(idx - 1, idx);
+    }
+
     /// If a car thinks it's reached the end of the queue, double check. Blockages or laggy heads
     /// might be in the way.
     pub fn is_car_at_front(&self, car: CarID) -> bool {
@@ -562,13 +594,23 @@ fn validate_positions(
     now: Time,
     id: Traversable,
 ) {
-    for pair in dists.windows(2) {
-        if pair[0].back - FOLLOWING_DISTANCE < pair[1].front {
-            dump_cars(dists, cars, id, now);
-            panic!(
-                "get_car_positions wound up with bad positioning: {} then {}\n{:?}",
-                pair[0].front, pair[1].front, dists
-            );
+    let mut leader: Option<&QueueEntry> = None;
+    for entry in dists {
+        if let Some(leader) = leader {
+            if leader.back - FOLLOWING_DISTANCE < entry.front {
+                dump_cars(dists, cars, id, now);
+                panic!(
+                    "get_car_positions wound up with bad positioning: {} then {}\n{:?}",
+                    leader.front, entry.front, dists
+                );
+            }
+        }
+        let overtaking = match entry.member {
+            Queued::Vehicle(id) => cars[&id].is_overtaking(now),
+            _ => false,
+        };
+        if !overtaking {
+            leader = Some(entry);
         }
     }
 }
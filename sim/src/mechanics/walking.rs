@@ -14,15 +14,27 @@ use crate::{
     AgentID, AgentProperties, Command, CommutersVehiclesCounts, CreatePedestrian, DistanceInterval,
     DrawPedCrowdInput, DrawPedestrianInput, Event, Intent, IntersectionSimState, ParkedCar,
     ParkingSpot, PedCrowdLocation, PedestrianID, PersonID, Scheduler, SidewalkPOI, SidewalkSpot,
-    TimeInterval, TransitSimState, TripID, TripManager, UnzoomedAgent,
+    SimOptions, TimeInterval, TransitSimState, TripID, TripManager, UnzoomedAgent,
 };
 
 const TIME_TO_START_BIKING: Duration = Duration::const_seconds(30.0);
 const TIME_TO_FINISH_BIKING: Duration = Duration::const_seconds(45.0);
 
+/// People can't pack together any more densely than this, in people per square meter.
+const JAM_DENSITY: f64 = 5.4;
+/// Even in a jammed crowd, people eventually shuffle along.
+const MIN_CROWD_SPEED_FACTOR: f64 = 0.1;
+/// When measuring density, treat very short sidewalks as at least this long. Otherwise just two
+/// people on a tiny sidewalk between two intersections would look like a huge crowd.
+const MIN_CROWD_LENGTH: Distance = Distance::const_meters(10.0);
+
 /// Simulates pedestrians. Unlike vehicles, pedestrians can move bidirectionally on sidewalks and
-/// just "ghost" through each other. There's no queueing or slowdown when many people are
-/// overlapping. They're simply grouped together into a DrawPedCrowdInput for rendering.
+/// just "ghost" through each other. There's no queueing when many people are overlapping. They're
+/// simply grouped together into a DrawPedCrowdInput for rendering.
+///
+/// If crowding is enabled, people walk more slowly when a sidewalk or crosswalk is dense. Whenever
+/// somebody enters or leaves, everybody else walking along it speeds up or slows down from
+/// wherever they are.
 #[derive(Serialize, Deserialize, Clone)]
 pub(crate) struct WalkingSimState {
     peds: FixedMap<PedestrianID, Pedestrian>,
@@ -32,14 +44,16 @@ pub(crate) struct WalkingSimState {
     )]
     peds_per_traversable: MultiMap<Traversable, PedestrianID>,
     events: Vec<Event>,
+    crowding: bool,
 }
 
 impl WalkingSimState {
-    pub fn new() -> WalkingSimState {
+    pub fn new(opts: &SimOptions) -> WalkingSimState {
         WalkingSimState {
             peds: FixedMap::new(),
             peds_per_traversable: MultiMap::new(),
             events: Vec::new(),
+            crowding: opts.pedestrian_crowding,
         }
     }
 
@@ -57,6 +71,14 @@ impl WalkingSimState {
             params.goal.sidewalk_pos.lane()
         );
 
+        let start_on = Traversable::Lane(start_lane);
+        self.peds_per_traversable.insert(start_on, params.id);
+        let crowds = if self.crowding {
+            Some(&self.peds_per_traversable)
+        } else {
+            None
+        };
+
         let mut ped = Pedestrian {
             id: params.id,
             // Temporary bogus thing
@@ -93,15 +115,12 @@ impl WalkingSimState {
                 Line::must_new(driving_pos.pt(map), params.start.sidewalk_pos.pt(map)),
                 TimeInterval::new(now, now + TIME_TO_FINISH_BIKING),
             ),
-            _ => ped.crossing_state(params.start.sidewalk_pos.dist_along(), now, map),
+            _ => ped.crossing_state(params.start.sidewalk_pos.dist_along(), now, map, crowds),
         };
 
         scheduler.push(ped.state.get_end_time(), Command::UpdatePed(ped.id));
         self.peds.insert(ped.id, ped);
-        self.peds_per_traversable.insert(
-            Traversable::Lane(params.start.sidewalk_pos.lane()),
-            params.id,
-        );
+        self.crowd_changed(start_on, now, map, scheduler);
     }
 
     pub fn get_draw_ped(
@@ -128,6 +147,33 @@ impl WalkingSimState {
         trips: &mut TripManager,
         transit: &mut TransitSimState,
     ) {
+        let old_on = self.peds[&id].path.current_step().as_traversable();
+        self.update_ped_state(id, now, ctx, trips, transit);
+        let new_on = self
+            .peds
+            .get(&id)
+            .map(|ped| ped.path.current_step().as_traversable());
+        if new_on != Some(old_on) {
+            self.crowd_changed(old_on, now, ctx.map, ctx.scheduler);
+            if let Some(on) = new_on {
+                self.crowd_changed(on, now, ctx.map, ctx.scheduler);
+            }
+        }
+    }
+
+    fn update_ped_state(
+        &mut self,
+        id: PedestrianID,
+        now: Time,
+        ctx: &mut Ctx,
+        trips: &mut TripManager,
+        transit: &mut TransitSimState,
+    ) {
+        let crowds = if self.crowding {
+            Some(&self.peds_per_traversable)
+        } else {
+            None
+        };
         let mut ped = self.peds.get_mut(&id).unwrap();
         match ped.state {
             PedState::Crossing { ref dist_int, .. } => {
@@ -233,6 +279,7 @@ impl WalkingSimState {
                         &mut self.peds_per_traversable,
                         &mut self.events,
                         ctx.scheduler,
+                        self.crowding,
                     ) {
                         ctx.scheduler
                             .push(ped.state.get_end_time(), Command::UpdatePed(ped.id));
@@ -250,6 +297,7 @@ impl WalkingSimState {
                     &mut self.peds_per_traversable,
                     &mut self.events,
                     ctx.scheduler,
+                    self.crowding,
                 ) {
                     ctx.scheduler
                         .push(ped.state.get_end_time(), Command::UpdatePed(ped.id));
@@ -263,8 +311,12 @@ impl WalkingSimState {
                 }
             }
             PedState::LeavingBuilding(b, _) => {
-                ped.state =
-                    ped.crossing_state(ctx.map.get_b(b).sidewalk_pos.dist_along(), now, ctx.map);
+                ped.state = ped.crossing_state(
+                    ctx.map.get_b(b).sidewalk_pos.dist_along(),
+                    now,
+                    ctx.map,
+                    crowds,
+                );
                 ctx.scheduler
                     .push(ped.state.get_end_time(), Command::UpdatePed(ped.id));
             }
@@ -282,8 +334,12 @@ impl WalkingSimState {
                 self.peds.remove(&id);
             }
             PedState::LeavingParkingLot(pl, _) => {
-                ped.state =
-                    ped.crossing_state(ctx.map.get_pl(pl).sidewalk_pos.dist_along(), now, ctx.map);
+                ped.state = ped.crossing_state(
+                    ctx.map.get_pl(pl).sidewalk_pos.dist_along(),
+                    now,
+                    ctx.map,
+                    crowds,
+                );
                 ctx.scheduler
                     .push(ped.state.get_end_time(), Command::UpdatePed(ped.id));
             }
@@ -317,7 +373,8 @@ impl WalkingSimState {
                 self.peds.remove(&id);
             }
             PedState::FinishingBiking(ref spot, _, _) => {
-                ped.state = ped.crossing_state(spot.sidewalk_pos.dist_along(), now, ctx.map);
+                ped.state =
+                    ped.crossing_state(spot.sidewalk_pos.dist_along(), now, ctx.map, crowds);
                 ctx.scheduler
                     .push(ped.state.get_end_time(), Command::UpdatePed(ped.id));
             }
@@ -325,25 +382,31 @@ impl WalkingSimState {
         }
     }
 
-    pub fn ped_boarded_bus(&mut self, now: Time, id: PedestrianID) {
+    pub fn ped_boarded_bus(&mut self, now: Time, id: PedestrianID, ctx: &mut Ctx) {
         let mut ped = self.peds.remove(&id).unwrap();
+        let on = ped.path.current_step().as_traversable();
         match ped.state {
             PedState::WaitingForBus(_, blocked_since) => {
-                self.peds_per_traversable
-                    .remove(ped.path.current_step().as_traversable(), id);
+                self.peds_per_traversable.remove(on, id);
                 ped.total_blocked_time += now - blocked_since;
             }
             _ => unreachable!(),
         };
+        self.crowd_changed(on, now, ctx.map, ctx.scheduler);
     }
 
     /// Abruptly remove a pedestrian from the simulation. They may be in any arbitrary state, like
     /// in the middle of a turn.
-    pub fn delete_ped(&mut self, id: PedestrianID, ctx: &mut Ctx) {
+    pub fn delete_ped(&mut self, id: PedestrianID, now: Time, ctx: &mut Ctx) {
         let ped = self.peds.remove(&id).unwrap();
-        self.peds_per_traversable
-            .remove(ped.path.current_step().as_traversable(), id);
+        let on = ped.path.current_step().as_traversable();
+        self.peds_per_traversable.remove(on, id);
         ctx.scheduler.cancel(Command::UpdatePed(id));
+        // During live edits, everybody else on this sidewalk is probably being deleted too, and the
+        // sidewalk itself might be gone.
+        if ctx.handling_live_edits.is_none() {
+            self.crowd_changed(on, now, ctx.map, ctx.scheduler);
+        }
 
         if let PathStep::Turn(t) = ped.path.current_step() {
             ctx.intersections
@@ -354,6 +417,26 @@ impl WalkingSimState {
         }
     }
 
+    /// Somebody entered or left a sidewalk or crosswalk, so the density changed. If crowding is
+    /// enabled, everybody else walking along it speeds up or slows down from where they are now.
+    fn crowd_changed(&mut self, on: Traversable, now: Time, map: &Map, scheduler: &mut Scheduler) {
+        if !self.crowding {
+            return;
+        }
+        self.events.push(Event::PedestrianDensity(
+            on,
+            density(&self.peds_per_traversable, on, map),
+        ));
+        for id in self.peds_per_traversable.get(on).clone() {
+            let ped = self.peds.get_mut(&id).unwrap();
+            if let PedState::Crossing { .. } = ped.state {
+                let dist = ped.get_dist_along(now, map);
+                ped.state = ped.crossing_state(dist, now, map, Some(&self.peds_per_traversable));
+                scheduler.update(ped.state.get_end_time(), Command::UpdatePed(id));
+            }
+        }
+    }
+
     pub fn debug_ped_json(&self, id: PedestrianID) -> String {
         if let Some(ped) = self.peds.get(&id) {
             abstutil::to_json(ped)
@@ -619,7 +702,14 @@ struct Pedestrian {
 }
 
 impl Pedestrian {
-    fn crossing_state(&self, start_dist: Distance, start_time: Time, map: &Map) -> PedState {
+    /// If crowds are passed in, walk more slowly when the current step is dense.
+    fn crossing_state(
+        &self,
+        start_dist: Distance,
+        start_time: Time,
+        map: &Map,
+        crowds: Option<&MultiMap<Traversable, PedestrianID>>,
+    ) -> PedState {
         let end_dist = if self.path.is_last_step() {
             self.goal.sidewalk_pos.dist_along()
         } else {
@@ -636,6 +726,12 @@ impl Pedestrian {
             PathConstraints::Pedestrian,
             map,
         );
+        let speed = if let Some(crowds) = crowds {
+            let on = self.path.current_step().as_traversable();
+            speed * crowd_speed_factor(density(crowds, on, map))
+        } else {
+            speed
+        };
         let time_int = TimeInterval::new(start_time, start_time + dist_int.length() / speed);
         PedState::Crossing {
             dist_int,
@@ -797,6 +893,7 @@ impl Pedestrian {
         peds_per_traversable: &mut MultiMap<Traversable, PedestrianID>,
         events: &mut Vec<Event>,
         scheduler: &mut Scheduler,
+        crowding: bool,
     ) -> bool {
         if let PathStep::Turn(t) = self.path.next_step() {
             if !intersections.maybe_start_turn(
@@ -823,12 +920,22 @@ impl Pedestrian {
             PathStep::ContraflowLane(l) => map.get_l(l).length(),
             PathStep::Turn(_) => Distance::ZERO,
         };
-        self.state = self.crossing_state(start_dist, now, map);
-        peds_per_traversable.insert(self.path.current_step().as_traversable(), self.id);
+        let on = self.path.current_step().as_traversable();
+        peds_per_traversable.insert(on, self.id);
+        self.state = self.crossing_state(
+            start_dist,
+            now,
+            map,
+            if crowding {
+                Some(&*peds_per_traversable)
+            } else {
+                None
+            },
+        );
         events.push(Event::AgentEntersTraversable(
             AgentID::Pedestrian(self.id),
             Some(self.trip),
-            self.path.current_step().as_traversable(),
+            on,
             None,
         ));
         true
@@ -877,6 +984,30 @@ impl PedState {
     }
 }
 
+/// How many people per square meter are on some sidewalk, shoulder, or crosswalk right now?
+fn density(
+    peds_per_traversable: &MultiMap<Traversable, PedestrianID>,
+    on: Traversable,
+    map: &Map,
+) -> f64 {
+    let width = match on {
+        Traversable::Lane(l) => map.get_l(l).width,
+        Traversable::Turn(t) => map.get_l(t.src).width,
+    };
+    let length = on.get_polyline(map).length().max(MIN_CROWD_LENGTH);
+    (peds_per_traversable.get(on).len() as f64) / (length.inner_meters() * width.inner_meters())
+}
+
+/// Following Weidmann's fundamental diagram for pedestrians, what fraction of their normal speed
+/// can people walk at some density?
+fn crowd_speed_factor(density: f64) -> f64 {
+    if density <= 0.0 {
+        return 1.0;
+    }
+    let factor = 1.0 - (-1.913 * (1.0 / density - 1.0 / JAM_DENSITY)).exp();
+    factor.max(MIN_CROWD_SPEED_FACTOR)
+}
+
 // The crowds returned here may have low/high values extending up to radius past the real geometry.
 fn find_crowds(
     input: Vec<(PedestrianID, Distance)>,
@@ -927,3 +1058,21 @@ impl IndexableKey for PedestrianID {
         self.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crowd_speed_factor() {
+        assert_eq!(crowd_speed_factor(0.0), 1.0);
+        // Barely noticeable with a few people around
+        assert!(crowd_speed_factor(0.1) > 0.99);
+        // Slower as it gets denser
+        assert!(crowd_speed_factor(1.0) < crowd_speed_factor(0.5));
+        assert!(crowd_speed_factor(2.0) < crowd_speed_factor(1.0));
+        // People still shuffle along in a jam
+        assert_eq!(crowd_speed_factor(JAM_DENSITY), MIN_CROWD_SPEED_FACTOR);
+        assert_eq!(crowd_speed_factor(10.0), MIN_CROWD_SPEED_FACTOR);
+    }
+}
@@ -0,0 +1,84 @@
+//! Rough estimates of road traffic noise, loosely following the UK's Calculation of Road Traffic
+//! Noise (CRTN) method. Hourly vehicle volumes come from road throughput, so this only makes sense
+//! for hours the simulation has already covered.
+
+use std::collections::BTreeMap;
+
+use geom::Distance;
+use map_model::{BuildingID, Map, RoadID};
+
+use crate::{AgentType, Analytics};
+
+/// Even without traffic, a city isn't silent.
+const BACKGROUND_DB: f64 = 40.0;
+/// CRTN measures the basic noise level 13.5m from the line where noise is emitted, 3.5m into the
+/// road.
+const REFERENCE_DISTANCE: Distance = Distance::const_meters(13.5);
+/// Sound reflecting off the building itself
+const FACADE_REFLECTION_DB: f64 = 2.5;
+
+impl Analytics {
+    /// Estimate the noise level along every road during one hour since midnight. This is the
+    /// level in decibels exceeded 10% of the time (L10), measured 10m from the edge of the road.
+    /// If the hour isn't over yet, only the vehicles so far count.
+    pub fn road_noise(&self, map: &Map, hour: usize) -> BTreeMap<RoadID, f64> {
+        let mut results = BTreeMap::new();
+        for r in map.all_roads() {
+            let count = |agent_type| {
+                self.road_thruput
+                    .counts
+                    .get(&(r.id, agent_type, hour))
+                    .cloned()
+                    .unwrap_or(0) as f64
+            };
+            // Bikes and pedestrians are quiet. Passengers ride in vehicles that're already
+            // counted.
+            let light = count(AgentType::Car);
+            let heavy = count(AgentType::Bus) + count(AgentType::Train);
+            let total = light + heavy;
+            if total == 0.0 {
+                results.insert(r.id, BACKGROUND_DB);
+                continue;
+            }
+
+            let speed = r.speed_limit.inner_meters_per_second() * 3.6;
+            let speed = speed.max(20.0);
+            let pct_heavy = 100.0 * heavy / total;
+            let traffic = 42.2
+                + 10.0 * total.log10()
+                + 33.0 * (speed + 40.0 + 500.0 / speed).log10()
+                + 10.0 * (1.0 + 5.0 * pct_heavy / speed).log10()
+                - 68.8
+                // Vehicles work harder on hills
+                + 0.3 * (r.percent_incline.abs() * 100.0);
+            results.insert(r.id, add_decibels(traffic, BACKGROUND_DB));
+        }
+        results
+    }
+}
+
+/// Using the output of road_noise, estimate the noise at the facade of every building, facing the
+/// road it's connected to.
+// TODO Buildings on corners hear more than one road.
+pub fn building_noise(road_noise: &BTreeMap<RoadID, f64>, map: &Map) -> BTreeMap<BuildingID, f64> {
+    let mut results = BTreeMap::new();
+    for b in map.all_buildings() {
+        let road = map.get_parent(b.sidewalk_pos.lane());
+        let level = road_noise[&road.id];
+        if level <= BACKGROUND_DB {
+            results.insert(b.id, BACKGROUND_DB);
+            continue;
+        }
+        // From the building to the middle of the road. CRTN doesn't go closer than 4m.
+        let dist =
+            (b.driveway_geom.length() + road.get_width(map) / 2.0).max(Distance::meters(4.0));
+        let level = level - 10.0 * (dist / REFERENCE_DISTANCE).log10() + FACADE_REFLECTION_DB;
+        results.insert(b.id, level.max(BACKGROUND_DB));
+    }
+    results
+}
+
+/// Noise from two sources doesn't simply add.
+fn add_decibels(db1: f64, db2: f64) -> f64 {
+    10.0 * (10.0_f64.powf(db1 / 10.0) + 10.0_f64.powf(db2 / 10.0)).log10()
+}
@@ -77,6 +77,7 @@ impl TrafficRecorder {
             scenario_name: "recorded".to_string(),
             map_name: map.get_name().clone(),
             people,
+            households: Vec::new(),
             only_seed_buses: None,
         }
         .save();
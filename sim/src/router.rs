@@ -1,14 +1,17 @@
 //! For vehicles only, not pedestrians. Follows a Path from map_model, but can opportunistically
-//! lane-change to avoid a slow lane, can can handle re-planning to look for available parking.
+//! lane-change to avoid a slow lane, can can handle re-planning to look for available parking or
+//! to avoid congestion.
 
 use std::collections::HashMap;
 
+use rand::{Rng, SeedableRng};
+use rand_xorshift::XorShiftRng;
 use serde::{Deserialize, Serialize};
 
-use geom::Distance;
+use geom::{Distance, Duration, Time};
 use map_model::{
-    BuildingID, IntersectionID, LaneID, Map, Path, PathConstraints, PathRequest, PathStep,
-    Position, Traversable, Turn, TurnID,
+    BuildingID, DirectedRoadID, IntersectionID, LaneID, Map, Path, PathConstraints, PathRequest,
+    PathStep, Position, Traversable, Turn, TurnID, VehiclePathfinder,
 };
 
 use crate::mechanics::Queue;
@@ -25,6 +28,55 @@ pub(crate) struct Router {
     owner: CarID,
 }
 
+/// When a driver reaches the lane closest to their destination and there's no free spot there, how
+/// do they look for one?
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ParkingStrategy {
+    /// Unrealistically head straight to the closest free spot, magically knowing where it is.
+    Omniscient,
+    /// Drive around nearby blocks, only noticing free spots on each lane as they pass it. After
+    /// cruising too long, give up and head to the closest free spot, no matter how far.
+    CircleBlock,
+    /// Head to the parking lot closest to the destination that has room, or act omniscient if
+    /// there isn't one nearby.
+    NearestLot,
+    /// Circle the block, but after cruising a while, take any free spot passed, even in a permit
+    /// zone the driver doesn't belong to. After cruising too long, head to the closest free spot,
+    /// allowed or not.
+    ParkIllegally,
+}
+
+/// How a driver's search for parking ended
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum CruisingOutcome {
+    Parked,
+    /// Took a spot in a permit zone the driver doesn't belong to
+    ParkedIllegally,
+    /// No free spot was reachable at all, so the driver abandoned the trip
+    GaveUp,
+}
+
+/// Drivers circling the block stay about this close to their destination...
+const CIRCLE_BLOCK_RADIUS: Distance = Distance::const_meters(300.0);
+/// ...until they've cruised this far.
+const CIRCLE_BLOCK_GIVE_UP: Distance = Distance::const_meters(2000.0);
+/// Drivers willing to park illegally start after cruising this far.
+const PARK_ILLEGALLY_AFTER: Distance = Distance::const_meters(500.0);
+/// Drivers won't head to a lot farther than this from their destination.
+const NEAREST_LOT_MAX_DIST: Distance = Distance::const_meters(800.0);
+
+/// Some drivers periodically look for a faster way to their destination, based on current
+/// traffic, like with a navigation app.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReroutingPolicy {
+    /// Between 0 and 1, the fraction of drivers who look for faster routes
+    pub informed_fraction: f64,
+    /// How often each of those drivers looks
+    pub every: Duration,
+    /// Only switch routes if the new one is estimated to save at least this much time
+    pub min_time_saved: Duration,
+}
+
 #[derive(Debug)]
 pub(crate) enum ActionAtEnd {
     VanishAtBorder(IntersectionID),
@@ -44,6 +96,9 @@ enum Goal {
         /// No parking available at all!
         stuck_end_dist: Option<Distance>,
         started_looking: bool,
+        /// When the driver started looking beyond the lane they first headed to, and the length of
+        /// the path at that point
+        cruising_since: Option<(Time, Distance)>,
     },
     EndAtBorder {
         end_dist: Distance,
@@ -93,6 +148,7 @@ impl Router {
                 spot: None,
                 stuck_end_dist: None,
                 started_looking: false,
+                cruising_since: None,
             },
             owner,
         }
@@ -154,8 +210,10 @@ impl Router {
     /// Returns the step just finished
     pub fn advance(
         &mut self,
+        now: Time,
         vehicle: &Vehicle,
         parking: &ParkingSimState,
+        strategy: ParkingStrategy,
         map: &Map,
         trip_and_person: Option<(TripID, PersonID)>,
         events: &mut Vec<Event>,
@@ -164,9 +222,11 @@ impl Router {
         if self.last_step() {
             // Do this to trigger the side-effect of looking for parking.
             self.maybe_handle_end(
+                now,
                 Distance::ZERO,
                 vehicle,
                 parking,
+                strategy,
                 map,
                 trip_and_person,
                 events,
@@ -191,9 +251,11 @@ impl Router {
     /// step.
     pub fn maybe_handle_end(
         &mut self,
+        now: Time,
         front: Distance,
         vehicle: &Vehicle,
         parking: &ParkingSimState,
+        strategy: ParkingStrategy,
         map: &Map,
         // TODO Not so nice to plumb all of this here
         trip_and_person: Option<(TripID, PersonID)>,
@@ -214,9 +276,20 @@ impl Router {
                 ref mut stuck_end_dist,
                 target,
                 ref mut started_looking,
+                ref mut cruising_since,
             } => {
                 if let Some(d) = stuck_end_dist {
                     if *d == front {
+                        if let Some((started, dist)) = cruising_since.take() {
+                            events.push(Event::CruisedForParking {
+                                car: vehicle.id,
+                                trip: trip_and_person.map(|(t, _)| t),
+                                target,
+                                duration: now - started,
+                                distance: self.path.total_length() - dist,
+                                outcome: CruisingOutcome::GaveUp,
+                            });
+                        }
                         return Some(ActionAtEnd::GiveUpOnParking);
                     } else {
                         return None;
@@ -230,32 +303,40 @@ impl Router {
                 if need_new_spot {
                     *started_looking = true;
                     let current_lane = self.path.current_step().as_lane();
-                    let candidates = parking.get_all_free_spots(
+                    let mut candidates = parking.get_all_free_spots(
                         Position::new(current_lane, front),
                         vehicle,
                         target,
                         map,
                     );
-                    let best =
-                        if let Some((driving_pos, _)) = map.get_b(target).driving_connection(map) {
-                            if driving_pos.lane() == current_lane {
-                                let target_dist = driving_pos.dist_along();
-                                // Closest to the building
-                                candidates
-                                    .into_iter()
-                                    .min_by_key(|(_, pos)| (pos.dist_along() - target_dist).abs())
-                            } else {
-                                // Closest to the road endpoint, I guess
-                                candidates
-                                    .into_iter()
-                                    .min_by_key(|(_, pos)| pos.dist_along())
+                    if candidates.is_empty() && strategy == ParkingStrategy::ParkIllegally {
+                        if let Some((_, dist)) = cruising_since {
+                            if self.path.total_length() - *dist >= PARK_ILLEGALLY_AFTER {
+                                candidates = parking.get_all_free_spots_ignoring_permits(
+                                    Position::new(current_lane, front),
+                                    vehicle,
+                                    target,
+                                    map,
+                                );
                             }
+                        }
+                    }
+                    let target_dist = map
+                        .get_b(target)
+                        .driving_connection(map)
+                        .map(|(pos, _)| pos)
+                        .filter(|pos| pos.lane() == current_lane)
+                        .map(|pos| pos.dist_along());
+                    let best = candidates.into_iter().min_by_key(|(spot, pos)| {
+                        let walk = if let Some(target_dist) = target_dist {
+                            // Closest to the building
+                            (pos.dist_along() - target_dist).abs()
                         } else {
                             // Closest to the road endpoint, I guess
-                            candidates
-                                .into_iter()
-                                .min_by_key(|(_, pos)| pos.dist_along())
+                            pos.dist_along()
                         };
+                        walk + spot.price_as_distance(map)
+                    });
                     if let Some((new_spot, new_pos)) = best {
                         if let Some((t, p)) = trip_and_person {
                             events.push(Event::TripPhaseStarting(
@@ -273,27 +354,52 @@ impl Router {
                         assert!(new_pos.dist_along() >= front);
                         *spot = Some((new_spot, new_pos.dist_along()));
                     } else {
-                        if let Some((new_path_steps, new_spot, new_pos)) =
-                            parking.path_to_free_parking_spot(current_lane, vehicle, target, map)
-                        {
+                        if cruising_since.is_none() {
+                            *cruising_since = Some((now, self.path.total_length()));
+                        }
+                        let cruised = self.path.total_length() - cruising_since.unwrap().1;
+                        if let Some((new_path_steps, maybe_spot)) = search_elsewhere(
+                            strategy,
+                            Position::new(current_lane, front),
+                            cruised,
+                            vehicle,
+                            target,
+                            parking,
+                            map,
+                        ) {
                             assert!(!new_path_steps.is_empty());
                             for step in new_path_steps {
                                 self.path.add(step, map);
                             }
-                            *spot = Some((new_spot, new_pos.dist_along()));
                             events.push(Event::PathAmended(self.path.clone()));
-                            // TODO This path might not be the same as the one found here...
-                            if let Some((t, p)) = trip_and_person {
-                                events.push(Event::TripPhaseStarting(
-                                    t,
-                                    p,
-                                    Some(PathRequest::vehicle(
-                                        Position::new(current_lane, front),
-                                        new_pos,
-                                        PathConstraints::Car,
-                                    )),
-                                    TripPhaseType::Parking,
-                                ));
+                            if let Some((new_spot, new_pos)) = maybe_spot {
+                                *spot = Some((new_spot, new_pos.dist_along()));
+                                // TODO This path might not be the same as the one found here...
+                                if let Some((t, p)) = trip_and_person {
+                                    events.push(Event::TripPhaseStarting(
+                                        t,
+                                        p,
+                                        Some(PathRequest::vehicle(
+                                            Position::new(current_lane, front),
+                                            new_pos,
+                                            PathConstraints::Car,
+                                        )),
+                                        TripPhaseType::Parking,
+                                    ));
+                                }
+                            } else {
+                                // Check the next lane when we get there
+                                if spot.is_none() && cruised == Distance::ZERO {
+                                    if let Some((t, p)) = trip_and_person {
+                                        events.push(Event::TripPhaseStarting(
+                                            t,
+                                            p,
+                                            None,
+                                            TripPhaseType::Parking,
+                                        ));
+                                    }
+                                }
+                                *spot = None;
                             }
                         } else {
                             if let Some((_, p)) = trip_and_person {
@@ -313,6 +419,20 @@ impl Router {
                 }
 
                 if spot.unwrap().1 == front {
+                    if let Some((started, dist)) = cruising_since.take() {
+                        events.push(Event::CruisedForParking {
+                            car: vehicle.id,
+                            trip: trip_and_person.map(|(t, _)| t),
+                            target,
+                            duration: now - started,
+                            distance: self.path.total_length() - dist,
+                            outcome: if spot.unwrap().0.permits(target, map) {
+                                CruisingOutcome::Parked
+                            } else {
+                                CruisingOutcome::ParkedIllegally
+                            },
+                        });
+                    }
                     Some(ActionAtEnd::StartParking(spot.unwrap().0))
                 } else {
                     None
@@ -466,6 +586,65 @@ impl Router {
         }
     }
 
+    /// Look for a faster way to the same place, using a pathfinder that knows about current
+    /// traffic delays. If switching would save at least `min_time_saved`, return the new path and
+    /// the estimated time saved. Only call this right after the vehicle starts a new lane.
+    pub fn find_reroute(
+        &self,
+        pathfinder: &VehiclePathfinder,
+        delays: &HashMap<DirectedRoadID, Duration>,
+        min_time_saved: Duration,
+        vehicle: &Vehicle,
+        map: &Map,
+    ) -> Option<(Path, Duration)> {
+        // Buses and bikes stick to their route, and drivers already searching for parking are
+        // busy enough.
+        match self.goal {
+            Goal::ParkNearBuilding {
+                started_looking, ..
+            } => {
+                if started_looking {
+                    return None;
+                }
+            }
+            Goal::EndAtBorder { .. } => {}
+            Goal::BikeThenStop { .. } | Goal::FollowBusRoute { .. } => {
+                return None;
+            }
+        }
+        // Near the end, there aren't many alternatives anyway.
+        if self.path.get_steps().len() < 5
+            || self.path.currently_inside_ut().is_some()
+            || self.path.about_to_start_ut().is_some()
+        {
+            return None;
+        }
+        let current_lane = match self.head() {
+            Traversable::Lane(l) => l,
+            Traversable::Turn(_) => {
+                return None;
+            }
+        };
+
+        let req = PathRequest::vehicle(
+            Position::start(current_lane),
+            self.path.get_req().end,
+            vehicle.vehicle_type.to_constraints(),
+        );
+        let new_path = pathfinder.pathfind(req, map)?.into_v1(map).ok()?;
+        let time_saved = live_cost(&self.path, vehicle, delays, map)
+            - live_cost(&new_path, vehicle, delays, map);
+        if time_saved < min_time_saved {
+            return None;
+        }
+        Some((new_path, time_saved))
+    }
+
+    /// Switch to a path found by `find_reroute`.
+    pub fn reroute(&mut self, new_path: Path) {
+        self.path.reroute(new_path);
+    }
+
     pub fn can_lanechange(&self, from: LaneID, to: LaneID, map: &Map) -> bool {
         let steps = self.path.get_steps();
         if steps.len() < 3 {
@@ -513,3 +692,144 @@ impl Router {
         }
     }
 }
+
+/// How long will the rest of the path take, with current traffic?
+fn live_cost(
+    path: &Path,
+    vehicle: &Vehicle,
+    delays: &HashMap<DirectedRoadID, Duration>,
+    map: &Map,
+) -> Duration {
+    let mut total = path.estimate_duration(map, vehicle.max_speed);
+    for step in path.get_steps() {
+        if let PathStep::Lane(l) = step {
+            if let Some(delay) = delays.get(&map.get_l(*l).get_directed_parent()) {
+                total += *delay;
+            }
+        }
+    }
+    total
+}
+
+/// There's no free spot on the current lane, so look elsewhere. Returns the steps to add to the
+/// path, starting with the turn after the current lane, and the spot to park at, if the driver
+/// knows of one yet.
+fn search_elsewhere(
+    strategy: ParkingStrategy,
+    current: Position,
+    cruised: Distance,
+    vehicle: &Vehicle,
+    target: BuildingID,
+    parking: &ParkingSimState,
+    map: &Map,
+) -> Option<(Vec<PathStep>, Option<(ParkingSpot, Position)>)> {
+    match strategy {
+        ParkingStrategy::Omniscient => {}
+        ParkingStrategy::CircleBlock => {
+            if cruised < CIRCLE_BLOCK_GIVE_UP {
+                if let Some(turn) = circle_block(current.lane(), cruised, vehicle, target, map) {
+                    return Some((vec![PathStep::Turn(turn), PathStep::Lane(turn.dst)], None));
+                }
+            }
+        }
+        ParkingStrategy::ParkIllegally => {
+            if cruised < CIRCLE_BLOCK_GIVE_UP {
+                if let Some(turn) = circle_block(current.lane(), cruised, vehicle, target, map) {
+                    return Some((vec![PathStep::Turn(turn), PathStep::Lane(turn.dst)], None));
+                }
+            }
+            // Rather than give up, head to the closest free spot, allowed or not
+            return parking
+                .path_to_free_parking_spot_ignoring_permits(current.lane(), vehicle, target, map)
+                .map(|(steps, spot, pos)| (steps, Some((spot, pos))));
+        }
+        ParkingStrategy::NearestLot => {
+            if let Some((steps, spot, pos)) =
+                path_to_nearest_lot(current, vehicle, target, parking, map)
+            {
+                return Some((steps, Some((spot, pos))));
+            }
+        }
+    }
+    parking
+        .path_to_free_parking_spot(current.lane(), vehicle, target, map)
+        .map(|(steps, spot, pos)| (steps, Some((spot, pos))))
+}
+
+/// Pick the next lane to check for parking, without knowing where any free spots are. Stay near
+/// the destination, but pick randomly between nearby lanes, so drivers don't all circle the same
+/// block.
+fn circle_block(
+    current_lane: LaneID,
+    cruised: Distance,
+    vehicle: &Vehicle,
+    target: BuildingID,
+    map: &Map,
+) -> Option<TurnID> {
+    let target_pt = map.get_b(target).polygon.center();
+    let mut nearby = Vec::new();
+    let mut closest: Option<(Distance, TurnID)> = None;
+    for turn in map.get_turns_for(current_lane, PathConstraints::Car) {
+        let lane = map.get_l(turn.id.dst);
+        if lane.driving_blackhole {
+            continue;
+        }
+        let dist = lane.lane_center_pts.middle().dist_to(target_pt);
+        if dist <= CIRCLE_BLOCK_RADIUS {
+            nearby.push(turn.id);
+        }
+        if closest.map(|(d, _)| dist < d).unwrap_or(true) {
+            closest = Some((dist, turn.id));
+        }
+    }
+    if nearby.is_empty() {
+        return closest.map(|(_, t)| t);
+    }
+    // Deterministic across runs, but different each time the same driver passes through here
+    let mut rng = XorShiftRng::seed_from_u64(
+        (vehicle.id.id + current_lane.0) as u64 + cruised.inner_meters() as u64,
+    );
+    Some(nearby[rng.gen_range(0..nearby.len())])
+}
+
+/// Find the parking lot closest to the destination with a free spot the driver is allowed to use.
+fn path_to_nearest_lot(
+    current: Position,
+    vehicle: &Vehicle,
+    target: BuildingID,
+    parking: &ParkingSimState,
+    map: &Map,
+) -> Option<(Vec<PathStep>, ParkingSpot, Position)> {
+    let target_pt = map.get_b(target).polygon.center();
+    let mut lots = Vec::new();
+    for pl in map.all_parking_lots() {
+        // A free spot here would've been found already
+        if pl.driving_pos.lane() == current.lane() {
+            continue;
+        }
+        let dist = pl.polygon.center().dist_to(target_pt);
+        if dist > NEAREST_LOT_MAX_DIST {
+            continue;
+        }
+        if let Some(spot) = parking
+            .get_free_lot_spots(pl.id)
+            .into_iter()
+            .find(|spot| spot.permits(target, map))
+        {
+            lots.push((dist, spot));
+        }
+    }
+    lots.sort_by_key(|(dist, _)| *dist);
+
+    for (_, spot) in lots {
+        let pos = parking.spot_to_driving_pos(spot, vehicle, map);
+        if let Ok(path) = map.pathfind(PathRequest::vehicle(current, pos, PathConstraints::Car)) {
+            // Don't include PathStep::Lane(current)
+            let steps: Vec<PathStep> = path.get_steps().iter().skip(1).cloned().collect();
+            if !steps.is_empty() {
+                return Some((steps, spot, pos));
+            }
+        }
+    }
+    None
+}
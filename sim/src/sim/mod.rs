@@ -19,10 +19,11 @@ use map_model::{
 
 pub use self::queries::{AgentProperties, DelayCause};
 use crate::{
-    AgentID, AlertLocation, Analytics, CarID, Command, CreateCar, DrivingSimState, Event,
-    IntersectionSimState, OrigPersonID, PandemicModel, ParkedCar, ParkingSim, ParkingSimState,
-    ParkingSpot, Person, PersonID, Router, Scheduler, SidewalkPOI, SidewalkSpot, StartTripArgs,
-    TrafficRecorder, TransitSimState, TripID, TripInfo, TripManager, TripPhaseType, Vehicle,
+    AgentID, AlertLocation, Analytics, CapSimState, CarID, Command, CreateCar, DrivingSimState,
+    EmissionTable, Event, FleetSimState, IntersectionSimState, OrigPersonID, PandemicModel,
+    ParkedCar, ParkingSim, ParkingSimState, ParkingSpot, ParkingStrategy, Person, PersonID,
+    ReroutingPolicy, Router, Scheduler, SidewalkPOI, SidewalkSpot, StartTripArgs, TrafficRecorder,
+    TransitSignalPriority, TransitSimState, TripID, TripInfo, TripManager, TripPhaseType, Vehicle,
     VehicleSpec, VehicleType, WalkingSimState, BUS_LENGTH, LIGHT_RAIL_LENGTH, MIN_CAR_LENGTH,
 };
 
@@ -39,6 +40,8 @@ pub struct Sim {
     walking: WalkingSimState,
     intersections: IntersectionSimState,
     transit: TransitSimState,
+    cap: CapSimState,
+    fleet: FleetSimState,
     trips: TripManager,
     #[serde(skip_serializing, skip_deserializing)]
     pandemic: Option<PandemicModel>,
@@ -58,6 +61,9 @@ pub struct Sim {
     // This is created interactively, and there's no reason to preserve one for savestates.
     #[serde(skip_serializing, skip_deserializing)]
     recorder: Option<TrafficRecorder>,
+    // Also only used interactively, by things streaming events to somewhere else.
+    #[serde(skip_serializing, skip_deserializing)]
+    event_buffer: Option<Vec<(Time, Event)>>,
 
     #[serde(skip_serializing, skip_deserializing)]
     alerts: AlertHandler,
@@ -66,6 +72,8 @@ pub struct Sim {
 pub(crate) struct Ctx<'a> {
     pub parking: &'a mut ParkingSimState,
     pub intersections: &'a mut IntersectionSimState,
+    pub cap: &'a mut CapSimState,
+    pub fleet: &'a mut FleetSimState,
     pub scheduler: &'a mut Scheduler,
     pub map: &'a Map,
     /// If present, live map edits are being processed, and the agents specified are in the process
@@ -111,6 +119,39 @@ pub struct SimOptions {
     /// Don't collect any analytics. Only useful for benchmarking and debugging gridlock more
     /// quickly.
     pub skip_analytics: bool,
+    /// When a driver would exceed a zone's hourly vehicle cap and there's no route avoiding the
+    /// zone, cancel their trip. By default, they instead wait until the zone has room again.
+    pub cancel_capped_trips: bool,
+    /// When a transit vehicle reaches a timepoint ahead of schedule, wait there until the
+    /// scheduled time.
+    pub hold_transit_at_timepoints: bool,
+    /// How many unowned ride-hailing vehicles to scatter around the map when a scenario is
+    /// instantiated.
+    pub ride_hail_fleet: usize,
+    /// Vehicles gradually speed up and brake, instead of instantly moving at their max speed.
+    /// They slow down for stop signs and the end of their trip, accelerate from zero after being
+    /// stopped anywhere, and don't plan to go faster than the vehicle ahead. This isn't a full
+    /// car-following model; vehicles still stop abruptly behind a queue.
+    pub acceleration: bool,
+    /// On roads without a second lane in the same direction, vehicles overtake cyclists, stopped
+    /// buses, and vehicles parking by briefly using the oncoming lane, when there's a safe gap.
+    pub oncoming_overtakes: bool,
+    /// Pedestrians walk more slowly along crowded sidewalks, shoulders, and crosswalks.
+    pub pedestrian_crowding: bool,
+    /// How much fuel, energy, and pollution each type of vehicle produces
+    pub emission_table: EmissionTable,
+    /// If present, buses on some routes ask traffic signals to extend a green or cut a red short.
+    pub transit_signal_priority: Option<TransitSignalPriority>,
+    /// Emergency vehicles approaching a traffic signal switch it to a stage that lets them
+    /// through, and hold it there until they've started their turn.
+    pub emergency_preemption: bool,
+    /// If present, some drivers periodically look for faster routes as traffic changes. Otherwise,
+    /// everybody sticks to the route they picked before starting.
+    pub rerouting: Option<ReroutingPolicy>,
+    /// How drivers look for parking when there's no free spot near their destination
+    pub parking_strategy: ParkingStrategy,
+    /// Measure how long cars take to cross each movement, for iterative traffic assignment.
+    pub record_movement_times: bool,
 }
 
 impl std::default::Default for SimOptions {
@@ -120,8 +161,66 @@ impl std::default::Default for SimOptions {
 }
 
 impl SimOptions {
-    pub fn from_args(args: &mut CmdArgs, rng_seed: u64) -> SimOptions {
-        SimOptions {
+    /// Fails if any flag has a bad value.
+    pub fn from_args(args: &mut CmdArgs, rng_seed: u64) -> Result<SimOptions> {
+        let alerts = match args.optional("--alerts") {
+            Some(x) => match x.as_ref() {
+                "print" => AlertHandler::Print,
+                "block" => AlertHandler::Block,
+                "silence" => AlertHandler::Silence,
+                _ => bail!("Bad --alerts={}. Must be print|block|silence", x),
+            },
+            None => AlertHandler::Print,
+        };
+        let emission_table = match args.optional("--emission_table") {
+            Some(path) => abstio::maybe_read_json(path, &mut Timer::throwaway())?,
+            None => EmissionTable::default(),
+        };
+        let transit_signal_priority = match args.optional("--tsp_routes") {
+            Some(routes) => Some(TransitSignalPriority {
+                routes: routes.split(',').map(|r| r.to_string()).collect(),
+                max_extension: args
+                    .try_optional_parse("--tsp_max_extension", Duration::parse)?
+                    .unwrap_or(Duration::seconds(10.0)),
+                max_truncation: args
+                    .try_optional_parse("--tsp_max_truncation", Duration::parse)?
+                    .unwrap_or(Duration::seconds(10.0)),
+            }),
+            None => None,
+        };
+        let rerouting = match args.try_optional_parse("--reroute_fraction", |s| s.parse::<f64>())? {
+            Some(informed_fraction) => {
+                if !(0.0..=1.0).contains(&informed_fraction) {
+                    bail!("--reroute_fraction must be between 0 and 1");
+                }
+                Some(ReroutingPolicy {
+                    informed_fraction,
+                    every: args
+                        .try_optional_parse("--reroute_every", Duration::parse)?
+                        .unwrap_or(Duration::minutes(5)),
+                    min_time_saved: args
+                        .try_optional_parse("--reroute_min_time_saved", Duration::parse)?
+                        .unwrap_or(Duration::minutes(1)),
+                })
+            }
+            None => None,
+        };
+        let parking_strategy = match args.optional("--parking_strategy") {
+            Some(x) => match x.as_ref() {
+                "omniscient" => ParkingStrategy::Omniscient,
+                "circle_block" => ParkingStrategy::CircleBlock,
+                "nearest_lot" => ParkingStrategy::NearestLot,
+                "park_illegally" => ParkingStrategy::ParkIllegally,
+                _ => bail!(
+                    "Bad --parking_strategy={}. Must be \
+                     omniscient|circle_block|nearest_lot|park_illegally",
+                    x
+                ),
+            },
+            None => ParkingStrategy::Omniscient,
+        };
+
+        Ok(SimOptions {
             run_name: args
                 .optional("--run_name")
                 .unwrap_or_else(|| "unnamed".to_string()),
@@ -135,19 +234,25 @@ impl SimOptions {
             } else {
                 None
             },
-            alerts: args
-                .optional("--alerts")
-                .map(|x| match x.as_ref() {
-                    "print" => AlertHandler::Print,
-                    "block" => AlertHandler::Block,
-                    "silence" => AlertHandler::Silence,
-                    _ => panic!("Bad --alerts={}. Must be print|block|silence", x),
-                })
-                .unwrap_or(AlertHandler::Print),
+            alerts,
             infinite_parking: args.enabled("--infinite_parking"),
             disable_turn_conflicts: args.enabled("--disable_turn_conflicts"),
             skip_analytics: args.enabled("--skip_analytics"),
-        }
+            cancel_capped_trips: args.enabled("--cancel_capped_trips"),
+            hold_transit_at_timepoints: args.enabled("--hold_transit"),
+            ride_hail_fleet: args
+                .try_optional_parse("--ride_hail_fleet", |s| s.parse())?
+                .unwrap_or(0),
+            acceleration: args.enabled("--acceleration"),
+            oncoming_overtakes: args.enabled("--oncoming_overtakes"),
+            pedestrian_crowding: args.enabled("--pedestrian_crowding"),
+            emission_table,
+            transit_signal_priority,
+            emergency_preemption: args.enabled("--emergency_preemption"),
+            rerouting,
+            parking_strategy,
+            record_movement_times: args.enabled("--record_movement_times"),
+        })
     }
 }
 
@@ -181,6 +286,18 @@ impl SimOptions {
             infinite_parking: false,
             disable_turn_conflicts: false,
             skip_analytics: false,
+            cancel_capped_trips: false,
+            hold_transit_at_timepoints: false,
+            ride_hail_fleet: 0,
+            acceleration: false,
+            oncoming_overtakes: false,
+            pedestrian_crowding: false,
+            emission_table: EmissionTable::default(),
+            transit_signal_priority: None,
+            emergency_preemption: false,
+            rerouting: None,
+            parking_strategy: ParkingStrategy::Omniscient,
+            record_movement_times: false,
         }
     }
 }
@@ -201,12 +318,17 @@ impl Sim {
             opts.infinite_parking = true;
         }
 
+        let mut analytics = Analytics::new(!opts.skip_analytics);
+        analytics.record_movement_times = opts.record_movement_times;
+
         Sim {
             driving: DrivingSimState::new(map, &opts),
             parking: ParkingSimState::new(map, opts.infinite_parking, &mut timer),
-            walking: WalkingSimState::new(),
+            walking: WalkingSimState::new(&opts),
             intersections: IntersectionSimState::new(map, &mut scheduler, &opts),
-            transit: TransitSimState::new(map),
+            transit: TransitSimState::new(map, opts.hold_transit_at_timepoints),
+            cap: CapSimState::new(map, opts.cancel_capped_trips),
+            fleet: FleetSimState::new(opts.ride_hail_fleet),
             trips: TripManager::new(),
             pandemic: opts.enable_pandemic_model.map(PandemicModel::new),
             scheduler,
@@ -219,22 +341,27 @@ impl Sim {
             highlighted_people: None,
             alerts: opts.alerts,
 
-            analytics: Analytics::new(!opts.skip_analytics),
+            analytics,
             recorder: None,
+            event_buffer: None,
         }
     }
 
+    /// Each carpool is a (driver, passenger) pair of indices into the input.
     pub(crate) fn spawn_trips(
         &mut self,
         input: Vec<(PersonID, TripInfo, StartTripArgs)>,
+        carpools: Vec<(usize, usize)>,
         map: &Map,
         timer: &mut Timer,
     ) {
+        let mut ids = Vec::new();
         timer.start_iter("spawn trips", input.len());
         for (p, info, args) in input {
             timer.next();
 
             let trip = self.trips.new_trip(p, info.clone());
+            ids.push(trip);
             // This might be immediately true due to ScenarioModifiers
             if let Some(msg) = info.cancellation_reason {
                 self.trips.cancel_unstarted_trip(trip, msg);
@@ -243,6 +370,9 @@ impl Sim {
                     .push(info.departure, Command::StartTrip(trip, args));
             }
         }
+        for (driver, passenger) in carpools {
+            self.trips.new_carpool(ids[driver], ids[passenger]);
+        }
 
         if let Some(ref mut m) = self.pandemic {
             m.initialize(self.trips.get_all_people(), &mut self.scheduler);
@@ -314,22 +444,45 @@ impl Sim {
     ) -> &Person {
         self.trips.new_person(orig_id, ped_speed, vehicle_specs)
     }
+    pub(crate) fn new_household(
+        &mut self,
+        home: BuildingID,
+        members: Vec<PersonID>,
+        vehicle_specs: Vec<VehicleSpec>,
+    ) -> Vec<Vehicle> {
+        self.trips.new_household(home, members, vehicle_specs)
+    }
     pub(crate) fn seed_parked_car(&mut self, vehicle: Vehicle, spot: ParkingSpot) {
         self.parking.reserve_spot(spot, vehicle.id);
-        self.parking.add_parked_car(ParkedCar {
+        self.parking.seed_parked_car(ParkedCar {
             vehicle,
             spot,
             parked_since: self.time,
         });
     }
 
+    pub(crate) fn ride_hail_fleet_size(&self) -> usize {
+        self.fleet.size()
+    }
+    pub(crate) fn seed_ride_hail_vehicle(&mut self, spec: VehicleSpec, spot: ParkingSpot) {
+        let vehicle = spec.make(
+            CarID {
+                id: self.trips.new_car_id(),
+                vehicle_type: VehicleType::Car,
+            },
+            None,
+        );
+        self.fleet.add_vehicle(vehicle.id, self.time);
+        self.seed_parked_car(vehicle, spot);
+    }
+
     pub(crate) fn seed_bus_route(&mut self, route: &BusRoute) {
         for t in &route.spawn_times {
             self.scheduler.push(*t, Command::StartBus(route.id, *t));
         }
     }
 
-    fn start_bus(&mut self, route: &BusRoute, map: &Map) {
+    fn start_bus(&mut self, route: &BusRoute, scheduled_start: Time, map: &Map) {
         // Spawn one bus for the first leg.
         let path = self.transit.create_empty_route(route, map);
 
@@ -361,7 +514,8 @@ impl Sim {
                     vehicle,
                     maybe_parked_car: None,
                     trip_and_person: None,
-                    maybe_route: Some(route.id),
+                    maybe_route: Some((route.id, scheduled_start)),
+                    passengers: 0,
                 },
                 true,
             ),
@@ -433,6 +587,8 @@ impl Sim {
         let mut ctx = Ctx {
             parking: &mut self.parking,
             intersections: &mut self.intersections,
+            cap: &mut self.cap,
+            fleet: &mut self.fleet,
             scheduler: &mut self.scheduler,
             map,
             handling_live_edits: None,
@@ -465,13 +621,21 @@ impl Sim {
                     }
                 }
                 if !ok {
-                    self.trips.cancel_trip(
-                        self.time,
-                        create_car.trip_and_person.unwrap().0,
-                        "path is no longer valid after map edits".to_string(),
-                        Some(create_car.vehicle),
-                        &mut ctx,
-                    );
+                    if let Some((trip, _)) = create_car.trip_and_person {
+                        self.trips.cancel_trip(
+                            self.time,
+                            trip,
+                            "path is no longer valid after map edits".to_string(),
+                            Some(create_car.vehicle),
+                            &mut ctx,
+                        );
+                    } else {
+                        self.trips.ride_hail_vehicle_lost(
+                            self.time,
+                            create_car.vehicle.id,
+                            &mut ctx,
+                        );
+                    }
                 } else {
                     // create_car contains a Path, which is expensive to clone. We need different
                     // parts of create_car after attempting start_car_on_lane.
@@ -524,7 +688,9 @@ impl Sim {
                                 trip,
                                 person,
                                 Some(req),
-                                if id.vehicle_type == VehicleType::Car {
+                                if id.vehicle_type == VehicleType::Car
+                                    || id.vehicle_type == VehicleType::Emergency
+                                {
                                     TripPhaseType::Driving
                                 } else {
                                     TripPhaseType::Biking
@@ -532,17 +698,18 @@ impl Sim {
                             ));
                         }
                         if let Some(parked_car) = maybe_parked_car {
-                            if let ParkingSpot::Offstreet(b, _) = parked_car.spot {
-                                // Buses don't start in parking garages, so trip must exist
-                                events.push(Event::PersonLeavesBuilding(
-                                    trip_and_person.unwrap().1,
-                                    b,
-                                ));
+                            // Ride-hailing vehicles might start in a garage, but have no driver
+                            // to track
+                            if let (ParkingSpot::Offstreet(b, _), Some((_, person))) =
+                                (parked_car.spot, trip_and_person)
+                            {
+                                events.push(Event::PersonLeavesBuilding(person, b));
                             }
                             self.parking.remove_parked_car(parked_car);
                         }
-                        if let Some(route) = maybe_route {
-                            self.transit.bus_created(id, route);
+                        if let Some((route, scheduled_start)) = maybe_route {
+                            self.transit
+                                .bus_created(id, map.get_br(route), scheduled_start);
                         }
                         self.analytics
                             .record_demand(self.driving.get_path(id).unwrap(), map);
@@ -626,8 +793,8 @@ impl Sim {
                     .unwrap()
                     .handle_cmd(self.time, cmd, &mut self.scheduler);
             }
-            Command::StartBus(r, _) => {
-                self.start_bus(map.get_br(r), map);
+            Command::StartBus(r, t) => {
+                self.start_bus(map.get_br(r), t, map);
             }
         }
 
@@ -640,6 +807,7 @@ impl Sim {
     fn dispatch_events(&mut self, mut events: Vec<Event>, map: &Map) {
         events.extend(self.trips.collect_events());
         events.extend(self.transit.collect_events());
+        events.extend(self.cap.collect_events());
         events.extend(self.driving.collect_events());
         events.extend(self.walking.collect_events());
         events.extend(self.intersections.collect_events());
@@ -651,6 +819,9 @@ impl Sim {
             if let Some(ref mut r) = self.recorder {
                 r.handle_event(self.time, &ev, map, &self.driving);
             }
+            if let Some(ref mut buffer) = self.event_buffer {
+                buffer.push((self.time, ev.clone()));
+            }
 
             self.analytics.event(ev, self.time, map);
         }
@@ -795,6 +966,10 @@ impl Sim {
                 "- transit: {} bytes",
                 prettyprint_usize(serialized_size_bytes(&self.transit))
             );
+            println!(
+                "- cap: {} bytes",
+                prettyprint_usize(serialized_size_bytes(&self.cap))
+            );
             println!(
                 "- trips: {} bytes",
                 prettyprint_usize(serialized_size_bytes(&self.trips))
@@ -836,9 +1011,11 @@ impl Sim {
     pub fn handle_live_edits(&mut self, map: &Map, timer: &mut Timer) -> (usize, usize) {
         self.edits_name = map.get_edits().edits_name.clone();
 
-        let (affected, num_parked_cars) = self.find_trips_affected_by_live_edits(map, timer);
+        let (affected, ride_hail, num_parked_cars) =
+            self.find_trips_affected_by_live_edits(map, timer);
         let num_trips_cancelled = affected.len();
-        let affected_agents: BTreeSet<AgentID> = affected.iter().map(|(a, _)| *a).collect();
+        let mut affected_agents: BTreeSet<AgentID> = affected.iter().map(|(a, _)| *a).collect();
+        affected_agents.extend(ride_hail.iter().map(|car| AgentID::Car(*car)));
 
         // V1: Just cancel every trip crossing an affected area.
         // (V2 is probably rerouting everyone, only cancelling when that fails)
@@ -846,6 +1023,8 @@ impl Sim {
         let mut ctx = Ctx {
             parking: &mut self.parking,
             intersections: &mut self.intersections,
+            cap: &mut self.cap,
+            fleet: &mut self.fleet,
             scheduler: &mut self.scheduler,
             map,
             handling_live_edits: Some(affected_agents),
@@ -865,7 +1044,7 @@ impl Sim {
                     self.trips.trip_abruptly_cancelled(trip, AgentID::Car(car));
                 }
                 AgentID::Pedestrian(ped) => {
-                    self.walking.delete_ped(ped, &mut ctx);
+                    self.walking.delete_ped(ped, self.time, &mut ctx);
                     self.trips.cancel_trip(
                         self.time,
                         trip,
@@ -879,20 +1058,30 @@ impl Sim {
                 AgentID::BusPassenger(_, _) => unreachable!(),
             }
         }
+        // Ride-hailing vehicles don't have a trip to cancel. Any rider gets stranded, and anybody
+        // waiting for the vehicle asks for another.
+        for car in ride_hail {
+            if self.driving.does_car_exist(car) {
+                self.driving.delete_car(car, self.time, &mut ctx);
+            }
+            self.trips.ride_hail_vehicle_lost(self.time, car, &mut ctx);
+        }
 
         self.driving.handle_live_edits(map);
         self.intersections.handle_live_edits(map);
+        self.cap.handle_live_edits(map);
 
         (num_trips_cancelled, num_parked_cars)
     }
 
-    /// Returns (trips affected, number of parked cars displaced)
+    /// Returns (trips affected, ride-hailing vehicles affected, number of parked cars displaced)
     fn find_trips_affected_by_live_edits(
         &mut self,
         map: &Map,
         timer: &mut Timer,
-    ) -> (BTreeSet<(AgentID, TripID)>, usize) {
+    ) -> (BTreeSet<(AgentID, TripID)>, BTreeSet<CarID>, usize) {
         let mut affected: BTreeSet<(AgentID, TripID)> = BTreeSet::new();
+        let mut ride_hail: BTreeSet<CarID> = BTreeSet::new();
 
         // TODO Handle changes to access restrictions
 
@@ -924,20 +1113,33 @@ impl Sim {
                 }
             }
 
-            affected.extend(
-                self.driving
-                    .find_vehicles_affected_by_live_edits(&closed_intersections, &edited_lanes),
+            let (trips, cars) = self.driving.find_vehicles_affected_by_live_edits(
+                &closed_intersections,
+                &edited_lanes,
+                &self.fleet,
             );
+            affected.extend(trips);
+            ride_hail.extend(cars);
         }
 
         let num_evicted = {
             let (evicted_cars, cars_parking_in_the_void) =
                 self.parking.handle_live_edits(map, timer);
             let num_evicted = evicted_cars.len();
+            // Idle ride-hailing vehicles parked in a spot that's gone
+            for p in &evicted_cars {
+                if self.fleet.has_vehicle(p.vehicle.id) {
+                    ride_hail.insert(p.vehicle.id);
+                }
+            }
             affected.extend(self.walking.find_trips_to_parking(evicted_cars));
             for car in cars_parking_in_the_void {
                 let a = AgentID::Car(car);
-                affected.insert((a, self.agent_to_trip(a).unwrap()));
+                if let Some(trip) = self.agent_to_trip(a) {
+                    affected.insert((a, trip));
+                } else if self.fleet.has_vehicle(car) {
+                    ride_hail.insert(car);
+                }
             }
 
             if !self.parking.is_infinite() {
@@ -945,12 +1147,16 @@ impl Sim {
                 let mut all_spots: BTreeSet<ParkingSpot> = BTreeSet::new();
                 all_spots.extend(filled);
                 all_spots.extend(avail);
-                affected.extend(self.driving.find_trips_to_edited_parking(all_spots));
+                let (trips, cars) = self
+                    .driving
+                    .find_trips_to_edited_parking(all_spots, &self.fleet);
+                affected.extend(trips);
+                ride_hail.extend(cars);
             }
             num_evicted
         };
 
-        (affected, num_evicted)
+        (affected, ride_hail, num_evicted)
     }
 }
 
@@ -961,6 +1167,8 @@ impl Sim {
             let mut ctx = Ctx {
                 parking: &mut self.parking,
                 intersections: &mut self.intersections,
+                cap: &mut self.cap,
+                fleet: &mut self.fleet,
                 scheduler: &mut self.scheduler,
                 map,
                 handling_live_edits: None,
@@ -1020,6 +1228,28 @@ impl Sim {
     }
 }
 
+// Streaming events
+impl Sim {
+    /// Start keeping a copy of every event, for the caller to periodically drain.
+    pub fn enable_event_buffer(&mut self) {
+        if self.event_buffer.is_none() {
+            self.event_buffer = Some(Vec::new());
+        }
+    }
+
+    pub fn disable_event_buffer(&mut self) {
+        self.event_buffer = None;
+    }
+
+    /// Returns every event since the last call, and when it happened.
+    pub fn drain_event_buffer(&mut self) -> Vec<(Time, Event)> {
+        match self.event_buffer {
+            Some(ref mut buffer) => buffer.drain(..).collect(),
+            None => Vec::new(),
+        }
+    }
+}
+
 // Managing highlighted people
 impl Sim {
     pub fn set_highlighted_people(&mut self, people: BTreeSet<PersonID>) {
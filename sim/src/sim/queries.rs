@@ -7,16 +7,16 @@ use std::collections::{BTreeMap, BTreeSet};
 use abstutil::Counter;
 use geom::{Distance, Duration, PolyLine, Pt2D, Time};
 use map_model::{
-    BuildingID, BusRouteID, BusStopID, IntersectionID, Lane, LaneID, Map, Path, Position,
-    Traversable, TurnID,
+    BuildingID, BusRouteID, BusStopID, IntersectionID, Lane, LaneID, Map, Path, Position, RoadID,
+    TransitCapacity, Traversable, TurnID,
 };
 
 use crate::analytics::SlidingWindow;
 use crate::{
     AgentID, AgentType, Analytics, CarID, CommutersVehiclesCounts, DrawCarInput, DrawPedCrowdInput,
     DrawPedestrianInput, OrigPersonID, PandemicModel, ParkedCar, ParkingSim, PedestrianID, Person,
-    PersonID, PersonState, Scenario, Sim, TripEndpoint, TripID, TripInfo, TripMode, TripResult,
-    UnzoomedAgent, VehicleType,
+    PersonID, PersonState, RideHailSummary, Scenario, Sim, TripEndpoint, TripID, TripInfo,
+    TripMode, TripResult, UnzoomedAgent, VehicleType,
 };
 
 // TODO Many of these just delegate to an inner piece. This is unorganized and hard to maintain.
@@ -38,7 +38,7 @@ impl Sim {
         self.trips.num_trips()
     }
     pub fn num_agents(&self) -> Counter<AgentType> {
-        self.trips.num_agents(&self.transit)
+        self.trips.num_agents(&self.transit, &self.fleet)
     }
     pub fn num_commuters_vehicles(&self) -> CommutersVehiclesCounts {
         self.trips
@@ -100,6 +100,10 @@ impl Sim {
         self.transit.get_passengers(car).len()
     }
 
+    pub fn transit_capacity(&self, car: CarID) -> TransitCapacity {
+        self.transit.get_capacity(car)
+    }
+
     pub fn bus_route_id(&self, maybe_bus: CarID) -> Option<BusRouteID> {
         if maybe_bus.vehicle_type == VehicleType::Bus
             || maybe_bus.vehicle_type == VehicleType::Train
@@ -204,6 +208,7 @@ impl Sim {
             VehicleType::Bike,
             VehicleType::Bus,
             VehicleType::Train,
+            VehicleType::Emergency,
         ] {
             let id = CarID {
                 id: idx,
@@ -214,13 +219,15 @@ impl Sim {
             }
         }
 
-        let id = CarID {
-            id: idx,
-            vehicle_type: VehicleType::Car,
-        };
-        // Only cars can be parked.
-        if self.parking.lookup_parked_car(id).is_some() {
-            return Some(id);
+        // Only cars and emergency vehicles can be parked.
+        for vehicle_type in [VehicleType::Car, VehicleType::Emergency] {
+            let id = CarID {
+                id: idx,
+                vehicle_type,
+            };
+            if self.parking.lookup_parked_car(id).is_some() {
+                return Some(id);
+            }
         }
 
         None
@@ -313,6 +320,12 @@ impl Sim {
         &self.analytics
     }
 
+    /// How has the ride-hailing fleet performed so far today?
+    pub fn ride_hail_summary(&self) -> RideHailSummary {
+        self.analytics
+            .ride_hail_summary(self.time, self.fleet.num_vehicles())
+    }
+
     /// For intersections with an agent waiting beyond some threshold, return when they started
     /// waiting. Sorted by earliest waiting (likely the root cause of gridlock).
     pub fn delayed_intersections(&self, threshold: Duration) -> Vec<(IntersectionID, Time)> {
@@ -390,6 +403,12 @@ impl Sim {
         self.trips.generate_scenario(map, name)
     }
 
+    /// For each zone with a vehicle cap, returns (the member roads, the cap, the number of vehicles
+    /// entering during the current hour).
+    pub fn get_capped_zone_counts(&self) -> Vec<(&BTreeSet<RoadID>, usize, usize)> {
+        self.cap.get_zone_counts(self.time)
+    }
+
     pub fn infinite_parking(&self) -> bool {
         self.parking.is_infinite()
     }
@@ -427,7 +446,7 @@ impl Sim {
                 let max_speed = match info.mode {
                     TripMode::Walk | TripMode::Transit => Some(person.ped_speed),
                     // TODO We should really search the vehicles and grab it from there
-                    TripMode::Drive => None,
+                    TripMode::Drive | TripMode::Carpool | TripMode::RideHail => None,
                     // Assume just one bike
                     TripMode::Bike => {
                         person
@@ -3,8 +3,11 @@ use std::collections::{BTreeMap, BTreeSet};
 use serde::{Deserialize, Serialize};
 
 use abstutil::{deserialize_btreemap, serialize_btreemap};
-use geom::Time;
-use map_model::{BusRoute, BusRouteID, BusStopID, Map, Path, PathRequest, Position};
+use geom::{Duration, Time};
+use map_model::{
+    BusRoute, BusRouteID, BusStopID, Map, Path, PathRequest, Position, ScheduledStop,
+    TransitCapacity,
+};
 
 use crate::sim::Ctx;
 use crate::{
@@ -15,6 +18,11 @@ use crate::{
 // These index stops along a route, not stops along a single sidewalk.
 type StopIdx = usize;
 
+// Dwell time at a stop is a fixed amount, plus some time for every passenger getting on or off.
+const BASE_DWELL_TIME: Duration = Duration::const_seconds(10.0);
+const TIME_PER_BOARDING: Duration = Duration::const_seconds(3.0);
+const TIME_PER_ALIGHTING: Duration = Duration::const_seconds(2.0);
+
 #[derive(Serialize, Deserialize, Clone)]
 struct Stop {
     id: BusStopID,
@@ -28,6 +36,10 @@ struct Route {
     start: Path,
     end_at_border: Option<Path>,
     active_vehicles: BTreeSet<CarID>,
+    /// Same order as stops
+    schedule: Vec<ScheduledStop>,
+    /// For each stop, when the previous vehicle actually arrived and was scheduled to arrive there
+    last_arrival: BTreeMap<StopIdx, (Time, Time)>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -36,6 +48,9 @@ struct Bus {
     route: BusRouteID,
     /// Where does each passenger want to deboard?
     passengers: Vec<(PersonID, Option<BusStopID>)>,
+    capacity: TransitCapacity,
+    /// When the bus was scheduled to leave the start of the route
+    scheduled_start: Time,
     state: BusState,
 }
 
@@ -67,12 +82,14 @@ pub(crate) struct TransitSimState {
         deserialize_with = "deserialize_btreemap"
     )]
     peds_waiting: BTreeMap<BusStopID, Vec<(PedestrianID, BusRouteID, Option<BusStopID>, Time)>>,
+    /// Vehicles ahead of schedule wait at timepoints
+    hold_at_timepoints: bool,
 
     events: Vec<Event>,
 }
 
 impl TransitSimState {
-    pub fn new(map: &Map) -> TransitSimState {
+    pub fn new(map: &Map, hold_at_timepoints: bool) -> TransitSimState {
         // Keep this filled out always so get_passengers can return &Vec without a hassle
         let mut peds_waiting = BTreeMap::new();
         for bs in map.all_bus_stops().keys() {
@@ -83,6 +100,7 @@ impl TransitSimState {
             buses: BTreeMap::new(),
             routes: BTreeMap::new(),
             peds_waiting,
+            hold_at_timepoints,
             events: Vec::new(),
         }
     }
@@ -142,33 +160,46 @@ impl TransitSimState {
             } else {
                 None
             };
+            let schedule = match bus_route.schedule {
+                Some(ref schedule) if schedule.len() == stops.len() => {
+                    anchor_schedule(schedule, &start, map)
+                }
+                _ => derive_schedule(&start, &stops, map),
+            };
             Route {
                 active_vehicles: BTreeSet::new(),
                 stops,
                 start,
                 end_at_border,
+                schedule,
+                last_arrival: BTreeMap::new(),
             }
         });
 
         self.routes[&bus_route.id].start.clone()
     }
 
-    pub fn bus_created(&mut self, bus: CarID, r: BusRouteID) {
-        let route = self.routes.get_mut(&r).unwrap();
+    pub fn bus_created(&mut self, bus: CarID, r: &BusRoute, scheduled_start: Time) {
+        let route = self.routes.get_mut(&r.id).unwrap();
         route.active_vehicles.insert(bus);
         self.buses.insert(
             bus,
             Bus {
                 car: bus,
-                route: r,
+                route: r.id,
                 passengers: Vec::new(),
+                capacity: r
+                    .capacity
+                    .or_else(|| bus.vehicle_type.default_transit_capacity())
+                    .unwrap(),
+                scheduled_start,
                 state: BusState::DrivingToStop(0),
             },
         );
     }
 
-    /// If true, the bus is idling. If false, the bus actually arrived at a border and should now
-    /// vanish.
+    /// If the bus is idling at the stop, returns when it should depart. If None, the bus actually
+    /// arrived at a border and should now vanish.
     pub fn bus_arrived_at_stop(
         &mut self,
         now: Time,
@@ -176,19 +207,36 @@ impl TransitSimState {
         trips: &mut TripManager,
         walking: &mut WalkingSimState,
         ctx: &mut Ctx,
-    ) -> bool {
+    ) -> Option<Time> {
         let mut bus = self.buses.get_mut(&id).unwrap();
         match bus.state {
             BusState::DrivingToStop(stop_idx) => {
                 bus.state = BusState::AtStop(stop_idx);
-                let stop1 = self.routes[&bus.route].stops[stop_idx].id;
+                let route = self.routes.get_mut(&bus.route).unwrap();
+                let stop1 = route.stops[stop_idx].id;
                 self.events
                     .push(Event::BusArrivedAtStop(id, bus.route, stop1));
 
+                // Compare to the schedule
+                let scheduled = bus.scheduled_start + route.schedule[stop_idx].offset;
+                let headway = route.last_arrival.insert(stop_idx, (now, scheduled)).map(
+                    |(prev_actual, prev_scheduled)| (now - prev_actual, scheduled - prev_scheduled),
+                );
+                self.events.push(Event::BusScheduleAdherence {
+                    bus: id,
+                    route: bus.route,
+                    stop: stop1,
+                    deviation: now - scheduled,
+                    headway,
+                });
+                let timepoint = route.schedule[stop_idx].timepoint;
+
                 // Deboard existing passengers.
+                let mut alightings = 0;
                 let mut still_riding = Vec::new();
                 for (person, maybe_stop2) in bus.passengers.drain(..) {
                     if Some(stop1) == maybe_stop2 {
+                        alightings += 1;
                         trips.person_left_bus(now, person, bus.car, ctx);
                         self.events.push(Event::PassengerAlightsTransit(
                             person, bus.car, bus.route, stop1,
@@ -199,18 +247,32 @@ impl TransitSimState {
                 }
                 bus.passengers = still_riding;
 
-                // Board new passengers.
+                // Board new passengers, in the order they started waiting, until the vehicle is
+                // full. Everybody else waits for the next one.
+                let mut boardings = 0;
                 let mut still_waiting = Vec::new();
                 for (ped, route, maybe_stop2, started_waiting) in
                     self.peds_waiting.remove(&stop1).unwrap()
                 {
-                    if bus.route == route {
+                    if bus.route == route && bus.passengers.len() >= bus.capacity.total() {
+                        let trip = trips.agent_to_trip(AgentID::Pedestrian(ped)).unwrap();
+                        self.events.push(Event::PassengerDeniedBoarding(
+                            trip,
+                            trips.trip_to_person(trip).unwrap(),
+                            bus.car,
+                            route,
+                            stop1,
+                        ));
+                        still_waiting.push((ped, route, maybe_stop2, started_waiting));
+                    } else if bus.route == route {
+                        boardings += 1;
                         let (trip, person) = trips.ped_boarded_bus(
                             now,
                             ped,
                             bus.car,
                             now - started_waiting,
                             walking,
+                            ctx,
                         );
                         self.events.push(Event::PassengerBoardsTransit(
                             person,
@@ -244,7 +306,15 @@ impl TransitSimState {
                     }
                 }
                 self.peds_waiting.insert(stop1, still_waiting);
-                true
+
+                let mut depart = now
+                    + BASE_DWELL_TIME
+                    + (boardings as f64) * TIME_PER_BOARDING
+                    + (alightings as f64) * TIME_PER_ALIGHTING;
+                if self.hold_at_timepoints && timepoint {
+                    depart = depart.max(scheduled);
+                }
+                Some(depart)
             }
             BusState::DrivingOffMap => {
                 self.routes
@@ -263,7 +333,7 @@ impl TransitSimState {
                     }
                     trips.transit_rider_reached_border(now, person, id, ctx);
                 }
-                false
+                None
             }
             BusState::AtStop(_) | BusState::Done => unreachable!(),
         }
@@ -300,7 +370,8 @@ impl TransitSimState {
         }
     }
 
-    /// Returns the bus if the pedestrian boarded immediately.
+    /// Returns the bus if the pedestrian boarded immediately. If every vehicle at the stop is full,
+    /// the pedestrian waits for the next one.
     pub fn ped_waiting_for_bus(
         &mut self,
         now: Time,
@@ -317,6 +388,12 @@ impl TransitSimState {
             for bus in &route.active_vehicles {
                 if let BusState::AtStop(idx) = self.buses[bus].state {
                     if route.stops[idx].id == stop1 {
+                        if self.buses[bus].passengers.len() >= self.buses[bus].capacity.total() {
+                            self.events.push(Event::PassengerDeniedBoarding(
+                                trip, person, *bus, route_id, stop1,
+                            ));
+                            continue;
+                        }
                         self.buses
                             .get_mut(bus)
                             .unwrap()
@@ -366,6 +443,10 @@ impl TransitSimState {
         self.buses[&bus].route
     }
 
+    pub fn get_capacity(&self, bus: CarID) -> TransitCapacity {
+        self.buses[&bus].capacity
+    }
+
     /// also stop idx that the bus is coming from
     pub fn buses_for_route(&self, route: BusRouteID) -> Vec<(CarID, Option<usize>)> {
         if let Some(r) = self.routes.get(&route) {
@@ -448,3 +529,33 @@ impl TransitSimState {
         results
     }
 }
+
+/// Imported schedules are relative to departing the first stop, but vehicles spawn some distance
+/// before that. Like a derived schedule, assume vehicles reach the first stop at the speed limit.
+fn anchor_schedule(schedule: &[ScheduledStop], start: &Path, map: &Map) -> Vec<ScheduledStop> {
+    let to_first_stop = start.estimate_duration(map, None);
+    schedule
+        .iter()
+        .map(|stop| ScheduledStop {
+            offset: to_first_stop + stop.offset,
+            timepoint: stop.timepoint,
+        })
+        .collect()
+}
+
+/// Without an imported schedule, assume vehicles travel at the speed limit and dwell at every stop
+/// for the minimum time. Every stop is a timepoint.
+fn derive_schedule(start: &Path, stops: &[Stop], map: &Map) -> Vec<ScheduledStop> {
+    let mut schedule = Vec::new();
+    let mut offset = start.estimate_duration(map, None);
+    for stop in stops {
+        schedule.push(ScheduledStop {
+            offset,
+            timepoint: true,
+        });
+        if let Some(ref path) = stop.next_stop {
+            offset += BASE_DWELL_TIME + path.estimate_duration(map, None);
+        }
+    }
+    schedule
+}
@@ -1,22 +1,29 @@
-use std::collections::{BTreeMap, VecDeque};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
 
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
 use abstutil::{deserialize_btreemap, serialize_btreemap, Counter};
 use geom::{Distance, Duration, Speed, Time};
 use map_model::{
-    BuildingID, BusRouteID, BusStopID, IntersectionID, Map, PathConstraints, PathRequest, Position,
+    BuildingID, BusRouteID, BusStopID, IntersectionID, Map, Path, PathConstraints, PathRequest,
+    Position,
 };
 
 use crate::sim::Ctx;
 use crate::{
-    AgentID, AgentType, AlertLocation, CarID, Command, CreateCar, CreatePedestrian, DrivingGoal,
-    Event, IndividTrip, OrigPersonID, ParkedCar, ParkingSim, ParkingSpot, PedestrianID, PersonID,
-    PersonSpec, Scenario, SidewalkPOI, SidewalkSpot, StartTripArgs, TransitSimState, TripEndpoint,
-    TripID, TripPhaseType, TripPurpose, TripSpec, Vehicle, VehicleSpec, VehicleType,
-    WalkingSimState,
+    AgentID, AgentType, AlertLocation, CapResult, CarID, Command, CreateCar, CreatePedestrian,
+    DrivingGoal, Event, FleetSimState, FleetVehicleState, HouseholdSpec, IndividTrip, OrigPersonID,
+    ParkedCar, ParkingSim, ParkingSimState, ParkingSpot, PedestrianID, PersonID, PersonSpec,
+    Scenario, SidewalkPOI, SidewalkSpot, StartTripArgs, TransitSimState, TripEndpoint, TripID,
+    TripPhaseType, TripPurpose, TripSpec, Vehicle, VehicleSpec, VehicleType, WalkingSimState,
 };
 
+/// A carpool driver waits at most this long past their departure time for passengers to show up.
+const CARPOOL_MAX_WAIT: Duration = Duration::const_seconds(15.0 * 60.0);
+/// Household members leaving home only take cars parked at most this far away.
+const HOUSEHOLD_CAR_MAX_DIST: Distance = Distance::const_meters(400.0);
+
 /// Manages people, each of which executes some trips through the day. Each trip is further broken
 /// down into legs -- for example, a driving trip might start with somebody walking to their car,
 /// driving somewhere, parking, and then walking to their final destination.
@@ -34,6 +41,9 @@ pub(crate) struct TripManager {
     )]
     active_trip_mode: BTreeMap<AgentID, TripID>,
     unfinished_trips: usize,
+    /// Keyed by the driver's trip
+    carpools: BTreeMap<TripID, Carpool>,
+    households: Vec<Household>,
 
     car_id_counter: usize,
 
@@ -48,6 +58,8 @@ impl TripManager {
             people: Vec::new(),
             active_trip_mode: BTreeMap::new(),
             unfinished_trips: 0,
+            carpools: BTreeMap::new(),
+            households: Vec::new(),
             car_id_counter: 0,
             events: Vec::new(),
         }
@@ -82,10 +94,44 @@ impl TripManager {
             vehicles,
             delayed_trips: Vec::new(),
             on_bus: None,
+            household: None,
         });
         self.get_person(id).unwrap()
     }
 
+    /// Several people share some cars, which the first member owns. Returns the new cars, which
+    /// should be parked near home.
+    pub fn new_household(
+        &mut self,
+        home: BuildingID,
+        members: Vec<PersonID>,
+        vehicle_specs: Vec<VehicleSpec>,
+    ) -> Vec<Vehicle> {
+        let owner = members[0];
+        let vehicles: Vec<Vehicle> = vehicle_specs
+            .into_iter()
+            .map(|v| {
+                let c = CarID {
+                    id: self.new_car_id(),
+                    vehicle_type: v.vehicle_type,
+                };
+                v.make(c, Some(owner))
+            })
+            .collect();
+        for p in &members {
+            assert!(self.people[p.0].household.is_none());
+            self.people[p.0].household = Some(self.households.len());
+        }
+        self.people[owner.0].vehicles.extend(vehicles.clone());
+        self.households.push(Household {
+            home,
+            members,
+            cars: vehicles.iter().map(|v| v.id).collect(),
+            borrowed: BTreeMap::new(),
+        });
+        vehicles
+    }
+
     pub fn new_car_id(&mut self) -> usize {
         let id = self.car_id_counter;
         self.car_id_counter += 1;
@@ -103,6 +149,7 @@ impl TripManager {
             total_blocked_time: Duration::ZERO,
             total_distance: Distance::ZERO,
             legs: VecDeque::new(),
+            driver: None,
         };
         self.unfinished_trips += 1;
         let person = &mut self.people[trip.person.0];
@@ -130,6 +177,27 @@ impl TripManager {
         id
     }
 
+    /// Make a passenger ride along with somebody driving. The passenger meets the driver where
+    /// their trip starts.
+    pub fn new_carpool(&mut self, driver: TripID, passenger: TripID) {
+        assert_eq!(self.trips[driver.0].info.mode, TripMode::Drive);
+        assert_eq!(self.trips[passenger.0].info.mode, TripMode::Carpool);
+        assert!(self.trips[passenger.0].driver.is_none());
+        self.trips[passenger.0].driver = Some(driver);
+        self.carpools
+            .entry(driver)
+            .or_insert_with(|| Carpool {
+                passengers: Vec::new(),
+                waiting: BTreeSet::new(),
+                walking: BTreeSet::new(),
+                riding: Vec::new(),
+                deferred_start: None,
+                ready_to_drive: None,
+            })
+            .passengers
+            .push(passenger);
+    }
+
     pub fn start_trip(&mut self, now: Time, trip: TripID, args: StartTripArgs, ctx: &mut Ctx) {
         assert!(self.trips[trip.0].info.cancellation_reason.is_none());
 
@@ -154,20 +222,67 @@ impl TripManager {
             ));
             return;
         }
+        if let Some(carpool) = self.carpools.get_mut(&trip) {
+            // Wait for the passengers, but not forever
+            let departure = self.trips[trip.0].info.departure;
+            if !carpool.everyone_here(&self.trips) && now < departure + CARPOOL_MAX_WAIT {
+                ctx.scheduler.push(
+                    departure + CARPOOL_MAX_WAIT,
+                    Command::StartTrip(trip, args.clone()),
+                );
+                carpool.deferred_start = Some(args);
+                self.events.push(Event::TripPhaseStarting(
+                    trip,
+                    person.id,
+                    None,
+                    TripPhaseType::WaitingForCarpool,
+                ));
+                return;
+            }
+            carpool.deferred_start = None;
+        }
         self.trips[trip.0].started = true;
 
+        let mut use_vehicle = args.use_vehicle;
+        if let Some(idx) = person.household {
+            let info = &self.trips[trip.0].info;
+            if info.mode == TripMode::Drive && use_vehicle.is_none() {
+                use_vehicle =
+                    self.households[idx].borrow_car(person.id, info.start, ctx.parking, ctx.map);
+            }
+        }
+
         let info = &self.trips[trip.0].info;
-        let spec = match TripSpec::maybe_new(
-            info.start,
-            info.end,
-            info.mode,
-            args.use_vehicle,
-            args.retry_if_no_room,
-            ctx.map,
-        ) {
+        let maybe_spec = if info.mode == TripMode::Carpool {
+            match self.trips[trip.0].driver {
+                Some(driver) if !self.trips[driver.0].is_done() => TripSpec::joining_carpool(
+                    info.start,
+                    info.end,
+                    driver,
+                    self.trips[driver.0].info.start,
+                    ctx.map,
+                ),
+                _ => Err(anyhow!("{} has no carpool driver to ride with", trip)),
+            }
+        } else if info.mode == TripMode::Drive && use_vehicle.is_none() {
+            Err(anyhow!(
+                "{} has no household car to drive; they're all in use or parked away from home",
+                person.id
+            ))
+        } else {
+            TripSpec::maybe_new(
+                info.start,
+                info.end,
+                info.mode,
+                use_vehicle,
+                args.retry_if_no_room,
+                ctx.map,
+            )
+        };
+        let spec = match maybe_spec {
             Ok(spec) => spec,
             Err(error) => TripSpec::SpawningFailure {
-                use_vehicle: args.use_vehicle,
+                use_vehicle,
                 error: error.to_string(),
             },
         };
@@ -190,8 +305,9 @@ impl TripManager {
                     ctx.map.get_l(start_pos.lane()).src_i,
                 ));
                 person.state = PersonState::Trip(trip);
+                let person = person.id;
 
-                let vehicle = person.get_vehicle(use_vehicle);
+                let vehicle = self.get_household_vehicle(person, use_vehicle);
                 assert!(ctx.parking.lookup_parked_car(vehicle.id).is_none());
                 let constraints = if use_vehicle.vehicle_type == VehicleType::Bike {
                     PathConstraints::Bike
@@ -203,13 +319,27 @@ impl TripManager {
                     goal.goal_pos(constraints, ctx.map).unwrap(),
                     constraints,
                 );
-                let person = person.id;
 
-                match ctx.map.pathfind(req) {
+                match ctx.map.pathfind(req.clone()) {
                     Ok(path) => {
+                        let (path, spawn_time) = if use_vehicle.vehicle_type == VehicleType::Car {
+                            match ctx
+                                .cap
+                                .validate_path(&req, path, now, vehicle.id, trip, false, ctx.map)
+                            {
+                                CapResult::Ok(path) => (path, now),
+                                CapResult::Delay(path, t) => (path, t),
+                                CapResult::Cancel(err) => {
+                                    self.cancel_trip(now, trip, err, Some(vehicle), ctx);
+                                    return;
+                                }
+                            }
+                        } else {
+                            (path, now)
+                        };
                         let router = goal.make_router(vehicle.id, path, ctx.map);
                         ctx.scheduler.push(
-                            now,
+                            spawn_time,
                             Command::SpawnCar(
                                 CreateCar::for_appearing(vehicle, router, trip, person),
                                 retry_if_no_room,
@@ -224,7 +354,8 @@ impl TripManager {
             TripSpec::SpawningFailure {
                 use_vehicle, error, ..
             } => {
-                let vehicle = use_vehicle.map(|v| person.get_vehicle(v));
+                let person = person.id;
+                let vehicle = use_vehicle.map(|v| self.get_household_vehicle(person, v));
                 self.cancel_trip(now, trip, error, vehicle, ctx);
             }
             TripSpec::UsingParkedCar {
@@ -246,12 +377,13 @@ impl TripManager {
                                     id: person.ped,
                                     speed: person.ped_speed,
                                     start,
-                                    goal: walking_goal,
-                                    path,
+                                    goal: walking_goal.clone(),
+                                    path: path.clone(),
                                     trip,
                                     person: person.id,
                                 }),
                             );
+                            self.carpool_heads_to_car(now, trip, walking_goal, path, ctx);
                         }
                         Err(err) => {
                             // Move the car to the destination
@@ -429,6 +561,53 @@ impl TripManager {
                     }
                 }
             }
+            TripSpec::JoiningCarpool { start, pickup, .. } => {
+                let start_bldg = match start.connection {
+                    SidewalkPOI::Building(b) => b,
+                    _ => unreachable!(),
+                };
+                assert_eq!(person.state, PersonState::Inside(start_bldg));
+                person.state = PersonState::Trip(trip);
+
+                if start_bldg == pickup {
+                    // Just head outside and wait
+                    self.events
+                        .push(Event::PersonLeavesBuilding(person.id, start_bldg));
+                    self.passenger_reached_pickup(now, trip, ctx);
+                    return;
+                }
+
+                let walk_to = SidewalkSpot::building(pickup, ctx.map);
+                let req = PathRequest::walking(start.sidewalk_pos, walk_to.sidewalk_pos);
+                match ctx.map.pathfind(req) {
+                    Ok(path) => {
+                        ctx.scheduler.push(
+                            now,
+                            Command::SpawnPed(CreatePedestrian {
+                                id: person.ped,
+                                speed: person.ped_speed,
+                                start,
+                                goal: walk_to,
+                                path,
+                                trip,
+                                person: person.id,
+                            }),
+                        );
+                    }
+                    Err(err) => {
+                        self.cancel_trip(now, trip, err.to_string(), None, ctx);
+                    }
+                }
+            }
+            TripSpec::UsingRideHail { start, .. } => {
+                assert_eq!(person.state, PersonState::Inside(start));
+                person.state = PersonState::Trip(trip);
+
+                // Head outside and wait
+                self.events
+                    .push(Event::PersonLeavesBuilding(person.id, start));
+                self.request_ride_hail(now, trip, start, ctx);
+            }
         }
     }
 
@@ -469,6 +648,12 @@ impl TripManager {
             _ => unreachable!(),
         };
 
+        let id = trip.id;
+        let (person, end) = (trip.person, trip.info.end);
+        self.return_household_car(person, car, end);
+        self.drop_off_carpool(now, id, spot, ctx);
+        let trip = &mut self.trips[id.0];
+
         match &trip.legs[0] {
             TripLeg::Walk(to) => match (spot, &to.connection) {
                 (ParkingSpot::Offstreet(b1, _), SidewalkPOI::Building(b2)) if b1 == *b2 => {
@@ -514,6 +699,12 @@ impl TripManager {
         trip.total_blocked_time += blocked_time;
         trip.total_distance += distance_crossed;
 
+        if let Some(TripLeg::RideCar(_)) = trip.legs.front() {
+            let id = trip.id;
+            self.passenger_reached_car(now, id, ctx);
+            return;
+        }
+
         trip.assert_walking_leg(SidewalkSpot::deferred_parking_spot());
         let parked_car = ctx.parking.get_car_at_spot(spot).unwrap().clone();
         let drive_to = match trip.legs[0] {
@@ -544,16 +735,44 @@ impl TripManager {
 
         let person = trip.person;
         let trip = trip.id;
-        match ctx.map.pathfind(req) {
+        match ctx.map.pathfind(req.clone()) {
             Ok(path) => {
-                let router = drive_to.make_router(parked_car.vehicle.id, path, ctx.map);
-                ctx.scheduler.push(
+                let (path, spawn_time) = match ctx.cap.validate_path(
+                    &req,
+                    path,
                     now,
-                    Command::SpawnCar(
-                        CreateCar::for_parked_car(parked_car, router, trip, person),
-                        true,
-                    ),
-                );
+                    parked_car.vehicle.id,
+                    trip,
+                    false,
+                    ctx.map,
+                ) {
+                    CapResult::Ok(path) => (path, now),
+                    CapResult::Delay(path, t) => (path, t),
+                    CapResult::Cancel(err) => {
+                        ctx.parking.remove_parked_car(parked_car.clone());
+                        self.cancel_trip(now, trip, err, Some(parked_car.vehicle), ctx);
+                        return;
+                    }
+                };
+                let router = drive_to.make_router(parked_car.vehicle.id, path, ctx.map);
+                let create_car = CreateCar::for_parked_car(parked_car, router, trip, person, 0);
+                let wait_for_passengers = self
+                    .carpools
+                    .get(&trip)
+                    .map(|carpool| !carpool.walking.is_empty())
+                    .unwrap_or(false);
+                if wait_for_passengers {
+                    self.carpools.get_mut(&trip).unwrap().ready_to_drive =
+                        Some((spawn_time, create_car));
+                    self.events.push(Event::TripPhaseStarting(
+                        trip,
+                        person,
+                        None,
+                        TripPhaseType::WaitingForCarpool,
+                    ));
+                } else {
+                    self.drive_parked_car(spawn_time, create_car, ctx);
+                }
             }
             Err(err) => {
                 // Move the car to the destination...
@@ -684,6 +903,11 @@ impl TripManager {
         trip.total_distance += distance_crossed;
 
         trip.assert_walking_leg(SidewalkSpot::building(bldg, ctx.map));
+        if let Some(TripLeg::RideCar(_)) = trip.legs.front() {
+            let id = trip.id;
+            self.passenger_reached_pickup(now, id, ctx);
+            return;
+        }
 
         self.people[trip.person.0].state = PersonState::Inside(bldg);
         self.events
@@ -755,6 +979,7 @@ impl TripManager {
         bus: CarID,
         blocked_time: Duration,
         walking: &mut WalkingSimState,
+        ctx: &mut Ctx,
     ) -> (TripID, PersonID) {
         let trip = &mut self.trips[self
             .active_trip_mode
@@ -765,7 +990,7 @@ impl TripManager {
         // No distance crossed between waiting for a bus and boarding
 
         trip.legs.pop_front();
-        walking.ped_boarded_bus(now, ped);
+        walking.ped_boarded_bus(now, ped, ctx);
         self.active_trip_mode
             .insert(AgentID::BusPassenger(trip.person, bus), trip.id);
         self.people[trip.person.0].on_bus = Some(bus);
@@ -888,6 +1113,9 @@ impl TripManager {
         }
 
         let id = trip.id;
+        let person = trip.person;
+        self.return_household_car(person, car, TripEndpoint::Border(i));
+        self.carpool_left_map(now, id, i, ctx);
         self.trip_finished(now, id, ctx);
     }
 
@@ -955,6 +1183,460 @@ impl TripManager {
             }
         }
     }
+
+    /// A carpool passenger reached the pickup. If the driver already left, they're out of luck.
+    fn passenger_reached_pickup(&mut self, now: Time, id: TripID, ctx: &mut Ctx) {
+        let trip = &self.trips[id.0];
+        let person = trip.person;
+        let driver = match trip.legs[0] {
+            TripLeg::RideCar(driver) => driver,
+            _ => unreachable!(),
+        };
+        // Once the driver heads to the car, anybody late is out of luck
+        if self.trips[driver.0].started || self.trips[driver.0].is_done() {
+            self.cancel_trip(
+                now,
+                id,
+                format!("missed their carpool with {}", driver),
+                None,
+                ctx,
+            );
+            return;
+        }
+
+        let carpool = self.carpools.get_mut(&driver).unwrap();
+        carpool.waiting.insert(id);
+        self.events.push(Event::TripPhaseStarting(
+            id,
+            person,
+            None,
+            TripPhaseType::WaitingForCarpool,
+        ));
+        if carpool.deferred_start.is_some() && carpool.everyone_here(&self.trips) {
+            let args = carpool.deferred_start.take().unwrap();
+            ctx.scheduler.update(now, Command::StartTrip(driver, args));
+        }
+    }
+
+    /// A carpool driver is heading from the pickup to their car, so everybody waiting there walks
+    /// along. If the car is parked inside the pickup building, they just wait for the driver to
+    /// get it.
+    fn carpool_heads_to_car(
+        &mut self,
+        now: Time,
+        driver: TripID,
+        goal: SidewalkSpot,
+        path: Path,
+        ctx: &mut Ctx,
+    ) {
+        if let SidewalkPOI::ParkingSpot(ParkingSpot::Offstreet(b, _)) = goal.connection {
+            if self.trips[driver.0].info.start == TripEndpoint::Bldg(b) {
+                return;
+            }
+        }
+        let carpool = match self.carpools.get_mut(&driver) {
+            Some(carpool) => carpool,
+            None => {
+                return;
+            }
+        };
+        // Everybody's already standing outside
+        let start = SidewalkSpot::suddenly_appear(path.get_req().start, ctx.map);
+        carpool.walking = std::mem::take(&mut carpool.waiting);
+        for id in &carpool.walking {
+            let person = &self.people[self.trips[id.0].person.0];
+            ctx.scheduler.push(
+                now,
+                Command::SpawnPed(CreatePedestrian {
+                    id: person.ped,
+                    speed: person.ped_speed,
+                    start: start.clone(),
+                    goal: goal.clone(),
+                    path: path.clone(),
+                    trip: *id,
+                    person: person.id,
+                }),
+            );
+        }
+    }
+
+    /// A carpool passenger walked with the driver to the car. If the driver's trip was cancelled
+    /// meanwhile, they're out of luck.
+    fn passenger_reached_car(&mut self, now: Time, id: TripID, ctx: &mut Ctx) {
+        let person = self.trips[id.0].person;
+        let driver = match self.trips[id.0].legs[0] {
+            TripLeg::RideCar(driver) => driver,
+            _ => unreachable!(),
+        };
+        let carpool = self.carpools.get_mut(&driver).unwrap();
+        carpool.walking.remove(&id);
+        if self.trips[driver.0].is_done() {
+            self.cancel_trip(
+                now,
+                id,
+                format!("their carpool driver's trip {} was cancelled", driver),
+                None,
+                ctx,
+            );
+            return;
+        }
+
+        carpool.waiting.insert(id);
+        self.events.push(Event::TripPhaseStarting(
+            id,
+            person,
+            None,
+            TripPhaseType::WaitingForCarpool,
+        ));
+        self.maybe_depart_carpool(now, driver, ctx);
+    }
+
+    /// If the carpool driver is waiting in the car and nobody's still walking there, leave.
+    fn maybe_depart_carpool(&mut self, now: Time, driver: TripID, ctx: &mut Ctx) {
+        let carpool = self.carpools.get_mut(&driver).unwrap();
+        if !carpool.walking.is_empty() {
+            return;
+        }
+        if let Some((spawn_time, create_car)) = carpool.ready_to_drive.take() {
+            self.drive_parked_car(spawn_time.max(now), create_car, ctx);
+        }
+    }
+
+    /// The driver is in their parked car. Anybody riding along gets in, and then they leave.
+    fn drive_parked_car(&mut self, spawn_time: Time, mut create_car: CreateCar, ctx: &mut Ctx) {
+        let (trip, _) = create_car.trip_and_person.unwrap();
+        create_car.passengers = self.board_carpool(trip, create_car.vehicle.id);
+        ctx.scheduler
+            .push(spawn_time, Command::SpawnCar(create_car, true));
+    }
+
+    /// A carpool driver is about to leave, so everybody waiting gets in. Returns the number of
+    /// passengers.
+    fn board_carpool(&mut self, driver: TripID, car: CarID) -> usize {
+        let carpool = match self.carpools.get_mut(&driver) {
+            Some(carpool) => carpool,
+            None => {
+                return 0;
+            }
+        };
+        carpool.riding = std::mem::take(&mut carpool.waiting).into_iter().collect();
+        for id in &carpool.riding {
+            let person = self.trips[id.0].person;
+            self.people[person.0].on_bus = Some(car);
+            self.active_trip_mode
+                .insert(AgentID::BusPassenger(person, car), *id);
+            self.events.push(Event::TripPhaseStarting(
+                *id,
+                person,
+                None,
+                TripPhaseType::RidingCar(car),
+            ));
+        }
+        let num = carpool.riding.len();
+        if num > 0 {
+            self.events.push(Event::CarpoolDeparted(driver, car, num));
+        }
+        num
+    }
+
+    /// The carpool driver parked, so passengers get out and walk the rest of the way.
+    fn drop_off_carpool(&mut self, now: Time, driver: TripID, spot: ParkingSpot, ctx: &mut Ctx) {
+        let riders = match self.carpools.get_mut(&driver) {
+            Some(carpool) => std::mem::take(&mut carpool.riding),
+            None => {
+                return;
+            }
+        };
+        for id in riders {
+            self.leave_car(id, driver);
+            self.passenger_got_out(now, id, spot, ctx);
+        }
+    }
+
+    /// A passenger got out of a car parked somewhere. If that's their destination, they're done;
+    /// otherwise they walk the rest of the way.
+    fn passenger_got_out(&mut self, now: Time, id: TripID, spot: ParkingSpot, ctx: &mut Ctx) {
+        let person = self.trips[id.0].person;
+        let parked_at_goal = match (spot, &self.trips[id.0].legs[0]) {
+            (ParkingSpot::Offstreet(b1, _), TripLeg::Walk(to)) => match to.connection {
+                SidewalkPOI::Building(b2) if b1 == b2 => Some(b1),
+                _ => None,
+            },
+            _ => None,
+        };
+        if let Some(b) = parked_at_goal {
+            self.trips[id.0].legs.pop_front().unwrap();
+            self.people[person.0].state = PersonState::Inside(b);
+            self.events.push(Event::PersonEntersBuilding(person, b));
+            self.trip_finished(now, id, ctx);
+        } else {
+            self.spawn_ped(
+                now,
+                id,
+                SidewalkSpot::parking_spot(spot, ctx.map, ctx.parking),
+                ctx,
+            );
+        }
+    }
+
+    /// The carpool driver left the map. Passengers headed off-map too are done; anybody else is
+    /// stuck.
+    fn carpool_left_map(&mut self, now: Time, driver: TripID, i: IntersectionID, ctx: &mut Ctx) {
+        let riders = match self.carpools.get_mut(&driver) {
+            Some(carpool) => std::mem::take(&mut carpool.riding),
+            None => {
+                return;
+            }
+        };
+        for id in riders {
+            let (person, car) = self.leave_car(id, driver);
+            if let TripEndpoint::Border(_) = self.trips[id.0].info.end {
+                self.trips[id.0].legs.clear();
+                self.events.push(Event::PersonLeavesMap(
+                    person,
+                    Some(AgentID::BusPassenger(person, car)),
+                    i,
+                ));
+                self.people[person.0].state = PersonState::OffMap;
+                self.trip_finished(now, id, ctx);
+            } else {
+                self.cancel_trip(
+                    now,
+                    id,
+                    format!("their carpool driver left the map at {}", i),
+                    None,
+                    ctx,
+                );
+            }
+        }
+    }
+
+    fn leave_car(&mut self, id: TripID, driver: TripID) -> (PersonID, CarID) {
+        let trip = &mut self.trips[id.0];
+        match trip.legs.pop_front() {
+            Some(TripLeg::RideCar(d)) => assert_eq!(d, driver),
+            _ => unreachable!(),
+        }
+        let car = self.people[trip.person.0].on_bus.take().unwrap();
+        self.active_trip_mode
+            .remove(&AgentID::BusPassenger(trip.person, car))
+            .unwrap();
+        (trip.person, car)
+    }
+}
+
+// Ride-hailing
+impl TripManager {
+    /// Somebody's ready to be picked up. Send the nearest free vehicle, or wait in line for one.
+    fn request_ride_hail(&mut self, now: Time, id: TripID, pickup: BuildingID, ctx: &mut Ctx) {
+        self.events.push(Event::TripPhaseStarting(
+            id,
+            self.trips[id.0].person,
+            None,
+            TripPhaseType::WaitingForRideHail,
+        ));
+        if ctx.fleet.num_vehicles() == 0 {
+            self.cancel_trip(
+                now,
+                id,
+                "there are no ride-hailing vehicles".to_string(),
+                None,
+                ctx,
+            );
+            return;
+        }
+        if let Some(car) = ctx.fleet.request(now, id, pickup, ctx.parking, ctx.map) {
+            self.dispatch_ride_hail(now, car, ctx);
+        }
+    }
+
+    /// Send an idle vehicle to pick up its assigned rider.
+    fn dispatch_ride_hail(&mut self, now: Time, car: CarID, ctx: &mut Ctx) {
+        let (trip, pickup) = ctx.fleet.assignment(car);
+        let parked_car = ctx.parking.lookup_parked_car(car).cloned().unwrap();
+        if matches!(parked_car.spot, ParkingSpot::Offstreet(b, _) if b == pickup) {
+            // Already there
+            self.ride_hail_arrived(now, car, trip, ctx);
+            return;
+        }
+        if let Err(err) = drive_ride_hail(now, parked_car, pickup, 0, ctx) {
+            ctx.fleet.abort_ride(now, car);
+            self.cancel_trip(
+                now,
+                trip,
+                format!("{} couldn't reach them: {}", car, err),
+                None,
+                ctx,
+            );
+            self.ride_hail_vehicle_free(now, car, ctx);
+        }
+    }
+
+    /// A ride-hailing vehicle finished parking, either to pick somebody up or to drop them off.
+    pub fn ride_hail_vehicle_parked(
+        &mut self,
+        now: Time,
+        car: CarID,
+        spot: ParkingSpot,
+        blocked_time: Duration,
+        distance_crossed: Distance,
+        ctx: &mut Ctx,
+    ) {
+        let (state, duration) = ctx.fleet.vehicle_parked(now, car);
+        let (trip, occupied) = match state {
+            FleetVehicleState::EnRoute(trip) => (trip, false),
+            FleetVehicleState::Occupied(trip) => (trip, true),
+            FleetVehicleState::Idle => unreachable!(),
+        };
+        self.events.push(Event::RideHailLegFinished {
+            car,
+            trip,
+            occupied,
+            distance: distance_crossed,
+            duration,
+        });
+
+        if occupied {
+            self.leave_ride_hail(trip, car);
+            self.trips[trip.0].total_blocked_time += blocked_time;
+            self.trips[trip.0].total_distance += distance_crossed;
+            self.passenger_got_out(now, trip, spot, ctx);
+            self.ride_hail_vehicle_free(now, car, ctx);
+        } else {
+            self.ride_hail_arrived(now, car, trip, ctx);
+        }
+    }
+
+    /// A parked ride-hailing vehicle reached its rider, who gets in.
+    fn ride_hail_arrived(&mut self, now: Time, car: CarID, trip: TripID, ctx: &mut Ctx) {
+        let wait = match ctx.fleet.pick_up(now, car, trip) {
+            Some(wait) => wait,
+            None => {
+                // Nobody's there anymore
+                self.ride_hail_vehicle_free(now, car, ctx);
+                return;
+            }
+        };
+        let person = self.trips[trip.0].person;
+        let goal = match self.trips[trip.0].legs[0] {
+            TripLeg::RideHail(b) => b,
+            _ => unreachable!(),
+        };
+        self.people[person.0].on_bus = Some(car);
+        self.active_trip_mode
+            .insert(AgentID::BusPassenger(person, car), trip);
+        self.events.push(Event::RideHailPickup(trip, car, wait));
+        self.events.push(Event::TripPhaseStarting(
+            trip,
+            person,
+            None,
+            TripPhaseType::RidingCar(car),
+        ));
+
+        let parked_car = ctx.parking.lookup_parked_car(car).cloned().unwrap();
+        if let Err(err) = drive_ride_hail(now, parked_car, goal, 1, ctx) {
+            self.leave_ride_hail(trip, car);
+            ctx.fleet.abort_ride(now, car);
+            self.cancel_trip(
+                now,
+                trip,
+                format!("{} couldn't take them to {}: {}", car, goal, err),
+                None,
+                ctx,
+            );
+            self.ride_hail_vehicle_free(now, car, ctx);
+        }
+    }
+
+    /// An idle vehicle goes to whoever's been waiting longest, if anybody.
+    fn ride_hail_vehicle_free(&mut self, now: Time, car: CarID, ctx: &mut Ctx) {
+        if ctx.fleet.next_request(now, car).is_some() {
+            self.dispatch_ride_hail(now, car, ctx);
+        }
+    }
+
+    /// A ride-hailing vehicle vanished, maybe because it couldn't find parking. Its rider is
+    /// stranded, or if it was on the way to somebody, they need a different vehicle.
+    pub fn ride_hail_vehicle_lost(&mut self, now: Time, car: CarID, ctx: &mut Ctx) {
+        if let Some(parked_car) = ctx.parking.lookup_parked_car(car).cloned() {
+            ctx.parking.remove_parked_car(parked_car);
+        }
+        match ctx.fleet.remove_vehicle(car) {
+            FleetVehicleState::Idle => {}
+            FleetVehicleState::EnRoute(trip) => {
+                if let Some(car) = ctx.fleet.reassign(now, trip, ctx.parking, ctx.map) {
+                    self.dispatch_ride_hail(now, car, ctx);
+                }
+            }
+            FleetVehicleState::Occupied(trip) => {
+                self.leave_ride_hail(trip, car);
+                self.cancel_trip(
+                    now,
+                    trip,
+                    format!("their ride-hailing vehicle {} vanished", car),
+                    None,
+                    ctx,
+                );
+            }
+        }
+
+        if ctx.fleet.num_vehicles() == 0 {
+            for trip in ctx.fleet.drain_queue() {
+                self.cancel_trip(
+                    now,
+                    trip,
+                    "there are no ride-hailing vehicles left".to_string(),
+                    None,
+                    ctx,
+                );
+            }
+        }
+    }
+
+    fn leave_ride_hail(&mut self, id: TripID, car: CarID) {
+        let trip = &mut self.trips[id.0];
+        match trip.legs.pop_front() {
+            Some(TripLeg::RideHail(_)) => {}
+            _ => unreachable!(),
+        }
+        assert_eq!(self.people[trip.person.0].on_bus.take(), Some(car));
+        self.active_trip_mode
+            .remove(&AgentID::BusPassenger(trip.person, car))
+            .unwrap();
+    }
+}
+
+/// Start a ride-hailing vehicle driving from wherever it's parked to somewhere near a building.
+fn drive_ride_hail(
+    now: Time,
+    parked_car: ParkedCar,
+    to: BuildingID,
+    passengers: usize,
+    ctx: &mut Ctx,
+) -> Result<()> {
+    let drive_to = DrivingGoal::ParkNear(to);
+    let start = ctx
+        .parking
+        .spot_to_driving_pos(parked_car.spot, &parked_car.vehicle, ctx.map);
+    let end = drive_to
+        .goal_pos(PathConstraints::Car, ctx.map)
+        .ok_or_else(|| anyhow!("can't drive to {}", to))?;
+    let req = match parked_car.spot {
+        ParkingSpot::Onstreet(_, _) => PathRequest::vehicle(start, end, PathConstraints::Car),
+        ParkingSpot::Offstreet(_, _) | ParkingSpot::Lot(_, _) => {
+            PathRequest::leave_from_driveway(start, end, PathConstraints::Car, ctx.map)
+        }
+    };
+    let path = ctx.map.pathfind(req)?;
+    let router = drive_to.make_router(parked_car.vehicle.id, path, ctx.map);
+    ctx.scheduler.push(
+        now,
+        Command::SpawnCar(
+            CreateCar::for_ride_hail(parked_car, router, passengers),
+            true,
+        ),
+    );
+    Ok(())
 }
 
 // Cancelling trips
@@ -1003,9 +1685,17 @@ impl TripManager {
             TripEndpoint::SuddenlyAppear(_) => unreachable!(),
         };
 
+        // Whatever happened to the car they were using, the rest of the household can take it now
+        if let Some(idx) = self.people[person.0].household {
+            self.households[idx].borrowed.remove(&person);
+        }
+
         // Don't forget the car!
         if let Some(vehicle) = abandoned_vehicle {
-            if vehicle.vehicle_type == VehicleType::Car {
+            let trip = &self.trips[id.0];
+            if vehicle.vehicle_type == VehicleType::Car
+                || vehicle.vehicle_type == VehicleType::Emergency
+            {
                 // First remove the parked car, if needed. Maybe the trip was cancelled while the
                 // car was parked in the starting building.
                 if let Some(parked_car) = ctx.parking.lookup_parked_car(vehicle.id).cloned() {
@@ -1061,9 +1751,78 @@ impl TripManager {
             }
         }
 
+        self.cancel_carpool(now, id, ctx);
+        self.carpool_passenger_cancelled(now, id, ctx);
+        ctx.fleet.cancel_request(id);
         self.start_delayed_trip(now, person, ctx);
     }
 
+    /// When a carpool driver's trip is cancelled, so are the trips of everybody waiting for or
+    /// riding with them. Passengers still on their way to the pickup find out when they get there.
+    fn cancel_carpool(&mut self, now: Time, driver: TripID, ctx: &mut Ctx) {
+        let (waiting, riding) = match self.carpools.get_mut(&driver) {
+            Some(carpool) => (
+                std::mem::take(&mut carpool.waiting),
+                std::mem::take(&mut carpool.riding),
+            ),
+            None => {
+                return;
+            }
+        };
+        let mut stranded: Vec<TripID> = waiting.into_iter().collect();
+        for id in riding {
+            self.leave_car(id, driver);
+            stranded.push(id);
+        }
+        for id in stranded {
+            self.cancel_trip(
+                now,
+                id,
+                format!("their carpool driver's trip {} was cancelled", driver),
+                None,
+                ctx,
+            );
+        }
+    }
+
+    /// A carpool passenger walking to the car won't make it, so the driver shouldn't wait for them.
+    fn carpool_passenger_cancelled(&mut self, now: Time, id: TripID, ctx: &mut Ctx) {
+        let driver = match self.trips[id.0].driver {
+            Some(driver) => driver,
+            None => {
+                return;
+            }
+        };
+        let carpool = self.carpools.get_mut(&driver).unwrap();
+        if carpool.walking.remove(&id) {
+            self.maybe_depart_carpool(now, driver, ctx);
+        }
+    }
+
+    /// Household cars all belong to the first member, but anybody in the household can use them.
+    fn get_household_vehicle(&self, person: PersonID, car: CarID) -> Vehicle {
+        let person = &self.people[person.0];
+        if let Some(vehicle) = person.vehicles.iter().find(|v| v.id == car) {
+            return vehicle.clone();
+        }
+        let owner = self.households[person.household.unwrap()].members[0];
+        self.people[owner.0].get_vehicle(car)
+    }
+
+    /// Once a household car is back home or has left the map, the member using it is done with it.
+    fn return_household_car(&mut self, person: PersonID, car: CarID, end: TripEndpoint) {
+        if let Some(idx) = self.people[person.0].household {
+            let household = &mut self.households[idx];
+            let done = match end {
+                TripEndpoint::Bldg(b) => b == household.home,
+                TripEndpoint::Border(_) | TripEndpoint::SuddenlyAppear(_) => true,
+            };
+            if done && household.borrowed.get(&person) == Some(&car) {
+                household.borrowed.remove(&person);
+            }
+        }
+    }
+
     pub fn trip_abruptly_cancelled(&mut self, trip: TripID, agent: AgentID) {
         assert_eq!(self.active_trip_mode.remove(&agent), Some(trip));
     }
@@ -1102,6 +1861,11 @@ impl TripManager {
             TripLeg::Walk(_) => AgentID::Pedestrian(person.ped),
             TripLeg::Drive(c, _) => AgentID::Car(*c),
             TripLeg::RideBus(_, _) => AgentID::BusPassenger(person.id, person.on_bus.unwrap()),
+            TripLeg::RideCar(_) | TripLeg::RideHail(_) => match person.on_bus {
+                Some(car) => AgentID::BusPassenger(person.id, car),
+                // Still waiting to be picked up, or walking to the car with the driver
+                None => AgentID::Pedestrian(person.ped),
+            },
         };
         if self.active_trip_mode.get(&a) == Some(&id) {
             TripResult::Ok(a)
@@ -1131,7 +1895,11 @@ impl TripManager {
             self.unfinished_trips,
         )
     }
-    pub fn num_agents(&self, transit: &TransitSimState) -> Counter<AgentType> {
+    pub fn num_agents(
+        &self,
+        transit: &TransitSimState,
+        fleet: &FleetSimState,
+    ) -> Counter<AgentType> {
         let mut cnt = Counter::new();
         for a in self.active_trip_mode.keys() {
             cnt.inc(a.to_type());
@@ -1139,6 +1907,7 @@ impl TripManager {
         let (buses, trains) = transit.active_vehicles();
         cnt.add(AgentType::Bus, buses);
         cnt.add(AgentType::Train, trains);
+        cnt.add(AgentType::Car, fleet.num_busy());
         cnt
     }
     pub fn num_commuters_vehicles(
@@ -1156,6 +1925,8 @@ impl TripManager {
             cyclists: 0,
 
             sov_drivers: 0,
+            carpool_drivers: 0,
+            car_passengers: 0,
 
             buses,
             trains,
@@ -1163,11 +1934,20 @@ impl TripManager {
             train_riders: 0,
         };
 
-        for a in self.active_trip_mode.keys() {
+        for (a, trip) in &self.active_trip_mode {
             match a {
                 AgentID::Car(c) => match c.vehicle_type {
-                    VehicleType::Car => {
-                        cnt.sov_drivers += 1;
+                    VehicleType::Car | VehicleType::Emergency => {
+                        if self
+                            .carpools
+                            .get(trip)
+                            .map(|carpool| !carpool.riding.is_empty())
+                            .unwrap_or(false)
+                        {
+                            cnt.carpool_drivers += 1;
+                        } else {
+                            cnt.sov_drivers += 1;
+                        }
                     }
                     VehicleType::Bike => {
                         cnt.cyclists += 1;
@@ -1181,7 +1961,10 @@ impl TripManager {
                     VehicleType::Train => {
                         cnt.train_riders += 1;
                     }
-                    VehicleType::Car | VehicleType::Bike => unreachable!(),
+                    VehicleType::Car => {
+                        cnt.car_passengers += 1;
+                    }
+                    VehicleType::Bike | VehicleType::Emergency => unreachable!(),
                 },
                 // These're counted separately
                 AgentID::Pedestrian(_) => {}
@@ -1267,6 +2050,7 @@ impl TripManager {
                         // TODO Not true for long. People will be able to spawn at borders already
                         // on a bus.
                         TripMode::Transit => AgentType::Pedestrian,
+                        TripMode::Carpool | TripMode::RideHail => AgentType::CarPassenger,
                     };
                     times.push((t.info.departure, agent_type));
                 }
@@ -1279,6 +2063,14 @@ impl TripManager {
     /// Recreate the Scenario from an instantiated simulation. The results should match the
     /// original Scenario used.
     pub fn generate_scenario(&self, map: &Map, name: String) -> Scenario {
+        // Carpool passengers refer to their driver's trip by position in the scenario
+        let mut trip_positions: BTreeMap<TripID, (usize, usize)> = BTreeMap::new();
+        for (person_idx, p) in self.people.iter().enumerate() {
+            for (trip_idx, t) in p.trips.iter().enumerate() {
+                trip_positions.insert(*t, (person_idx, trip_idx));
+            }
+        }
+
         let mut scenario = Scenario::empty(map, &name);
         for p in &self.people {
             scenario.people.push(PersonSpec {
@@ -1288,17 +2080,26 @@ impl TripManager {
                     .iter()
                     .map(|t| {
                         let trip = &self.trips[t.0];
-                        IndividTrip::new(
+                        let mut individ = IndividTrip::new(
                             trip.info.departure,
                             trip.info.purpose,
                             trip.info.start,
                             trip.info.end,
                             trip.info.mode,
-                        )
+                        );
+                        individ.carpool_driver = trip.driver.map(|d| trip_positions[&d]);
+                        individ
                     })
                     .collect(),
             });
         }
+        for household in &self.households {
+            scenario.households.push(HouseholdSpec {
+                home: household.home,
+                members: household.members.iter().map(|p| p.0).collect(),
+                num_vehicles: household.cars.len(),
+            });
+        }
         scenario
     }
 }
@@ -1314,6 +2115,8 @@ struct Trip {
     // Not filled out until the trip starts
     legs: VecDeque<TripLeg>,
     person: PersonID,
+    /// For carpool passengers, the trip of the person driving
+    driver: Option<TripID>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -1330,6 +2133,10 @@ pub struct TripInfo {
 }
 
 impl Trip {
+    fn is_done(&self) -> bool {
+        self.finished_at.is_some() || self.info.cancellation_reason.is_some()
+    }
+
     fn assert_walking_leg(&mut self, goal: SidewalkSpot) {
         match self.legs.pop_front() {
             Some(TripLeg::Walk(spot)) => {
@@ -1349,6 +2156,36 @@ pub(crate) enum TripLeg {
     Drive(CarID, DrivingGoal),
     /// Maybe get off at a stop, maybe ride off-map
     RideBus(BusRouteID, Option<BusStopID>),
+    /// Ride along with somebody else's trip, getting out wherever they park
+    RideCar(TripID),
+    /// Get picked up by a ride-hailing vehicle and dropped off somewhere near this building
+    RideHail(BuildingID),
+}
+
+/// A driver giving a ride to some passengers, who all meet at the building where the driver starts.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Carpool {
+    /// Every passenger, whether or not they've shown up yet
+    passengers: Vec<TripID>,
+    /// Passengers at the pickup, or at the car after walking there with the driver
+    waiting: BTreeSet<TripID>,
+    /// Passengers walking with the driver from the pickup to the car
+    walking: BTreeSet<TripID>,
+    /// Passengers currently in the car
+    riding: Vec<TripID>,
+    /// If the driver is waiting for passengers, how to start their trip once everybody's here
+    deferred_start: Option<StartTripArgs>,
+    /// If the driver reached the car before everybody walking with them, when and how to drive
+    /// away once they're all there
+    ready_to_drive: Option<(Time, CreateCar)>,
+}
+
+impl Carpool {
+    fn everyone_here(&self, trips: &[Trip]) -> bool {
+        self.passengers
+            .iter()
+            .all(|id| self.waiting.contains(id) || trips[id.0].is_done())
+    }
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy, PartialOrd, Ord)]
@@ -1357,6 +2194,10 @@ pub enum TripMode {
     Bike,
     Transit,
     Drive,
+    /// Riding in somebody else's car
+    Carpool,
+    /// Riding in a ride-hailing vehicle
+    RideHail,
 }
 
 impl TripMode {
@@ -1366,6 +2207,8 @@ impl TripMode {
             TripMode::Bike,
             TripMode::Transit,
             TripMode::Drive,
+            TripMode::Carpool,
+            TripMode::RideHail,
         ]
     }
 
@@ -1375,6 +2218,8 @@ impl TripMode {
             TripMode::Bike => "bike",
             TripMode::Transit => "use transit",
             TripMode::Drive => "drive",
+            TripMode::Carpool => "carpool",
+            TripMode::RideHail => "hail a ride",
         }
     }
 
@@ -1385,6 +2230,8 @@ impl TripMode {
             TripMode::Bike => "biking",
             TripMode::Transit => "using transit",
             TripMode::Drive => "driving",
+            TripMode::Carpool => "carpooling",
+            TripMode::RideHail => "ride-hailing",
         }
     }
 
@@ -1394,6 +2241,8 @@ impl TripMode {
             TripMode::Bike => "Bike",
             TripMode::Transit => "Bus",
             TripMode::Drive => "Car",
+            TripMode::Carpool => "Carpool",
+            TripMode::RideHail => "Ride-hail",
         }
     }
 
@@ -1403,7 +2252,7 @@ impl TripMode {
             TripMode::Bike => PathConstraints::Bike,
             // TODO WRONG
             TripMode::Transit => PathConstraints::Bus,
-            TripMode::Drive => PathConstraints::Car,
+            TripMode::Drive | TripMode::Carpool | TripMode::RideHail => PathConstraints::Car,
         }
     }
 
@@ -1460,7 +2309,10 @@ pub struct Person {
     pub vehicles: Vec<Vehicle>,
 
     delayed_trips: Vec<(TripID, StartTripArgs)>,
+    /// The bus, train, carpool, or ride-hailing vehicle they're riding
     on_bus: Option<CarID>,
+    /// Index into the TripManager's households
+    household: Option<usize>,
 }
 
 impl Person {
@@ -1469,6 +2321,59 @@ impl Person {
     }
 }
 
+/// People sharing a home and some cars
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Household {
+    home: BuildingID,
+    members: Vec<PersonID>,
+    cars: Vec<CarID>,
+    /// Who's using which car. They might have parked it away from home.
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    borrowed: BTreeMap<PersonID, CarID>,
+}
+
+impl Household {
+    /// A member keeps using the car they're already borrowing. Otherwise, when leaving home, they
+    /// take any car that's parked near home and nobody else is using.
+    fn borrow_car(
+        &mut self,
+        person: PersonID,
+        start: TripEndpoint,
+        parking: &ParkingSimState,
+        map: &Map,
+    ) -> Option<CarID> {
+        if let Some(car) = self.borrowed.get(&person) {
+            return parking.lookup_parked_car(*car).map(|_| *car);
+        }
+        if start != TripEndpoint::Bldg(self.home) {
+            return None;
+        }
+        let home = map.get_b(self.home).sidewalk_pos.pt(map);
+        let car = self
+            .cars
+            .iter()
+            .find(|c| {
+                let near_home = match parking.lookup_parked_car(**c) {
+                    Some(p) => {
+                        parking
+                            .spot_to_sidewalk_pos(p.spot, map)
+                            .pt(map)
+                            .dist_to(home)
+                            <= HOUSEHOLD_CAR_MAX_DIST
+                    }
+                    None => false,
+                };
+                near_home && !self.borrowed.values().any(|borrowed| borrowed == *c)
+            })
+            .cloned()?;
+        self.borrowed.insert(person, car);
+        Some(car)
+    }
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub enum PersonState {
     Trip(TripID),
@@ -1486,6 +2391,8 @@ pub struct CommutersVehiclesCounts {
     pub cyclists: usize,
 
     pub sov_drivers: usize,
+    pub carpool_drivers: usize,
+    pub car_passengers: usize,
 
     pub buses: usize,
     pub trains: usize,
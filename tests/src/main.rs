@@ -8,9 +8,14 @@ use rand::seq::SliceRandom;
 
 use abstio::{CityName, MapName};
 use abstutil::Timer;
-use geom::{Distance, Duration, Time};
-use map_model::{IntersectionID, Map};
-use sim::{IndividTrip, PersonSpec, Scenario, TripEndpoint, TripMode, TripPurpose};
+use geom::{Distance, Duration, LonLat, Speed, Time};
+use map_model::{
+    osm, BuildingID, EditCmd, EditIntersection, ExternalTransitRoute, IntersectionID, LaneType, Map,
+};
+use sim::{
+    AgentID, CruisingOutcome, HouseholdSpec, IndividTrip, ParkingArea, ParkingStrategy, PersonID,
+    PersonSpec, Scenario, TripEndpoint, TripMode, TripPurpose, TripResult,
+};
 
 fn main() -> Result<()> {
     test_lane_changing(&import_map(abstio::path(
@@ -19,6 +24,15 @@ fn main() -> Result<()> {
     test_map_importer()?;
     check_proposals()?;
     smoke_test()?;
+    test_ride_hail_live_edits()?;
+    test_pedestrian_crowding()?;
+    test_oncoming_overtake()?;
+    test_parking_pricing()?;
+    test_external_transit_route()?;
+    test_parking_strategies()?;
+    test_movement_times()?;
+    test_household_shared_car()?;
+    test_carpool_walks_to_car()?;
     Ok(())
 }
 
@@ -237,3 +251,672 @@ fn test_lane_changing(map: &Map) -> Result<()> {
 
     Ok(())
 }
+
+/// The handcrafted map used by simulation tests: one street with parking, homes on the west end,
+/// workplaces on the east end, and an intersection in the middle.
+fn parking_map() -> Map {
+    import_map(abstio::path("../tests/input/parking_and_buildings.osm"))
+}
+
+/// The westernmost and easternmost buildings, on opposite sides of the middle intersection
+fn home_and_work(map: &Map) -> (BuildingID, BuildingID) {
+    let mut bldgs: Vec<(f64, BuildingID)> = map
+        .all_buildings()
+        .iter()
+        .map(|b| (b.polygon.center().x(), b.id))
+        .collect();
+    bldgs.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+    (bldgs[0].1, bldgs.last().unwrap().1)
+}
+
+fn middle_intersection(map: &Map) -> IntersectionID {
+    map.all_intersections()
+        .iter()
+        .find(|i| i.roads.len() == 3)
+        .unwrap()
+        .id
+}
+
+/// A ride-hailing vehicle whose route gets cut off by live map edits should vanish and strand its
+/// rider, not keep driving through the closed intersection.
+fn test_ride_hail_live_edits() -> Result<()> {
+    let mut map = parking_map();
+    let (home, work) = home_and_work(&map);
+    let mut scenario = Scenario::empty(&map, "ride_hail_live_edits");
+    scenario.people.push(PersonSpec {
+        orig_id: None,
+        trips: vec![IndividTrip::new(
+            Time::START_OF_DAY + Duration::minutes(1),
+            TripPurpose::Work,
+            TripEndpoint::Bldg(home),
+            TripEndpoint::Bldg(work),
+            TripMode::RideHail,
+        )],
+    });
+
+    let mut opts = sim::SimOptions::new("test_ride_hail_live_edits");
+    opts.alerts = sim::AlertHandler::Silence;
+    opts.infinite_parking = true;
+    opts.ride_hail_fleet = 1;
+    let mut sim = sim::Sim::new(&map, opts);
+    let mut rng = sim::SimFlags::for_test("test_ride_hail_live_edits").make_rng();
+    let mut timer = Timer::throwaway();
+    scenario.instantiate(&mut sim, &map, &mut rng, &mut timer);
+    while sim.ride_hail_summary().rides == 0 {
+        if sim.time() > Time::START_OF_DAY + Duration::minutes(30) {
+            panic!("The ride-hailing vehicle never picked anybody up");
+        }
+        sim.tiny_step(&map, &mut None);
+    }
+
+    // The rider is on the way to work, west of the middle intersection
+    let i = middle_intersection(&map);
+    let mut edits = map.get_edits().clone();
+    edits.commands.push(EditCmd::ChangeIntersection {
+        i,
+        old: map.get_i_edit(i),
+        new: EditIntersection::Closed,
+    });
+    map.must_apply_edits(edits);
+    map.recalculate_pathfinding_after_edits(&mut timer);
+    sim.handle_live_edits(&map, &mut timer);
+    sim.timed_step(&map, Duration::seconds(10.0), &mut None, &mut timer);
+
+    assert_eq!(sim.ride_hail_summary().fleet_size, 0);
+    assert!(sim.is_done());
+    Ok(())
+}
+
+/// With crowding enabled, somebody already walking along a sidewalk should slow down when a crowd
+/// joins them partway through. Without crowding, density isn't tracked at all.
+fn test_pedestrian_crowding() -> Result<()> {
+    let map = parking_map();
+    let (home, work) = home_and_work(&map);
+    let sidewalk = map.get_b(home).sidewalk_pos.lane();
+
+    // Returns how long the first person took to get to work, and the sim
+    let run = |crowd: usize, crowding: bool| -> (Duration, sim::Sim) {
+        let mut scenario = Scenario::empty(&map, "pedestrian_crowding");
+        for idx in 0..=crowd {
+            scenario.people.push(PersonSpec {
+                orig_id: None,
+                trips: vec![IndividTrip::new(
+                    // Everybody else starts once the first person is already on the sidewalk
+                    Time::START_OF_DAY
+                        + if idx == 0 {
+                            Duration::ZERO
+                        } else {
+                            Duration::seconds(30.0)
+                        },
+                    TripPurpose::Work,
+                    TripEndpoint::Bldg(home),
+                    TripEndpoint::Bldg(work),
+                    TripMode::Walk,
+                )],
+            });
+        }
+
+        let mut opts = sim::SimOptions::new("test_pedestrian_crowding");
+        opts.alerts = sim::AlertHandler::Silence;
+        opts.pedestrian_crowding = crowding;
+        let mut sim = sim::Sim::new(&map, opts);
+        let mut rng = sim::SimFlags::for_test("test_pedestrian_crowding").make_rng();
+        scenario.instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+        let trip = sim.get_person(PersonID(0)).trips[0];
+        while sim.finished_trip_details(trip).is_none() {
+            if sim.time() > Time::START_OF_DAY + Duration::hours(1) {
+                panic!("The first pedestrian never reached work");
+            }
+            sim.tiny_step(&map, &mut None);
+        }
+        (sim.finished_trip_details(trip).unwrap().0, sim)
+    };
+
+    let (alone, _) = run(0, true);
+    let (crowded, sim) = run(1000, true);
+    if crowded < alone + Duration::seconds(30.0) {
+        panic!(
+            "Walking alone took {}, but only {} once a crowd joined",
+            alone, crowded
+        );
+    }
+    assert!(sim.get_analytics().worst_pedestrian_los(sidewalk).is_some());
+
+    let (_, sim) = run(1000, false);
+    assert!(sim.get_analytics().worst_pedestrian_los(sidewalk).is_none());
+    Ok(())
+}
+
+/// A car stuck behind a bike should pass it using the oncoming lane, moving smoothly the whole time
+/// instead of jumping ahead of the bike.
+fn test_oncoming_overtake() -> Result<()> {
+    let map = parking_map();
+    let (home, work) = home_and_work(&map);
+
+    // Returns how long after the bike started that the bike and the car reached work
+    let run = |overtakes: bool| -> (Duration, Duration) {
+        let mut scenario = Scenario::empty(&map, "oncoming_overtake");
+        for (delay, mode) in vec![(0.0, TripMode::Bike), (20.0, TripMode::Drive)] {
+            scenario.people.push(PersonSpec {
+                orig_id: None,
+                trips: vec![IndividTrip::new(
+                    Time::START_OF_DAY + Duration::seconds(delay),
+                    TripPurpose::Work,
+                    TripEndpoint::Bldg(home),
+                    TripEndpoint::Bldg(work),
+                    mode,
+                )],
+            });
+        }
+
+        let mut opts = sim::SimOptions::new("test_oncoming_overtake");
+        opts.alerts = sim::AlertHandler::Silence;
+        opts.infinite_parking = true;
+        opts.oncoming_overtakes = overtakes;
+        let mut sim = sim::Sim::new(&map, opts);
+        let mut rng = sim::SimFlags::for_test("test_oncoming_overtake").make_rng();
+        let mut timer = Timer::throwaway();
+        scenario.instantiate(&mut sim, &map, &mut rng, &mut timer);
+        let bike_trip = sim.get_person(PersonID(0)).trips[0];
+        let car_trip = sim.get_person(PersonID(1)).trips[0];
+
+        let mut last_pt = None;
+        while !sim.is_done() {
+            if sim.time() > Time::START_OF_DAY + Duration::minutes(30) {
+                panic!("The bike and car never reached work");
+            }
+            sim.timed_step(&map, Duration::seconds(0.1), &mut None, &mut timer);
+
+            if let TripResult::Ok(AgentID::Car(car)) = sim.trip_to_agent(car_trip) {
+                let pt = sim.canonical_pt_for_agent(AgentID::Car(car), &map).unwrap();
+                if let Some(last_pt) = last_pt {
+                    let moved = pt.dist_to(last_pt);
+                    if moved > Distance::meters(4.0) {
+                        panic!("{} jumped {} at {}", car, moved, sim.time());
+                    }
+                }
+                last_pt = Some(pt);
+            } else {
+                last_pt = None;
+            }
+        }
+        (
+            sim.finished_trip_details(bike_trip).unwrap().0,
+            Duration::seconds(20.0) + sim.finished_trip_details(car_trip).unwrap().0,
+        )
+    };
+
+    // Without overtaking, the car is stuck behind the bike
+    let (bike, car) = run(false);
+    assert!(car > bike);
+    let (bike, car) = run(true);
+    assert!(car < bike);
+    Ok(())
+}
+
+/// Drivers pay for every hour they stay in priced spots, but cars parked since the start of the
+/// simulation never arrived, so they don't pay.
+fn test_parking_pricing() -> Result<()> {
+    let mut map = parking_map();
+    let (home, work) = home_and_work(&map);
+
+    // $2 an hour everywhere
+    let mut edits = map.get_edits().clone();
+    for r in map.all_roads() {
+        if r.lanes_ltr()
+            .iter()
+            .any(|(_, _, lt)| *lt == LaneType::Parking)
+        {
+            let mut new = map.get_r_edit(r.id);
+            new.parking_rules.price_per_hour = 2.0;
+            edits.commands.push(EditCmd::ChangeRoad {
+                r: r.id,
+                old: map.get_r_edit(r.id),
+                new,
+            });
+        }
+    }
+    let mut timer = Timer::throwaway();
+    map.must_apply_edits(edits);
+    map.recalculate_pathfinding_after_edits(&mut timer);
+
+    let mut scenario = Scenario::empty(&map, "parking_pricing");
+    scenario.people.push(PersonSpec {
+        orig_id: None,
+        trips: vec![
+            IndividTrip::new(
+                Time::START_OF_DAY + Duration::hours(7),
+                TripPurpose::Work,
+                TripEndpoint::Bldg(home),
+                TripEndpoint::Bldg(work),
+                TripMode::Drive,
+            ),
+            IndividTrip::new(
+                Time::START_OF_DAY + Duration::hours(9),
+                TripPurpose::Home,
+                TripEndpoint::Bldg(work),
+                TripEndpoint::Bldg(home),
+                TripMode::Drive,
+            ),
+        ],
+    });
+
+    let mut opts = sim::SimOptions::new("test_parking_pricing");
+    opts.alerts = sim::AlertHandler::Silence;
+    let mut sim = sim::Sim::new(&map, opts);
+    let mut rng = sim::SimFlags::for_test("test_parking_pricing").make_rng();
+    scenario.instantiate(&mut sim, &map, &mut rng, &mut timer);
+
+    // Halfway through 8am, the car parked at work hasn't paid yet, but already owes $1 for 8am and
+    // occupies a spot for half of the hour
+    sim.timed_step(
+        &map,
+        Duration::hours(8) + Duration::minutes(30),
+        &mut None,
+        &mut timer,
+    );
+    let revenue = sim
+        .get_analytics()
+        .parking_revenue_by_hour(sim.time(), &map);
+    assert_eq!(revenue.len(), 9);
+    assert!((revenue[8] - 1.0).abs() < 0.01);
+    let (per_lane, _) = sim.get_analytics().parking_occupancy_by_hour(sim.time());
+    let occupied: f64 = per_lane.values().filter_map(|hours| hours.get(8)).sum();
+    assert!((occupied - 0.5).abs() < 0.01);
+
+    sim.timed_step(
+        &map,
+        Duration::hours(1) + Duration::minutes(30),
+        &mut None,
+        &mut timer,
+    );
+    assert!(sim.is_done());
+
+    // Only the stay at work counts
+    let stays = &sim.get_analytics().parking_stays;
+    assert_eq!(stays.len(), 1);
+    let (left, _, arrived, price, _) = stays[0];
+    assert!(arrived > Time::START_OF_DAY + Duration::hours(7));
+    assert!(left > Time::START_OF_DAY + Duration::hours(9));
+    let expected = 2.0 * (left - arrived).inner_seconds() / 3600.0;
+    assert!((price - expected).abs() < 0.01);
+
+    // Most of the stay was during 8am, and nothing was paid overnight. After returning home
+    // sometime after 9am, the car owes at most another hour.
+    let revenue = sim
+        .get_analytics()
+        .parking_revenue_by_hour(sim.time(), &map);
+    assert!(revenue[0..7].iter().all(|x| *x == 0.0));
+    assert!((revenue[8] - 2.0).abs() < 0.01);
+    let total = revenue.iter().sum::<f64>();
+    assert!(total > price - 0.01 && total < price + 2.0);
+    Ok(())
+}
+
+/// Pathfinding should handle transit routes added after importing a map, like from GTFS, even after
+/// map edits.
+fn test_external_transit_route() -> Result<()> {
+    let mut map = parking_map();
+    let mut timer = Timer::throwaway();
+    let gps_bounds = map.get_gps_bounds().clone();
+    let stop = |name: &str, lon: f64| {
+        (
+            name.to_string(),
+            LonLat::new(lon, 47.7200).to_pt(&gps_bounds),
+        )
+    };
+    let br = map.hack_add_transit_route(
+        ExternalTransitRoute {
+            full_name: "Crosstown to East".to_string(),
+            short_name: "X".to_string(),
+            gtfs_trip_marker: None,
+            osm_rel_id: osm::RelationID(-1),
+            is_bus: true,
+            stops: vec![stop("West", -122.4540), stop("East", -122.4460)],
+            spawn_times: vec![Time::START_OF_DAY + Duration::hours(7)],
+            schedule: None,
+        },
+        &mut timer,
+    )?;
+    map.hack_rebuild_pathfinding(&mut timer);
+
+    let stops = map.get_br(br).stops.clone();
+    assert_eq!(stops.len(), 2);
+    let start = map.get_bs(stops[0]).sidewalk_pos;
+    let end = map.get_bs(stops[1]).sidewalk_pos;
+    let before = map.should_use_transit(start, end);
+
+    // Any edit updates pathfinding for the new stops too
+    let east = map
+        .all_roads()
+        .iter()
+        .find(|r| r.get_name(None) == "East Street")
+        .unwrap()
+        .id;
+    let mut edits = map.get_edits().clone();
+    let mut new = map.get_r_edit(east);
+    new.speed_limit = Speed::miles_per_hour(15.0);
+    edits.commands.push(EditCmd::ChangeRoad {
+        r: east,
+        old: map.get_r_edit(east),
+        new,
+    });
+    map.must_apply_edits(edits);
+    map.recalculate_pathfinding_after_edits(&mut timer);
+    assert_eq!(map.should_use_transit(start, end), before);
+    Ok(())
+}
+
+/// A block that drivers can circle, with a home and workplace. The only parking is in a permit zone
+/// reserved for residents.
+fn parking_loop_with_permit_zone() -> (Map, BuildingID, BuildingID) {
+    let mut map = import_map(abstio::path("../tests/input/parking_loop.osm"));
+    let mut bldgs: Vec<(f64, BuildingID)> = map
+        .all_buildings()
+        .iter()
+        .map(|b| (b.polygon.center().y(), b.id))
+        .collect();
+    bldgs.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+    // Home is on the north side of the block
+    let (home, work) = (bldgs[0].1, bldgs[1].1);
+
+    let mut edits = map.get_edits().clone();
+    let north = map
+        .all_roads()
+        .iter()
+        .find(|r| r.get_name(None) == "North Street")
+        .unwrap()
+        .id;
+    let mut new = map.get_r_edit(north);
+    new.parking_rules.permit_zone = Some("residents".to_string());
+    edits.commands.push(EditCmd::ChangeRoad {
+        r: north,
+        old: map.get_r_edit(north),
+        new,
+    });
+    map.must_apply_edits(edits);
+    map.recalculate_pathfinding_after_edits(&mut Timer::throwaway());
+    (map, home, work)
+}
+
+/// Drive to a workplace with no parking nearby, except for a permit zone reserved for residents.
+/// Depending on the strategy, the driver gives up or eventually parks in the zone anyway.
+fn test_parking_strategies() -> Result<()> {
+    let (map, home, work) = parking_loop_with_permit_zone();
+    let mut scenario = Scenario::empty(&map, "parking_strategies");
+    scenario.people.push(PersonSpec {
+        orig_id: None,
+        trips: vec![IndividTrip::new(
+            Time::START_OF_DAY + Duration::minutes(1),
+            TripPurpose::Work,
+            TripEndpoint::Bldg(home),
+            TripEndpoint::Bldg(work),
+            TripMode::Drive,
+        )],
+    });
+
+    // Returns how the search for parking ended, how far the driver cruised, and if they reached
+    // work
+    let run = |strategy: ParkingStrategy| -> (CruisingOutcome, Distance, bool) {
+        let mut opts = sim::SimOptions::new("test_parking_strategies");
+        opts.alerts = sim::AlertHandler::Silence;
+        opts.parking_strategy = strategy;
+        let mut sim = sim::Sim::new(&map, opts);
+        let mut rng = sim::SimFlags::for_test("test_parking_strategies").make_rng();
+        let mut timer = Timer::throwaway();
+        scenario.instantiate(&mut sim, &map, &mut rng, &mut timer);
+        sim.timed_step(&map, Duration::hours(1), &mut None, &mut timer);
+        assert!(sim.is_done());
+
+        let trip = sim.get_person(PersonID(0)).trips[0];
+        let analytics = sim.get_analytics();
+        let (_, _, distance, outcome) = analytics.cruising_for_parking[&trip];
+        let per_area = analytics.cruising_per_area(&map);
+        assert_eq!(per_area.len(), 1);
+        assert_eq!(per_area[0].area, ParkingArea::containing(work, &map));
+        assert_eq!(
+            per_area[0].gave_up,
+            (outcome == CruisingOutcome::GaveUp) as usize
+        );
+        assert_eq!(
+            per_area[0].parked_illegally,
+            (outcome == CruisingOutcome::ParkedIllegally) as usize
+        );
+        (outcome, distance, sim.finished_trip_details(trip).is_some())
+    };
+
+    // Nobody with permission to park in the zone is heading to work, so the only spots are off
+    // limits
+    let (outcome, _, arrived) = run(ParkingStrategy::Omniscient);
+    assert_eq!(outcome, CruisingOutcome::GaveUp);
+    assert!(!arrived);
+
+    // Circling the block doesn't help
+    let (outcome, distance, arrived) = run(ParkingStrategy::CircleBlock);
+    assert_eq!(outcome, CruisingOutcome::GaveUp);
+    assert!(distance >= Distance::meters(2000.0));
+    assert!(!arrived);
+
+    // After circling a while, the driver parks in the zone anyway
+    let (outcome, distance, arrived) = run(ParkingStrategy::ParkIllegally);
+    assert_eq!(outcome, CruisingOutcome::ParkedIllegally);
+    assert!(distance >= Distance::meters(500.0));
+    assert!(arrived);
+    Ok(())
+}
+
+/// Two people share one car. Whoever leaves home first takes it, and the other can't drive. When
+/// the member who doesn't own the car can't start a trip, it's cancelled cleanly.
+fn test_household_shared_car() -> Result<()> {
+    let mut map = parking_map();
+    let (home, work) = home_and_work(&map);
+
+    // Cars can't reach the northern border anymore
+    let middle = middle_intersection(&map);
+    let north = map
+        .all_roads()
+        .iter()
+        .find(|r| r.get_name(None) == "North Street")
+        .unwrap();
+    let (north, north_border) = (
+        north.id,
+        if north.src_i == middle {
+            north.dst_i
+        } else {
+            north.src_i
+        },
+    );
+    let mut edits = map.get_edits().clone();
+    let mut new = map.get_r_edit(north);
+    for spec in &mut new.lanes_ltr {
+        if spec.lt == LaneType::Driving {
+            spec.lt = LaneType::Biking;
+        }
+    }
+    edits.commands.push(EditCmd::ChangeRoad {
+        r: north,
+        old: map.get_r_edit(north),
+        new,
+    });
+    let mut timer = Timer::throwaway();
+    map.must_apply_edits(edits);
+    map.recalculate_pathfinding_after_edits(&mut timer);
+
+    let at = |hour: usize, minute: usize, from: TripEndpoint, to: TripEndpoint, mode| {
+        IndividTrip::new(
+            Time::START_OF_DAY + Duration::hours(hour) + Duration::minutes(minute),
+            TripPurpose::Work,
+            from,
+            to,
+            mode,
+        )
+    };
+    let (h, w) = (TripEndpoint::Bldg(home), TripEndpoint::Bldg(work));
+    let mut scenario = Scenario::empty(&map, "household_shared_car");
+    // The car belongs to the first member of the household
+    scenario.people.push(PersonSpec {
+        orig_id: None,
+        trips: vec![
+            at(8, 0, h, w, TripMode::Drive),
+            at(8, 30, w, h, TripMode::Walk),
+            at(10, 0, h, w, TripMode::Drive),
+            at(10, 30, w, h, TripMode::Drive),
+        ],
+    });
+    scenario.people.push(PersonSpec {
+        orig_id: None,
+        trips: vec![
+            at(7, 0, h, w, TripMode::Drive),
+            at(9, 0, w, h, TripMode::Drive),
+            at(
+                11,
+                0,
+                h,
+                TripEndpoint::Border(north_border),
+                TripMode::Drive,
+            ),
+        ],
+    });
+    scenario.households.push(HouseholdSpec {
+        home,
+        members: vec![0, 1],
+        num_vehicles: 1,
+    });
+
+    let mut opts = sim::SimOptions::new("test_household_shared_car");
+    opts.alerts = sim::AlertHandler::Silence;
+    let mut sim = sim::Sim::new(&map, opts);
+    let mut rng = sim::SimFlags::for_test("test_household_shared_car").make_rng();
+    scenario.instantiate(&mut sim, &map, &mut rng, &mut timer);
+    sim.timed_step(&map, Duration::hours(12), &mut None, &mut timer);
+    assert!(sim.is_done());
+
+    let owner = sim.get_person(PersonID(0)).trips.clone();
+    let other = sim.get_person(PersonID(1)).trips.clone();
+    for trip in vec![other[0], other[1], owner[1], owner[2], owner[3]] {
+        assert!(sim.finished_trip_details(trip).is_some());
+    }
+
+    // The other member took the car to work first, so the owner's first trip is cancelled, not
+    // silently switched to another mode
+    let info = sim.trip_info(owner[0]);
+    assert_eq!(info.mode, TripMode::Drive);
+    assert!(info.cancellation_reason.is_some());
+    assert!(sim.finished_trip_details(owner[0]).is_none());
+
+    // The other member can't drive to the border
+    let info = sim.trip_info(other[2]);
+    assert_eq!(info.mode, TripMode::Drive);
+    assert!(info.cancellation_reason.is_some());
+    assert!(sim.finished_trip_details(other[2]).is_none());
+    Ok(())
+}
+
+/// Time spent parked between trips shouldn't count as time crossing a movement.
+fn test_movement_times() -> Result<()> {
+    let (map, home, work) = parking_loop_with_permit_zone();
+    let mut scenario = Scenario::empty(&map, "movement_times");
+    scenario.people.push(PersonSpec {
+        orig_id: None,
+        trips: vec![
+            IndividTrip::new(
+                Time::START_OF_DAY + Duration::hours(7),
+                TripPurpose::Work,
+                TripEndpoint::Bldg(home),
+                TripEndpoint::Bldg(work),
+                TripMode::Drive,
+            ),
+            IndividTrip::new(
+                Time::START_OF_DAY + Duration::hours(12),
+                TripPurpose::Home,
+                TripEndpoint::Bldg(work),
+                TripEndpoint::Bldg(home),
+                TripMode::Drive,
+            ),
+        ],
+    });
+
+    let run = |record_movement_times: bool| {
+        let mut opts = sim::SimOptions::new("test_movement_times");
+        opts.alerts = sim::AlertHandler::Silence;
+        // Circling the block before parking crosses whole roads
+        opts.parking_strategy = ParkingStrategy::ParkIllegally;
+        opts.record_movement_times = record_movement_times;
+        let mut sim = sim::Sim::new(&map, opts);
+        let mut rng = sim::SimFlags::for_test("test_movement_times").make_rng();
+        let mut timer = Timer::throwaway();
+        scenario.instantiate(&mut sim, &map, &mut rng, &mut timer);
+        sim.timed_step(&map, Duration::hours(13), &mut None, &mut timer);
+        assert!(sim.is_done());
+        sim.get_analytics().movement_times.clone()
+    };
+
+    // Only measured when asked
+    assert!(run(false).is_empty());
+
+    let times = run(true);
+    assert!(!times.is_empty());
+    for (total, count) in times.values() {
+        assert!(*total / (*count as f64) < Duration::minutes(5));
+    }
+    Ok(())
+}
+
+/// Carpool passengers walk with the driver from the pickup to wherever the car is parked.
+fn test_carpool_walks_to_car() -> Result<()> {
+    let map = parking_map();
+    let (home, work) = home_and_work(&map);
+    let mut scenario = Scenario::empty(&map, "carpool_walks_to_car");
+    scenario.people.push(PersonSpec {
+        orig_id: None,
+        trips: vec![IndividTrip::new(
+            Time::START_OF_DAY + Duration::hours(8),
+            TripPurpose::Work,
+            TripEndpoint::Bldg(home),
+            TripEndpoint::Bldg(work),
+            TripMode::Drive,
+        )],
+    });
+    let mut passenger = IndividTrip::new(
+        Time::START_OF_DAY + Duration::hours(8) - Duration::minutes(5),
+        TripPurpose::Work,
+        TripEndpoint::Bldg(home),
+        TripEndpoint::Bldg(work),
+        TripMode::Carpool,
+    );
+    passenger.carpool_driver = Some((0, 0));
+    scenario.people.push(PersonSpec {
+        orig_id: None,
+        trips: vec![passenger],
+    });
+
+    let mut opts = sim::SimOptions::new("test_carpool_walks_to_car");
+    opts.alerts = sim::AlertHandler::Silence;
+    let mut sim = sim::Sim::new(&map, opts);
+    let mut rng = sim::SimFlags::for_test("test_carpool_walks_to_car").make_rng();
+    let mut timer = Timer::throwaway();
+    scenario.instantiate(&mut sim, &map, &mut rng, &mut timer);
+
+    // Right after the driver leaves home, the passenger is walking with them. There's no parking
+    // inside buildings on this map, so the car is out on the street.
+    sim.timed_step(
+        &map,
+        Duration::hours(8) + Duration::seconds(1.0),
+        &mut None,
+        &mut timer,
+    );
+    let driver = sim.get_person(PersonID(0)).trips[0];
+    let passenger = sim.get_person(PersonID(1)).trips[0];
+    match sim.trip_to_agent(passenger) {
+        TripResult::Ok(AgentID::Pedestrian(_)) => {}
+        _ => panic!("{} isn't walking to the car", passenger),
+    }
+
+    sim.timed_step(&map, Duration::hours(1), &mut None, &mut timer);
+    assert!(sim.is_done());
+    for trip in vec![driver, passenger] {
+        assert!(sim.trip_info(trip).cancellation_reason.is_none());
+        assert!(sim.finished_trip_details(trip).is_some());
+    }
+    Ok(())
+}
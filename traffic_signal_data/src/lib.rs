@@ -60,6 +60,22 @@ pub enum StageType {
     /// is 20, and additional is 40, the maximum cycle duration is 60.
     /// If there are crosswalks, the minimum is the minimum for the maximum crosswalks
     Variable(usize, usize, usize),
+    /// Detectors on the approach lanes call for the stage and extend it. If nobody is waiting for
+    /// a protected movement and no pedestrian pushed the button for a crosswalk, the stage is
+    /// skipped. Otherwise it lasts at least `min_green_seconds`. Every detected vehicle extends it
+    /// by `passage_seconds`, and it ends when that gap passes with no vehicle or after
+    /// `max_green_seconds`.
+    Actuated {
+        min_green_seconds: usize,
+        passage_seconds: usize,
+        max_green_seconds: usize,
+    },
+    /// Each cycle, the time for all adaptive stages is re-split in proportion to the queues
+    /// measured for each stage, keeping each between its minimum and maximum.
+    Adaptive {
+        min_green_seconds: usize,
+        max_green_seconds: usize,
+    },
 }
 
 /// A movement through an intersection.